@@ -0,0 +1,297 @@
+//! Canonical byte encodings for everything a provider or payer signs.
+//!
+//! Each payload starts with its domain tag, so a signature over one kind can
+//! never be replayed as another. Variable-length fields (the tag and the
+//! call id) carry a `u32` little-endian length prefix; integers are
+//! little-endian and hashes are raw 32 bytes. Programs and client signing
+//! helpers must both build payloads through this crate.
+
+use solana_sha256_hasher::hash;
+
+pub const FULFILL_DOMAIN: &[u8] = b"x402-assured:fulfill:v1";
+pub const CHUNK_DOMAIN: &[u8] = b"x402-assured:chunk:v1";
+pub const DISPUTE_DOMAIN: &[u8] = b"x402-assured:dispute:v1";
+pub const DATA_FULFILL_DOMAIN: &[u8] = b"x402-assured:fulfill-data:v1";
+pub const PROOF_DOMAIN: &[u8] = b"x402-assured:proof:v1";
+pub const ABORT_DOMAIN: &[u8] = b"x402-assured:abort:v1";
+
+pub trait SigningPayload {
+    const DOMAIN: &'static [u8];
+
+    fn encode_fields(&self, out: &mut Vec<u8>);
+
+    /// The exact bytes to sign.
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(128);
+        put_bytes(&mut out, Self::DOMAIN);
+        self.encode_fields(&mut out);
+        out
+    }
+
+    /// SHA-256 of `encode`, for signers that take a digest.
+    fn hash(&self) -> [u8; 32] {
+        hash(&self.encode()).to_bytes()
+    }
+}
+
+/// Signed by the provider (`provider_sig`) when it fulfills a call.
+pub struct FulfillPayload<'a> {
+    pub call_id: &'a str,
+    pub response_hash: [u8; 32],
+    pub ts: u64,
+}
+
+impl SigningPayload for FulfillPayload<'_> {
+    const DOMAIN: &'static [u8] = FULFILL_DOMAIN;
+
+    fn encode_fields(&self, out: &mut Vec<u8>) {
+        put_bytes(out, self.call_id.as_bytes());
+        out.extend_from_slice(&self.response_hash);
+        out.extend_from_slice(&self.ts.to_le_bytes());
+    }
+}
+
+/// Signed by the provider for each streamed chunk or partial release.
+/// `content_cid` is empty except on the chunk that locates the response.
+pub struct ChunkPayload<'a> {
+    pub call_id: &'a str,
+    pub chunk_index: u32,
+    pub chunk_hash: [u8; 32],
+    pub units: u64,
+    pub ts: u64,
+    pub content_cid: &'a [u8],
+}
+
+impl SigningPayload for ChunkPayload<'_> {
+    const DOMAIN: &'static [u8] = CHUNK_DOMAIN;
+
+    fn encode_fields(&self, out: &mut Vec<u8>) {
+        put_bytes(out, self.call_id.as_bytes());
+        out.extend_from_slice(&self.chunk_index.to_le_bytes());
+        out.extend_from_slice(&self.chunk_hash);
+        out.extend_from_slice(&self.units.to_le_bytes());
+        out.extend_from_slice(&self.ts.to_le_bytes());
+        put_bytes(out, self.content_cid);
+    }
+}
+
+/// Signed by the payer (`reporter_sig`) when raising a dispute.
+pub struct DisputePayload<'a> {
+    pub call_id: &'a str,
+    pub kind: u8,
+    pub reason_hash: [u8; 32],
+}
+
+impl SigningPayload for DisputePayload<'_> {
+    const DOMAIN: &'static [u8] = DISPUTE_DOMAIN;
+
+    fn encode_fields(&self, out: &mut Vec<u8>) {
+        put_bytes(out, self.call_id.as_bytes());
+        out.push(self.kind);
+        out.extend_from_slice(&self.reason_hash);
+    }
+}
+
+/// `FulfillPayload` plus the delivered byte count that data-volume calls
+/// bill on and where the response is stored.
+pub struct DataFulfillPayload<'a> {
+    pub call_id: &'a str,
+    pub response_hash: [u8; 32],
+    pub ts: u64,
+    pub response_size_bytes: u64,
+    pub content_cid: &'a [u8],
+}
+
+impl SigningPayload for DataFulfillPayload<'_> {
+    const DOMAIN: &'static [u8] = DATA_FULFILL_DOMAIN;
+
+    fn encode_fields(&self, out: &mut Vec<u8>) {
+        put_bytes(out, self.call_id.as_bytes());
+        out.extend_from_slice(&self.response_hash);
+        out.extend_from_slice(&self.ts.to_le_bytes());
+        out.extend_from_slice(&self.response_size_bytes.to_le_bytes());
+        put_bytes(out, self.content_cid);
+    }
+}
+
+/// Signed by the provider when rebutting a dispute with a proof.
+pub struct ProofPayload<'a> {
+    pub call_id: &'a str,
+    pub proof_hash: [u8; 32],
+}
+
+impl SigningPayload for ProofPayload<'_> {
+    const DOMAIN: &'static [u8] = PROOF_DOMAIN;
+
+    fn encode_fields(&self, out: &mut Vec<u8>) {
+        put_bytes(out, self.call_id.as_bytes());
+        out.extend_from_slice(&self.proof_hash);
+    }
+}
+
+/// Signed by the provider to abandon the rest of a call. `start_ts` ties the
+/// consent to one escrow, not a later one reusing the call id.
+pub struct AbortPayload<'a> {
+    pub call_id: &'a str,
+    pub start_ts: u64,
+}
+
+impl SigningPayload for AbortPayload<'_> {
+    const DOMAIN: &'static [u8] = ABORT_DOMAIN;
+
+    fn encode_fields(&self, out: &mut Vec<u8>) {
+        put_bytes(out, self.call_id.as_bytes());
+        out.extend_from_slice(&self.start_ts.to_le_bytes());
+    }
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    // Clients must reproduce these byte for byte.
+    #[test]
+    fn fulfill_vector() {
+        let payload = FulfillPayload {
+            call_id: "call-1",
+            response_hash: [0xab; 32],
+            ts: 1_700_000_000,
+        };
+        assert_eq!(
+            hex(&payload.encode()),
+            "17000000783430322d617373757265643a66756c66696c6c3a76310600000063616c6c2d31\
+             abababababababababababababababababababababababababababababababab\
+             00f1536500000000"
+        );
+        assert_eq!(
+            hex(&payload.hash()),
+            "c115b6615e94b9ef7f54ca80dacc8808092f9491c2b4b9ec5dc365e68330cf4c"
+        );
+    }
+
+    #[test]
+    fn chunk_vector() {
+        let payload = ChunkPayload {
+            call_id: "call-1",
+            chunk_index: 3,
+            chunk_hash: [0xcd; 32],
+            units: 10,
+            ts: 1_700_000_001,
+            content_cid: b"",
+        };
+        assert_eq!(
+            hex(&payload.encode()),
+            "15000000783430322d617373757265643a6368756e6b3a76310600000063616c6c2d31\
+             03000000\
+             cdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcdcd\
+             0a00000000000000\
+             01f1536500000000\
+             00000000"
+        );
+        assert_eq!(
+            hex(&payload.hash()),
+            "9ed21a90519fe0196dd27f5d31e6c192ae96e5e649cd0408fb6e0e554447dddf"
+        );
+    }
+
+    #[test]
+    fn dispute_vector() {
+        let payload = DisputePayload {
+            call_id: "call-1",
+            kind: 2,
+            reason_hash: [0xef; 32],
+        };
+        assert_eq!(
+            hex(&payload.encode()),
+            "17000000783430322d617373757265643a646973707574653a76310600000063616c6c2d31\
+             02\
+             efefefefefefefefefefefefefefefefefefefefefefefefefefefefefefefef"
+        );
+        assert_eq!(
+            hex(&payload.hash()),
+            "0bd8074db10e167245f1f799cc0753236b704b38835538b357b982df8c2b6bd2"
+        );
+    }
+
+    #[test]
+    fn data_fulfill_vector() {
+        let payload = DataFulfillPayload {
+            call_id: "call-1",
+            response_hash: [0xab; 32],
+            ts: 1_700_000_000,
+            response_size_bytes: 500,
+            content_cid: b"bafy",
+        };
+        assert_eq!(
+            hex(&payload.encode()),
+            "1c000000783430322d617373757265643a66756c66696c6c2d646174613a76310600000063616c6c2d31\
+             abababababababababababababababababababababababababababababababab\
+             00f1536500000000\
+             f401000000000000\
+             0400000062616679"
+        );
+        assert_eq!(
+            hex(&payload.hash()),
+            "d4afa5a3fbe4f859d55b2460feab9278b38c2ab57df0801c7262533d980f4e9c"
+        );
+    }
+
+    #[test]
+    fn proof_vector() {
+        let payload = ProofPayload {
+            call_id: "call-1",
+            proof_hash: [0x12; 32],
+        };
+        assert_eq!(
+            hex(&payload.encode()),
+            "15000000783430322d617373757265643a70726f6f663a76310600000063616c6c2d31\
+             1212121212121212121212121212121212121212121212121212121212121212"
+        );
+        assert_eq!(
+            hex(&payload.hash()),
+            "4cfa6854e83f93e044395fd4a91d20bc1e266b9d7fb4bbb60e7e293c2142a0e1"
+        );
+    }
+
+    #[test]
+    fn abort_vector() {
+        let payload = AbortPayload {
+            call_id: "call-1",
+            start_ts: 1_700_000_000,
+        };
+        assert_eq!(
+            hex(&payload.encode()),
+            "15000000783430322d617373757265643a61626f72743a76310600000063616c6c2d31\
+             00f1536500000000"
+        );
+        assert_eq!(
+            hex(&payload.hash()),
+            "9e604db8b7481d113783f50d3a2e394e4e080397cc7e5af3e339e83612368bcd"
+        );
+    }
+
+    #[test]
+    fn length_prefix_keeps_call_ids_unambiguous() {
+        // Without the prefix "ab" + hash starting 'c' could collide with "abc".
+        let short = DisputePayload {
+            call_id: "ab",
+            kind: b'c',
+            reason_hash: [0; 32],
+        };
+        let long = DisputePayload {
+            call_id: "abc",
+            kind: 0,
+            reason_hash: [0; 32],
+        };
+        assert_ne!(short.encode(), long.encode());
+    }
+}
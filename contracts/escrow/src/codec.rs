@@ -0,0 +1,515 @@
+//! Dense off-chain encoding of `EscrowCall` for indexers.
+//!
+//! Integers and length prefixes are LEB128 varints, pubkeys and hashes are
+//! raw bytes, and `Option`/`bool` take a single tag byte. The layout is not
+//! Borsh and is never read on-chain.
+
+use crate::{AssuredError, CallState, EscrowCall, PayoutSplit, SlaTier};
+use anchor_lang::prelude::*;
+
+pub fn pack_escrow_state(ec: &EscrowCall) -> Vec<u8> {
+    let mut out = Vec::with_capacity(128 + ec.call_id.len() + ec.service_id.len());
+    out.push(ec.version);
+    put_str(&mut out, &ec.call_id);
+    out.extend_from_slice(ec.payer.as_ref());
+    put_str(&mut out, &ec.service_id);
+    out.extend_from_slice(ec.provider.as_ref());
+    put_varint(&mut out, ec.amount);
+    put_varint(&mut out, ec.start_ts);
+    put_varint(&mut out, ec.sla_ms);
+    put_varint(&mut out, ec.dispute_window_s);
+    out.push(ec.status);
+    match ec.delivered_ts {
+        Some(ts) => {
+            out.push(1);
+            put_varint(&mut out, ts);
+        }
+        None => out.push(0),
+    }
+    out.extend_from_slice(&ec.response_hash);
+    out.push(ec.disputed as u8);
+    put_varint(&mut out, ec.total_units);
+    put_varint(&mut out, ec.units_released);
+    put_bytes(&mut out, &ec.provider_sig);
+    put_varint(&mut out, ec.last_heartbeat_ts);
+    put_varint(&mut out, ec.refund_after_ts);
+    put_varint(&mut out, ec.heartbeat_interval_s);
+    out.push(ec.heartbeat_required as u8);
+    put_ivarint(&mut out, ec.appeal_deadline_ts);
+    put_opt_pubkey(&mut out, &ec.appeal_filed_by);
+    put_opt_byte(&mut out, ec.ruling);
+    out.push(ec.appeal_resolved as u8);
+    out.extend_from_slice(&ec.provider_attest_key);
+    out.push(ec.requires_proofs as u8);
+    out.push(ec.remainder_policy);
+    out.extend_from_slice(ec.rent_recipient.as_ref());
+    put_varint(&mut out, ec.disputed_units);
+    put_varint(&mut out, ec.max_partials as u64);
+    put_varint(&mut out, ec.partial_count as u64);
+    put_ivarint(&mut out, ec.disputed_at_ts);
+    out.extend_from_slice(&ec.provider_proof_hash);
+    out.extend_from_slice(&ec.hashlock);
+    put_splits(&mut out, &ec.payout_splits);
+    put_opt_pubkey(&mut out, &ec.referrer);
+    put_varint(&mut out, ec.referral_fee_bps as u64);
+    match ec.provider_eth_address {
+        Some(address) => {
+            out.push(1);
+            out.extend_from_slice(&address);
+        }
+        None => out.push(0),
+    }
+    put_varint(&mut out, ec.dispute_bond);
+    put_ivarint(&mut out, ec.finalized_at);
+    out.push(ec.outstanding_tracked as u8);
+    out.push(ec.billing_mode);
+    put_varint(&mut out, ec.response_size_bytes);
+    out.push(ec.sig_mode);
+    put_varint(&mut out, ec.expiry_ts);
+    put_bytes(&mut out, &ec.content_cid);
+    put_varint(&mut out, ec.late_penalty_bps as u64);
+    put_tiers(&mut out, &ec.sla_tiers);
+    out.extend_from_slice(&ec.last_chunk_nonce);
+    match ec.redemption_code {
+        Some(code) => {
+            out.push(1);
+            out.extend_from_slice(&code);
+        }
+        None => out.push(0),
+    }
+    put_keys(&mut out, &ec.arbiters);
+    out.push(ec.arbiter_threshold);
+    out.push(ec.dispute_kind);
+    out.push(ec.dispute_bond_state);
+    put_varint(&mut out, ec.fulfilled_at);
+    put_ivarint(&mut out, ec.settled_at);
+    put_varint(&mut out, ec.last_chunk_ts);
+    out
+}
+
+pub fn unpack_escrow_state(bytes: &[u8]) -> Result<EscrowCall> {
+    let mut r = Reader { bytes, pos: 0 };
+    let version = r.byte()?;
+    let call_id = r.string()?;
+    let payer = r.pubkey()?;
+    let service_id = r.string()?;
+    let provider = r.pubkey()?;
+    let ec = EscrowCall {
+        version,
+        call_id,
+        payer,
+        provider,
+        state: CallState {
+            service_id,
+            amount: r.varint()?,
+            start_ts: r.varint()?,
+            sla_ms: r.varint()?,
+            dispute_window_s: r.varint()?,
+            status: r.byte()?,
+            delivered_ts: if r.flag()? { Some(r.varint()?) } else { None },
+            response_hash: r.array()?,
+            disputed: r.flag()?,
+            total_units: r.varint()?,
+            units_released: r.varint()?,
+            provider_sig: r.bytes()?,
+            last_heartbeat_ts: r.varint()?,
+            refund_after_ts: r.varint()?,
+            heartbeat_interval_s: r.varint()?,
+            heartbeat_required: r.flag()?,
+            appeal_deadline_ts: r.ivarint()?,
+            appeal_filed_by: r.opt_pubkey()?,
+            ruling: r.opt_byte()?,
+            appeal_resolved: r.flag()?,
+            provider_attest_key: r.array()?,
+            requires_proofs: r.flag()?,
+            remainder_policy: r.byte()?,
+            rent_recipient: r.pubkey()?,
+            disputed_units: r.varint()?,
+            max_partials: r.short()?,
+            partial_count: r.short()?,
+            disputed_at_ts: r.ivarint()?,
+            provider_proof_hash: r.array()?,
+            hashlock: r.array()?,
+            payout_splits: r.splits()?,
+            referrer: r.opt_pubkey()?,
+            referral_fee_bps: r.short()?,
+            provider_eth_address: if r.flag()? { Some(r.array()?) } else { None },
+            dispute_bond: r.varint()?,
+            finalized_at: r.ivarint()?,
+            outstanding_tracked: r.flag()?,
+            billing_mode: r.byte()?,
+            response_size_bytes: r.varint()?,
+            sig_mode: r.byte()?,
+            expiry_ts: r.varint()?,
+            content_cid: r.bytes()?,
+            late_penalty_bps: r.short()?,
+            sla_tiers: r.tiers()?,
+            last_chunk_nonce: r.array()?,
+            redemption_code: if r.flag()? { Some(r.array()?) } else { None },
+            arbiters: r.keys()?,
+            arbiter_threshold: r.byte()?,
+            dispute_kind: r.byte()?,
+            dispute_bond_state: r.byte()?,
+            fulfilled_at: r.varint()?,
+            settled_at: r.ivarint()?,
+            last_chunk_ts: r.varint()?,
+        },
+    };
+    require!(r.pos == bytes.len(), AssuredError::MalformedEscrowState);
+    Ok(ec)
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn put_ivarint(out: &mut Vec<u8>, value: i64) {
+    put_varint(out, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+fn put_opt_pubkey(out: &mut Vec<u8>, value: &Option<Pubkey>) {
+    match value {
+        Some(key) => {
+            out.push(1);
+            out.extend_from_slice(key.as_ref());
+        }
+        None => out.push(0),
+    }
+}
+
+fn put_opt_byte(out: &mut Vec<u8>, value: Option<u8>) {
+    match value {
+        Some(b) => out.extend_from_slice(&[1, b]),
+        None => out.push(0),
+    }
+}
+
+fn put_bytes(out: &mut Vec<u8>, data: &[u8]) {
+    put_varint(out, data.len() as u64);
+    out.extend_from_slice(data);
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_bytes(out, s.as_bytes());
+}
+
+fn put_tiers(out: &mut Vec<u8>, tiers: &[SlaTier]) {
+    put_varint(out, tiers.len() as u64);
+    for tier in tiers {
+        put_varint(out, tier.deadline_factor_bps as u64);
+        put_varint(out, tier.multiplier_bps as u64);
+    }
+}
+
+fn put_keys(out: &mut Vec<u8>, keys: &[Pubkey]) {
+    put_varint(out, keys.len() as u64);
+    for key in keys {
+        out.extend_from_slice(key.as_ref());
+    }
+}
+
+fn put_splits(out: &mut Vec<u8>, splits: &[PayoutSplit]) {
+    put_varint(out, splits.len() as u64);
+    for split in splits {
+        out.extend_from_slice(split.recipient.as_ref());
+        put_varint(out, split.bps as u64);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(AssuredError::MalformedEscrowState)?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn flag(&mut self) -> Result<bool> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => err!(AssuredError::MalformedEscrowState),
+        }
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            let chunk = (b & 0x7f) as u64;
+            require!(shift < 63 || chunk <= 1, AssuredError::MalformedEscrowState);
+            value |= chunk << shift;
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        err!(AssuredError::MalformedEscrowState)
+    }
+
+    fn short(&mut self) -> Result<u16> {
+        u16::try_from(self.varint()?).map_err(|_| error!(AssuredError::MalformedEscrowState))
+    }
+
+    fn ivarint(&mut self) -> Result<i64> {
+        let raw = self.varint()?;
+        Ok((raw >> 1) as i64 ^ -((raw & 1) as i64))
+    }
+
+    fn opt_pubkey(&mut self) -> Result<Option<Pubkey>> {
+        Ok(if self.flag()? {
+            Some(self.pubkey()?)
+        } else {
+            None
+        })
+    }
+
+    fn opt_byte(&mut self) -> Result<Option<u8>> {
+        Ok(if self.flag()? {
+            Some(self.byte()?)
+        } else {
+            None
+        })
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    fn pubkey(&mut self) -> Result<Pubkey> {
+        Ok(Pubkey::new_from_array(self.array()?))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let len = usize::try_from(self.varint()?)
+            .map_err(|_| error!(AssuredError::MalformedEscrowState))?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn string(&mut self) -> Result<String> {
+        String::from_utf8(self.bytes()?).map_err(|_| error!(AssuredError::MalformedEscrowState))
+    }
+
+    fn tiers(&mut self) -> Result<Vec<SlaTier>> {
+        let len = self.varint()?;
+        let mut tiers = Vec::new();
+        for _ in 0..len {
+            tiers.push(SlaTier {
+                deadline_factor_bps: u32::try_from(self.varint()?)
+                    .map_err(|_| error!(AssuredError::MalformedEscrowState))?,
+                multiplier_bps: self.short()?,
+            });
+        }
+        Ok(tiers)
+    }
+
+    fn keys(&mut self) -> Result<Vec<Pubkey>> {
+        let len = self.varint()?;
+        let mut keys = Vec::new();
+        for _ in 0..len {
+            keys.push(self.pubkey()?);
+        }
+        Ok(keys)
+    }
+
+    fn splits(&mut self) -> Result<Vec<PayoutSplit>> {
+        let len = self.varint()?;
+        let mut splits = Vec::new();
+        for _ in 0..len {
+            splits.push(PayoutSplit {
+                recipient: self.pubkey()?,
+                bps: self.short()?,
+            });
+        }
+        Ok(splits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arbitrary::{Arbitrary, Unstructured};
+
+    fn arbitrary_call(u: &mut Unstructured) -> arbitrary::Result<EscrowCall> {
+        Ok(EscrowCall {
+            version: u.arbitrary()?,
+            call_id: String::arbitrary(u)?,
+            payer: Pubkey::new_from_array(u.arbitrary()?),
+            provider: Pubkey::new_from_array(u.arbitrary()?),
+            state: CallState {
+                service_id: String::arbitrary(u)?,
+                amount: u.arbitrary()?,
+                start_ts: u.arbitrary()?,
+                sla_ms: u.arbitrary()?,
+                dispute_window_s: u.arbitrary()?,
+                status: u.arbitrary()?,
+                delivered_ts: u.arbitrary()?,
+                response_hash: u.arbitrary()?,
+                disputed: u.arbitrary()?,
+                total_units: u.arbitrary()?,
+                units_released: u.arbitrary()?,
+                provider_sig: u.arbitrary()?,
+                last_heartbeat_ts: u.arbitrary()?,
+                refund_after_ts: u.arbitrary()?,
+                heartbeat_interval_s: u.arbitrary()?,
+                heartbeat_required: u.arbitrary()?,
+                appeal_deadline_ts: u.arbitrary()?,
+                appeal_filed_by: Option::<[u8; 32]>::arbitrary(u)?.map(Pubkey::new_from_array),
+                ruling: u.arbitrary()?,
+                appeal_resolved: u.arbitrary()?,
+                provider_attest_key: u.arbitrary()?,
+                requires_proofs: u.arbitrary()?,
+                remainder_policy: u.arbitrary()?,
+                rent_recipient: Pubkey::new_from_array(u.arbitrary()?),
+                disputed_units: u.arbitrary()?,
+                max_partials: u.arbitrary()?,
+                partial_count: u.arbitrary()?,
+                disputed_at_ts: u.arbitrary()?,
+                provider_proof_hash: u.arbitrary()?,
+                hashlock: u.arbitrary()?,
+                payout_splits: arbitrary_splits(u)?,
+                referrer: Option::<[u8; 32]>::arbitrary(u)?.map(Pubkey::new_from_array),
+                referral_fee_bps: u.arbitrary()?,
+                provider_eth_address: u.arbitrary()?,
+                dispute_bond: u.arbitrary()?,
+                finalized_at: u.arbitrary()?,
+                outstanding_tracked: u.arbitrary()?,
+                billing_mode: u.arbitrary()?,
+                response_size_bytes: u.arbitrary()?,
+                sig_mode: u.arbitrary()?,
+                expiry_ts: u.arbitrary()?,
+                content_cid: u.arbitrary()?,
+                late_penalty_bps: u.arbitrary()?,
+                sla_tiers: arbitrary_tiers(u)?,
+                last_chunk_nonce: u.arbitrary()?,
+                redemption_code: u.arbitrary()?,
+                arbiters: arbitrary_keys(u)?,
+                arbiter_threshold: u.arbitrary()?,
+                dispute_kind: u.arbitrary()?,
+                dispute_bond_state: u.arbitrary()?,
+                fulfilled_at: u.arbitrary()?,
+                settled_at: u.arbitrary()?,
+                last_chunk_ts: u.arbitrary()?,
+            },
+        })
+    }
+
+    fn arbitrary_splits(u: &mut Unstructured) -> arbitrary::Result<Vec<PayoutSplit>> {
+        let len = u.int_in_range(0..=crate::MAX_PAYOUT_SPLITS)?;
+        (0..len)
+            .map(|_| {
+                Ok(PayoutSplit {
+                    recipient: Pubkey::new_from_array(u.arbitrary()?),
+                    bps: u.arbitrary()?,
+                })
+            })
+            .collect()
+    }
+
+    fn arbitrary_keys(u: &mut Unstructured) -> arbitrary::Result<Vec<Pubkey>> {
+        let len = u.int_in_range(0..=crate::MAX_ARBITERS)?;
+        (0..len)
+            .map(|_| Ok(Pubkey::new_from_array(u.arbitrary()?)))
+            .collect()
+    }
+
+    fn arbitrary_tiers(u: &mut Unstructured) -> arbitrary::Result<Vec<SlaTier>> {
+        let len = u.int_in_range(0..=crate::MAX_SLA_TIERS)?;
+        (0..len)
+            .map(|_| {
+                Ok(SlaTier {
+                    deadline_factor_bps: u.arbitrary()?,
+                    multiplier_bps: u.arbitrary()?,
+                })
+            })
+            .collect()
+    }
+
+    /// xorshift64 keeps the fuzz corpus deterministic without pulling in rand.
+    fn seeded_bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed.max(1);
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_fuzzed_calls() {
+        for seed in 1..=256u64 {
+            let raw = seeded_bytes(seed, 512);
+            let mut u = Unstructured::new(&raw);
+            let ec = arbitrary_call(&mut u).unwrap();
+            let packed = pack_escrow_state(&ec);
+            let unpacked = unpack_escrow_state(&packed).unwrap();
+            assert_eq!(unpacked.try_to_vec().unwrap(), ec.try_to_vec().unwrap());
+        }
+    }
+
+    #[test]
+    fn round_trips_content_cid() {
+        let raw = seeded_bytes(11, 512);
+        let mut ec = arbitrary_call(&mut Unstructured::new(&raw)).unwrap();
+        ec.content_cid = b"ar://bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_U".to_vec();
+        let unpacked = unpack_escrow_state(&pack_escrow_state(&ec)).unwrap();
+        assert_eq!(unpacked.content_cid, ec.content_cid);
+        assert_eq!(unpacked.try_to_vec().unwrap(), ec.try_to_vec().unwrap());
+    }
+
+    #[test]
+    fn rejects_truncated_and_trailing_bytes() {
+        let raw = seeded_bytes(7, 512);
+        let ec = arbitrary_call(&mut Unstructured::new(&raw)).unwrap();
+        let packed = pack_escrow_state(&ec);
+        assert!(unpack_escrow_state(&packed[..packed.len() - 1]).is_err());
+
+        let mut trailing = packed.clone();
+        trailing.push(0);
+        assert!(unpack_escrow_state(&trailing).is_err());
+    }
+
+    #[test]
+    fn varint_round_trips_extremes() {
+        for value in [i64::MIN, -1, 0, 1, i64::MAX] {
+            let mut out = Vec::new();
+            put_ivarint(&mut out, value);
+            let mut r = Reader {
+                bytes: &out,
+                pos: 0,
+            };
+            assert_eq!(r.ivarint().unwrap(), value);
+        }
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut out = Vec::new();
+            put_varint(&mut out, value);
+            let mut r = Reader {
+                bytes: &out,
+                pos: 0,
+            };
+            assert_eq!(r.varint().unwrap(), value);
+            assert_eq!(r.pos, out.len());
+        }
+    }
+}
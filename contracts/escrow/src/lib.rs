@@ -1,7 +1,89 @@
 use anchor_lang::prelude::*;
-use anchor_lang::system_program::{self, Transfer};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::system_program::{self, CreateAccount, Transfer};
+use solana_instructions_sysvar::{get_instruction_relative, load_current_index_checked};
+use solana_sha256_hasher::{hash, hashv};
+
+/// Canonical signing payloads; new signature checks must encode through
+/// these rather than adding another ad hoc `*_message` layout.
+pub use assured_core as payload;
+#[cfg(not(target_os = "solana"))]
+pub mod codec;
 
 const MAX_PROVIDER_SIG_LEN: usize = 128;
+/// Preimages are kept in `provider_sig`, so they share its bound.
+pub const MAX_PREIMAGE_LEN: usize = MAX_PROVIDER_SIG_LEN;
+const MAX_CALL_ID_LEN: usize = 64;
+const MAX_SERVICE_ID_LEN: usize = 64;
+const MIN_COMPLIANCE_BPS: u16 = 5_000;
+const MAX_APPROVED_PROVIDERS: usize = 8;
+const MAX_APPROVED_REFERRERS: usize = 8;
+const MAX_FEE_BPS: u16 = 10_000;
+const DISPUTE_NO_RESPONSE: u8 = 1;
+/// Bumped to 2 when `payer`/`provider` moved ahead of `call_id`, and to 3
+/// for the fields appended from `finalized_at` on. `migrate_escrow` brings
+/// older accounts up to date.
+pub const ESCROW_CALL_VERSION: u8 = 3;
+/// Byte offsets (discriminator included) for `getProgramAccounts` filters.
+pub const ESCROW_CALL_PAYER_OFFSET: usize = 8 + 1;
+pub const ESCROW_CALL_PROVIDER_OFFSET: usize = ESCROW_CALL_PAYER_OFFSET + 32;
+pub const MAX_BATCH_INIT: usize = 5;
+/// Escrow PDA, provider, reputation service, the owner's allowlist, the
+/// provider's call index and the service's stats (or the escrow program
+/// id to skip them).
+pub const BATCH_ACCOUNTS_PER_LEG: usize = 6;
+pub const MAX_SLA_MS: u64 = 30 * 24 * 60 * 60 * 1_000;
+pub const MAX_DISPUTE_WINDOW_S: u64 = 90 * 24 * 60 * 60;
+pub const MAX_TOTAL_UNITS: u64 = 100_000;
+pub const MAX_PROOF_CHUNKS: usize = 64;
+pub const DEFAULT_MAX_PARTIALS: u16 = 64;
+pub const MAX_BLACKLIST_SIZE: usize = 256;
+pub const MAX_ALLOWLIST_SIZE: usize = 64;
+pub const PAYER_INDEX_LEN: usize = 32;
+pub const MAX_INDEX_ENTRIES: usize = 64;
+/// Debits a channel holds before they mature past the dispute window.
+pub const MAX_CHANNEL_PENDING: usize = 16;
+pub const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+pub const APPEAL_WINDOW_S: i64 = 2 * 24 * 60 * 60;
+/// How long past the dispute window a fulfilled call waits for `settle`
+/// before anyone may refund it with `timeout_refund`.
+pub const SETTLE_GRACE_PERIOD_S: u64 = 7 * 24 * 60 * 60;
+/// How long a settled call stays readable before `finalize` may close it.
+pub const FINALIZE_DELAY_S: i64 = 60 * 60;
+/// Paid out of the closed escrow's rent to whoever submits `timeout_refund`.
+pub const SETTLE_BOUNTY_LAMPORTS: u64 = 10_000;
+/// How long past the SLA an unfulfilled call waits before `trigger_expiry`
+/// may refund it. `ConfigParams::validate` keeps `fulfill_grace_s` below
+/// it, so expiry never cuts into the fulfill window.
+pub const EXPIRY_GRACE_S: u64 = 24 * 60 * 60;
+/// Paid out of the closed escrow's rent to whoever submits `trigger_expiry`.
+pub const EXPIRY_BOUNTY_LAMPORTS: u64 = 10_000;
+pub const MAX_PAYOUT_SPLITS: usize = 4;
+pub const MAX_MULTISIG_SIGNERS: usize = 5;
+/// How long a multisig call may wait for approvals before its payer can
+/// cancel it and take the parked amount back.
+pub const MULTISIG_APPROVAL_WINDOW_S: u64 = 7 * 24 * 60 * 60;
+pub const MAX_SLA_TIERS: usize = 4;
+pub const MAX_EVIDENCE_PER_PARTY: usize = 4;
+pub const MAX_VOUCHERS: usize = 32;
+pub const MAX_ARBITERS: usize = 5;
+/// How long an arbiter committee has to reach quorum after a dispute is
+/// raised before `finalize_resolution` falls back to a refund.
+pub const COMMITTEE_VOTE_WINDOW_S: i64 = 7 * 24 * 60 * 60;
+/// `collect_orphan_rent` PDA kinds.
+pub const ORPHAN_PROOF_SET: u8 = 0;
+pub const ORPHAN_EVIDENCE: u8 = 1;
+/// Reviews live in the reputation program, so this kind is always rejected.
+pub const ORPHAN_REVIEW: u8 = 2;
+/// Room for a CIDv1 in base32 (59 chars) or an Arweave tx id (43).
+pub const MAX_CONTENT_CID_LEN: usize = 64;
+const BPS_DENOMINATOR: u64 = 10_000;
+pub const MAX_REFERRAL_FEE_BPS: u16 = 1_000;
+/// Refunds on escrows above this may be topped up from the insurance pool.
+pub const INSURANCE_THRESHOLD_LAMPORTS: u64 = 1_000_000_000;
+pub const MAX_CLAIMS_PER_EPOCH: u32 = 10;
+/// Claim cap a new insurance pool starts with.
+pub const DEFAULT_INSURANCE_MAX_CLAIM_BPS: u16 = 5_000;
 
 declare_id!("6zpAcx4Yo9MmDf4w8pBGez8bm47zyKuyjr5Y5QkC3ayL");
 
@@ -9,6 +91,10 @@ declare_id!("6zpAcx4Yo9MmDf4w8pBGez8bm47zyKuyjr5Y5QkC3ayL");
 pub mod escrow {
     use super::*;
 
+    /// Opens an escrow. Everything past the core call terms is optional and
+    /// travels in `params`; `InitPaymentParams::default()` gives a plain
+    /// all-or-nothing escrow.
+    #[allow(clippy::too_many_arguments)]
     pub fn init_payment(
         ctx: Context<InitPayment>,
         call_id: String,
@@ -17,8 +103,96 @@ pub mod escrow {
         sla_ms: u64,
         dispute_window_s: u64,
         total_units: u64,
+        params: InitPaymentParams,
     ) -> Result<()> {
+        let InitPaymentParams {
+            refund_after_ts,
+            heartbeat_interval_s,
+            heartbeat_required,
+            remainder_policy,
+            rent_recipient,
+            hashlock,
+            referrer,
+            referral_fee_bps,
+            min_reputation_bps,
+            billing_mode,
+            sig_mode,
+            sla_tiers,
+            redemption_code,
+            arbiters,
+            arbiter_threshold,
+        } = params;
+        ensure_call_id_free(&ctx.accounts.escrow_call)?;
+        validate_call_params(amount, sla_ms, dispute_window_s, total_units)?;
+        validate_arbiters(&arbiters, arbiter_threshold)?;
+        validate_sla_tiers(&sla_tiers)?;
+        validate_referral_fee(referral_fee_bps)?;
+        check_referrer(
+            referrer,
+            referral_fee_bps,
+            ctx.accounts.referrers.as_deref().map(|list| &**list),
+        )?;
+        validate_counterparties(&ctx.accounts.payer.key(), &ctx.accounts.provider.key())?;
+        require!(
+            remainder_policy <= RemainderPolicy::LastUnits as u8,
+            AssuredError::InvalidRemainderPolicy
+        );
+        let provider_keys = ctx.accounts.provider_keys.as_deref();
+        let provider_attest_key = registered_attest_key(provider_keys);
+        let (sig_mode, provider_eth_address) = resolve_sig_mode(sig_mode, provider_keys)?;
+        require!(
+            !heartbeat_required || heartbeat_interval_s > 0,
+            AssuredError::InvalidHeartbeatInterval
+        );
+        check_init_gates(
+            &ctx.accounts.config,
+            &ctx.accounts.service,
+            &ctx.accounts.provider.key(),
+            ctx.accounts.approval.as_deref(),
+            &ctx.accounts.blacklist,
+            &ctx.accounts.allowlist,
+            &InitTerms {
+                amount,
+                sla_ms,
+                dispute_window_s,
+                total_units,
+                billing_mode,
+                min_reputation_bps,
+                now: Clock::get()?.unix_timestamp,
+            },
+        )?;
+        let discount_amount = match &redemption_code {
+            Some(code) => {
+                let registry = ctx
+                    .accounts
+                    .voucher_registry
+                    .as_mut()
+                    .ok_or(AssuredError::VoucherRegistryRequired)?;
+                voucher_discount(amount, registry.redeem(code)?)
+            }
+            None => 0,
+        };
+        // The price check above runs on the undiscounted amount; the payer
+        // escrows what is left after the voucher.
+        let amount = amount - discount_amount;
+        check_payer_funds(
+            ctx.accounts.payer.lamports(),
+            amount,
+            Rent::get()?.minimum_balance(0),
+        )?;
+        let accounts = &ctx.accounts;
+        let outstanding_tracked = claim_outstanding_slot(
+            &accounts.service,
+            &accounts.service.to_account_info(),
+            reputation_cpi(
+                &accounts.reputation_config,
+                &accounts.reputation_program,
+                &accounts.reputation_authority,
+            ),
+            &service_id,
+        )?;
         let ec = &mut ctx.accounts.escrow_call;
+        ec.version = ESCROW_CALL_VERSION;
         ec.call_id = call_id;
         ec.payer = ctx.accounts.payer.key();
         ec.service_id = service_id;
@@ -27,24 +201,646 @@ pub mod escrow {
         ec.start_ts = Clock::get()?.unix_timestamp as u64;
         ec.sla_ms = sla_ms;
         ec.dispute_window_s = dispute_window_s;
-        ec.total_units = total_units.max(1);
+        ec.refund_after_ts = match refund_after_ts {
+            Some(deadline) => {
+                require!(deadline > ec.start_ts, AssuredError::InvalidDeadline);
+                deadline
+            }
+            None => default_refund_after_ts(ec.start_ts, sla_ms, dispute_window_s),
+        };
+        ec.total_units = total_units;
         ec.units_released = 0;
         ec.provider_sig = Vec::new();
         ec.status = Status::Init as u8;
+        ec.last_heartbeat_ts = 0;
+        ec.heartbeat_interval_s = heartbeat_interval_s;
+        ec.heartbeat_required = heartbeat_required;
+        ec.appeal_deadline_ts = 0;
+        ec.appeal_filed_by = None;
+        ec.ruling = None;
+        ec.appeal_resolved = false;
+        ec.provider_attest_key = provider_attest_key;
+        ec.requires_proofs = false;
+        ec.remainder_policy = remainder_policy;
+        ec.rent_recipient = rent_recipient.unwrap_or(ec.payer);
+        ec.disputed_units = 0;
+        ec.max_partials = DEFAULT_MAX_PARTIALS;
+        ec.partial_count = 0;
+        ec.disputed_at_ts = 0;
+        ec.provider_proof_hash = [0; 32];
+        ec.hashlock = hashlock;
+        ec.payout_splits = Vec::new();
+        ec.referrer = referrer;
+        ec.referral_fee_bps = referral_fee_bps;
+        ec.provider_eth_address = provider_eth_address;
+        ec.dispute_bond = 0;
+        ec.finalized_at = 0;
+        ec.outstanding_tracked = outstanding_tracked;
+        ec.billing_mode = billing_mode;
+        ec.response_size_bytes = 0;
+        ec.sig_mode = sig_mode;
+        ec.expiry_ts = default_expiry_ts(ec.start_ts, sla_ms);
+        if hashlock != [0u8; 32] {
+            // Never cut short the window the provider has to redeem.
+            ec.expiry_ts = ec.expiry_ts.max(ec.refund_after_ts);
+        }
+        ec.content_cid = Vec::new();
+        ec.late_penalty_bps = ctx.accounts.config.late_penalty_bps;
+        ec.sla_tiers = sla_tiers;
+        ec.last_chunk_nonce = [0; 8];
+        ec.redemption_code = redemption_code;
+        ec.arbiters = arbiters;
+        ec.arbiter_threshold = arbiter_threshold;
+        ec.dispute_kind = 0;
+        ec.dispute_bond_state = 0;
+        ec.fulfilled_at = 0;
+        ec.settled_at = 0;
+        ec.last_chunk_ts = 0;
+        if let Some(code) = redemption_code {
+            emit!(VoucherRedeemed {
+                call_id: ec.call_id.clone(),
+                code,
+                discount_amount,
+            });
+        }
+        if let Some(index) = &mut ctx.accounts.payer_index {
+            index.record(&ctx.accounts.escrow_call.call_id);
+        }
+        let provider_index = &mut ctx.accounts.provider_index;
+        provider_index.provider = ctx.accounts.provider.key();
+        provider_index.push(&ctx.accounts.escrow_call.call_id);
+        ctx.accounts.global_stats.record_open(amount);
+        if let Some(stats) = &mut ctx.accounts.service_stats {
+            stats.record_open(amount);
+        }
         transfer_into_escrow(
             &ctx.accounts.payer,
-            &ctx.accounts.escrow_call,
+            &ctx.accounts.escrow_call.to_account_info(),
             &ctx.accounts.system_program,
             amount,
         )?;
+        if let Some(memo_program) = &ctx.accounts.memo_program {
+            let ec = &ctx.accounts.escrow_call;
+            write_memo(memo_program, &init_memo(&ec.call_id, &ec.service_id))?;
+        }
+        Ok(())
+    }
+
+    /// Opens up to `MAX_BATCH_INIT` escrows at once. `remaining_accounts`
+    /// carries an (escrow PDA, provider, service, allowlist, provider index,
+    /// service stats) group per leg, in leg order, and each leg runs the
+    /// same gates as `init_payment`.
+    pub fn batch_init_payment<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchInit<'info>>,
+        legs: Vec<BatchInitLeg>,
+    ) -> Result<()> {
+        let space = 8 + EscrowCall::INIT_SPACE;
+        let rent_per_leg = Rent::get()?.minimum_balance(space);
+        let total_amount = plan_batch(&legs, ctx.accounts.payer.lamports(), rent_per_leg)?;
+        require!(
+            ctx.remaining_accounts.len() == legs.len() * BATCH_ACCOUNTS_PER_LEG,
+            AssuredError::BatchAccountsMismatch
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let payer = &ctx.accounts.payer;
+        let system_program = &ctx.accounts.system_program;
+        let mut call_ids = Vec::with_capacity(legs.len());
+        for (i, accounts) in ctx
+            .remaining_accounts
+            .chunks(BATCH_ACCOUNTS_PER_LEG)
+            .enumerate()
+        {
+            let leg = &legs[i];
+            let (escrow_info, provider_info) = (&accounts[0], &accounts[1]);
+            validate_counterparties(payer.key, provider_info.key)?;
+            let service = load_leg_service(&accounts[2], &leg.service_id)?;
+            check_batch_capacity(&legs[i..], &service)?;
+            let (allowlist_key, _) =
+                Pubkey::find_program_address(&[b"allowlist", service.owner.as_ref()], &crate::ID);
+            require_keys_eq!(
+                accounts[3].key(),
+                allowlist_key,
+                AssuredError::BatchAccountsMismatch
+            );
+            check_init_gates(
+                &ctx.accounts.config,
+                &service,
+                provider_info.key,
+                ctx.accounts.approval.as_deref(),
+                &ctx.accounts.blacklist,
+                &accounts[3],
+                &InitTerms {
+                    amount: leg.amount,
+                    sla_ms: leg.sla_ms,
+                    dispute_window_s: leg.dispute_window_s,
+                    total_units: leg.total_units,
+                    billing_mode: BillingMode::PerUnit as u8,
+                    min_reputation_bps: None,
+                    now,
+                },
+            )?;
+            let (expected, bump) =
+                Pubkey::find_program_address(&[b"call", leg.call_id.as_bytes()], &crate::ID);
+            require_keys_eq!(
+                escrow_info.key(),
+                expected,
+                AssuredError::BatchAccountsMismatch
+            );
+            require!(escrow_info.data_is_empty(), AssuredError::CallIdTaken);
+            let outstanding_tracked = claim_outstanding_slot(
+                &service,
+                &accounts[2],
+                reputation_cpi(
+                    &ctx.accounts.reputation_config,
+                    &ctx.accounts.reputation_program,
+                    &ctx.accounts.reputation_authority,
+                ),
+                &leg.service_id,
+            )?;
+            system_program::create_account(
+                CpiContext::new_with_signer(
+                    system_program.to_account_info(),
+                    CreateAccount {
+                        from: payer.to_account_info(),
+                        to: escrow_info.clone(),
+                    },
+                    &[&[b"call", leg.call_id.as_bytes(), &[bump]]],
+                ),
+                rent_per_leg,
+                space as u64,
+                &crate::ID,
+            )?;
+            let mut ec = batch_call(leg, payer.key(), provider_info.key(), now as u64);
+            ec.late_penalty_bps = ctx.accounts.config.late_penalty_bps;
+            ec.outstanding_tracked = outstanding_tracked;
+            ec.try_serialize(&mut &mut escrow_info.try_borrow_mut_data()?[..])?;
+            if leg.amount > 0 {
+                system_program::transfer(
+                    CpiContext::new(
+                        system_program.to_account_info(),
+                        Transfer {
+                            from: payer.to_account_info(),
+                            to: escrow_info.clone(),
+                        },
+                    ),
+                    leg.amount,
+                )?;
+            }
+            if let Some(index) = &mut ctx.accounts.payer_index {
+                index.record(&leg.call_id);
+            }
+            push_batch_provider_index(
+                &accounts[4],
+                provider_info.key,
+                &leg.call_id,
+                payer,
+                system_program,
+            )?;
+            ctx.accounts.global_stats.record_open(leg.amount);
+            record_batch_service_open(
+                &accounts[5],
+                &leg.service_id,
+                leg.amount,
+                payer,
+                system_program,
+            )?;
+            call_ids.push(leg.call_id.clone());
+        }
+        emit!(BatchInitialized {
+            call_ids,
+            total_amount
+        });
+        Ok(())
+    }
+
+    /// Opens an escrow that only goes live once `threshold` of `signers`
+    /// approve it. The amount is parked on the multisig account until
+    /// then. The init gates run now, against the terms being approved.
+    #[allow(clippy::too_many_arguments)]
+    pub fn init_multisig_payment(
+        ctx: Context<InitMultisigPayment>,
+        call_id: String,
+        service_id: String,
+        amount: u64,
+        sla_ms: u64,
+        dispute_window_s: u64,
+        total_units: u64,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        validate_call_params(amount, sla_ms, dispute_window_s, total_units)?;
+        validate_counterparties(&ctx.accounts.payer.key(), &ctx.accounts.provider.key())?;
+        check_init_gates(
+            &ctx.accounts.config,
+            &ctx.accounts.service,
+            &ctx.accounts.provider.key(),
+            ctx.accounts.approval.as_deref(),
+            &ctx.accounts.blacklist,
+            &ctx.accounts.allowlist,
+            &InitTerms {
+                amount,
+                sla_ms,
+                dispute_window_s,
+                total_units,
+                billing_mode: BillingMode::PerUnit as u8,
+                min_reputation_bps: None,
+                now: Clock::get()?.unix_timestamp,
+            },
+        )?;
+        ctx.accounts
+            .multisig
+            .set_inner(MultiSigConfig::new(&signers, threshold)?);
+        // A call awaiting approval already holds its slot, so approvals can
+        // never push the service past its cap.
+        let accounts = &ctx.accounts;
+        let outstanding_tracked = claim_outstanding_slot(
+            &accounts.service,
+            &accounts.service.to_account_info(),
+            reputation_cpi(
+                &accounts.reputation_config,
+                &accounts.reputation_program,
+                &accounts.reputation_authority,
+            ),
+            &service_id,
+        )?;
+        let leg = BatchInitLeg {
+            call_id,
+            service_id,
+            amount,
+            sla_ms,
+            dispute_window_s,
+            total_units,
+        };
+        let now = Clock::get()?.unix_timestamp as u64;
+        let mut ec = batch_call(
+            &leg,
+            ctx.accounts.payer.key(),
+            ctx.accounts.provider.key(),
+            now,
+        );
+        ec.status = Status::AwaitingApproval as u8;
+        ec.late_penalty_bps = ctx.accounts.config.late_penalty_bps;
+        ec.outstanding_tracked = outstanding_tracked;
+        let provider_index = &mut ctx.accounts.provider_index;
+        provider_index.provider = ctx.accounts.provider.key();
+        provider_index.push(&ec.call_id);
+        ctx.accounts.escrow_call.set_inner(ec);
+        ctx.accounts.global_stats.record_open(amount);
+        if let Some(stats) = &mut ctx.accounts.service_stats {
+            stats.record_open(amount);
+        }
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: ctx.accounts.multisig.to_account_info(),
+                },
+            ),
+            amount,
+        )
+    }
+
+    /// Records one signer's approval. The approval that reaches the
+    /// threshold moves the funds into the escrow and starts the SLA clock.
+    pub fn approve_payment(ctx: Context<ApproveMultisig>) -> Result<()> {
+        require!(
+            ctx.accounts.escrow_call.status == Status::AwaitingApproval as u8,
+            AssuredError::InvalidStatus
+        );
+        let signer = ctx.accounts.signer.key();
+        let unlocked = ctx.accounts.multisig.approve(&signer)?;
+        emit!(PaymentApproved {
+            call_id: ctx.accounts.escrow_call.call_id.clone(),
+            signer,
+            approved_count: ctx.accounts.multisig.approved_count,
+            threshold: ctx.accounts.multisig.threshold,
+        });
+        if !unlocked {
+            return Ok(());
+        }
+        pay_out(
+            ctx.accounts.escrow_call.amount,
+            &ctx.accounts.multisig.to_account_info(),
+            &ctx.accounts.escrow_call.to_account_info(),
+        )?;
+        let now = Clock::get()?.unix_timestamp as u64;
+        let ec = &mut ctx.accounts.escrow_call;
+        unlock_multisig_call(ec, now);
+        emit!(MultisigUnlocked {
+            call_id: ec.call_id.clone(),
+            amount: ec.amount,
+        });
+        // Its job is done; the rent goes back to the payer.
+        let payer_info = ctx.accounts.payer.to_account_info();
+        ctx.accounts.multisig.close(payer_info)
+    }
+
+    /// Lets the payer back out of a multisig call that has not reached its
+    /// threshold within `MULTISIG_APPROVAL_WINDOW_S`: the parked amount and
+    /// the rent of both accounts go back to the payer.
+    pub fn cancel_multisig_payment(ctx: Context<CancelMultisig>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp as u64;
+        check_multisig_cancel(&ctx.accounts.escrow_call, now)?;
+        let accounts = &ctx.accounts;
+        release_call_slot(
+            &accounts.escrow_call,
+            accounts.service.as_ref().map(|a| a.to_account_info()),
+            reputation_cpi(
+                &accounts.reputation_config,
+                &accounts.reputation_program,
+                &accounts.reputation_authority,
+            ),
+        )?;
+        if let Some(index) = &mut ctx.accounts.provider_index {
+            index.remove(&ctx.accounts.escrow_call.call_id);
+        }
+        ctx.accounts
+            .global_stats
+            .record_refund(ctx.accounts.escrow_call.amount, 0);
+        emit!(MultisigCancelled {
+            call_id: ctx.accounts.escrow_call.call_id.clone(),
+            amount: ctx.accounts.escrow_call.amount,
+        });
+        Ok(())
+    }
+
+    /// Withdraws a signer's approval; only possible before the threshold
+    /// is reached, since funds move the moment it is.
+    pub fn revoke_approval(ctx: Context<ApproveMultisig>) -> Result<()> {
+        require!(
+            ctx.accounts.escrow_call.status == Status::AwaitingApproval as u8,
+            AssuredError::InvalidStatus
+        );
+        let signer = ctx.accounts.signer.key();
+        ctx.accounts.multisig.revoke(&signer)?;
+        emit!(ApprovalRevoked {
+            call_id: ctx.accounts.escrow_call.call_id.clone(),
+            signer,
+            approved_count: ctx.accounts.multisig.approved_count,
+        });
+        Ok(())
+    }
+
+    pub fn initialize_config(ctx: Context<InitializeConfig>, params: ConfigParams) -> Result<()> {
+        params.validate()?;
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.apply(&params);
+        emit!(config.updated_event());
+        Ok(())
+    }
+
+    /// Reallocs a config written before its later fields existed and
+    /// rewrites it from `params`. Older layouts did not only append
+    /// (`fulfill_grace_s` went in ahead of `paused`), so nothing past the
+    /// admin key is carried over.
+    pub fn migrate_config(ctx: Context<MigrateConfig>, params: ConfigParams) -> Result<()> {
+        params.validate()?;
+        let info = ctx.accounts.config.to_account_info();
+        let admin = {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() >= 8 + 32 && data[..8] == *Config::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+            require!(
+                data.len() < 8 + Config::INIT_SPACE,
+                AssuredError::AlreadyMigrated
+            );
+            Pubkey::try_from(&data[8..40]).map_err(|_| AssuredError::MalformedEscrowState)?
+        };
+        require_keys_eq!(ctx.accounts.admin.key(), admin, AssuredError::Unauthorized);
+        let mut config = Config {
+            admin,
+            ..Config::default()
+        };
+        config.apply(&params);
+        resize_account(
+            &info,
+            &ctx.accounts.admin,
+            &ctx.accounts.system_program,
+            8 + Config::INIT_SPACE,
+        )?;
+        config.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+        emit!(config.updated_event());
+        Ok(())
+    }
+
+    pub fn update_config(ctx: Context<UpdateConfig>, params: ConfigParams) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.check_admin(&ctx.accounts.admin.key())?;
+        params.validate()?;
+        config.apply(&params);
+        emit!(config.updated_event());
+        Ok(())
+    }
+
+    /// Creates the payer's call index. Payers who skip it simply don't pass
+    /// `payer_index` to `init_payment`/`settle`.
+    pub fn init_payer_index(ctx: Context<InitPayerIndex>) -> Result<()> {
+        ctx.accounts.payer_index.payer = ctx.accounts.payer.key();
+        Ok(())
+    }
+
+    /// Emits the provider's open call ids, oldest first, for clients to page
+    /// through with `simulateTransaction`.
+    pub fn get_provider_calls(ctx: Context<QueryIndex>) -> Result<()> {
+        let index = &ctx.accounts.provider_index;
+        emit!(CallIndexSnapshot {
+            provider: index.provider,
+            call_ids: index.active(),
+        });
+        Ok(())
+    }
+
+    /// Registers the key the provider signs attestations with. Escrows and
+    /// channels opened afterwards copy it, so consent checks such as
+    /// `abort` only ever trust a key the provider chose.
+    pub fn set_provider_attest_key(
+        ctx: Context<SetProviderKeys>,
+        attest_key: [u8; 32],
+    ) -> Result<()> {
+        let keys = &mut ctx.accounts.provider_keys;
+        keys.provider = ctx.accounts.provider.key();
+        keys.attest_key = attest_key;
+        Ok(())
+    }
+
+    /// Registers the Ethereum address the provider signs with under
+    /// `SigMode::Secp256k1`; `init_payment` copies it onto the call.
+    pub fn set_provider_eth_address(
+        ctx: Context<SetProviderKeys>,
+        eth_address: [u8; 20],
+    ) -> Result<()> {
+        let keys = &mut ctx.accounts.provider_keys;
+        keys.provider = ctx.accounts.provider.key();
+        keys.eth_address = eth_address;
+        Ok(())
+    }
+
+    pub fn approve_provider(ctx: Context<ManageApproval>, provider: Pubkey) -> Result<()> {
+        let approval = &mut ctx.accounts.approval;
+        if approval.payer == Pubkey::default() {
+            approval.payer = ctx.accounts.payer.key();
+        }
+        approval.approve(provider)
+    }
+
+    pub fn revoke_provider_approval(ctx: Context<ManageApproval>, provider: Pubkey) -> Result<()> {
+        ctx.accounts.approval.revoke(&provider)
+    }
+
+    /// Provider approves a referrer; `init_payment` only accepts a paid
+    /// referral to one of these.
+    pub fn approve_referrer(ctx: Context<ManageReferrers>, referrer: Pubkey) -> Result<()> {
+        let referrers = &mut ctx.accounts.referrers;
+        if referrers.provider == Pubkey::default() {
+            referrers.provider = ctx.accounts.provider.key();
+        }
+        referrers.approve(referrer)
+    }
+
+    pub fn revoke_referrer(ctx: Context<ManageReferrers>, referrer: Pubkey) -> Result<()> {
+        ctx.accounts.referrers.revoke(&referrer)
+    }
+
+    /// The first call creates the singleton and binds it to the config
+    /// admin; later calls must come from that authority.
+    pub fn add_to_blacklist(ctx: Context<ManageBlacklist>, provider: Pubkey) -> Result<()> {
+        let blacklist = &mut ctx.accounts.blacklist;
+        if blacklist.authority == Pubkey::default() {
+            ctx.accounts
+                .config
+                .check_admin(&ctx.accounts.authority.key())?;
+            blacklist.authority = ctx.accounts.authority.key();
+        }
+        blacklist.check_authority(&ctx.accounts.authority.key())?;
+        blacklist.add(provider)?;
+        emit!(ProviderBlacklisted { provider });
+        Ok(())
+    }
+
+    pub fn remove_from_blacklist(ctx: Context<ManageBlacklist>, provider: Pubkey) -> Result<()> {
+        let blacklist = &mut ctx.accounts.blacklist;
+        blacklist.check_authority(&ctx.accounts.authority.key())?;
+        blacklist.remove(&provider)?;
+        emit!(ProviderUnblacklisted { provider });
+        Ok(())
+    }
+
+    /// Lets `provider` be named on calls to the signer's services. The
+    /// allowlist is created on first use but only enforced once enabled.
+    pub fn add_to_allowlist(ctx: Context<ManageAllowlist>, provider: Pubkey) -> Result<()> {
+        let allowlist = &mut ctx.accounts.allowlist;
+        allowlist.owner = ctx.accounts.owner.key();
+        allowlist.add(provider)?;
+        emit!(ProviderAllowlisted {
+            owner: allowlist.owner,
+            provider,
+        });
+        Ok(())
+    }
+
+    pub fn remove_from_allowlist(ctx: Context<ManageAllowlist>, provider: Pubkey) -> Result<()> {
+        let allowlist = &mut ctx.accounts.allowlist;
+        allowlist.remove(&provider)?;
+        emit!(ProviderUnallowlisted {
+            owner: allowlist.owner,
+            provider,
+        });
+        Ok(())
+    }
+
+    /// Opts the signer's services in or out of allowlist enforcement.
+    pub fn set_allowlist_enabled(ctx: Context<ManageAllowlist>, enabled: bool) -> Result<()> {
+        let allowlist = &mut ctx.accounts.allowlist;
+        allowlist.owner = ctx.accounts.owner.key();
+        allowlist.enabled = enabled;
+        emit!(AllowlistToggled {
+            owner: allowlist.owner,
+            enabled,
+        });
+        Ok(())
+    }
+
+    /// Adds a one-time voucher to the signer's registry, created on first
+    /// use. Payers redeem it through `init_payment` on calls to the
+    /// registry authority as provider.
+    pub fn issue_voucher(
+        ctx: Context<IssueVoucher>,
+        code: [u8; 16],
+        discount_bps: u16,
+    ) -> Result<()> {
+        let registry = &mut ctx.accounts.voucher_registry;
+        registry.authority = ctx.accounts.authority.key();
+        registry.issue(code, discount_bps)?;
+        emit!(VoucherIssued {
+            authority: registry.authority,
+            code,
+            discount_bps,
+        });
         Ok(())
     }
 
+    /// Records delivery with a signature over `payload::FulfillPayload`, or
+    /// the legacy `attestation_message`. Data-volume calls must use
+    /// `fulfill_with_data`, which signs the size.
     pub fn fulfill(
         ctx: Context<Fulfill>,
         response_hash: [u8; 32],
         ts: u64,
         provider_sig: Vec<u8>,
+    ) -> Result<()> {
+        check_size_unsigned(&ctx.accounts.escrow_call)?;
+        let messages = fulfill_messages(&ctx.accounts.escrow_call.call_id, &response_hash, ts);
+        fulfill_call(
+            ctx.accounts,
+            response_hash,
+            ts,
+            provider_sig,
+            0,
+            Vec::new(),
+            &messages,
+        )
+    }
+
+    /// `fulfill` with the delivered byte count and where the response is
+    /// stored, both covered by the signature through `data_attestation_message`.
+    pub fn fulfill_with_data(
+        ctx: Context<Fulfill>,
+        response_hash: [u8; 32],
+        ts: u64,
+        provider_sig: Vec<u8>,
+        response_size_bytes: u64,
+        content_cid: Vec<u8>,
+    ) -> Result<()> {
+        let message = data_attestation_message(
+            &ctx.accounts.escrow_call.call_id,
+            &response_hash,
+            ts,
+            response_size_bytes,
+            &content_cid,
+        );
+        fulfill_call(
+            ctx.accounts,
+            response_hash,
+            ts,
+            provider_sig,
+            response_size_bytes,
+            content_cid,
+            &[message],
+        )
+    }
+
+    /// Opts a call into chunked delivery: `fulfill`, and the
+    /// `fulfill_partial` that would release the last unit, are rejected
+    /// until every chunk proof has been submitted and
+    /// `verify_complete_proofs` has run.
+    pub fn init_proof_set(
+        ctx: Context<InitProofSet>,
+        call_id: String,
+        expected_chunks: u32,
     ) -> Result<()> {
         let ec = &mut ctx.accounts.escrow_call;
         require!(ec.status == Status::Init as u8, AssuredError::InvalidStatus);
@@ -54,32 +850,57 @@ pub mod escrow {
             AssuredError::InvalidProvider
         );
         require!(
-            provider_sig.len() <= MAX_PROVIDER_SIG_LEN,
-            AssuredError::SignatureTooLong
+            expected_chunks > 0 && expected_chunks as usize <= MAX_PROOF_CHUNKS,
+            AssuredError::InvalidChunkCount
         );
-        ec.response_hash = response_hash;
-        ec.delivered_ts = Some(ts);
-        ec.status = Status::Fulfilled as u8;
-        ec.units_released = ec.total_units;
-        ec.provider_sig = provider_sig.clone();
-        emit!(Fulfilled {
-            call_id: ec.call_id.clone(),
-            ts
-        });
-        emit!(TraceSaved {
-            call_id: ec.call_id.clone(),
-            response_hash,
-            provider_sig,
+        ec.requires_proofs = true;
+        let proof_set = &mut ctx.accounts.proof_set;
+        proof_set.call_id = call_id;
+        proof_set.rent_payer = ctx.accounts.provider.key();
+        proof_set.chunk_count = expected_chunks;
+        proof_set.submitted_count = 0;
+        proof_set.proof_hashes = vec![[0u8; 32]; expected_chunks as usize];
+        proof_set.verified = false;
+        Ok(())
+    }
+
+    pub fn submit_chunk_proof(
+        ctx: Context<SubmitChunkProof>,
+        chunk_index: u32,
+        hash: [u8; 32],
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.provider.key(),
+            ctx.accounts.escrow_call.provider,
+            AssuredError::InvalidProvider
+        );
+        ctx.accounts.proof_set.record_chunk(chunk_index, hash)
+    }
+
+    pub fn verify_complete_proofs(ctx: Context<SubmitChunkProof>) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.provider.key(),
+            ctx.accounts.escrow_call.provider,
+            AssuredError::InvalidProvider
+        );
+        let proof_set = &mut ctx.accounts.proof_set;
+        proof_set.mark_verified()?;
+        emit!(ProofSetComplete {
+            call_id: proof_set.call_id.clone(),
+            chunk_count: proof_set.chunk_count,
         });
         Ok(())
     }
 
-    pub fn fulfill_partial(
-        ctx: Context<Fulfill>,
+    /// When the call has payout splits, each recipient must be passed as a
+    /// writable account in `remaining_accounts`.
+    pub fn fulfill_partial<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Fulfill<'info>>,
         chunk_hash: [u8; 32],
         units: u64,
         ts: u64,
         provider_sig: Vec<u8>,
+        content_cid: Vec<u8>,
     ) -> Result<()> {
         require_keys_eq!(
             ctx.accounts.provider.key(),
@@ -90,6 +911,24 @@ pub mod escrow {
             provider_sig.len() <= MAX_PROVIDER_SIG_LEN,
             AssuredError::SignatureTooLong
         );
+        validate_content_cid(&content_cid)?;
+        require!(
+            ctx.accounts.escrow_call.hashlock == [0u8; 32],
+            AssuredError::HashlockRequiresRedeem
+        );
+        // Each chunk is signed like `fulfill`, per the call's `SigMode`.
+        let ec = &ctx.accounts.escrow_call;
+        verify_attestation(
+            ec,
+            &ctx.accounts.instructions,
+            &chunk_message(ec, chunk_hash, units, ts, &content_cid),
+            &provider_sig,
+        )?;
+        if ec.units_released.saturating_add(units) >= ec.total_units {
+            check_proof_set(ec, ctx.accounts.proof_set.as_deref())?;
+        }
+        let now = Clock::get()?.unix_timestamp as u64;
+        check_fulfill_deadline(ec, now, ctx.accounts.config.fulfill_grace_s)?;
 
         let result = apply_partial_release(
             &mut ctx.accounts.escrow_call,
@@ -97,468 +936,10838 @@ pub mod escrow {
             units,
             ts,
             &provider_sig,
+            &content_cid,
+            now,
         )?;
 
         if result.payout > 0 {
             let escrow_info = ctx.accounts.escrow_call.to_account_info();
+            let rent = Rent::get()?.minimum_balance(escrow_info.data_len());
+            require!(
+                escrow_info.lamports().saturating_sub(result.payout) >= rent,
+                AssuredError::PayoutExceedsAmount
+            );
+            let referrer_info = ctx.accounts.referrer.as_ref().map(|a| a.to_account_info());
+            let provider_payout = pay_referral(
+                &ctx.accounts.escrow_call,
+                result.payout,
+                &escrow_info,
+                referrer_info.as_ref(),
+            )?;
             let provider_info = ctx.accounts.provider.to_account_info();
-            pay_out(result.payout, &escrow_info, &provider_info)?;
+            pay_out_split(
+                provider_payout,
+                &escrow_info,
+                &provider_info,
+                &ctx.accounts.escrow_call.payout_splits,
+                ctx.remaining_accounts,
+            )?;
         }
 
+        // Only the final chunk's CID is kept; it locates the whole response.
+        if result.emit_trace {
+            ctx.accounts.escrow_call.content_cid = content_cid.clone();
+        }
         let ec = &ctx.accounts.escrow_call;
-        emit!(PartialReleased {
-            call_id: ec.call_id.clone(),
-            units: result.units,
-            total_units: result.total_units,
-        });
+        emit!(partial_released_event(ec, result.units));
         if result.emit_trace {
             emit!(TraceSaved {
                 call_id: ec.call_id.clone(),
                 response_hash: chunk_hash,
                 provider_sig,
+                content_cid,
             });
         }
         Ok(())
     }
 
-    pub fn raise_dispute(
-        ctx: Context<RaiseDispute>,
-        kind: u8, // enum: 0 LATE, 1 NO_RESPONSE, 2 BAD_PROOF, 3 MISMATCH_HASH
-        reason_hash: [u8; 32],
-        _reporter_sig: Vec<u8>,
-    ) -> Result<()> {
-        let ec = &mut ctx.accounts.escrow_call;
-        // TODO: verify reporter_sig over (call_id, kind, reason_hash)
+    /// Settles a hash-locked call in one step: the provider reveals the
+    /// preimage and is paid at once. There is no dispute window since the
+    /// preimage is the proof of delivery.
+    pub fn redeem(ctx: Context<Redeem>, preimage: Vec<u8>) -> Result<()> {
         require_keys_eq!(
-            ctx.accounts.reporter.key(),
-            ec.payer,
-            AssuredError::InvalidReporter
-        );
-        require!(
-            ec.status == Status::Init as u8 || ec.status == Status::Fulfilled as u8,
-            AssuredError::InvalidStatus
+            ctx.accounts.provider.key(),
+            ctx.accounts.escrow_call.provider,
+            AssuredError::InvalidProvider
         );
-        ec.disputed = true;
-        emit!(Disputed {
+        let now = Clock::get()?.unix_timestamp as u64;
+        let payout = redeem_hashlock(&mut ctx.accounts.escrow_call, &preimage, now)?;
+        if payout > 0 {
+            let escrow_info = ctx.accounts.escrow_call.to_account_info();
+            let provider_info = ctx.accounts.provider.to_account_info();
+            pay_out(payout, &escrow_info, &provider_info)?;
+        }
+        let ec = &ctx.accounts.escrow_call;
+        emit!(Redeemed {
             call_id: ec.call_id.clone(),
-            kind,
-            reason_hash
+            amount_to_provider: payout,
+        });
+        emit!(TraceSaved {
+            call_id: ec.call_id.clone(),
+            response_hash: ec.hashlock,
+            provider_sig: preimage,
+            content_cid: ec.content_cid.clone(),
         });
         Ok(())
     }
 
-    pub fn settle(ctx: Context<Settle>) -> Result<()> {
+    /// Opens a payment channel funded once with `budget`, so many small
+    /// calls to one provider share a single account.
+    pub fn open_channel(
+        ctx: Context<OpenChannel>,
+        channel_id: [u8; 32],
+        budget: u64,
+        price_cap: u64,
+        dispute_window_s: u64,
+    ) -> Result<()> {
+        validate_counterparties(&ctx.accounts.payer.key(), &ctx.accounts.provider.key())?;
+        require!(budget > 0 && price_cap > 0, AssuredError::ZeroAmount);
+        require!(price_cap <= budget, AssuredError::ChannelPriceCapExceeded);
         require!(
-            ctx.accounts.escrow_call.status == Status::Fulfilled as u8
-                || ctx.accounts.escrow_call.status == Status::Init as u8,
-            AssuredError::InvalidStatus
-        );
-        require_keys_eq!(
-            ctx.accounts.payer.key(),
-            ctx.accounts.escrow_call.payer,
-            AssuredError::InvalidPayer
-        );
-        require_keys_eq!(
-            ctx.accounts.provider.key(),
-            ctx.accounts.escrow_call.provider,
-            AssuredError::InvalidProvider
+            dispute_window_s <= MAX_DISPUTE_WINDOW_S,
+            AssuredError::DisputeWindowTooLong
         );
-        let now = Clock::get()?.unix_timestamp as u64;
-        let outcome = evaluate_settlement(&ctx.accounts.escrow_call, now);
-        let amount = ctx.accounts.escrow_call.amount;
-        let released_so_far = amount_for_units(
-            &ctx.accounts.escrow_call,
-            0,
-            ctx.accounts.escrow_call.units_released,
+        let channel = &mut ctx.accounts.channel;
+        channel.channel_id = channel_id;
+        channel.payer = ctx.accounts.payer.key();
+        channel.provider = ctx.accounts.provider.key();
+        channel.budget = budget;
+        channel.spent = 0;
+        channel.price_cap = price_cap;
+        channel.dispute_window_s = dispute_window_s;
+        channel.provider_attest_key = registered_attest_key(ctx.accounts.provider_keys.as_deref());
+        channel.trace_commitment = [0; 32];
+        channel.debit_count = 0;
+        channel.pending = Vec::new();
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: ctx.accounts.channel.to_account_info(),
+                },
+            ),
+            budget,
+        )
+    }
+
+    /// Debits one call from the channel and pays out any earlier debits
+    /// whose dispute window has passed.
+    pub fn fulfill_channel(
+        ctx: Context<FulfillChannel>,
+        call_id_hash: [u8; 32],
+        amount: u64,
+        response_hash: [u8; 32],
+        provider_sig: Vec<u8>,
+    ) -> Result<()> {
+        require!(
+            provider_sig.len() <= MAX_PROVIDER_SIG_LEN,
+            AssuredError::SignatureTooLong
         );
-        let remaining_units = ctx
-            .accounts
-            .escrow_call
-            .total_units
-            .saturating_sub(ctx.accounts.escrow_call.units_released);
-        let remaining_amount = amount.saturating_sub(released_so_far);
-        match outcome {
-            SettlementOutcome::Release => {
-                if remaining_units > 0 {
-                    let payout = amount_for_units(
-                        &ctx.accounts.escrow_call,
-                        ctx.accounts.escrow_call.units_released,
-                        remaining_units,
-                    );
-                    if payout > 0 {
-                        let escrow_info = ctx.accounts.escrow_call.to_account_info();
-                        let provider_info = ctx.accounts.provider.to_account_info();
-                        pay_out(payout, &escrow_info, &provider_info)?;
-                    }
-                }
-                let ec = &mut ctx.accounts.escrow_call;
-                ec.units_released = ec.total_units;
-                ec.status = Status::Released as u8;
-                emit!(Released {
-                    call_id: ec.call_id.clone()
-                });
-            }
-            SettlementOutcome::Refund => {
-                if remaining_amount > 0 {
-                    let escrow_info = ctx.accounts.escrow_call.to_account_info();
-                    let payer_info = ctx.accounts.payer.to_account_info();
-                    pay_out(remaining_amount, &escrow_info, &payer_info)?;
-                }
-                let ec = &mut ctx.accounts.escrow_call;
-                ec.status = Status::Refunded as u8;
-                emit!(Refunded {
-                    call_id: ec.call_id.clone()
-                });
-            }
+        let channel = &ctx.accounts.channel;
+        if channel.provider_attest_key != [0u8; 32] {
+            let ed25519_ix = get_instruction_relative(-1, &ctx.accounts.instructions)
+                .map_err(|_| error!(AssuredError::InvalidAttestation))?;
+            verify_ed25519_ix(
+                &ed25519_ix,
+                &channel.provider_attest_key,
+                &channel_debit_message(&channel.key(), &call_id_hash, &response_hash, amount),
+                &provider_sig,
+            )?;
         }
+        let now = Clock::get()?.unix_timestamp as u64;
+        let channel = &mut ctx.accounts.channel;
+        let matured = channel.take_matured(now);
+        channel.debit(call_id_hash, amount, &response_hash, now)?;
+        let event = ChannelDebited {
+            channel: channel.key(),
+            call_id_hash,
+            amount,
+            response_hash,
+            trace_commitment: channel.trace_commitment,
+        };
+        let channel_info = ctx.accounts.channel.to_account_info();
+        pay_out(
+            matured,
+            &channel_info,
+            &ctx.accounts.provider.to_account_info(),
+        )?;
+        emit!(event);
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(call_id: String)]
-pub struct InitPayment<'info> {
-    #[account(init, payer = payer, space = 8 + EscrowCall::MAX_LEN, seeds=[b"call", call_id.as_bytes()], bump)]
-    pub escrow_call: Account<'info, EscrowCall>,
+    pub fn dispute_channel_debit(
+        ctx: Context<DisputeChannelDebit>,
+        call_id_hash: [u8; 32],
+        reason_hash: [u8; 32],
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp as u64;
+        let amount = ctx.accounts.channel.dispute(&call_id_hash, now)?;
+        emit!(ChannelDebitDisputed {
+            channel: ctx.accounts.channel.key(),
+            call_id_hash,
+            amount,
+            reason_hash,
+        });
+        Ok(())
+    }
+
+    /// Config admin's ruling (0 = release, 1 = refund) on a disputed
+    /// channel debit. A release pays the provider now; a refund returns
+    /// the amount to the channel's budget. Either way the debit's pending
+    /// slot is freed.
+    pub fn arbitrate_channel_debit(
+        ctx: Context<ArbitrateChannelDebit>,
+        call_id_hash: [u8; 32],
+        ruling: u8,
+    ) -> Result<()> {
+        ctx.accounts
+            .config
+            .check_admin(&ctx.accounts.arbitrator.key())?;
+        let channel = &mut ctx.accounts.channel;
+        let (amount, to_provider) = channel.rule(&call_id_hash, ruling)?;
+        let event = ChannelDebitArbitrated {
+            channel: channel.key(),
+            call_id_hash,
+            ruling,
+            amount,
+        };
+        let channel_info = ctx.accounts.channel.to_account_info();
+        pay_out(
+            to_provider,
+            &channel_info,
+            &ctx.accounts.provider.to_account_info(),
+        )?;
+        emit!(event);
+        Ok(())
+    }
+
+    /// Pays the provider its matured debits and returns the rest (unspent
+    /// budget and rent) to the payer. Only allowed once every debit is past
+    /// its dispute window and every disputed debit has been ruled on;
+    /// either party may submit it.
+    pub fn close_channel(ctx: Context<CloseChannel>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp as u64;
+        let channel = &mut ctx.accounts.channel;
+        let (to_provider, refunded) = channel.close_split(now)?;
+        let event = ChannelClosed {
+            channel: channel.key(),
+            to_provider,
+            refunded,
+        };
+        let channel_info = ctx.accounts.channel.to_account_info();
+        pay_out(
+            to_provider,
+            &channel_info,
+            &ctx.accounts.provider.to_account_info(),
+        )?;
+        emit!(event);
+        Ok(())
+    }
+
+    /// Stamped with the Clock, never a provider-supplied time, so a
+    /// heartbeat cannot claim to be later than it is and keep the call
+    /// alive past `heartbeat_lapsed`.
+    pub fn heartbeat(ctx: Context<Fulfill>) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.provider.key(),
+            ctx.accounts.escrow_call.provider,
+            AssuredError::InvalidProvider
+        );
+        let now = Clock::get()?.unix_timestamp as u64;
+        let ec = &mut ctx.accounts.escrow_call;
+        record_heartbeat(ec, now)?;
+        emit!(Heartbeat {
+            call_id: ec.call_id.clone(),
+            ts: now
+        });
+        Ok(())
+    }
+
+    /// Grows an escrow, v1 or v2, written by an older layout to the
+    /// current size and rewrites it at `ESCROW_CALL_VERSION`; the payer
+    /// covers the extra rent.
+    pub fn migrate_escrow(ctx: Context<MigrateEscrow>) -> Result<()> {
+        let info = ctx.accounts.escrow_call.to_account_info();
+        let (migrated, len) = {
+            let data = info.try_borrow_data()?;
+            let is_v2 = data.get(..8) == Some(EscrowCallV2::DISCRIMINATOR);
+            let len = if is_v2 {
+                EscrowCallV2::MAX_LEN_V2
+            } else {
+                EscrowCall::MAX_LEN
+            };
+            require!(
+                data.len() < len || data.get(8) != Some(&ESCROW_CALL_VERSION),
+                AssuredError::AlreadyMigrated
+            );
+            let mut migrated = Vec::with_capacity(len);
+            if is_v2 {
+                EscrowCallV2::load_versioned(&data)?.try_serialize(&mut migrated)?;
+            } else {
+                EscrowCall::load_versioned(&data)?.try_serialize(&mut migrated)?;
+            }
+            (migrated, len)
+        };
+        resize_account(
+            &info,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            len,
+        )?;
+        let mut data = info.try_borrow_mut_data()?;
+        data[..migrated.len()].copy_from_slice(&migrated);
+        data[migrated.len()..].fill(0);
+        Ok(())
+    }
+
+    pub fn extend_sla(ctx: Context<ExtendSla>, new_sla_ms: u64) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.payer.key(),
+            ctx.accounts.escrow_call.payer,
+            AssuredError::InvalidPayer
+        );
+        require!(
+            new_sla_ms <= ctx.accounts.config.max_sla_ms,
+            AssuredError::SlaTooLong
+        );
+        let ec = &mut ctx.accounts.escrow_call;
+        let old_sla_ms = extend_call_sla(ec, new_sla_ms)?;
+        emit!(SlaExtended {
+            call_id: ec.call_id.clone(),
+            old_sla_ms,
+            new_sla_ms,
+        });
+        Ok(())
+    }
+
+    pub fn quote_units(ctx: Context<QuoteUnits>, start: u64, units: u64) -> Result<u64> {
+        quote_amount(&ctx.accounts.escrow_call, start, units)
+    }
+
+    pub fn raise_dispute(
+        ctx: Context<RaiseDispute>,
+        kind: u8, // enum: 0 LATE, 1 NO_RESPONSE, 2 BAD_PROOF, 3 MISMATCH_HASH
+        reason_hash: [u8; 32],
+        reporter_sig: Vec<u8>,
+    ) -> Result<()> {
+        let ec = &mut ctx.accounts.escrow_call;
+        let ed25519_ix = get_instruction_relative(-1, &ctx.accounts.instructions)
+            .map_err(|_| error!(AssuredError::InvalidAttestation))?;
+        verify_ed25519_ix(
+            &ed25519_ix,
+            &ctx.accounts.reporter.key().to_bytes(),
+            &dispute_message(&ec.call_id, kind, &reason_hash),
+            &reporter_sig,
+        )?;
+        let now = Clock::get()?.unix_timestamp as u64;
+        open_dispute(ec, &ctx.accounts.reporter.key(), kind, now)?;
+        hold_dispute_bond(ec, ctx.accounts.config.dispute_bond_lamports);
+        ctx.accounts.global_stats.record_dispute();
+        emit!(Disputed {
+            call_id: ec.call_id.clone(),
+            kind,
+            reason_hash
+        });
+        transfer_into_escrow(
+            &ctx.accounts.reporter,
+            &ctx.accounts.escrow_call.to_account_info(),
+            &ctx.accounts.system_program,
+            ctx.accounts.config.dispute_bond_lamports,
+        )
+    }
+
+    /// Ends a stream early with the provider's consent: units already
+    /// released stay paid and the rest of the escrow goes back to the payer.
+    /// Anyone can submit it, but the preceding instruction must be an
+    /// Ed25519 check of `provider_sig` over `abort_message` by the same key
+    /// `submit_proof` uses.
+    pub fn abort(ctx: Context<Abort>, provider_sig: Vec<u8>) -> Result<()> {
+        let ec = &ctx.accounts.escrow_call;
+        let ed25519_ix = get_instruction_relative(-1, &ctx.accounts.instructions)
+            .map_err(|_| error!(AssuredError::InvalidAttestation))?;
+        verify_ed25519_ix(
+            &ed25519_ix,
+            &proof_signing_key(ec),
+            &abort_message(&ec.call_id, ec.start_ts),
+            &provider_sig,
+        )?;
+        let (refund, released_so_far) = abort_split(ec)?;
+        let accounts = &ctx.accounts;
+        release_call_slot(
+            ec,
+            accounts.service.as_ref().map(|a| a.to_account_info()),
+            reputation_cpi(
+                &accounts.reputation_config,
+                &accounts.reputation_program,
+                &accounts.reputation_authority,
+            ),
+        )?;
+        record_volume(
+            accounts.service.as_ref().map(|a| a.to_account_info()),
+            reputation_cpi(
+                &accounts.reputation_config,
+                &accounts.reputation_program,
+                &accounts.reputation_authority,
+            ),
+            ec.service_id.clone(),
+            released_so_far,
+            false,
+        )?;
+        let escrow_info = ctx.accounts.escrow_call.to_account_info();
+        ctx.accounts
+            .global_stats
+            .record_refund(refund, released_so_far);
+        // An aborted call has no dispute left to rule on, so any bond goes back.
+        let refund = refund.saturating_add(ec.dispute_bond);
+        pay_out(refund, &escrow_info, &ctx.accounts.payer.to_account_info())?;
+        let ec = &mut ctx.accounts.escrow_call;
+        if ec.dispute_bond_state == BondCustody::Held as u8 {
+            ec.dispute_bond_state = BondCustody::Returned as u8;
+        }
+        ec.status = Status::Refunded as u8;
+        if let Some(index) = &mut ctx.accounts.provider_index {
+            index.remove(&ctx.accounts.escrow_call.call_id);
+        }
+        let ec = &ctx.accounts.escrow_call;
+        emit!(Aborted {
+            call_id: ec.call_id.clone(),
+            refund_amount: refund,
+            amount_already_released: released_so_far,
+        });
+        let recipient_info = ctx.accounts.rent_recipient.to_account_info();
+        sweep_rent(&escrow_info, &recipient_info)?;
+        ctx.accounts.escrow_call.close(recipient_info)
+    }
+
+    /// Provider routes its own payouts on an unsettled call: up to
+    /// `MAX_PAYOUT_SPLITS` recipients whose bps sum to 10,000, or an empty
+    /// list to be paid directly again.
+    pub fn set_payout_splits(
+        ctx: Context<SetPayoutSplits>,
+        payout_splits: Vec<PayoutSplit>,
+    ) -> Result<()> {
+        let ec = &mut ctx.accounts.escrow_call;
+        apply_payout_splits(ec, &ctx.accounts.provider.key(), payout_splits)?;
+        emit!(PayoutSplitsSet {
+            call_id: ec.call_id.clone(),
+            payout_splits: ec.payout_splits.clone(),
+        });
+        Ok(())
+    }
+
+    /// Provider's rebuttal to a dispute, recorded for the arbitrator. The
+    /// preceding instruction must be an Ed25519 precompile check of
+    /// `provider_sig` over `proof_message` by the call's attestation key, or
+    /// by the provider's own key when none was set.
+    pub fn submit_proof(
+        ctx: Context<SubmitProof>,
+        proof_hash: [u8; 32],
+        provider_sig: Vec<u8>,
+    ) -> Result<()> {
+        let ec = &mut ctx.accounts.escrow_call;
+        let ed25519_ix = get_instruction_relative(-1, &ctx.accounts.instructions)
+            .map_err(|_| error!(AssuredError::InvalidAttestation))?;
+        verify_ed25519_ix(
+            &ed25519_ix,
+            &proof_signing_key(ec),
+            &proof_message(&ec.call_id, &proof_hash),
+            &provider_sig,
+        )?;
+        record_provider_proof(ec, ctx.accounts.provider.key(), proof_hash)?;
+        emit!(ProofSubmitted {
+            call_id: ec.call_id.clone(),
+            proof_hash,
+        });
+        Ok(())
+    }
+
+    /// Opens an escrow keyed by the SHA-256 of its call id, at PDA
+    /// `["callv2", call_id_hash]`. Takes the same defaults as a batch leg.
+    pub fn init_payment_v2(
+        ctx: Context<InitPaymentV2>,
+        call_id_hash: [u8; 32],
+        service_id: String,
+        amount: u64,
+        sla_ms: u64,
+        dispute_window_s: u64,
+        total_units: u64,
+    ) -> Result<()> {
+        validate_call_params(amount, sla_ms, dispute_window_s, total_units)?;
+        validate_counterparties(&ctx.accounts.payer.key(), &ctx.accounts.provider.key())?;
+        let now = Clock::get()?.unix_timestamp;
+        check_init_gates(
+            &ctx.accounts.config,
+            &ctx.accounts.service,
+            &ctx.accounts.provider.key(),
+            ctx.accounts.approval.as_deref(),
+            &ctx.accounts.blacklist,
+            &ctx.accounts.allowlist,
+            &InitTerms {
+                amount,
+                sla_ms,
+                dispute_window_s,
+                total_units,
+                billing_mode: BillingMode::PerUnit as u8,
+                min_reputation_bps: None,
+                now,
+            },
+        )?;
+        let accounts = &ctx.accounts;
+        let outstanding_tracked = claim_outstanding_slot(
+            &accounts.service,
+            &accounts.service.to_account_info(),
+            reputation_cpi(
+                &accounts.reputation_config,
+                &accounts.reputation_program,
+                &accounts.reputation_authority,
+            ),
+            &service_id,
+        )?;
+        let leg = BatchInitLeg {
+            call_id: String::new(),
+            service_id,
+            amount,
+            sla_ms,
+            dispute_window_s,
+            total_units,
+        };
+        let mut ec = batch_call(
+            &leg,
+            ctx.accounts.payer.key(),
+            ctx.accounts.provider.key(),
+            now as u64,
+        );
+        ec.late_penalty_bps = ctx.accounts.config.late_penalty_bps;
+        ec.outstanding_tracked = outstanding_tracked;
+        *ctx.accounts.escrow_call = EscrowCallV2::from_v1(call_id_hash, &ec);
+        let provider_index = &mut ctx.accounts.provider_index;
+        provider_index.provider = ctx.accounts.provider.key();
+        provider_index.push_hashed(&call_id_hash);
+        ctx.accounts.global_stats.record_open(amount);
+        if let Some(stats) = &mut ctx.accounts.service_stats {
+            stats.record_open(amount);
+        }
+        let escrow_info = ctx.accounts.escrow_call.to_account_info();
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: escrow_info,
+                },
+            ),
+            amount,
+        )
+    }
+
+    pub fn fulfill_v2(
+        ctx: Context<FulfillV2>,
+        response_hash: [u8; 32],
+        ts: u64,
+        provider_sig: Vec<u8>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp as u64;
+        let grace_s = ctx.accounts.config.fulfill_grace_s;
+        let mut ec = ctx.accounts.escrow_call.to_v1();
+        check_fulfill(
+            &ec,
+            &ctx.accounts.provider.key(),
+            &provider_sig,
+            now,
+            grace_s,
+        )?;
+        // Proof sets are keyed by the string call id, which v2 doesn't keep.
+        require!(!ec.requires_proofs, AssuredError::ProofSetIncomplete);
+        verify_any_attestation(
+            &ec,
+            &ctx.accounts.instructions,
+            &fulfill_messages(&ec.call_id, &response_hash, ts),
+            &provider_sig,
+        )?;
+        record_fulfillment(&mut ec, response_hash, ts, &provider_sig, now);
+        let call_id_hash = ctx.accounts.escrow_call.call_id;
+        *ctx.accounts.escrow_call = EscrowCallV2::from_v1(call_id_hash, &ec);
+        emit!(Fulfilled {
+            call_id: ec.call_id.clone(),
+            ts
+        });
+        emit!(TraceSaved {
+            call_id: ec.call_id,
+            response_hash,
+            provider_sig,
+            content_cid: ec.state.content_cid,
+        });
+        Ok(())
+    }
+
+    /// `raise_dispute` for a v2 escrow. `reporter_sig` is checked the same
+    /// way, over `dispute_message` with the hex call id hash as the id.
+    pub fn raise_dispute_v2(
+        ctx: Context<RaiseDisputeV2>,
+        kind: u8,
+        reason_hash: [u8; 32],
+        reporter_sig: Vec<u8>,
+    ) -> Result<()> {
+        let mut ec = ctx.accounts.escrow_call.to_v1();
+        let ed25519_ix = get_instruction_relative(-1, &ctx.accounts.instructions)
+            .map_err(|_| error!(AssuredError::InvalidAttestation))?;
+        verify_ed25519_ix(
+            &ed25519_ix,
+            &ctx.accounts.reporter.key().to_bytes(),
+            &dispute_message(&ec.call_id, kind, &reason_hash),
+            &reporter_sig,
+        )?;
+        let now = Clock::get()?.unix_timestamp as u64;
+        open_dispute(&mut ec, &ctx.accounts.reporter.key(), kind, now)?;
+        hold_dispute_bond(&mut ec, ctx.accounts.config.dispute_bond_lamports);
+        ctx.accounts.global_stats.record_dispute();
+        let call_id_hash = ctx.accounts.escrow_call.call_id;
+        *ctx.accounts.escrow_call = EscrowCallV2::from_v1(call_id_hash, &ec);
+        emit!(Disputed {
+            call_id: ec.call_id,
+            kind,
+            reason_hash
+        });
+        transfer_into_escrow(
+            &ctx.accounts.reporter,
+            &ctx.accounts.escrow_call.to_account_info(),
+            &ctx.accounts.system_program,
+            ctx.accounts.config.dispute_bond_lamports,
+        )
+    }
+
+    /// `arbitrate` for a v2 escrow: the config admin rules on its dispute.
+    pub fn arbitrate_v2(ctx: Context<ArbitrateV2>, ruling: u8) -> Result<()> {
+        ctx.accounts
+            .config
+            .check_admin(&ctx.accounts.arbitrator.key())?;
+        let now = Clock::get()?.unix_timestamp;
+        let mut ec = ctx.accounts.escrow_call.to_v1();
+        require!(ec.arbiters.is_empty(), AssuredError::CommitteeArbitrated);
+        record_ruling(&mut ec, ruling, now)?;
+        let call_id_hash = ctx.accounts.escrow_call.call_id;
+        *ctx.accounts.escrow_call = EscrowCallV2::from_v1(call_id_hash, &ec);
+        emit!(Arbitrated {
+            call_id: ec.call_id,
+            ruling,
+            appeal_deadline_ts: ec.state.appeal_deadline_ts,
+            payer_evidence: 0,
+            provider_evidence: 0,
+        });
+        Ok(())
+    }
+
+    /// `settle` for a v2 escrow: the same payouts, reputation reports and
+    /// stats, leaving the account open for `finalize_v2`. v2 calls keep no
+    /// payer index and write no memo, and never carry payout splits or a
+    /// referrer (`check_v2_supported`), so there is nothing to pass in
+    /// `remaining_accounts`.
+    pub fn settle_v2<'info>(ctx: Context<'_, '_, 'info, 'info, SettleV2<'info>>) -> Result<()> {
+        let mut ec = ctx.accounts.escrow_call.to_v1();
+        settle_call(
+            &mut ctx.accounts.settle_accounts(&mut ec),
+            ctx.remaining_accounts,
+        )?;
+        if let Some(index) = &mut ctx.accounts.provider_index {
+            index.remove_hashed(&ctx.accounts.escrow_call.call_id);
+        }
+        let call_id_hash = ctx.accounts.escrow_call.call_id;
+        *ctx.accounts.escrow_call = EscrowCallV2::from_v1(call_id_hash, &ec);
+        Ok(())
+    }
+
+    /// `finalize` for a v2 escrow.
+    pub fn finalize_v2(ctx: Context<SettleV2>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let mut ec = ctx.accounts.escrow_call.to_v1();
+        let outcome = finalize_call(&mut ec, now)?;
+        emit!(Finalized {
+            call_id: ec.call_id,
+            outcome,
+        });
+        let escrow_info = ctx.accounts.escrow_call.to_account_info();
+        let recipient_info = ctx.accounts.rent_recipient.to_account_info();
+        sweep_rent(&escrow_info, &recipient_info)?;
+        ctx.accounts.escrow_call.close(recipient_info)
+    }
+
+    /// Moves an open v1 escrow, lamports included, to its v2 PDA and closes
+    /// the v1 account.
+    pub fn migrate_v1_to_v2(ctx: Context<MigrateV1ToV2>, call_id_hash: [u8; 32]) -> Result<()> {
+        let ec = &ctx.accounts.escrow_call;
+        require!(
+            call_id_hash == call_id_to_hash(&ec.call_id),
+            AssuredError::CallIdHashMismatch
+        );
+        require!(
+            ec.status == Status::Init as u8 || ec.status == Status::Fulfilled as u8,
+            AssuredError::InvalidStatus
+        );
+        check_v2_supported(ec)?;
+        *ctx.accounts.escrow_call_v2 = EscrowCallV2::from_v1(call_id_hash, ec);
+        Ok(())
+    }
+
+    /// Lets the payer put a delivered call back into dispute with new
+    /// evidence, as long as the dispute window is still open.
+    pub fn reopen_dispute(ctx: Context<ReopenDispute>, new_reason_hash: [u8; 32]) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let ec = &mut ctx.accounts.escrow_call;
+        reopen_call_dispute(ec, ctx.accounts.payer.key(), new_reason_hash, now)?;
+        hold_dispute_bond(ec, ctx.accounts.config.dispute_bond_lamports);
+        ctx.accounts.global_stats.record_dispute();
+        emit!(DisputeReopened {
+            call_id: ec.call_id.clone(),
+            new_reason_hash,
+        });
+        transfer_into_escrow(
+            &ctx.accounts.payer,
+            &ctx.accounts.escrow_call.to_account_info(),
+            &ctx.accounts.system_program,
+            ctx.accounts.config.dispute_bond_lamports,
+        )
+    }
+
+    /// Commits one piece of dispute evidence for the payer or provider.
+    /// Each party gets `MAX_EVIDENCE_PER_PARTY` slots; the account is
+    /// created by whoever submits first.
+    pub fn submit_evidence(
+        ctx: Context<SubmitEvidence>,
+        role: u8,
+        evidence_hash: [u8; 32],
+        uri_hash: [u8; 32],
+    ) -> Result<()> {
+        let submitter = ctx.accounts.submitter.key();
+        check_evidence_submitter(&ctx.accounts.escrow_call, role, &submitter)?;
+        let now = Clock::get()?.unix_timestamp;
+        let evidence = &mut ctx.accounts.evidence;
+        if evidence.call_id.is_empty() {
+            evidence.call_id = ctx.accounts.escrow_call.call_id.clone();
+            evidence.rent_payer = submitter;
+        }
+        let slot = evidence.submit(
+            role,
+            EvidenceEntry {
+                evidence_hash,
+                uri_hash,
+                submitted_at: now,
+            },
+        )?;
+        emit!(EvidenceSubmitted {
+            call_id: ctx.accounts.escrow_call.call_id.clone(),
+            role,
+            submitter,
+            slot,
+            evidence_hash,
+            uri_hash,
+        });
+        Ok(())
+    }
+
+    /// First-level ruling on a disputed call by the config admin. Opens an
+    /// appeal window for the losing party.
+    ///
+    /// `disputed_units` marks already-released units the payer should be
+    /// made whole for out of the provider's performance bond if the refund stands.
+    /// Passing the call's `Evidence` account records how much each side
+    /// had submitted when the ruling was made.
+    pub fn arbitrate(ctx: Context<Arbitrate>, ruling: u8, disputed_units: u64) -> Result<()> {
+        ctx.accounts
+            .config
+            .check_admin(&ctx.accounts.arbitrator.key())?;
+        let now = Clock::get()?.unix_timestamp;
+        let ec = &mut ctx.accounts.escrow_call;
+        require!(ec.arbiters.is_empty(), AssuredError::CommitteeArbitrated);
+        record_ruling(ec, ruling, now)?;
+        set_disputed_units(ec, disputed_units)?;
+        let (payer_evidence, provider_evidence) = ctx
+            .accounts
+            .evidence
+            .as_ref()
+            .map_or((0, 0), |e| e.counts());
+        emit!(Arbitrated {
+            call_id: ec.call_id.clone(),
+            ruling,
+            appeal_deadline_ts: ec.appeal_deadline_ts,
+            payer_evidence,
+            provider_evidence,
+        });
+        Ok(())
+    }
+
+    /// One committee arbiter's vote (0 = release, 1 = refund) on a
+    /// disputed call. Votes are final once cast and close
+    /// `COMMITTEE_VOTE_WINDOW_S` after the dispute was raised.
+    pub fn resolve_dispute(ctx: Context<ResolveDispute>, verdict: u8) -> Result<()> {
+        let arbiter = ctx.accounts.arbiter.key();
+        let now = Clock::get()?.unix_timestamp;
+        let ec = &ctx.accounts.escrow_call;
+        let slot = check_arbiter_vote(ec, &arbiter, now)?;
+        ctx.accounts.votes.cast(ec, slot, verdict)?;
+        emit!(ArbiterVoted {
+            call_id: ec.call_id.clone(),
+            arbiter,
+            verdict,
+        });
+        Ok(())
+    }
+
+    /// Returns the rent of a proof set or evidence account left behind
+    /// after its call was closed. The orphan goes in `remaining_accounts`;
+    /// anyone may submit, but the lamports only go to whoever funded it.
+    pub fn collect_orphan_rent<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CollectOrphanRent<'info>>,
+        pda_type: u8,
+    ) -> Result<()> {
+        let orphan = ctx
+            .remaining_accounts
+            .first()
+            .ok_or(AssuredError::InvalidOrphan)?;
+        let amount = reclaim_orphan(
+            pda_type,
+            orphan,
+            &ctx.accounts.escrow_call.to_account_info(),
+            &ctx.accounts.rent_payer.to_account_info(),
+        )?;
+        emit!(OrphanRentCollected {
+            pda: orphan.key(),
+            amount,
+        });
+        Ok(())
+    }
+
+    /// Permissionless. Records the verdict that reached the committee's
+    /// threshold as the call's ruling, or a refund once the vote window
+    /// has closed without quorum. Appeals then work as for `arbitrate`.
+    pub fn finalize_resolution(ctx: Context<FinalizeResolution>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let ec = &mut ctx.accounts.escrow_call;
+        let (ruling, quorum_reached) = committee_verdict(ec, ctx.accounts.votes.as_deref(), now)?;
+        record_ruling(ec, ruling, now)?;
+        emit!(ResolutionFinalized {
+            call_id: ec.call_id.clone(),
+            ruling,
+            quorum_reached,
+            appeal_deadline_ts: ec.appeal_deadline_ts,
+        });
+        Ok(())
+    }
+
+    pub fn appeal_arbitration(
+        ctx: Context<AppealArbitration>,
+        new_evidence_hash: [u8; 32],
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let appellant = ctx.accounts.appellant.key();
+        let ec = &mut ctx.accounts.escrow_call;
+        file_appeal(ec, appellant, now)?;
+        emit!(AppealFiled {
+            call_id: ec.call_id.clone(),
+            appellant,
+            new_evidence_hash,
+        });
+        Ok(())
+    }
+
+    pub fn initialize_super_arbitrator(
+        ctx: Context<InitializeSuperArbitrator>,
+        authority: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.config.check_admin(&ctx.accounts.admin.key())?;
+        ctx.accounts.super_arbitrator.authority = authority;
+        Ok(())
+    }
+
+    /// Emits the program-wide `["stats"]` counters for dashboards; anyone
+    /// may call it.
+    pub fn emit_stats_snapshot(ctx: Context<StatsSnapshotQuery>) -> Result<()> {
+        emit!(ctx.accounts.global_stats.snapshot(Clock::get()?.slot));
+        Ok(())
+    }
+
+    /// Grows an `EscrowStats` account written before `disputed_count`
+    /// existed; the new counter starts at zero. Anyone may pay for it.
+    pub fn migrate_stats(ctx: Context<MigrateStats>) -> Result<()> {
+        let info = ctx.accounts.stats.to_account_info();
+        let len = info.data_len();
+        {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() >= 8 && data[..8] == *EscrowStats::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+        }
+        require!(
+            len < 8 + EscrowStats::INIT_SPACE,
+            AssuredError::AlreadyMigrated
+        );
+        resize_account(
+            &info,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            8 + EscrowStats::INIT_SPACE,
+        )?;
+        info.try_borrow_mut_data()?[len..].fill(0);
+        Ok(())
+    }
+
+    /// Admin funds the insurance pool, creating it on the first deposit.
+    pub fn deposit_insurance(ctx: Context<DepositInsurance>, amount: u64) -> Result<()> {
+        ctx.accounts.config.check_admin(&ctx.accounts.admin.key())?;
+        let accounts = Transfer {
+            from: ctx.accounts.admin.to_account_info(),
+            to: ctx.accounts.insurance_pool.to_account_info(),
+        };
+        system_program::transfer(
+            CpiContext::new(ctx.accounts.system_program.to_account_info(), accounts),
+            amount,
+        )?;
+        let pool = &mut ctx.accounts.insurance_pool;
+        if pool.max_claim_bps == 0 {
+            pool.max_claim_bps = DEFAULT_INSURANCE_MAX_CLAIM_BPS;
+        }
+        pool.balance = pool.balance.saturating_add(amount);
+        Ok(())
+    }
+
+    pub fn reset_epoch_claims(ctx: Context<ResetEpochClaims>) -> Result<()> {
+        ctx.accounts.config.check_admin(&ctx.accounts.admin.key())?;
+        ctx.accounts.insurance_pool.claims_this_epoch = 0;
+        Ok(())
+    }
+
+    pub fn resolve_appeal(ctx: Context<ResolveAppeal>, ruling: u8) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.authority.key(),
+            ctx.accounts.super_arbitrator.authority,
+            AssuredError::Unauthorized
+        );
+        let ec = &mut ctx.accounts.escrow_call;
+        resolve_call_appeal(ec, ruling)?;
+        emit!(AppealResolved {
+            call_id: ec.call_id.clone(),
+            ruling,
+        });
+        Ok(())
+    }
+
+    /// A released call with payout splits pays each recipient, passed as a
+    /// writable account in `remaining_accounts`, instead of the provider.
+    pub fn settle<'info>(ctx: Context<'_, '_, 'info, 'info, Settle<'info>>) -> Result<()> {
+        settle_call(&mut ctx.accounts.settle_accounts(), ctx.remaining_accounts)?;
+        if let Some(index) = &mut ctx.accounts.payer_index {
+            index.mark_settled(&ctx.accounts.escrow_call.call_id);
+        }
+        if let Some(index) = &mut ctx.accounts.provider_index {
+            index.remove(&ctx.accounts.escrow_call.call_id);
+        }
+        if let Some(memo_program) = &ctx.accounts.memo_program {
+            let ec = &ctx.accounts.escrow_call;
+            write_memo(memo_program, &settle_memo(&ec.call_id, ec.status))?;
+        }
+        Ok(())
+    }
+
+    /// Closes a settled call. `settle` leaves the account open in its
+    /// Released or Refunded state so indexers can still read the outcome;
+    /// anyone may finalize it afterwards to return the rent.
+    pub fn finalize(ctx: Context<Settle>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let ec = &mut ctx.accounts.escrow_call;
+        let outcome = finalize_call(ec, now)?;
+        emit!(Finalized {
+            call_id: ec.call_id.clone(),
+            outcome,
+        });
+        // Released by `redeem`, the call may still be listed.
+        if let Some(index) = &mut ctx.accounts.provider_index {
+            index.remove(&ctx.accounts.escrow_call.call_id);
+        }
+        let escrow_info = ctx.accounts.escrow_call.to_account_info();
+        let recipient_info = ctx.accounts.rent_recipient.to_account_info();
+        sweep_rent(&escrow_info, &recipient_info)?;
+        ctx.accounts.escrow_call.close(recipient_info)
+    }
+
+    /// Refunds an undelivered call nobody settled once `refund_after_ts`
+    /// plus `SETTLE_GRACE_PERIOD_S` has passed. Anyone may submit it; the
+    /// `submitter` collects `SETTLE_BOUNTY_LAMPORTS` from the freed rent.
+    /// Delivered calls are never refunded here: anyone can crank their
+    /// release through `settle`.
+    pub fn timeout_refund(ctx: Context<Settle>) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.payer.key(),
+            ctx.accounts.escrow_call.payer,
+            AssuredError::InvalidPayer
+        );
+        let submitter = ctx
+            .accounts
+            .submitter
+            .as_ref()
+            .ok_or(AssuredError::SubmitterRequired)?
+            .to_account_info();
+        let now = Clock::get()?.unix_timestamp as u64;
+        let ec = &ctx.accounts.escrow_call;
+        check_settle_timeout(ec, now)?;
+        let released_so_far = amount_for_units(ec, 0, ec.units_released);
+        let refund = ec.amount.saturating_sub(released_so_far);
+        let escrow_info = ctx.accounts.escrow_call.to_account_info();
+        pay_out(refund, &escrow_info, &ctx.accounts.payer.to_account_info())?;
+        ctx.accounts
+            .global_stats
+            .record_refund(refund, released_so_far);
+        if let Some(stats) = &mut ctx.accounts.service_stats {
+            stats.record_refund(refund, released_so_far);
+        }
+        record_settle_volume(&ctx.accounts.settle_accounts(), released_so_far, false)?;
+        let ec = &mut ctx.accounts.escrow_call;
+        let mut event = refunded_event(ec, refund, released_so_far);
+        event.reason = RefundReason::Timeout as u8;
+        ec.status = Status::Refunded as u8;
+        emit!(event);
+        if let Some(index) = &mut ctx.accounts.payer_index {
+            index.mark_settled(&ctx.accounts.escrow_call.call_id);
+        }
+        if let Some(index) = &mut ctx.accounts.provider_index {
+            index.remove(&ctx.accounts.escrow_call.call_id);
+        }
+        release_outstanding_slot(&ctx.accounts.settle_accounts())?;
+        let bounty = SETTLE_BOUNTY_LAMPORTS.min(escrow_info.lamports());
+        pay_out(bounty, &escrow_info, &submitter)?;
+        let recipient_info = ctx.accounts.rent_recipient.to_account_info();
+        sweep_rent(&escrow_info, &recipient_info)?;
+        ctx.accounts.escrow_call.close(recipient_info)
+    }
+
+    /// Refunds a call the provider never fulfilled once `expiry_ts` has
+    /// passed. Unlike `settle`, it needs no payer signature: anyone may
+    /// submit it and the `submitter` collects `EXPIRY_BOUNTY_LAMPORTS` from
+    /// the freed rent. Units already streamed stay paid.
+    pub fn trigger_expiry(ctx: Context<Settle>) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.payer.key(),
+            ctx.accounts.escrow_call.payer,
+            AssuredError::InvalidPayer
+        );
+        let submitter = ctx
+            .accounts
+            .submitter
+            .as_ref()
+            .ok_or(AssuredError::SubmitterRequired)?
+            .to_account_info();
+        let now = Clock::get()?.unix_timestamp as u64;
+        let ec = &ctx.accounts.escrow_call;
+        check_expiry(ec, now)?;
+        let released_so_far = amount_for_units(ec, 0, ec.units_released);
+        let refund = ec.amount.saturating_sub(released_so_far);
+        let escrow_info = ctx.accounts.escrow_call.to_account_info();
+        pay_out(refund, &escrow_info, &ctx.accounts.payer.to_account_info())?;
+        ctx.accounts
+            .global_stats
+            .record_refund(refund, released_so_far);
+        if let Some(stats) = &mut ctx.accounts.service_stats {
+            stats.record_refund(refund, released_so_far);
+        }
+        record_settle_volume(&ctx.accounts.settle_accounts(), released_so_far, false)?;
+        let ec = &mut ctx.accounts.escrow_call;
+        let mut event = refunded_event(ec, refund, released_so_far);
+        event.reason = RefundReason::Expired as u8;
+        ec.status = Status::Refunded as u8;
+        emit!(event);
+        if let Some(index) = &mut ctx.accounts.payer_index {
+            index.mark_settled(&ctx.accounts.escrow_call.call_id);
+        }
+        if let Some(index) = &mut ctx.accounts.provider_index {
+            index.remove(&ctx.accounts.escrow_call.call_id);
+        }
+        release_outstanding_slot(&ctx.accounts.settle_accounts())?;
+        let bounty = EXPIRY_BOUNTY_LAMPORTS.min(escrow_info.lamports());
+        pay_out(bounty, &escrow_info, &submitter)?;
+        let recipient_info = ctx.accounts.rent_recipient.to_account_info();
+        sweep_rent(&escrow_info, &recipient_info)?;
+        ctx.accounts.escrow_call.close(recipient_info)
+    }
+
+    /// `trigger_expiry` for a v2 escrow, which `settle_v2` can only refund
+    /// with the payer's signature.
+    pub fn trigger_expiry_v2(ctx: Context<TriggerExpiryV2>) -> Result<()> {
+        let ec = ctx.accounts.escrow_call.to_v1();
+        let now = Clock::get()?.unix_timestamp as u64;
+        check_expiry(&ec, now)?;
+        let released_so_far = amount_for_units(&ec, 0, ec.units_released);
+        let refund = ec.amount.saturating_sub(released_so_far);
+        let escrow_info = ctx.accounts.escrow_call.to_account_info();
+        pay_out(refund, &escrow_info, &ctx.accounts.payer.to_account_info())?;
+        ctx.accounts
+            .global_stats
+            .record_refund(refund, released_so_far);
+        if let Some(stats) = &mut ctx.accounts.service_stats {
+            stats.record_refund(refund, released_so_far);
+        }
+        let mut event = refunded_event(&ec, refund, released_so_far);
+        event.reason = RefundReason::Expired as u8;
+        emit!(event);
+        if let Some(index) = &mut ctx.accounts.provider_index {
+            index.remove_hashed(&ctx.accounts.escrow_call.call_id);
+        }
+        let accounts = &ctx.accounts;
+        release_call_slot(
+            &ec,
+            accounts.service.as_ref().map(|a| a.to_account_info()),
+            reputation_cpi(
+                &accounts.reputation_config,
+                &accounts.reputation_program,
+                &accounts.reputation_authority,
+            ),
+        )?;
+        let bounty = EXPIRY_BOUNTY_LAMPORTS.min(escrow_info.lamports());
+        pay_out(
+            bounty,
+            &escrow_info,
+            &ctx.accounts.submitter.to_account_info(),
+        )?;
+        let recipient_info = ctx.accounts.rent_recipient.to_account_info();
+        sweep_rent(&escrow_info, &recipient_info)?;
+        ctx.accounts.escrow_call.close(recipient_info)
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(call_id: String, service_id: String)]
+pub struct InitPayment<'info> {
+    /// `init_if_needed` so a taken call id reaches the handler and fails
+    /// with `CallIdTaken` instead of Anchor's generic in-use error.
+    #[account(init_if_needed, payer = payer, space = 8 + EscrowCall::INIT_SPACE, seeds=[b"call", call_id.as_bytes()], bump)]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Provider is recorded and later enforced
+    pub provider: UncheckedAccount<'info>,
+    /// Read-only so payments to one service do not serialize on it. Pass
+    /// it writable only when the service caps its outstanding calls.
+    #[account(seeds=[b"svc", service_id.as_bytes()], bump, seeds::program = reputation::ID)]
+    pub service: Box<Account<'info, reputation::Service>>,
+    /// Required when the service caps its outstanding calls, so the
+    /// new call can be counted via CPI.
+    /// CHECK: reputation validates its own config PDA
+    pub reputation_config: Option<UncheckedAccount<'info>>,
+    pub reputation_program: Option<Program<'info, reputation::program::Reputation>>,
+    /// Signs reputation's escrow-only instructions; reputation accepts
+    /// only this PDA of the escrow program it is configured with.
+    /// CHECK: a data-less signing PDA
+    #[account(seeds=[reputation::ESCROW_AUTHORITY_SEED], bump)]
+    pub reputation_authority: Option<UncheckedAccount<'info>>,
+    #[account(seeds=[b"ppa", payer.key().as_ref()], bump)]
+    pub approval: Option<Account<'info, PayerProviderApproval>>,
+    /// CHECK: the protocol blacklist PDA, which may not exist yet; read
+    /// by `check_blacklist`
+    #[account(seeds=[b"blacklist"], bump)]
+    pub blacklist: UncheckedAccount<'info>,
+    /// CHECK: the service owner's allowlist PDA, which may not exist;
+    /// read by `check_allowlist`
+    #[account(seeds=[b"allowlist", service.owner.as_ref()], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+    /// The provider's registered attestation key; without it the call
+    /// falls back to the provider's own key.
+    #[account(seeds=[b"provider_keys", provider.key().as_ref()], bump)]
+    pub provider_keys: Option<Account<'info, ProviderKeys>>,
+    /// Required when the call pays a referrer.
+    #[account(seeds=[b"referrers", provider.key().as_ref()], bump)]
+    pub referrers: Option<Box<Account<'info, ProviderReferrers>>>,
+    #[account(mut, seeds=[b"index", payer.key().as_ref()], bump)]
+    pub payer_index: Option<Box<Account<'info, PayerCallIndex>>>,
+    /// Created on first use, funded by that call's payer.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + ProviderCallIndex::INIT_SPACE,
+        seeds=[b"provider_index", provider.key().as_ref()],
+        bump
+    )]
+    pub provider_index: Box<Account<'info, ProviderCallIndex>>,
+    /// Pass the memo program to stamp the transaction with a readable
+    /// `assured:` memo; omit it to save compute.
+    #[account(address = MEMO_PROGRAM_ID)]
+    pub memo_program: Option<UncheckedAccount<'info>>,
+    /// Created on first use; the payer of that call funds its rent.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + EscrowStats::INIT_SPACE,
+        seeds=[b"stats"],
+        bump
+    )]
+    pub global_stats: Account<'info, EscrowStats>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + EscrowStats::INIT_SPACE,
+        seeds=[b"svc_stats", service_id.as_bytes()],
+        bump
+    )]
+    pub service_stats: Option<Account<'info, EscrowStats>>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Box<Account<'info, Config>>,
+    /// The provider's voucher registry; required with a `redemption_code`.
+    #[account(mut, seeds=[b"vouchers", provider.key().as_ref()], bump)]
+    pub voucher_registry: Option<Box<Account<'info, VoucherRegistry>>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BatchInit<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// Required when a leg's service caps its outstanding calls, so the
+    /// leg can be counted via CPI; that leg's service must then be
+    /// writable.
+    /// CHECK: reputation validates its own config PDA
+    pub reputation_config: Option<UncheckedAccount<'info>>,
+    pub reputation_program: Option<Program<'info, reputation::program::Reputation>>,
+    /// Signs reputation's escrow-only instructions; reputation accepts
+    /// only this PDA of the escrow program it is configured with.
+    /// CHECK: a data-less signing PDA
+    #[account(seeds=[reputation::ESCROW_AUTHORITY_SEED], bump)]
+    pub reputation_authority: Option<UncheckedAccount<'info>>,
+    #[account(seeds=[b"ppa", payer.key().as_ref()], bump)]
+    pub approval: Option<Account<'info, PayerProviderApproval>>,
+    /// CHECK: the protocol blacklist PDA, which may not exist yet; read
+    /// by `check_blacklist`
+    #[account(seeds=[b"blacklist"], bump)]
+    pub blacklist: UncheckedAccount<'info>,
+    #[account(mut, seeds=[b"index", payer.key().as_ref()], bump)]
+    pub payer_index: Option<Account<'info, PayerCallIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + EscrowStats::INIT_SPACE,
+        seeds=[b"stats"],
+        bump
+    )]
+    pub global_stats: Account<'info, EscrowStats>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    pub system_program: Program<'info, System>,
+}
+
+/// The optional settings of `init_payment`. The default leaves every
+/// feature off: the refund deadline follows the SLA, and there is no
+/// heartbeat, split, referrer, tier, voucher or arbiter committee.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct InitPaymentParams {
+    pub refund_after_ts: Option<u64>,
+    pub heartbeat_interval_s: u64,
+    pub heartbeat_required: bool,
+    pub remainder_policy: u8,
+    pub rent_recipient: Option<Pubkey>,
+    pub hashlock: [u8; 32],
+    pub referrer: Option<Pubkey>,
+    pub referral_fee_bps: u16,
+    pub min_reputation_bps: Option<u16>,
+    pub billing_mode: u8,
+    pub sig_mode: u8,
+    pub sla_tiers: Vec<SlaTier>,
+    pub redemption_code: Option<[u8; 16]>,
+    pub arbiters: Vec<Pubkey>,
+    pub arbiter_threshold: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BatchInitLeg {
+    pub call_id: String,
+    pub service_id: String,
+    pub amount: u64,
+    pub sla_ms: u64,
+    pub dispute_window_s: u64,
+    pub total_units: u64,
+}
+
+#[derive(Accounts)]
+#[instruction(call_id: String, service_id: String)]
+pub struct InitMultisigPayment<'info> {
+    #[account(init, payer = payer, space = 8 + EscrowCall::INIT_SPACE, seeds=[b"call", call_id.as_bytes()], bump)]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(init, payer = payer, space = 8 + MultiSigConfig::INIT_SPACE, seeds=[b"multisig", call_id.as_bytes()], bump)]
+    pub multisig: Account<'info, MultiSigConfig>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Provider is recorded and later enforced
+    pub provider: UncheckedAccount<'info>,
+    /// Pass it writable when the service caps its outstanding calls.
+    #[account(seeds=[b"svc", service_id.as_bytes()], bump, seeds::program = reputation::ID)]
+    pub service: Box<Account<'info, reputation::Service>>,
+    /// Required when the service caps its outstanding calls, so the
+    /// new call can be counted via CPI.
+    /// CHECK: reputation validates its own config PDA
+    pub reputation_config: Option<UncheckedAccount<'info>>,
+    pub reputation_program: Option<Program<'info, reputation::program::Reputation>>,
+    /// Signs reputation's escrow-only instructions; reputation accepts
+    /// only this PDA of the escrow program it is configured with.
+    /// CHECK: a data-less signing PDA
+    #[account(seeds=[reputation::ESCROW_AUTHORITY_SEED], bump)]
+    pub reputation_authority: Option<UncheckedAccount<'info>>,
+    #[account(seeds=[b"ppa", payer.key().as_ref()], bump)]
+    pub approval: Option<Account<'info, PayerProviderApproval>>,
+    /// CHECK: the protocol blacklist PDA, which may not exist yet; read
+    /// by `check_blacklist`
+    #[account(seeds=[b"blacklist"], bump)]
+    pub blacklist: UncheckedAccount<'info>,
+    /// CHECK: the service owner's allowlist PDA, which may not exist;
+    /// read by `check_allowlist`
+    #[account(seeds=[b"allowlist", service.owner.as_ref()], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+    /// Created on first use, funded by that call's payer.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + ProviderCallIndex::INIT_SPACE,
+        seeds=[b"provider_index", provider.key().as_ref()],
+        bump
+    )]
+    pub provider_index: Box<Account<'info, ProviderCallIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + EscrowStats::INIT_SPACE,
+        seeds=[b"stats"],
+        bump
+    )]
+    pub global_stats: Account<'info, EscrowStats>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + EscrowStats::INIT_SPACE,
+        seeds=[b"svc_stats", service_id.as_bytes()],
+        bump
+    )]
+    pub service_stats: Option<Account<'info, EscrowStats>>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveMultisig<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(mut, seeds=[b"multisig", escrow_call.call_id.as_bytes()], bump)]
+    pub multisig: Account<'info, MultiSigConfig>,
+    pub signer: Signer<'info>,
+    /// Receives the multisig's rent when the final approval closes it.
+    #[account(mut, address = escrow_call.payer @ AssuredError::InvalidPayer)]
+    pub payer: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelMultisig<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion,
+        close = payer
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(mut, seeds=[b"multisig", escrow_call.call_id.as_bytes()], bump, close = payer)]
+    pub multisig: Account<'info, MultiSigConfig>,
+    #[account(mut, address = escrow_call.payer @ AssuredError::InvalidPayer)]
+    pub payer: Signer<'info>,
+    #[account(mut, seeds=[b"provider_index", escrow_call.provider.as_ref()], bump)]
+    pub provider_index: Option<Box<Account<'info, ProviderCallIndex>>>,
+    #[account(mut, seeds=[b"stats"], bump)]
+    pub global_stats: Account<'info, EscrowStats>,
+    /// Required, with the reputation accounts, when the call holds a slot
+    /// on its service's cap.
+    #[account(
+        mut,
+        seeds=[b"svc", escrow_call.service_id.as_bytes()],
+        bump,
+        seeds::program = reputation::ID
+    )]
+    pub service: Option<Box<Account<'info, reputation::Service>>>,
+    /// CHECK: reputation validates its own config PDA
+    pub reputation_config: Option<UncheckedAccount<'info>>,
+    pub reputation_program: Option<Program<'info, reputation::program::Reputation>>,
+    /// Signs reputation's escrow-only instructions; reputation accepts
+    /// only this PDA of the escrow program it is configured with.
+    /// CHECK: a data-less signing PDA
+    #[account(seeds=[reputation::ESCROW_AUTHORITY_SEED], bump)]
+    pub reputation_authority: Option<UncheckedAccount<'info>>,
+}
+
+/// Late-delivery tier: a call delivered within `deadline_factor_bps` of its
+/// SLA (15_000 = 1.5x) releases `multiplier_bps` of its payout.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct SlaTier {
+    pub deadline_factor_bps: u32,
+    pub multiplier_bps: u16,
+}
+
+/// One recipient's share of a call's release payouts.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct PayoutSplit {
+    pub recipient: Pubkey,
+    pub bps: u16,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(init, payer = admin, space = 8 + Config::INIT_SPACE, seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::Escrow>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ AssuredError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut, seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    /// CHECK: an older layout doesn't deserialize as Config; the handler
+    /// checks the discriminator and the stored admin
+    #[account(mut, seeds=[b"config"], bump, owner = crate::ID)]
+    pub config: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct QueryIndex<'info> {
+    #[account(seeds=[b"provider_index", provider_index.provider.as_ref()], bump)]
+    pub provider_index: Box<Account<'info, ProviderCallIndex>>,
+}
+
+#[derive(Accounts)]
+pub struct InitPayerIndex<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PayerCallIndex::INIT_SPACE,
+        seeds=[b"index", payer.key().as_ref()],
+        bump
+    )]
+    pub payer_index: Account<'info, PayerCallIndex>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetProviderKeys<'info> {
+    #[account(
+        init_if_needed,
+        payer = provider,
+        space = 8 + ProviderKeys::INIT_SPACE,
+        seeds=[b"provider_keys", provider.key().as_ref()],
+        bump
+    )]
+    pub provider_keys: Account<'info, ProviderKeys>,
+    #[account(mut)]
+    pub provider: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageApproval<'info> {
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + PayerProviderApproval::INIT_SPACE,
+        seeds=[b"ppa", payer.key().as_ref()],
+        bump
+    )]
+    pub approval: Account<'info, PayerProviderApproval>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageReferrers<'info> {
+    #[account(
+        init_if_needed,
+        payer = provider,
+        space = 8 + ProviderReferrers::INIT_SPACE,
+        seeds=[b"referrers", provider.key().as_ref()],
+        bump
+    )]
+    pub referrers: Account<'info, ProviderReferrers>,
+    #[account(mut)]
+    pub provider: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageAllowlist<'info> {
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + ProviderAllowlist::INIT_SPACE,
+        seeds=[b"allowlist", owner.key().as_ref()],
+        bump
+    )]
+    pub allowlist: Account<'info, ProviderAllowlist>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ManageBlacklist<'info> {
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + ProviderBlacklist::INIT_SPACE,
+        seeds=[b"blacklist"],
+        bump
+    )]
+    pub blacklist: Account<'info, ProviderBlacklist>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct IssueVoucher<'info> {
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + VoucherRegistry::INIT_SPACE,
+        seeds=[b"vouchers", authority.key().as_ref()],
+        bump
+    )]
+    pub voucher_registry: Account<'info, VoucherRegistry>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Fulfill<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    pub provider: Signer<'info>,
+    /// CHECK: address-constrained to the instructions sysvar
+    #[account(address = solana_sdk_ids::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    #[account(seeds=[b"proofs", escrow_call.call_id.as_bytes()], bump)]
+    pub proof_set: Option<Account<'info, FulfillmentProofSet>>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    /// Required when a partial release pays a referrer.
+    #[account(mut)]
+    pub referrer: Option<SystemAccount<'info>>,
+}
+
+#[derive(Accounts)]
+#[instruction(call_id: String)]
+pub struct InitProofSet<'info> {
+    #[account(
+        init,
+        payer = provider,
+        space = 8 + FulfillmentProofSet::INIT_SPACE,
+        seeds=[b"proofs", call_id.as_bytes()],
+        bump
+    )]
+    pub proof_set: Account<'info, FulfillmentProofSet>,
+    #[account(
+        mut, seeds=[b"call", call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(mut)]
+    pub provider: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitChunkProof<'info> {
+    #[account(mut, seeds=[b"proofs", escrow_call.call_id.as_bytes()], bump)]
+    pub proof_set: Account<'info, FulfillmentProofSet>,
+    #[account(
+        seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    pub provider: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateEscrow<'info> {
+    /// CHECK: legacy layouts don't deserialize as EscrowCall; the handler
+    /// checks the discriminator before rewriting
+    #[account(mut, owner = crate::ID)]
+    pub escrow_call: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendSla<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    pub payer: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct Arbitrate<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    pub arbitrator: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(seeds=[b"evidence", escrow_call.call_id.as_bytes()], bump)]
+    pub evidence: Option<Account<'info, Evidence>>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveDispute<'info> {
+    #[account(
+        seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    /// Created by the first arbiter to vote.
+    #[account(
+        init_if_needed,
+        payer = arbiter,
+        space = 8 + Votes::INIT_SPACE,
+        seeds=[b"votes", escrow_call.call_id.as_bytes()],
+        bump
+    )]
+    pub votes: Account<'info, Votes>,
+    #[account(mut)]
+    pub arbiter: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeResolution<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    /// Absent when no arbiter voted; only the timeout refund applies then.
+    #[account(seeds=[b"votes", escrow_call.call_id.as_bytes()], bump)]
+    pub votes: Option<Account<'info, Votes>>,
+}
+
+#[derive(Accounts)]
+pub struct CollectOrphanRent<'info> {
+    /// CHECK: must be the orphan's `["call", call_id]` address and already
+    /// closed; checked in the handler
+    pub escrow_call: UncheckedAccount<'info>,
+    /// CHECK: must be the rent payer recorded on the orphan
+    #[account(mut)]
+    pub rent_payer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitEvidence<'info> {
+    #[account(
+        seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(
+        init_if_needed,
+        payer = submitter,
+        space = 8 + Evidence::INIT_SPACE,
+        seeds=[b"evidence", escrow_call.call_id.as_bytes()],
+        bump
+    )]
+    pub evidence: Account<'info, Evidence>,
+    #[account(mut)]
+    pub submitter: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AppealArbitration<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    pub appellant: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeSuperArbitrator<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + SuperArbitrator::INIT_SPACE,
+        seeds=[b"super_arbitrator"],
+        bump
+    )]
+    pub super_arbitrator: Account<'info, SuperArbitrator>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositInsurance<'info> {
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + InsurancePool::INIT_SPACE,
+        seeds=[b"insurance"],
+        bump
+    )]
+    pub insurance_pool: Account<'info, InsurancePool>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StatsSnapshotQuery<'info> {
+    #[account(seeds=[b"stats"], bump)]
+    pub global_stats: Account<'info, EscrowStats>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateStats<'info> {
+    /// CHECK: an older layout doesn't deserialize as EscrowStats; the
+    /// handler checks the discriminator and owner
+    #[account(mut, owner = crate::ID)]
+    pub stats: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResetEpochClaims<'info> {
+    #[account(mut, seeds=[b"insurance"], bump)]
+    pub insurance_pool: Account<'info, InsurancePool>,
+    pub admin: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveAppeal<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(seeds=[b"super_arbitrator"], bump)]
+    pub super_arbitrator: Account<'info, SuperArbitrator>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct QuoteUnits<'info> {
+    #[account(
+        seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+}
+
+#[derive(Accounts)]
+pub struct RaiseDispute<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(mut)]
+    pub reporter: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds=[b"stats"], bump)]
+    pub global_stats: Account<'info, EscrowStats>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: address-constrained to the instructions sysvar
+    #[account(address = solana_sdk_ids::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(call_id_hash: [u8; 32], service_id: String)]
+pub struct InitPaymentV2<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = EscrowCallV2::MAX_LEN_V2,
+        seeds=[b"callv2", call_id_hash.as_ref()],
+        bump
+    )]
+    pub escrow_call: Account<'info, EscrowCallV2>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: Provider is recorded and later enforced
+    pub provider: UncheckedAccount<'info>,
+    /// Pass it writable when the service caps its outstanding calls.
+    #[account(seeds=[b"svc", service_id.as_bytes()], bump, seeds::program = reputation::ID)]
+    pub service: Account<'info, reputation::Service>,
+    /// Required when the service caps its outstanding calls, so the
+    /// new call can be counted via CPI.
+    /// CHECK: reputation validates its own config PDA
+    pub reputation_config: Option<UncheckedAccount<'info>>,
+    pub reputation_program: Option<Program<'info, reputation::program::Reputation>>,
+    /// Signs reputation's escrow-only instructions; reputation accepts
+    /// only this PDA of the escrow program it is configured with.
+    /// CHECK: a data-less signing PDA
+    #[account(seeds=[reputation::ESCROW_AUTHORITY_SEED], bump)]
+    pub reputation_authority: Option<UncheckedAccount<'info>>,
+    #[account(seeds=[b"ppa", payer.key().as_ref()], bump)]
+    pub approval: Option<Account<'info, PayerProviderApproval>>,
+    /// CHECK: the protocol blacklist PDA, which may not exist yet; read
+    /// by `check_blacklist`
+    #[account(seeds=[b"blacklist"], bump)]
+    pub blacklist: UncheckedAccount<'info>,
+    /// CHECK: the service owner's allowlist PDA, which may not exist;
+    /// read by `check_allowlist`
+    #[account(seeds=[b"allowlist", service.owner.as_ref()], bump)]
+    pub allowlist: UncheckedAccount<'info>,
+    /// Created on first use, funded by that call's payer.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + ProviderCallIndex::INIT_SPACE,
+        seeds=[b"provider_index", provider.key().as_ref()],
+        bump
+    )]
+    pub provider_index: Box<Account<'info, ProviderCallIndex>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + EscrowStats::INIT_SPACE,
+        seeds=[b"stats"],
+        bump
+    )]
+    pub global_stats: Account<'info, EscrowStats>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + EscrowStats::INIT_SPACE,
+        seeds=[b"svc_stats", service_id.as_bytes()],
+        bump
+    )]
+    pub service_stats: Option<Account<'info, EscrowStats>>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FulfillV2<'info> {
+    #[account(
+        mut, seeds=[b"callv2", escrow_call.call_id.as_ref()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Account<'info, EscrowCallV2>,
+    pub provider: Signer<'info>,
+    /// CHECK: address-constrained to the instructions sysvar
+    #[account(address = solana_sdk_ids::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct RaiseDisputeV2<'info> {
+    #[account(
+        mut, seeds=[b"callv2", escrow_call.call_id.as_ref()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Account<'info, EscrowCallV2>,
+    #[account(mut)]
+    pub reporter: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds=[b"stats"], bump)]
+    pub global_stats: Account<'info, EscrowStats>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: address-constrained to the instructions sysvar
+    #[account(address = solana_sdk_ids::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ArbitrateV2<'info> {
+    #[account(
+        mut, seeds=[b"callv2", escrow_call.call_id.as_ref()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Account<'info, EscrowCallV2>,
+    pub arbitrator: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct SettleV2<'info> {
+    #[account(
+        mut, seeds=[b"callv2", escrow_call.call_id.as_ref()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Account<'info, EscrowCallV2>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+    #[account(mut)]
+    pub provider: SystemAccount<'info>,
+    #[account(mut, address = escrow_call.rent_recipient @ AssuredError::InvalidRentRecipient)]
+    pub rent_recipient: SystemAccount<'info>,
+    /// Lists the call by its hex call id hash, or under its original id if
+    /// it was migrated from v1.
+    #[account(mut, seeds=[b"provider_index", escrow_call.provider.as_ref()], bump)]
+    pub provider_index: Option<Box<Account<'info, ProviderCallIndex>>>,
+    #[account(mut, seeds=[b"stats"], bump)]
+    pub global_stats: Account<'info, EscrowStats>,
+    #[account(mut, seeds=[b"svc_stats", escrow_call.service_id.as_bytes()], bump)]
+    pub service_stats: Option<Account<'info, EscrowStats>>,
+    /// Required, with the reputation accounts, when the call holds a slot
+    /// on its service's cap.
+    #[account(
+        mut,
+        seeds=[b"svc", escrow_call.service_id.as_bytes()],
+        bump,
+        seeds::program = reputation::ID
+    )]
+    pub service: Option<Box<Account<'info, reputation::Service>>>,
+    /// CHECK: reputation validates its own config PDA
+    pub reputation_config: Option<UncheckedAccount<'info>>,
+    pub reputation_program: Option<Program<'info, reputation::program::Reputation>>,
+    /// Signs reputation's escrow-only instructions; reputation accepts
+    /// only this PDA of the escrow program it is configured with.
+    /// CHECK: a data-less signing PDA
+    #[account(seeds=[reputation::ESCROW_AUTHORITY_SEED], bump)]
+    pub reputation_authority: Option<UncheckedAccount<'info>>,
+    /// Pass to let a refund on a large escrow claim from the pool.
+    #[account(mut, seeds=[b"insurance"], bump)]
+    pub insurance_pool: Option<Box<Account<'info, InsurancePool>>>,
+    /// Reputation's treasury; with the reputation accounts, lets an upheld
+    /// dispute slash the provider's bond to the payer.
+    #[account(mut)]
+    pub treasury: Option<SystemAccount<'info>>,
+    /// The service's reputation audit log; the CPIs that change its score
+    /// or bond are skipped without it, like the other reputation accounts.
+    /// CHECK: reputation reads it as its `ServiceAuditLog`
+    #[account(
+        mut,
+        seeds=[b"audit", escrow_call.service_id.as_bytes()],
+        bump,
+        seeds::program = reputation::ID
+    )]
+    pub audit_log: Option<UncheckedAccount<'info>>,
+    /// With `treasury` and the reputation accounts, an upheld dispute on a
+    /// service bonded in tokens also slashes that bond: pass its mint, the
+    /// service's vault, the payer's and treasury's token accounts for the
+    /// mint and the token program.
+    /// CHECK: reputation checks it against the service's `bond_mint`
+    pub bond_mint: Option<UncheckedAccount<'info>>,
+    /// CHECK: reputation checks it is the service PDA's ATA for the mint
+    #[account(mut)]
+    pub bond_vault: Option<UncheckedAccount<'info>>,
+    /// CHECK: reputation checks it is the payer's ATA for the mint
+    #[account(mut)]
+    pub payer_bond_token: Option<UncheckedAccount<'info>>,
+    /// CHECK: reputation checks it is the treasury's ATA for the mint
+    #[account(mut)]
+    pub treasury_bond_token: Option<UncheckedAccount<'info>>,
+    /// CHECK: reputation checks it is the token program
+    pub token_program: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct TriggerExpiryV2<'info> {
+    #[account(
+        mut, seeds=[b"callv2", escrow_call.call_id.as_ref()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Account<'info, EscrowCallV2>,
+    #[account(mut, address = escrow_call.payer @ AssuredError::InvalidPayer)]
+    pub payer: SystemAccount<'info>,
+    #[account(mut, address = escrow_call.rent_recipient @ AssuredError::InvalidRentRecipient)]
+    pub rent_recipient: SystemAccount<'info>,
+    #[account(mut)]
+    pub submitter: Signer<'info>,
+    /// Lists the call by its hex call id hash, or under its original id if
+    /// it was migrated from v1.
+    #[account(mut, seeds=[b"provider_index", escrow_call.provider.as_ref()], bump)]
+    pub provider_index: Option<Box<Account<'info, ProviderCallIndex>>>,
+    #[account(mut, seeds=[b"stats"], bump)]
+    pub global_stats: Account<'info, EscrowStats>,
+    #[account(mut, seeds=[b"svc_stats", escrow_call.service_id.as_bytes()], bump)]
+    pub service_stats: Option<Account<'info, EscrowStats>>,
+    /// Required, with the reputation accounts, when the call holds a slot
+    /// on its service's cap.
+    #[account(
+        mut,
+        seeds=[b"svc", escrow_call.service_id.as_bytes()],
+        bump,
+        seeds::program = reputation::ID
+    )]
+    pub service: Option<Box<Account<'info, reputation::Service>>>,
+    /// CHECK: reputation validates its own config PDA
+    pub reputation_config: Option<UncheckedAccount<'info>>,
+    pub reputation_program: Option<Program<'info, reputation::program::Reputation>>,
+    /// Signs reputation's escrow-only instructions; reputation accepts
+    /// only this PDA of the escrow program it is configured with.
+    /// CHECK: a data-less signing PDA
+    #[account(seeds=[reputation::ESCROW_AUTHORITY_SEED], bump)]
+    pub reputation_authority: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+#[instruction(call_id_hash: [u8; 32])]
+pub struct MigrateV1ToV2<'info> {
+    #[account(
+        mut,
+        seeds=[b"call", escrow_call.call_id.as_bytes()],
+        bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion,
+        close = escrow_call_v2
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(
+        init,
+        payer = payer,
+        space = EscrowCallV2::MAX_LEN_V2,
+        seeds=[b"callv2", call_id_hash.as_ref()],
+        bump
+    )]
+    pub escrow_call_v2: Account<'info, EscrowCallV2>,
+    #[account(mut, address = escrow_call.payer @ AssuredError::InvalidPayer)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(channel_id: [u8; 32])]
+pub struct OpenChannel<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Channel::INIT_SPACE,
+        seeds=[b"channel", payer.key().as_ref(), channel_id.as_ref()],
+        bump
+    )]
+    pub channel: Account<'info, Channel>,
     #[account(mut)]
     pub payer: Signer<'info>,
     /// CHECK: Provider is recorded and later enforced
     pub provider: UncheckedAccount<'info>,
+    #[account(seeds=[b"provider_keys", provider.key().as_ref()], bump)]
+    pub provider_keys: Option<Account<'info, ProviderKeys>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FulfillChannel<'info> {
+    #[account(
+        mut,
+        seeds=[b"channel", channel.payer.as_ref(), channel.channel_id.as_ref()],
+        bump,
+        has_one = provider @ AssuredError::InvalidProvider
+    )]
+    pub channel: Account<'info, Channel>,
+    #[account(mut)]
+    pub provider: Signer<'info>,
+    /// CHECK: address-constrained to the instructions sysvar
+    #[account(address = solana_sdk_ids::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DisputeChannelDebit<'info> {
+    #[account(
+        mut,
+        seeds=[b"channel", channel.payer.as_ref(), channel.channel_id.as_ref()],
+        bump,
+        has_one = payer @ AssuredError::InvalidPayer
+    )]
+    pub channel: Account<'info, Channel>,
+    pub payer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ArbitrateChannelDebit<'info> {
+    #[account(
+        mut,
+        seeds=[b"channel", channel.payer.as_ref(), channel.channel_id.as_ref()],
+        bump,
+        has_one = provider @ AssuredError::InvalidProvider
+    )]
+    pub channel: Account<'info, Channel>,
+    #[account(mut)]
+    pub provider: SystemAccount<'info>,
+    pub arbitrator: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct CloseChannel<'info> {
+    #[account(
+        mut,
+        seeds=[b"channel", channel.payer.as_ref(), channel.channel_id.as_ref()],
+        bump,
+        has_one = payer @ AssuredError::InvalidPayer,
+        has_one = provider @ AssuredError::InvalidProvider,
+        close = payer
+    )]
+    pub channel: Account<'info, Channel>,
+    /// Either party, so a provider is not stranded by a payer who walks
+    /// away from the channel.
+    #[account(
+        constraint = closer.key() == channel.payer || closer.key() == channel.provider
+            @ AssuredError::Unauthorized
+    )]
+    pub closer: Signer<'info>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+    #[account(mut)]
+    pub provider: SystemAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Redeem<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(mut)]
+    pub provider: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Abort<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(mut, address = escrow_call.payer @ AssuredError::InvalidPayer)]
+    pub payer: SystemAccount<'info>,
+    #[account(mut, address = escrow_call.rent_recipient @ AssuredError::InvalidRentRecipient)]
+    pub rent_recipient: SystemAccount<'info>,
+    /// CHECK: address-constrained to the instructions sysvar
+    #[account(address = solana_sdk_ids::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    /// Required when the call counts against the service's
+    /// `max_outstanding_calls`, to release its slot.
+    #[account(
+        mut,
+        seeds=[b"svc", escrow_call.service_id.as_bytes()],
+        bump,
+        seeds::program = reputation::ID
+    )]
+    pub service: Option<Account<'info, reputation::Service>>,
+    /// CHECK: reputation validates its own config PDA
+    pub reputation_config: Option<UncheckedAccount<'info>>,
+    pub reputation_program: Option<Program<'info, reputation::program::Reputation>>,
+    /// Signs reputation's escrow-only instructions; reputation accepts
+    /// only this PDA of the escrow program it is configured with.
+    /// CHECK: a data-less signing PDA
+    #[account(seeds=[reputation::ESCROW_AUTHORITY_SEED], bump)]
+    pub reputation_authority: Option<UncheckedAccount<'info>>,
+    #[account(mut, seeds=[b"provider_index", escrow_call.provider.as_ref()], bump)]
+    pub provider_index: Option<Box<Account<'info, ProviderCallIndex>>>,
+    #[account(mut, seeds=[b"stats"], bump)]
+    pub global_stats: Account<'info, EscrowStats>,
+}
+
+#[derive(Accounts)]
+pub struct SetPayoutSplits<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    pub provider: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitProof<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    pub provider: Signer<'info>,
+    /// CHECK: address-constrained to the instructions sysvar
+    #[account(address = solana_sdk_ids::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReopenDispute<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds=[b"stats"], bump)]
+    pub global_stats: Account<'info, EscrowStats>,
     pub system_program: Program<'info, System>,
 }
 
-#[derive(Accounts)]
-pub struct Fulfill<'info> {
-    #[account(mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump)]
-    pub escrow_call: Account<'info, EscrowCall>,
-    pub provider: Signer<'info>,
-}
+#[derive(Accounts)]
+pub struct Settle<'info> {
+    #[account(
+        mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump,
+        constraint = escrow_call.version == ESCROW_CALL_VERSION @ AssuredError::UnsupportedVersion
+    )]
+    pub escrow_call: Box<Account<'info, EscrowCall>>,
+    #[account(mut)]
+    pub payer: SystemAccount<'info>,
+    #[account(mut)]
+    pub provider: SystemAccount<'info>,
+    /// Receives the escrow's rent when it closes; usually the payer, but a
+    /// relayer that funded `init_payment` can name itself instead.
+    #[account(mut, address = escrow_call.rent_recipient @ AssuredError::InvalidRentRecipient)]
+    pub rent_recipient: SystemAccount<'info>,
+    #[account(mut, seeds=[b"index", payer.key().as_ref()], bump)]
+    pub payer_index: Option<Box<Account<'info, PayerCallIndex>>>,
+    #[account(mut, seeds=[b"provider_index", provider.key().as_ref()], bump)]
+    pub provider_index: Option<Box<Account<'info, ProviderCallIndex>>>,
+    /// Pass the memo program to stamp the transaction with a readable
+    /// `assured:` memo; omit it to save compute.
+    #[account(address = MEMO_PROGRAM_ID)]
+    pub memo_program: Option<UncheckedAccount<'info>>,
+    #[account(mut, seeds=[b"stats"], bump)]
+    pub global_stats: Account<'info, EscrowStats>,
+    #[account(mut, seeds=[b"svc_stats", escrow_call.service_id.as_bytes()], bump)]
+    pub service_stats: Option<Account<'info, EscrowStats>>,
+    /// Reputation accounts are optional; when all are supplied a refund
+    /// extends the provider's suspension backoff via CPI.
+    #[account(
+        mut,
+        seeds=[b"svc", escrow_call.service_id.as_bytes()],
+        bump,
+        seeds::program = reputation::ID
+    )]
+    pub service: Option<Box<Account<'info, reputation::Service>>>,
+    /// CHECK: reputation validates its own config PDA
+    pub reputation_config: Option<UncheckedAccount<'info>>,
+    pub reputation_program: Option<Program<'info, reputation::program::Reputation>>,
+    /// Signs reputation's escrow-only instructions; reputation accepts
+    /// only this PDA of the escrow program it is configured with.
+    /// CHECK: a data-less signing PDA
+    #[account(seeds=[reputation::ESCROW_AUTHORITY_SEED], bump)]
+    pub reputation_authority: Option<UncheckedAccount<'info>>,
+    /// Required when a released call has a referrer.
+    #[account(mut)]
+    pub referrer: Option<SystemAccount<'info>>,
+    /// Collects the bounty on `timeout_refund` and `trigger_expiry`;
+    /// unused by `settle`.
+    #[account(mut)]
+    pub submitter: Option<Signer<'info>>,
+    /// Pass to let a refund on a large escrow claim from the pool.
+    #[account(mut, seeds=[b"insurance"], bump)]
+    pub insurance_pool: Option<Box<Account<'info, InsurancePool>>>,
+    /// Reputation's treasury; with the reputation accounts, lets an upheld
+    /// dispute slash the provider's bond to the payer.
+    #[account(mut)]
+    pub treasury: Option<SystemAccount<'info>>,
+    /// The service's reputation audit log; the CPIs that change its score
+    /// or bond are skipped without it, like the other reputation accounts.
+    /// CHECK: reputation reads it as its `ServiceAuditLog`
+    #[account(
+        mut,
+        seeds=[b"audit", escrow_call.service_id.as_bytes()],
+        bump,
+        seeds::program = reputation::ID
+    )]
+    pub audit_log: Option<UncheckedAccount<'info>>,
+    /// With `treasury` and the reputation accounts, an upheld dispute on a
+    /// service bonded in tokens also slashes that bond: pass its mint, the
+    /// service's vault, the payer's and treasury's token accounts for the
+    /// mint and the token program.
+    /// CHECK: reputation checks it against the service's `bond_mint`
+    pub bond_mint: Option<UncheckedAccount<'info>>,
+    /// CHECK: reputation checks it is the service PDA's ATA for the mint
+    #[account(mut)]
+    pub bond_vault: Option<UncheckedAccount<'info>>,
+    /// CHECK: reputation checks it is the payer's ATA for the mint
+    #[account(mut)]
+    pub payer_bond_token: Option<UncheckedAccount<'info>>,
+    /// CHECK: reputation checks it is the treasury's ATA for the mint
+    #[account(mut)]
+    pub treasury_bond_token: Option<UncheckedAccount<'info>>,
+    /// CHECK: reputation checks it is the token program
+    pub token_program: Option<UncheckedAccount<'info>>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct EscrowCall {
+    pub version: u8,
+    /// Fixed-size keys come before the first string so indexers can
+    /// `memcmp` them at `ESCROW_CALL_PAYER_OFFSET`/`_PROVIDER_OFFSET`.
+    pub payer: Pubkey,
+    pub provider: Pubkey,
+    #[max_len(MAX_CALL_ID_LEN)]
+    pub call_id: String,
+    pub state: CallState,
+}
+
+/// Everything a call records past its keys, laid out the same in
+/// `EscrowCall` and `EscrowCallV2`. Both deref to it, so the lifecycle
+/// helpers read `ec.amount` whichever layout the call is in. New fields
+/// are appended here.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct CallState {
+    #[max_len(MAX_SERVICE_ID_LEN)]
+    pub service_id: String,
+    pub amount: u64,
+    pub start_ts: u64,
+    pub sla_ms: u64,
+    /// Seconds after delivery during which the payer may dispute. Zero
+    /// means no window: a delivery within the SLA can be settled at once
+    /// and can no longer be disputed. Capped by `MAX_DISPUTE_WINDOW_S`.
+    pub dispute_window_s: u64,
+    pub status: u8, // 0 init, 1 fulfilled, 2 released, 3 refunded
+    pub delivered_ts: Option<u64>,
+    pub response_hash: [u8; 32],
+    pub disputed: bool,
+    pub total_units: u64,
+    pub units_released: u64,
+    #[max_len(MAX_PROVIDER_SIG_LEN)]
+    pub provider_sig: Vec<u8>,
+    pub last_heartbeat_ts: u64,
+    pub refund_after_ts: u64,
+    pub heartbeat_interval_s: u64,
+    pub heartbeat_required: bool,
+    pub appeal_deadline_ts: i64,
+    pub appeal_filed_by: Option<Pubkey>,
+    pub ruling: Option<u8>,
+    pub appeal_resolved: bool,
+    pub provider_attest_key: [u8; 32],
+    pub requires_proofs: bool,
+    pub remainder_policy: u8,
+    pub rent_recipient: Pubkey,
+    pub disputed_units: u64,
+    pub max_partials: u16,
+    pub partial_count: u16,
+    pub disputed_at_ts: i64,
+    pub provider_proof_hash: [u8; 32],
+    pub hashlock: [u8; 32],
+    /// Empty pays the provider; otherwise releases are split by `bps`.
+    #[max_len(MAX_PAYOUT_SPLITS)]
+    pub payout_splits: Vec<PayoutSplit>,
+    pub referrer: Option<Pubkey>,
+    pub referral_fee_bps: u16,
+    /// Ethereum address a `SigMode::Secp256k1` call is verified against,
+    /// copied from the provider's `provider_keys` at `init_payment`.
+    pub provider_eth_address: Option<[u8; 20]>,
+    /// Lamports the payer posted with the open dispute, held on the escrow.
+    pub dispute_bond: u64,
+    /// When `finalize` closed out the call; 0 until then.
+    pub finalized_at: i64,
+    /// Whether the call was counted against the service's
+    /// `max_outstanding_calls`; closing it must then release the slot.
+    pub outstanding_tracked: bool,
+    /// How `settle` prices the call (`BillingMode`); fixed at `init_payment`.
+    pub billing_mode: u8,
+    /// Bytes the provider reported delivering on `fulfill`, for data-volume calls.
+    pub response_size_bytes: u64,
+    /// How `fulfill` checks `provider_sig` (`SigMode`).
+    pub sig_mode: u8,
+    /// Past this an unfulfilled call can be refunded by anyone via
+    /// `trigger_expiry`.
+    pub expiry_ts: u64,
+    /// Where the provider stored the full response (e.g. an IPFS or
+    /// Arweave CID); empty when not given. Only the length is checked.
+    #[max_len(MAX_CONTENT_CID_LEN)]
+    pub content_cid: Vec<u8>,
+    /// Share of the payout withheld and refunded to the payer when delivery
+    /// was late but undisputed, copied from the config at init. 0 keeps the
+    /// old behaviour of refunding late calls in full.
+    pub late_penalty_bps: u16,
+    /// Payout tiers for late delivery; empty keeps all-or-nothing (plus
+    /// `late_penalty_bps`).
+    #[max_len(MAX_SLA_TIERS)]
+    pub sla_tiers: Vec<SlaTier>,
+    /// `chunk_nonce` of the last chunk: a hash of its data, units and CID,
+    /// so the same chunk cannot be paid twice under a later `ts`.
+    pub last_chunk_nonce: [u8; 8],
+    /// Voucher redeemed when the call was opened, if any.
+    pub redemption_code: Option<[u8; 16]>,
+    /// M-of-N committee that resolves disputes on this call instead of
+    /// the config admin; empty for admin arbitration.
+    #[max_len(MAX_ARBITERS)]
+    pub arbiters: Vec<Pubkey>,
+    pub arbiter_threshold: u8,
+    /// Kind of the open dispute, as passed to `raise_dispute`.
+    pub dispute_kind: u8,
+    /// Where `dispute_bond` is (`BondCustody`): held on the escrow until
+    /// settlement returns or forfeits it.
+    pub dispute_bond_state: u8,
+    /// Clock time the call was fulfilled, 0 before. The dispute window
+    /// runs from here, not from the provider-supplied `delivered_ts`.
+    pub fulfilled_at: u64,
+    /// When `settle` released or refunded the call; `finalize` may close
+    /// it `FINALIZE_DELAY_S` later.
+    pub settled_at: i64,
+    /// `ts` of the last chunk `fulfill_partial` accepted. Each chunk must
+    /// be later, so no earlier chunk can be replayed, signed or not.
+    pub last_chunk_ts: u64,
+}
+
+impl std::ops::Deref for EscrowCall {
+    type Target = CallState;
+
+    fn deref(&self) -> &CallState {
+        &self.state
+    }
+}
+
+impl std::ops::DerefMut for EscrowCall {
+    fn deref_mut(&mut self) -> &mut CallState {
+        &mut self.state
+    }
+}
+
+impl EscrowCall {
+    pub const MAX_LEN: usize = 8 + EscrowCall::INIT_SPACE;
+
+    /// Reads any layout an escrow has been written with, for
+    /// `migrate_escrow`; every other instruction rejects an account below
+    /// `ESCROW_CALL_VERSION` with `UnsupportedVersion`. Since version 1
+    /// fields are only ever appended, and an all-zero field decodes as its
+    /// default, a short account is zero-extended and read as the current
+    /// layout; version 1 first has its keys moved ahead of the strings.
+    pub fn load_versioned(data: &[u8]) -> Result<EscrowCall> {
+        require!(
+            data.len() >= 8 && data[..8] == *EscrowCall::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        if data.len() == 8 + EscrowCallV0::LEN {
+            let legacy = EscrowCallV0::deserialize(&mut &data[8..])?;
+            return Ok(legacy.into_current());
+        }
+        let mut buf = data.to_vec();
+        buf.resize(buf.len().max(8 + EscrowCall::INIT_SPACE), 0);
+        let version = buf[8];
+        match version {
+            1 => move_v1_keys_forward(&mut buf[9..])?,
+            2 | ESCROW_CALL_VERSION => {}
+            _ => return err!(AssuredError::UnsupportedVersion),
+        }
+        let mut ec = EscrowCall::try_deserialize(&mut &buf[..])?;
+        if version < ESCROW_CALL_VERSION {
+            ec.fill_appended_defaults();
+        }
+        Ok(ec)
+    }
+
+    /// Replaces the zeroes an older account reads back for fields it
+    /// predates with what `init_payment` would have set.
+    fn fill_appended_defaults(&mut self) {
+        self.version = ESCROW_CALL_VERSION;
+        if self.rent_recipient == Pubkey::default() {
+            self.rent_recipient = self.payer;
+        }
+        if self.max_partials == 0 {
+            self.max_partials = DEFAULT_MAX_PARTIALS;
+        }
+        if self.expiry_ts == 0 {
+            self.expiry_ts = default_expiry_ts(self.start_ts, self.sla_ms);
+            if self.hashlock != [0u8; 32] {
+                self.expiry_ts = self.expiry_ts.max(self.refund_after_ts);
+            }
+        }
+        if self.sig_mode == SigMode::None as u8 && self.provider_attest_key != [0u8; 32] {
+            self.sig_mode = SigMode::Ed25519 as u8;
+        }
+        // Bonds predating custody tracking sit on the escrow until settle.
+        let settled =
+            self.status == Status::Released as u8 || self.status == Status::Refunded as u8;
+        if self.dispute_bond > 0 && self.dispute_bond_state == BondCustody::None as u8 && !settled {
+            self.dispute_bond_state = BondCustody::Held as u8;
+        }
+        if self.fulfilled_at == 0 {
+            self.fulfilled_at = self.delivered_ts.unwrap_or(0);
+        }
+    }
+}
+
+/// Rewrites a version-1 body, `call_id, payer, service_id, provider`, into
+/// the version-2 order `payer, provider, call_id, service_id`. The byte
+/// count doesn't change.
+fn move_v1_keys_forward(body: &mut [u8]) -> Result<()> {
+    let string_len = |at: usize| -> Result<usize> {
+        let prefix = body
+            .get(at..at + 4)
+            .ok_or(AssuredError::MalformedEscrowState)?;
+        Ok(4 + u32::from_le_bytes(prefix.try_into().unwrap()) as usize)
+    };
+    let payer_at = string_len(0)?;
+    let service_id_at = payer_at + 32;
+    let provider_at = service_id_at + string_len(service_id_at)?;
+    let end = provider_at + 32;
+    require!(end <= body.len(), AssuredError::MalformedEscrowState);
+    let mut reordered = Vec::with_capacity(end);
+    reordered.extend_from_slice(&body[payer_at..service_id_at]);
+    reordered.extend_from_slice(&body[provider_at..end]);
+    reordered.extend_from_slice(&body[..payer_at]);
+    reordered.extend_from_slice(&body[service_id_at..provider_at]);
+    body[..end].copy_from_slice(&reordered);
+    Ok(())
+}
+
+/// Rewrites a version-1 `EscrowCallV2` body from `service_id`, laid out
+/// `service_id, provider`, into the version-2 order `provider, service_id`.
+fn move_v1_provider_forward(body: &mut [u8]) -> Result<()> {
+    let prefix = body.get(..4).ok_or(AssuredError::MalformedEscrowState)?;
+    let provider_at = 4 + u32::from_le_bytes(prefix.try_into().unwrap()) as usize;
+    let end = provider_at + 32;
+    require!(end <= body.len(), AssuredError::MalformedEscrowState);
+    body[..end].rotate_left(provider_at);
+    Ok(())
+}
+
+/// EscrowCall keyed by the SHA-256 of its call id (see `call_id_to_hash`)
+/// instead of the string itself, so seeds and size no longer depend on the
+/// id's length. Only the keys differ; the `CallState` is the same.
+#[account]
+#[derive(InitSpace)]
+pub struct EscrowCallV2 {
+    pub version: u8,
+    pub call_id: [u8; 32],
+    pub payer: Pubkey,
+    pub provider: Pubkey,
+    pub state: CallState,
+}
+
+impl std::ops::Deref for EscrowCallV2 {
+    type Target = CallState;
+
+    fn deref(&self) -> &CallState {
+        &self.state
+    }
+}
+
+impl std::ops::DerefMut for EscrowCallV2 {
+    fn deref_mut(&mut self) -> &mut CallState {
+        &mut self.state
+    }
+}
+
+impl EscrowCallV2 {
+    pub const MAX_LEN_V2: usize = 8 + EscrowCallV2::INIT_SPACE;
+
+    /// `EscrowCall::load_versioned` for v2 escrows, which share its version
+    /// numbers. Version 1 had `provider` after `service_id`.
+    pub fn load_versioned(data: &[u8]) -> Result<EscrowCallV2> {
+        require!(
+            data.len() >= 8 && data[..8] == *EscrowCallV2::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        let mut buf = data.to_vec();
+        buf.resize(buf.len().max(EscrowCallV2::MAX_LEN_V2), 0);
+        let version = buf[8];
+        match version {
+            // Discriminator, version, call id hash and payer come first.
+            1 => move_v1_provider_forward(&mut buf[8 + 1 + 32 + 32..])?,
+            2 | ESCROW_CALL_VERSION => {}
+            _ => return err!(AssuredError::UnsupportedVersion),
+        }
+        let ec = EscrowCallV2::try_deserialize(&mut &buf[..])?;
+        if version == ESCROW_CALL_VERSION {
+            return Ok(ec);
+        }
+        let mut current = ec.to_v1();
+        current.fill_appended_defaults();
+        Ok(EscrowCallV2::from_v1(ec.call_id, &current))
+    }
+
+    pub fn from_v1(call_id_hash: [u8; 32], ec: &EscrowCall) -> EscrowCallV2 {
+        EscrowCallV2 {
+            version: ec.version,
+            call_id: call_id_hash,
+            payer: ec.payer,
+            provider: ec.provider,
+            state: ec.state.clone(),
+        }
+    }
+
+    /// Working copy for the shared lifecycle helpers. The string call id is
+    /// not stored, so events carry the hex digest instead.
+    pub fn to_v1(&self) -> EscrowCall {
+        EscrowCall {
+            version: self.version,
+            call_id: self.call_id.iter().map(|b| format!("{b:02x}")).collect(),
+            payer: self.payer,
+            provider: self.provider,
+            state: self.state.clone(),
+        }
+    }
+}
+
+pub fn call_v2_address(call_id_hash: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"callv2", call_id_hash], &crate::ID)
+}
+
+/// EscrowCall as laid out before accounts carried a version byte.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct EscrowCallV0 {
+    pub call_id: String,
+    pub payer: Pubkey,
+    pub service_id: String,
+    pub provider: Pubkey,
+    pub amount: u64,
+    pub start_ts: u64,
+    pub sla_ms: u64,
+    pub dispute_window_s: u64,
+    pub status: u8,
+    pub delivered_ts: Option<u64>,
+    pub response_hash: [u8; 32],
+    pub disputed: bool,
+    pub total_units: u64,
+    pub units_released: u64,
+    pub provider_sig: Vec<u8>,
+}
+
+impl EscrowCallV0 {
+    pub const LEN: usize = 4 + MAX_CALL_ID_LEN // call_id
+        + 32 // payer
+        + 4 + MAX_SERVICE_ID_LEN // service_id
+        + 32 // provider
+        + 8 * 4 // amount, start_ts, sla_ms, dispute_window_s
+        + 1 // status
+        + 9 // delivered_ts
+        + 32 // response_hash
+        + 1 // disputed
+        + 8 * 2 // total_units, units_released
+        + 4 + MAX_PROVIDER_SIG_LEN; // provider_sig
+
+    pub fn into_current(self) -> EscrowCall {
+        EscrowCall {
+            version: ESCROW_CALL_VERSION,
+            call_id: self.call_id,
+            payer: self.payer,
+            provider: self.provider,
+            state: CallState {
+                refund_after_ts: default_refund_after_ts(
+                    self.start_ts,
+                    self.sla_ms,
+                    self.dispute_window_s,
+                ),
+                rent_recipient: self.payer,
+                disputed_units: 0,
+                max_partials: DEFAULT_MAX_PARTIALS,
+                partial_count: 0,
+                disputed_at_ts: 0,
+                provider_proof_hash: [0; 32],
+                hashlock: [0; 32],
+                payout_splits: Vec::new(),
+                referrer: None,
+                referral_fee_bps: 0,
+                provider_eth_address: None,
+                dispute_bond: 0,
+                finalized_at: 0,
+                outstanding_tracked: false,
+                billing_mode: 0,
+                response_size_bytes: 0,
+                sig_mode: 0,
+                expiry_ts: default_expiry_ts(self.start_ts, self.sla_ms),
+                content_cid: Vec::new(),
+                late_penalty_bps: 0,
+                sla_tiers: Vec::new(),
+                last_chunk_nonce: [0; 8],
+                redemption_code: None,
+                arbiters: Vec::new(),
+                arbiter_threshold: 0,
+                dispute_kind: 0,
+                dispute_bond_state: 0,
+                fulfilled_at: self.delivered_ts.unwrap_or(0),
+                settled_at: 0,
+                last_chunk_ts: 0,
+                service_id: self.service_id,
+                amount: self.amount,
+                start_ts: self.start_ts,
+                sla_ms: self.sla_ms,
+                dispute_window_s: self.dispute_window_s,
+                status: self.status,
+                delivered_ts: self.delivered_ts,
+                response_hash: self.response_hash,
+                disputed: self.disputed,
+                total_units: self.total_units,
+                units_released: self.units_released,
+                provider_sig: self.provider_sig,
+                last_heartbeat_ts: 0,
+                heartbeat_interval_s: 0,
+                heartbeat_required: false,
+                appeal_deadline_ts: 0,
+                appeal_filed_by: None,
+                ruling: None,
+                appeal_resolved: false,
+                provider_attest_key: [0; 32],
+                requires_proofs: false,
+                remainder_policy: RemainderPolicy::FirstUnits as u8,
+            },
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace, Default)]
+pub struct Config {
+    pub admin: Pubkey,
+    pub max_sla_ms: u64,
+    pub max_dispute_window_s: u64,
+    /// Protocol fee in basis points; recorded here ahead of fee collection.
+    pub fee_bps: u16,
+    /// Seconds past the SLA during which `fulfill` is still accepted.
+    pub fulfill_grace_s: u64,
+    pub paused: bool,
+    /// Performance bond a service must hold before it can be paid through
+    /// escrow; 0 disables the check.
+    pub min_bond_required: u64,
+    /// Deposit a payer posts with each dispute; 0 makes disputes free.
+    pub dispute_bond_lamports: u64,
+    /// Reject escrows below the service's `price_per_unit * total_units`,
+    /// unless the service opted out.
+    pub strict_pricing: bool,
+    /// Penalty on late but undisputed deliveries, snapshotted into each
+    /// new call; 0 refunds late calls in full as before.
+    pub late_penalty_bps: u16,
+}
+
+impl Config {
+    pub fn check_admin(&self, signer: &Pubkey) -> Result<()> {
+        require_keys_eq!(*signer, self.admin, AssuredError::Unauthorized);
+        Ok(())
+    }
+
+    pub fn apply(&mut self, params: &ConfigParams) {
+        self.max_sla_ms = params.max_sla_ms;
+        self.max_dispute_window_s = params.max_dispute_window_s;
+        self.fee_bps = params.fee_bps;
+        self.fulfill_grace_s = params.fulfill_grace_s;
+        self.paused = params.paused;
+        self.min_bond_required = params.min_bond_required;
+        self.dispute_bond_lamports = params.dispute_bond_lamports;
+        self.strict_pricing = params.strict_pricing;
+        self.late_penalty_bps = params.late_penalty_bps;
+    }
+
+    pub fn updated_event(&self) -> ConfigUpdated {
+        ConfigUpdated {
+            admin: self.admin,
+            max_sla_ms: self.max_sla_ms,
+            max_dispute_window_s: self.max_dispute_window_s,
+            fee_bps: self.fee_bps,
+            fulfill_grace_s: self.fulfill_grace_s,
+            paused: self.paused,
+            min_bond_required: self.min_bond_required,
+            dispute_bond_lamports: self.dispute_bond_lamports,
+            strict_pricing: self.strict_pricing,
+            late_penalty_bps: self.late_penalty_bps,
+        }
+    }
+
+    /// Only new escrows are gated; settlement of existing calls never reads
+    /// the config so a pause cannot trap funds.
+    pub fn validate_init(&self, sla_ms: u64, dispute_window_s: u64) -> Result<()> {
+        require!(!self.paused, AssuredError::ProgramPaused);
+        require!(sla_ms <= self.max_sla_ms, AssuredError::SlaTooLong);
+        require!(
+            dispute_window_s <= self.max_dispute_window_s,
+            AssuredError::DisputeWindowTooLong
+        );
+        Ok(())
+    }
+
+    pub fn check_bond(&self, performance_bond_balance: u64) -> Result<()> {
+        require!(
+            performance_bond_balance >= self.min_bond_required,
+            AssuredError::InsufficientPerformanceBond
+        );
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ConfigParams {
+    pub max_sla_ms: u64,
+    pub max_dispute_window_s: u64,
+    pub fee_bps: u16,
+    pub fulfill_grace_s: u64,
+    pub paused: bool,
+    pub min_bond_required: u64,
+    pub dispute_bond_lamports: u64,
+    pub strict_pricing: bool,
+    pub late_penalty_bps: u16,
+}
+
+impl ConfigParams {
+    pub fn validate(&self) -> Result<()> {
+        require!(self.fee_bps <= MAX_FEE_BPS, AssuredError::InvalidFee);
+        require!(
+            self.max_dispute_window_s <= MAX_DISPUTE_WINDOW_S,
+            AssuredError::DisputeWindowTooLong
+        );
+        require!(
+            self.late_penalty_bps as u64 <= BPS_DENOMINATOR,
+            AssuredError::InvalidLatePenalty
+        );
+        require!(
+            self.fulfill_grace_s < EXPIRY_GRACE_S,
+            AssuredError::FulfillGraceTooLong
+        );
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct FulfillmentProofSet {
+    #[max_len(MAX_CALL_ID_LEN)]
+    pub call_id: String,
+    pub chunk_count: u32,
+    pub submitted_count: u32,
+    #[max_len(MAX_PROOF_CHUNKS)]
+    pub proof_hashes: Vec<[u8; 32]>,
+    pub verified: bool,
+    /// Who paid the rent; `collect_orphan_rent` returns it here.
+    pub rent_payer: Pubkey,
+}
+
+impl FulfillmentProofSet {
+    /// Chunks may arrive in any order; each slot is written once and a zero
+    /// hash is reserved to mean "not yet submitted".
+    pub fn record_chunk(&mut self, chunk_index: u32, hash: [u8; 32]) -> Result<()> {
+        require!(hash != [0u8; 32], AssuredError::InvalidChunkProof);
+        let slot = self
+            .proof_hashes
+            .get_mut(chunk_index as usize)
+            .ok_or(AssuredError::InvalidChunkProof)?;
+        require!(*slot == [0u8; 32], AssuredError::ChunkAlreadySubmitted);
+        *slot = hash;
+        self.submitted_count += 1;
+        Ok(())
+    }
+
+    pub fn mark_verified(&mut self) -> Result<()> {
+        require!(
+            self.submitted_count == self.chunk_count,
+            AssuredError::ProofSetIncomplete
+        );
+        self.verified = true;
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct EvidenceEntry {
+    pub evidence_hash: [u8; 32],
+    /// Hash of where the full evidence can be fetched.
+    pub uri_hash: [u8; 32],
+    pub submitted_at: i64,
+}
+
+/// Both parties' dispute evidence for one call, at `["evidence", call_id]`.
+#[account]
+#[derive(InitSpace)]
+pub struct Evidence {
+    #[max_len(MAX_CALL_ID_LEN)]
+    pub call_id: String,
+    /// The first submitter, who paid the rent.
+    pub rent_payer: Pubkey,
+    #[max_len(MAX_EVIDENCE_PER_PARTY)]
+    pub payer_entries: Vec<EvidenceEntry>,
+    #[max_len(MAX_EVIDENCE_PER_PARTY)]
+    pub provider_entries: Vec<EvidenceEntry>,
+}
+
+impl Evidence {
+    /// Appends to `role`'s slots and returns the slot index used.
+    pub fn submit(&mut self, role: u8, entry: EvidenceEntry) -> Result<u8> {
+        let entries = match role {
+            r if r == EvidenceRole::Payer as u8 => &mut self.payer_entries,
+            r if r == EvidenceRole::Provider as u8 => &mut self.provider_entries,
+            _ => return err!(AssuredError::InvalidEvidenceRole),
+        };
+        require!(
+            entries.len() < MAX_EVIDENCE_PER_PARTY,
+            AssuredError::EvidenceSlotsFull
+        );
+        entries.push(entry);
+        Ok((entries.len() - 1) as u8)
+    }
+
+    /// (payer, provider) submission counts.
+    pub fn counts(&self) -> (u8, u8) {
+        (
+            self.payer_entries.len() as u8,
+            self.provider_entries.len() as u8,
+        )
+    }
+}
+
+/// Committee votes on one dispute, at `["votes", call_id]`. Slots follow
+/// the order of the call's `arbiters`.
+#[account]
+#[derive(InitSpace)]
+pub struct Votes {
+    /// Identify the dispute being voted on, so votes left over from an
+    /// earlier call or dispute under the same id never count.
+    pub call_start_ts: u64,
+    pub disputed_at_ts: i64,
+    pub verdicts: [Option<u8>; MAX_ARBITERS],
+}
+
+impl Votes {
+    fn is_for(&self, ec: &EscrowCall) -> bool {
+        self.call_start_ts == ec.start_ts && self.disputed_at_ts == ec.disputed_at_ts
+    }
+
+    pub fn cast(&mut self, ec: &EscrowCall, slot: usize, verdict: u8) -> Result<()> {
+        require!(verdict <= Ruling::Refund as u8, AssuredError::InvalidRuling);
+        if !self.is_for(ec) {
+            *self = Self {
+                call_start_ts: ec.start_ts,
+                disputed_at_ts: ec.disputed_at_ts,
+                verdicts: [None; MAX_ARBITERS],
+            };
+        }
+        require!(self.verdicts[slot].is_none(), AssuredError::AlreadyVoted);
+        self.verdicts[slot] = Some(verdict);
+        Ok(())
+    }
+
+    /// The verdict with at least `arbiter_threshold` votes. Thresholds are
+    /// a strict majority, so at most one verdict can qualify.
+    pub fn quorum(&self, ec: &EscrowCall) -> Option<u8> {
+        if !self.is_for(ec) {
+            return None;
+        }
+        [Ruling::Release as u8, Ruling::Refund as u8]
+            .into_iter()
+            .find(|verdict| {
+                self.verdicts
+                    .iter()
+                    .filter(|v| **v == Some(*verdict))
+                    .count()
+                    >= ec.arbiter_threshold as usize
+            })
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct SuperArbitrator {
+    pub authority: Pubkey,
+}
+
+/// Lamports set aside to cover refunds on large escrows; `balance` excludes
+/// the account's own rent.
+#[account]
+#[derive(InitSpace)]
+pub struct InsurancePool {
+    pub balance: u64,
+    pub max_claim_bps: u16,
+    pub claims_this_epoch: u32,
+    /// Epoch `claims_this_epoch` counts; a claim in a later epoch restarts it.
+    pub epoch: u64,
+}
+
+impl InsurancePool {
+    /// Reserves and returns what the pool pays towards `shortfall`: at most
+    /// `max_claim_bps` of its balance, nothing for escrows at or below
+    /// `INSURANCE_THRESHOLD_LAMPORTS` or once the epoch's claims run out.
+    pub fn claim(&mut self, escrow_amount: u64, shortfall: u64, epoch: u64) -> u64 {
+        if epoch != self.epoch {
+            self.epoch = epoch;
+            self.claims_this_epoch = 0;
+        }
+        if escrow_amount <= INSURANCE_THRESHOLD_LAMPORTS
+            || self.claims_this_epoch >= MAX_CLAIMS_PER_EPOCH
+        {
+            return 0;
+        }
+        let cap =
+            (self.balance as u128 * self.max_claim_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        let amount = cap.min(shortfall);
+        if amount > 0 {
+            self.balance -= amount;
+            self.claims_this_epoch = self.claims_this_epoch.saturating_add(1);
+        }
+        amount
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace, PartialEq, Eq, Debug)]
+pub struct ChannelDebit {
+    pub call_id_hash: [u8; 32],
+    pub amount: u64,
+    pub ts: u64,
+    pub disputed: bool,
+}
+
+/// Prepaid budget for many calls from one payer to one provider, at PDA
+/// `["channel", payer, channel_id]`. Debits stay in `pending` until their
+/// dispute window passes, or until a disputed one is ruled on;
+/// `trace_commitment` chains every debit's call id, response hash and
+/// amount.
+#[account]
+#[derive(InitSpace)]
+pub struct Channel {
+    pub channel_id: [u8; 32],
+    pub payer: Pubkey,
+    pub provider: Pubkey,
+    pub budget: u64,
+    pub spent: u64,
+    pub price_cap: u64,
+    pub dispute_window_s: u64,
+    pub provider_attest_key: [u8; 32],
+    pub trace_commitment: [u8; 32],
+    pub debit_count: u64,
+    #[max_len(MAX_CHANNEL_PENDING)]
+    pub pending: Vec<ChannelDebit>,
+}
+
+impl Channel {
+    pub fn debit(
+        &mut self,
+        call_id_hash: [u8; 32],
+        amount: u64,
+        response_hash: &[u8; 32],
+        now: u64,
+    ) -> Result<()> {
+        require!(
+            amount > 0 && amount <= self.price_cap,
+            AssuredError::ChannelPriceCapExceeded
+        );
+        let spent = self
+            .spent
+            .checked_add(amount)
+            .filter(|spent| *spent <= self.budget)
+            .ok_or(AssuredError::ChannelBudgetExhausted)?;
+        require!(
+            self.pending.len() < MAX_CHANNEL_PENDING,
+            AssuredError::ChannelBacklogFull
+        );
+        require!(
+            !self.pending.iter().any(|d| d.call_id_hash == call_id_hash),
+            AssuredError::CallIdTaken
+        );
+        self.pending.push(ChannelDebit {
+            call_id_hash,
+            amount,
+            ts: now,
+            disputed: false,
+        });
+        self.spent = spent;
+        self.debit_count = self.debit_count.saturating_add(1);
+        self.trace_commitment = hashv(&[
+            &self.trace_commitment,
+            &call_id_hash,
+            response_hash,
+            &amount.to_le_bytes(),
+        ])
+        .to_bytes();
+        Ok(())
+    }
+
+    fn matured(&self, debit: &ChannelDebit, now: u64) -> bool {
+        now >= debit.ts.saturating_add(self.dispute_window_s)
+    }
+
+    /// Drops matured, undisputed debits and returns what they owe the
+    /// provider. Disputed debits stay until `rule` clears them.
+    pub fn take_matured(&mut self, now: u64) -> u64 {
+        let mut owed = 0u64;
+        let window = self.dispute_window_s;
+        self.pending.retain(|d| {
+            let pay = !d.disputed && now >= d.ts.saturating_add(window);
+            if pay {
+                owed = owed.saturating_add(d.amount);
+            }
+            !pay
+        });
+        owed
+    }
+
+    pub fn dispute(&mut self, call_id_hash: &[u8; 32], now: u64) -> Result<u64> {
+        let window = self.dispute_window_s;
+        let debit = self
+            .pending
+            .iter_mut()
+            .find(|d| d.call_id_hash == *call_id_hash)
+            .ok_or(AssuredError::UnknownChannelDebit)?;
+        require!(!debit.disputed, AssuredError::AlreadyDisputed);
+        require!(
+            now < debit.ts.saturating_add(window),
+            AssuredError::DisputeWindowClosed
+        );
+        debit.disputed = true;
+        Ok(debit.amount)
+    }
+
+    /// Settles a disputed debit and frees its slot. Returns `(amount,
+    /// to_provider)`: a release owes the provider the debit, a refund
+    /// credits it back to the budget.
+    pub fn rule(&mut self, call_id_hash: &[u8; 32], ruling: u8) -> Result<(u64, u64)> {
+        require!(ruling <= Ruling::Refund as u8, AssuredError::InvalidRuling);
+        let index = self
+            .pending
+            .iter()
+            .position(|d| d.call_id_hash == *call_id_hash)
+            .ok_or(AssuredError::UnknownChannelDebit)?;
+        require!(self.pending[index].disputed, AssuredError::NotDisputed);
+        let debit = self.pending.remove(index);
+        if ruling == Ruling::Refund as u8 {
+            self.spent = self.spent.saturating_sub(debit.amount);
+            return Ok((debit.amount, 0));
+        }
+        Ok((debit.amount, debit.amount))
+    }
+
+    /// Returns `(to_provider, refunded)` where `refunded` is the unspent
+    /// budget, including debits refunded by a ruling.
+    pub fn close_split(&mut self, now: u64) -> Result<(u64, u64)> {
+        require!(
+            self.pending.iter().all(|d| !d.disputed),
+            AssuredError::ChannelDisputesUnresolved
+        );
+        require!(
+            self.pending.iter().all(|d| self.matured(d, now)),
+            AssuredError::ChannelDebitsPending
+        );
+        let to_provider = self.take_matured(now);
+        let refunded = self.budget.saturating_sub(self.spent);
+        Ok((to_provider, refunded))
+    }
+}
+
+/// Bytes a provider signs with its attestation key for a channel debit.
+/// The channel address keeps a signature from being replayed on another
+/// channel sharing the same attestation key.
+pub fn channel_debit_message(
+    channel: &Pubkey,
+    call_id_hash: &[u8; 32],
+    response_hash: &[u8; 32],
+    amount: u64,
+) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(104);
+    msg.extend_from_slice(channel.as_ref());
+    msg.extend_from_slice(call_id_hash);
+    msg.extend_from_slice(response_hash);
+    msg.extend_from_slice(&amount.to_le_bytes());
+    msg
+}
+
+/// Running totals for dashboards, kept program-wide at `["stats"]` and per
+/// service at `["svc_stats", service_id]`. Lamports paid to providers
+/// through partial releases are counted when the call settles. Every path
+/// that opens, settles or disputes a call updates the program-wide account.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct EscrowStats {
+    pub escrow_count: u64,
+    pub volume_lamports: u64,
+    pub released_count: u64,
+    pub refunded_count: u64,
+    pub released_lamports: u64,
+    pub refunded_lamports: u64,
+    pub disputed_count: u64,
+}
+
+impl EscrowStats {
+    pub fn record_open(&mut self, amount: u64) {
+        self.escrow_count = self.escrow_count.saturating_add(1);
+        self.volume_lamports = self.volume_lamports.saturating_add(amount);
+    }
+
+    pub fn record_release(&mut self, paid_to_provider: u64) {
+        self.released_count = self.released_count.saturating_add(1);
+        self.released_lamports = self.released_lamports.saturating_add(paid_to_provider);
+    }
+
+    pub fn record_refund(&mut self, refunded: u64, already_released: u64) {
+        self.refunded_count = self.refunded_count.saturating_add(1);
+        self.refunded_lamports = self.refunded_lamports.saturating_add(refunded);
+        self.released_lamports = self.released_lamports.saturating_add(already_released);
+    }
+
+    /// Counts raised and reopened disputes alike.
+    pub fn record_dispute(&mut self) {
+        self.disputed_count = self.disputed_count.saturating_add(1);
+    }
+
+    /// Share of settled calls that were refunded, in basis points.
+    pub fn refund_rate_bps(&self) -> u64 {
+        let settled = self.released_count.saturating_add(self.refunded_count);
+        if settled == 0 {
+            return 0;
+        }
+        (self.refunded_count as u128 * 10_000 / settled as u128) as u64
+    }
+
+    pub fn snapshot(&self, slot: u64) -> StatsSnapshot {
+        StatsSnapshot {
+            escrow_count: self.escrow_count,
+            volume_lamports: self.volume_lamports,
+            released_count: self.released_count,
+            refunded_count: self.refunded_count,
+            released_lamports: self.released_lamports,
+            refunded_lamports: self.refunded_lamports,
+            disputed_count: self.disputed_count,
+            slot,
+        }
+    }
+}
+
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace, PartialEq, Eq, Debug,
+)]
+pub struct IndexedCall {
+    pub call_id_hash: [u8; 32],
+    pub settled: bool,
+}
+
+/// Ring buffer of a payer's most recent calls, so wallets can list them
+/// without `getProgramAccounts`. `count` never decreases; the slot for the
+/// n-th call is `n % PAYER_INDEX_LEN`.
+#[account]
+#[derive(InitSpace)]
+pub struct PayerCallIndex {
+    pub payer: Pubkey,
+    pub count: u64,
+    pub entries: [IndexedCall; PAYER_INDEX_LEN],
+}
+
+impl PayerCallIndex {
+    pub fn record(&mut self, call_id: &str) {
+        let slot = (self.count % PAYER_INDEX_LEN as u64) as usize;
+        self.entries[slot] = IndexedCall {
+            call_id_hash: call_id_to_hash(call_id),
+            settled: false,
+        };
+        self.count = self.count.saturating_add(1);
+    }
+
+    /// Calls that have already rotated out of the buffer are ignored.
+    pub fn mark_settled(&mut self, call_id: &str) {
+        let target = call_id_to_hash(call_id);
+        let live = (self.count as usize).min(PAYER_INDEX_LEN);
+        if let Some(entry) = self.entries[..live]
+            .iter_mut()
+            .find(|e| e.call_id_hash == target)
+        {
+            entry.settled = true;
+        }
+    }
+
+    /// Live entries, oldest first.
+    pub fn recent(&self) -> Vec<IndexedCall> {
+        let live = (self.count as usize).min(PAYER_INDEX_LEN);
+        let start = if live < PAYER_INDEX_LEN {
+            0
+        } else {
+            (self.count % PAYER_INDEX_LEN as u64) as usize
+        };
+        (0..live)
+            .map(|i| self.entries[(start + i) % PAYER_INDEX_LEN])
+            .collect()
+    }
+
+    pub fn open_calls(&self) -> Vec<[u8; 32]> {
+        self.recent()
+            .into_iter()
+            .filter(|e| !e.settled)
+            .map(|e| e.call_id_hash)
+            .collect()
+    }
+}
+
+/// Ring buffer of a provider's open call ids. A v2 escrow, which only
+/// knows its call id's hash, is listed by that hash in hex. Settled calls
+/// leave an empty string in their slot; once full, `head` is the oldest
+/// slot and the next push overwrites it.
+#[account]
+#[derive(InitSpace)]
+pub struct ProviderCallIndex {
+    pub provider: Pubkey,
+    #[max_len(MAX_INDEX_ENTRIES, MAX_CALL_ID_LEN)]
+    pub call_ids: Vec<String>,
+    pub head: u32,
+}
+
+impl ProviderCallIndex {
+    pub fn push(&mut self, call_id: &str) {
+        let slot = self.head as usize % MAX_INDEX_ENTRIES;
+        if self.call_ids.len() < MAX_INDEX_ENTRIES {
+            self.call_ids.push(call_id.to_string());
+        } else {
+            self.call_ids[slot] = call_id.to_string();
+        }
+        self.head = ((slot + 1) % MAX_INDEX_ENTRIES) as u32;
+    }
+
+    /// `push` for a v2 escrow.
+    pub fn push_hashed(&mut self, call_id_hash: &[u8; 32]) {
+        self.push(&call_id_hash_hex(call_id_hash));
+    }
+
+    /// Calls that have already rotated out of the buffer are ignored.
+    pub fn remove(&mut self, call_id: &str) {
+        if let Some(slot) = self.call_ids.iter_mut().find(|c| *c == call_id) {
+            slot.clear();
+        }
+    }
+
+    /// `remove` for a v2 escrow: matches the hash pushed by
+    /// `push_hashed`, or the plain id of a call migrated from v1.
+    pub fn remove_hashed(&mut self, call_id_hash: &[u8; 32]) {
+        let hex = call_id_hash_hex(call_id_hash);
+        if let Some(slot) = self
+            .call_ids
+            .iter_mut()
+            .find(|c| !c.is_empty() && (**c == hex || call_id_to_hash(c) == *call_id_hash))
+        {
+            slot.clear();
+        }
+    }
+
+    /// Open call ids, oldest first.
+    pub fn active(&self) -> Vec<String> {
+        let start = if self.call_ids.len() < MAX_INDEX_ENTRIES {
+            0
+        } else {
+            self.head as usize
+        };
+        let len = self.call_ids.len();
+        (0..len)
+            .map(|i| &self.call_ids[(start + i) % len])
+            .filter(|c| !c.is_empty())
+            .cloned()
+            .collect()
+    }
+}
+
+/// SHA-256 of a call id; keys `EscrowCallV2` and the payer index.
+pub fn call_id_to_hash(call_id: &str) -> [u8; 32] {
+    hash(call_id.as_bytes()).to_bytes()
+}
+
+/// How the provider index lists a v2 escrow; exactly `MAX_CALL_ID_LEN`.
+pub fn call_id_hash_hex(call_id_hash: &[u8; 32]) -> String {
+    call_id_hash.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// M-of-N approval state for a call opened by `init_multisig_payment`.
+/// Holds the call's amount until `approved_count` reaches `threshold`.
+#[account]
+#[derive(InitSpace)]
+pub struct MultiSigConfig {
+    /// Unused slots are `Pubkey::default()`.
+    pub signers: [Pubkey; MAX_MULTISIG_SIGNERS],
+    pub threshold: u8,
+    pub approvals: [bool; MAX_MULTISIG_SIGNERS],
+    pub approved_count: u8,
+}
+
+impl MultiSigConfig {
+    pub fn new(signers: &[Pubkey], threshold: u8) -> Result<Self> {
+        require!(
+            !signers.is_empty() && signers.len() <= MAX_MULTISIG_SIGNERS,
+            AssuredError::InvalidMultisigSigners
+        );
+        for (i, signer) in signers.iter().enumerate() {
+            require!(
+                *signer != Pubkey::default() && !signers[..i].contains(signer),
+                AssuredError::InvalidMultisigSigners
+            );
+        }
+        require!(
+            threshold > 0 && threshold as usize <= signers.len(),
+            AssuredError::InvalidMultisigThreshold
+        );
+        let mut config = Self {
+            signers: [Pubkey::default(); MAX_MULTISIG_SIGNERS],
+            threshold,
+            approvals: [false; MAX_MULTISIG_SIGNERS],
+            approved_count: 0,
+        };
+        config.signers[..signers.len()].copy_from_slice(signers);
+        Ok(config)
+    }
+
+    fn slot(&self, signer: &Pubkey) -> Result<usize> {
+        self.signers
+            .iter()
+            .position(|s| s == signer && *s != Pubkey::default())
+            .ok_or_else(|| error!(AssuredError::NotMultisigSigner))
+    }
+
+    /// Returns true once this approval brings the count to the threshold.
+    pub fn approve(&mut self, signer: &Pubkey) -> Result<bool> {
+        let slot = self.slot(signer)?;
+        require!(!self.approvals[slot], AssuredError::AlreadyApproved);
+        self.approvals[slot] = true;
+        self.approved_count += 1;
+        Ok(self.is_approved())
+    }
+
+    pub fn revoke(&mut self, signer: &Pubkey) -> Result<()> {
+        let slot = self.slot(signer)?;
+        require!(self.approvals[slot], AssuredError::NotApproved);
+        self.approvals[slot] = false;
+        self.approved_count -= 1;
+        Ok(())
+    }
+
+    pub fn is_approved(&self) -> bool {
+        self.approved_count >= self.threshold
+    }
+}
+
+/// Network-wide list of providers no escrow may name.
+#[account]
+#[derive(InitSpace)]
+pub struct ProviderBlacklist {
+    pub authority: Pubkey,
+    #[max_len(MAX_BLACKLIST_SIZE)]
+    pub entries: Vec<Pubkey>,
+    pub count: u32,
+}
+
+impl ProviderBlacklist {
+    pub fn check_authority(&self, signer: &Pubkey) -> Result<()> {
+        require_keys_eq!(*signer, self.authority, AssuredError::Unauthorized);
+        Ok(())
+    }
+
+    pub fn contains(&self, provider: &Pubkey) -> bool {
+        self.entries.contains(provider)
+    }
+
+    pub fn add(&mut self, provider: Pubkey) -> Result<()> {
+        if self.contains(&provider) {
+            return Ok(());
+        }
+        require!(
+            self.entries.len() < MAX_BLACKLIST_SIZE,
+            AssuredError::BlacklistFull
+        );
+        self.entries.push(provider);
+        self.count = self.entries.len() as u32;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, provider: &Pubkey) -> Result<()> {
+        let idx = self
+            .entries
+            .iter()
+            .position(|p| p == provider)
+            .ok_or(AssuredError::ProviderNotBlacklisted)?;
+        self.entries.swap_remove(idx);
+        self.count = self.entries.len() as u32;
+        Ok(())
+    }
+}
+
+/// Providers a service owner accepts on calls to its services, at
+/// `["allowlist", owner]`. Ignored until the owner enables it.
+#[account]
+#[derive(InitSpace)]
+pub struct ProviderAllowlist {
+    pub owner: Pubkey,
+    pub enabled: bool,
+    #[max_len(MAX_ALLOWLIST_SIZE)]
+    pub providers: Vec<Pubkey>,
+}
+
+impl ProviderAllowlist {
+    pub fn admits(&self, provider: &Pubkey) -> bool {
+        !self.enabled || self.providers.contains(provider)
+    }
+
+    pub fn add(&mut self, provider: Pubkey) -> Result<()> {
+        if self.providers.contains(&provider) {
+            return Ok(());
+        }
+        require!(
+            self.providers.len() < MAX_ALLOWLIST_SIZE,
+            AssuredError::AllowlistFull
+        );
+        self.providers.push(provider);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, provider: &Pubkey) -> Result<()> {
+        let idx = self
+            .providers
+            .iter()
+            .position(|p| p == provider)
+            .ok_or(AssuredError::ProviderNotOnAllowlist)?;
+        self.providers.swap_remove(idx);
+        Ok(())
+    }
+}
+
+/// A provider's one-time discount codes, at `["vouchers", authority]`.
+/// The three vecs are parallel; redeemed codes keep their slot.
+#[account]
+#[derive(InitSpace)]
+pub struct VoucherRegistry {
+    pub authority: Pubkey,
+    #[max_len(MAX_VOUCHERS)]
+    pub codes: Vec<[u8; 16]>,
+    #[max_len(MAX_VOUCHERS)]
+    pub redeemed: Vec<bool>,
+    #[max_len(MAX_VOUCHERS)]
+    pub discount_bps: Vec<u16>,
+}
+
+impl VoucherRegistry {
+    /// Discounts must leave something to pay, so 10000 bps is rejected.
+    pub fn issue(&mut self, code: [u8; 16], discount_bps: u16) -> Result<()> {
+        require!(
+            discount_bps > 0 && (discount_bps as u64) < BPS_DENOMINATOR,
+            AssuredError::InvalidVoucherDiscount
+        );
+        require!(!self.codes.contains(&code), AssuredError::VoucherExists);
+        require!(
+            self.codes.len() < MAX_VOUCHERS,
+            AssuredError::VoucherRegistryFull
+        );
+        self.codes.push(code);
+        self.redeemed.push(false);
+        self.discount_bps.push(discount_bps);
+        Ok(())
+    }
+
+    /// Marks `code` redeemed and returns its discount.
+    pub fn redeem(&mut self, code: &[u8; 16]) -> Result<u16> {
+        let idx = self
+            .codes
+            .iter()
+            .position(|c| c == code)
+            .ok_or(AssuredError::InvalidVoucher)?;
+        require!(!self.redeemed[idx], AssuredError::VoucherAlreadyRedeemed);
+        self.redeemed[idx] = true;
+        Ok(self.discount_bps[idx])
+    }
+}
+
+/// Keys a provider registered for itself, at PDA `["provider_keys",
+/// provider]`.
+#[account]
+#[derive(InitSpace)]
+pub struct ProviderKeys {
+    pub provider: Pubkey,
+    pub attest_key: [u8; 32],
+    /// Ethereum address the provider signs secp256k1 responses with.
+    pub eth_address: [u8; 20],
+}
+
+/// Referrers a provider accepts, at PDA `["referrers", provider]`.
+#[account]
+#[derive(InitSpace)]
+pub struct ProviderReferrers {
+    pub provider: Pubkey,
+    pub referrers: [Pubkey; MAX_APPROVED_REFERRERS],
+    pub count: u8,
+}
+
+impl ProviderReferrers {
+    pub fn is_approved(&self, referrer: &Pubkey) -> bool {
+        self.referrers[..self.count as usize].contains(referrer)
+    }
+
+    pub fn approve(&mut self, referrer: Pubkey) -> Result<()> {
+        if self.is_approved(&referrer) {
+            return Ok(());
+        }
+        let count = self.count as usize;
+        require!(
+            count < MAX_APPROVED_REFERRERS,
+            AssuredError::ApprovalListFull
+        );
+        self.referrers[count] = referrer;
+        self.count += 1;
+        Ok(())
+    }
+
+    pub fn revoke(&mut self, referrer: &Pubkey) -> Result<()> {
+        let count = self.count as usize;
+        let idx = self.referrers[..count]
+            .iter()
+            .position(|r| r == referrer)
+            .ok_or(AssuredError::ReferrerNotApproved)?;
+        self.referrers[idx] = self.referrers[count - 1];
+        self.referrers[count - 1] = Pubkey::default();
+        self.count -= 1;
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct PayerProviderApproval {
+    pub payer: Pubkey,
+    pub approved_providers: [Pubkey; MAX_APPROVED_PROVIDERS],
+    pub count: u8,
+}
+
+impl PayerProviderApproval {
+    pub fn is_approved(&self, provider: &Pubkey) -> bool {
+        self.approved_providers[..self.count as usize].contains(provider)
+    }
+
+    pub fn approve(&mut self, provider: Pubkey) -> Result<()> {
+        if self.is_approved(&provider) {
+            return Ok(());
+        }
+        let count = self.count as usize;
+        require!(
+            count < MAX_APPROVED_PROVIDERS,
+            AssuredError::ApprovalListFull
+        );
+        self.approved_providers[count] = provider;
+        self.count += 1;
+        Ok(())
+    }
+
+    pub fn revoke(&mut self, provider: &Pubkey) -> Result<()> {
+        let count = self.count as usize;
+        let idx = self.approved_providers[..count]
+            .iter()
+            .position(|p| p == provider)
+            .ok_or(AssuredError::ProviderNotApproved)?;
+        self.approved_providers[idx] = self.approved_providers[count - 1];
+        self.approved_providers[count - 1] = Pubkey::default();
+        self.count -= 1;
+        Ok(())
+    }
+}
+
+#[event]
+pub struct Fulfilled {
+    pub call_id: String,
+    pub ts: u64,
+}
+#[event]
+pub struct DataDelivered {
+    pub call_id: String,
+    pub response_size_bytes: u64,
+    /// What `settle` will release for these bytes.
+    pub amount_paid: u64,
+}
+#[event]
+pub struct Released {
+    pub call_id: String,
+    pub delivered_latency_ms: u64,
+    /// Lamports moved to the provider by this settlement.
+    pub amount_to_provider: u64,
+    pub sla_met: bool,
+}
+#[event]
+pub struct Refunded {
+    pub call_id: String,
+    /// Lamports moved back to the payer by this settlement.
+    pub refund_amount: u64,
+    /// Lamports already paid to the provider through partial releases.
+    pub amount_already_released: u64,
+    pub disputed: bool,
+    pub reason: u8,
+}
+#[event]
+pub struct Disputed {
+    pub call_id: String,
+    pub kind: u8,
+    pub reason_hash: [u8; 32],
+}
+#[event]
+pub struct ChannelDebited {
+    pub channel: Pubkey,
+    pub call_id_hash: [u8; 32],
+    pub amount: u64,
+    pub response_hash: [u8; 32],
+    pub trace_commitment: [u8; 32],
+}
+#[event]
+pub struct ChannelDebitDisputed {
+    pub channel: Pubkey,
+    pub call_id_hash: [u8; 32],
+    pub amount: u64,
+    pub reason_hash: [u8; 32],
+}
+#[event]
+pub struct PayoutSplitsSet {
+    pub call_id: String,
+    pub payout_splits: Vec<PayoutSplit>,
+}
+#[event]
+pub struct ChannelDebitArbitrated {
+    pub channel: Pubkey,
+    pub call_id_hash: [u8; 32],
+    pub ruling: u8,
+    pub amount: u64,
+}
+#[event]
+pub struct ChannelClosed {
+    pub channel: Pubkey,
+    pub to_provider: u64,
+    pub refunded: u64,
+}
+#[event]
+pub struct Redeemed {
+    pub call_id: String,
+    pub amount_to_provider: u64,
+}
+#[event]
+pub struct Finalized {
+    pub call_id: String,
+    /// The status `settle` left: Released or Refunded.
+    pub outcome: u8,
+}
+#[event]
+pub struct InsuranceClaimed {
+    pub call_id: String,
+    pub amount_supplemented: u64,
+}
+#[event]
+pub struct ReferralPaid {
+    pub call_id: String,
+    pub referrer: Pubkey,
+    pub amount: u64,
+}
+#[event]
+pub struct Aborted {
+    pub call_id: String,
+    pub refund_amount: u64,
+    pub amount_already_released: u64,
+}
+#[event]
+pub struct ProofSubmitted {
+    pub call_id: String,
+    pub proof_hash: [u8; 32],
+}
+#[event]
+pub struct CallIndexSnapshot {
+    pub provider: Pubkey,
+    pub call_ids: Vec<String>,
+}
+#[event]
+pub struct DisputeBondSettled {
+    pub call_id: String,
+    pub amount: u64,
+    /// True when the bond went to the provider, false when returned.
+    pub forfeited: bool,
+    /// Provider bond slashed to the payer on an upheld dispute.
+    pub reporter_reward: u64,
+}
+#[event]
+pub struct DisputeReopened {
+    pub call_id: String,
+    pub new_reason_hash: [u8; 32],
+}
+#[event]
+pub struct PartialReleased {
+    pub call_id: String,
+    pub units: u64,
+    pub total_units: u64,
+    /// Cumulative units paid so far, including this release.
+    pub units_released: u64,
+}
+#[event]
+pub struct BatchInitialized {
+    pub call_ids: Vec<String>,
+    pub total_amount: u64,
+}
+#[event]
+pub struct ConfigUpdated {
+    pub admin: Pubkey,
+    pub max_sla_ms: u64,
+    pub max_dispute_window_s: u64,
+    pub fee_bps: u16,
+    pub fulfill_grace_s: u64,
+    pub paused: bool,
+    pub min_bond_required: u64,
+    pub dispute_bond_lamports: u64,
+    pub strict_pricing: bool,
+    pub late_penalty_bps: u16,
+}
+#[event]
+pub struct Heartbeat {
+    pub call_id: String,
+    pub ts: u64,
+}
+#[event]
+pub struct ProviderBlacklisted {
+    pub provider: Pubkey,
+}
+#[event]
+pub struct ProviderUnblacklisted {
+    pub provider: Pubkey,
+}
+#[event]
+pub struct ProviderAllowlisted {
+    pub owner: Pubkey,
+    pub provider: Pubkey,
+}
+#[event]
+pub struct ProviderUnallowlisted {
+    pub owner: Pubkey,
+    pub provider: Pubkey,
+}
+#[event]
+pub struct AllowlistToggled {
+    pub owner: Pubkey,
+    pub enabled: bool,
+}
+#[event]
+pub struct VoucherIssued {
+    pub authority: Pubkey,
+    pub code: [u8; 16],
+    pub discount_bps: u16,
+}
+#[event]
+pub struct VoucherRedeemed {
+    pub call_id: String,
+    pub code: [u8; 16],
+    pub discount_amount: u64,
+}
+#[event]
+pub struct Arbitrated {
+    pub call_id: String,
+    pub ruling: u8,
+    pub appeal_deadline_ts: i64,
+    /// Evidence on file at ruling time; zero when none was passed.
+    pub payer_evidence: u8,
+    pub provider_evidence: u8,
+}
+#[event]
+pub struct ArbiterVoted {
+    pub call_id: String,
+    pub arbiter: Pubkey,
+    pub verdict: u8,
+}
+#[event]
+pub struct OrphanRentCollected {
+    pub pda: Pubkey,
+    pub amount: u64,
+}
+#[event]
+pub struct ResolutionFinalized {
+    pub call_id: String,
+    pub ruling: u8,
+    /// False when the vote window closed without quorum and the call
+    /// fell back to a refund.
+    pub quorum_reached: bool,
+    pub appeal_deadline_ts: i64,
+}
+#[event]
+pub struct EvidenceSubmitted {
+    pub call_id: String,
+    pub role: u8,
+    pub submitter: Pubkey,
+    pub slot: u8,
+    pub evidence_hash: [u8; 32],
+    pub uri_hash: [u8; 32],
+}
+#[event]
+pub struct AppealFiled {
+    pub call_id: String,
+    pub appellant: Pubkey,
+    pub new_evidence_hash: [u8; 32],
+}
+#[event]
+pub struct AppealResolved {
+    pub call_id: String,
+    pub ruling: u8,
+}
+#[event]
+pub struct ProofSetComplete {
+    pub call_id: String,
+    pub chunk_count: u32,
+}
+#[event]
+pub struct SlaExtended {
+    pub call_id: String,
+    pub old_sla_ms: u64,
+    pub new_sla_ms: u64,
+}
+#[event]
+pub struct LatePenaltyApplied {
+    pub call_id: String,
+    pub penalty_bps: u16,
+    pub penalty: u64,
+}
+#[event]
+pub struct StatsSnapshot {
+    pub escrow_count: u64,
+    pub volume_lamports: u64,
+    pub released_count: u64,
+    pub refunded_count: u64,
+    pub released_lamports: u64,
+    pub refunded_lamports: u64,
+    pub disputed_count: u64,
+    pub slot: u64,
+}
+#[event]
+pub struct PaymentApproved {
+    pub call_id: String,
+    pub signer: Pubkey,
+    pub approved_count: u8,
+    pub threshold: u8,
+}
+#[event]
+pub struct ApprovalRevoked {
+    pub call_id: String,
+    pub signer: Pubkey,
+    pub approved_count: u8,
+}
+#[event]
+pub struct MultisigUnlocked {
+    pub call_id: String,
+    pub amount: u64,
+}
+#[event]
+pub struct MultisigCancelled {
+    pub call_id: String,
+    pub amount: u64,
+}
+#[event]
+pub struct TraceSaved {
+    pub call_id: String,
+    pub response_hash: [u8; 32],
+    pub provider_sig: Vec<u8>,
+    pub content_cid: Vec<u8>,
+}
+
+#[error_code]
+pub enum AssuredError {
+    #[msg("Invalid status")]
+    InvalidStatus,
+    #[msg("Invalid provider")]
+    InvalidProvider,
+    #[msg("Invalid payer")]
+    InvalidPayer,
+    #[msg("Invalid reporter")]
+    InvalidReporter,
+    #[msg("Balance too low to fund or pay out the escrow")]
+    EscrowBalanceLow,
+    #[msg("Payouts would exceed the escrowed amount")]
+    PayoutExceedsAmount,
+    #[msg("Escrow has not expired yet")]
+    NotExpired,
+    #[msg("Provider signature too long")]
+    SignatureTooLong,
+    #[msg("Invalid units for partial release")]
+    InvalidUnits,
+    #[msg("Provider compliance rate below minimum")]
+    ProviderNotCompliant,
+    #[msg("Refund deadline must be after the call start")]
+    InvalidDeadline,
+    #[msg("Call is undelivered and its refund deadline has not passed")]
+    SettlementPending,
+    #[msg("Provider is not on the payer's approved list")]
+    ProviderNotApproved,
+    #[msg("Approved provider or referrer list is full")]
+    ApprovalListFull,
+    #[msg("Signer is not the config admin")]
+    Unauthorized,
+    #[msg("Program is paused")]
+    ProgramPaused,
+    #[msg("SLA exceeds configured maximum")]
+    SlaTooLong,
+    #[msg("Dispute window exceeds configured maximum")]
+    DisputeWindowTooLong,
+    #[msg("Fee exceeds 100%")]
+    InvalidFee,
+    #[msg("Heartbeat interval must be positive when heartbeats are required")]
+    InvalidHeartbeatInterval,
+    #[msg("Dispute raised before the call could be considered unresponsive")]
+    DisputeTooEarly,
+    #[msg("Packed escrow state is malformed")]
+    MalformedEscrowState,
+    #[msg("Account is already on the current layout")]
+    AlreadyMigrated,
+    #[msg("Unsupported account version")]
+    UnsupportedVersion,
+    #[msg("SLA can only be extended, never tightened")]
+    SlaNotExtended,
+    #[msg("Batch is empty or exceeds the maximum number of legs")]
+    BatchTooLarge,
+    #[msg("Remaining accounts do not match the batch legs")]
+    BatchAccountsMismatch,
+    #[msg("Payer cannot fund the requested escrows")]
+    InsufficientFunds,
+    #[msg("Escrow amount must be positive")]
+    ZeroAmount,
+    #[msg("SLA must be positive")]
+    ZeroSla,
+    #[msg("Total units must be between 1 and 100000")]
+    InvalidTotalUnits,
+    #[msg("Call is not disputed")]
+    NotDisputed,
+    #[msg("Call already has a ruling")]
+    AlreadyRuled,
+    #[msg("Invalid ruling")]
+    InvalidRuling,
+    #[msg("Appeal window has closed")]
+    AppealWindowClosed,
+    #[msg("An appeal has already been filed")]
+    AppealAlreadyFiled,
+    #[msg("Only the losing party may appeal")]
+    InvalidAppellant,
+    #[msg("No pending appeal to resolve")]
+    NoAppealPending,
+    #[msg("Provider attestation signature missing or mismatched")]
+    InvalidAttestation,
+    #[msg("Payer cannot be the provider")]
+    SelfDealingNotAllowed,
+    #[msg("Service is suspended")]
+    ServiceSuspended,
+    #[msg("Service is paused by its owner")]
+    ServicePaused,
+    #[msg("Escrow amount is below the service's published price")]
+    UnderpricedEscrow,
+    #[msg("Rent recipient does not match the escrow")]
+    InvalidRentRecipient,
+    #[msg("Partial release limit reached; deliver the remaining units at once")]
+    PartialLimitReached,
+    #[msg("Fulfillment arrived after the SLA deadline and grace period")]
+    SlaDeadlineExpired,
+    #[msg("Provider is blacklisted")]
+    ProviderBlacklisted,
+    #[msg("Blacklist is full")]
+    BlacklistFull,
+    #[msg("Provider is not blacklisted")]
+    ProviderNotBlacklisted,
+    #[msg("Dispute window has closed")]
+    DisputeWindowClosed,
+    #[msg("Unknown remainder policy")]
+    InvalidRemainderPolicy,
+    #[msg("Unknown billing mode")]
+    InvalidBillingMode,
+    #[msg("Unknown signature mode")]
+    InvalidSigMode,
+    #[msg("Secp256k1 calls need the provider's provider_keys account")]
+    MissingEthSigner,
+    #[msg("Response size exceeds the contracted byte budget")]
+    DataOverBudget,
+    #[msg("Chunk count must be between 1 and 64")]
+    InvalidChunkCount,
+    #[msg("Chunk index out of range or empty hash")]
+    InvalidChunkProof,
+    #[msg("Chunk proof already submitted")]
+    ChunkAlreadySubmitted,
+    #[msg("Not all chunk proofs have been submitted and verified")]
+    ProofSetIncomplete,
+    #[msg("Call is already disputed")]
+    AlreadyDisputed,
+    #[msg("Service performance bond is below the required minimum")]
+    InsufficientPerformanceBond,
+    #[msg("Hash-locked calls are fulfilled through redeem")]
+    HashlockRequiresRedeem,
+    #[msg("Call has no hashlock")]
+    NotHashlocked,
+    #[msg("Preimage does not match the hashlock")]
+    HashlockMismatch,
+    #[msg("Preimage too long")]
+    PreimageTooLong,
+    #[msg("Redeem window has passed; the call can only be refunded")]
+    RedeemExpired,
+    #[msg("Call id hash does not match the v1 call id")]
+    CallIdHashMismatch,
+    #[msg("Call id is already in use by an open escrow; pick a new call id")]
+    CallIdTaken,
+    #[msg("Debit exceeds the channel's per-call price cap")]
+    ChannelPriceCapExceeded,
+    #[msg("Channel budget exhausted")]
+    ChannelBudgetExhausted,
+    #[msg("Too many debits awaiting their dispute window")]
+    ChannelBacklogFull,
+    #[msg("No pending debit for this call id")]
+    UnknownChannelDebit,
+    #[msg("Channel has debits still inside their dispute window")]
+    ChannelDebitsPending,
+    #[msg("Channel has disputed debits awaiting a ruling")]
+    ChannelDisputesUnresolved,
+    #[msg("Settle grace period has not elapsed")]
+    SettleGraceNotElapsed,
+    #[msg("timeout_refund needs a submitter to receive the bounty")]
+    SubmitterRequired,
+    #[msg("Payout splits must be 1-4 distinct recipients with bps summing to 10000")]
+    InvalidPayoutSplits,
+    #[msg("Payout split recipient account missing or not writable")]
+    MissingSplitRecipient,
+    #[msg("Referral fee exceeds 1000 bps")]
+    ReferralFeeTooHigh,
+    #[msg("Referrer account missing or does not match the call")]
+    InvalidReferrer,
+    #[msg("Referrer is not on the provider's approved list")]
+    ReferrerNotApproved,
+    #[msg("Call uses features v2 escrows do not support")]
+    NotSupportedByV2,
+    #[msg("Only the payer may settle this call as a refund")]
+    PayerSignatureRequired,
+    #[msg("Provider has reached its max outstanding calls")]
+    ProviderOverloaded,
+    #[msg("Reputation accounts are required for this call")]
+    ReputationAccountsRequired,
+    #[msg("Service reputation is below the payer's minimum")]
+    ReputationBelowMinimum,
+    #[msg("Multisig needs 1-5 distinct, non-default signers")]
+    InvalidMultisigSigners,
+    #[msg("Multisig threshold must be between 1 and the signer count")]
+    InvalidMultisigThreshold,
+    #[msg("Signer is not part of this multisig")]
+    NotMultisigSigner,
+    #[msg("Signer has already approved this payment")]
+    AlreadyApproved,
+    #[msg("Signer has not approved this payment")]
+    NotApproved,
+    #[msg("Content CID exceeds 64 bytes")]
+    ContentCidTooLong,
+    #[msg("Late penalty exceeds 10000 bps")]
+    InvalidLatePenalty,
+    #[msg(
+        "SLA tiers must be at most 4, past 1x, with widening deadlines and non-increasing payouts"
+    )]
+    InvalidSlaTiers,
+    #[msg("Chunk repeats or predates the previous chunk")]
+    DuplicateChunk,
+    #[msg("Chunk timestamp must be after the escrow start")]
+    InvalidChunkTimestamp,
+    #[msg("Evidence role must be 0 (payer) or 1 (provider)")]
+    InvalidEvidenceRole,
+    #[msg("No evidence slots left for this party")]
+    EvidenceSlotsFull,
+    #[msg("Voucher discount must be between 1 and 9999 bps")]
+    InvalidVoucherDiscount,
+    #[msg("Voucher code already issued")]
+    VoucherExists,
+    #[msg("Voucher registry is full")]
+    VoucherRegistryFull,
+    #[msg("Unknown voucher code")]
+    InvalidVoucher,
+    #[msg("Voucher already redeemed")]
+    VoucherAlreadyRedeemed,
+    #[msg("A redemption code needs the provider's voucher registry")]
+    VoucherRegistryRequired,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("No dispute bond is held on this call")]
+    DisputeBondNotHeld,
+    #[msg("Arbiters must be 1-5 distinct keys with a majority threshold")]
+    InvalidArbiterCommittee,
+    #[msg("Call has no arbiter committee")]
+    NoArbiterCommittee,
+    #[msg("Call is resolved by its arbiter committee")]
+    CommitteeArbitrated,
+    #[msg("Signer is not one of the call's arbiters")]
+    NotArbiter,
+    #[msg("Arbiter has already voted")]
+    AlreadyVoted,
+    #[msg("Committee vote window has closed")]
+    VotingClosed,
+    #[msg("Committee has not reached quorum and the vote window is open")]
+    QuorumNotReached,
+    #[msg("Not an orphaned proof set or evidence account")]
+    InvalidOrphan,
+    #[msg("The orphan's call account still exists")]
+    ParentCallOpen,
+    #[msg("Provider is not on the service's allowlist")]
+    ProviderNotAllowlisted,
+    #[msg("Allowlist is full")]
+    AllowlistFull,
+    #[msg("Provider is not on the allowlist")]
+    ProviderNotOnAllowlist,
+    #[msg("Provider has not registered an Ethereum address")]
+    UnregisteredEthAddress,
+    #[msg("The call settled too recently to finalize")]
+    FinalizeTooEarly,
+    #[msg("Service is neither owned by nor allowlists the provider")]
+    ServiceNotBoundToProvider,
+    #[msg("Service caps outstanding calls; pass its account writable")]
+    ServiceNotWritable,
+    #[msg("Data-volume calls must sign their size through fulfill_with_data")]
+    ResponseSizeUnsigned,
+    #[msg("fulfill_grace_s must stay below EXPIRY_GRACE_S")]
+    FulfillGraceTooLong,
+}
+
+#[repr(u8)]
+pub enum Status {
+    Init = 0,
+    Fulfilled = 1,
+    Released = 2,
+    Refunded = 3,
+    /// Closed by `finalize`; only ever seen inside that instruction.
+    Finalized = 4,
+    /// Opened by `init_multisig_payment`; unfunded until approved.
+    AwaitingApproval = 5,
+}
+
+#[repr(u8)]
+pub enum RefundReason {
+    SlaMiss = 0,
+    Dispute = 1,
+    NeverDelivered = 2,
+    /// Never delivered or settled before `SETTLE_GRACE_PERIOD_S` ran out.
+    Timeout = 3,
+    /// Never fulfilled before `expiry_ts`; refunded by `trigger_expiry`.
+    Expired = 4,
+}
+
+/// Where the `amount % total_units` dust lands when paying per unit.
+#[repr(u8)]
+pub enum RemainderPolicy {
+    /// One extra lamport on each of the first `remainder` units.
+    FirstUnits = 0,
+    /// The whole remainder on the final unit.
+    LastUnit = 1,
+    /// One extra lamport on each of the last `remainder` units, so partial
+    /// delivery never earns more per unit than finishing the job.
+    LastUnits = 2,
+}
+
+/// How `fulfill` treats `provider_sig`.
+#[repr(u8)]
+pub enum SigMode {
+    /// Stored for off-chain audit only.
+    None = 0,
+    /// Checked against a preceding Ed25519 precompile instruction, by the
+    /// attestation key or else the provider's own key.
+    Ed25519 = 1,
+    /// For EVM-origin providers: checked against a preceding secp256k1
+    /// precompile instruction by the provider's registered
+    /// `provider_eth_address`.
+    Secp256k1 = 2,
+}
+
+/// How `settle` prices a released call. Codes 1-3 are reserved.
+#[repr(u8)]
+pub enum BillingMode {
+    /// `amount` spread over `total_units`, paid as units are delivered.
+    PerUnit = 0,
+    /// `total_units` is a byte budget; the provider reports
+    /// `response_size_bytes` on `fulfill` and is paid pro rata.
+    DataVolume = 4,
+}
+
+#[repr(u8)]
+pub enum EvidenceRole {
+    Payer = 0,
+    Provider = 1,
+}
+
+#[repr(u8)]
+pub enum Ruling {
+    Release = 0,
+    Refund = 1,
+}
+
+/// Where a payer's dispute bond is.
+#[repr(u8)]
+pub enum BondCustody {
+    None = 0,
+    Held = 1,
+    Returned = 2,
+    Forfeited = 3,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SettlementOutcome {
+    Release,
+    /// Delivered late but undisputed: release less `bps` of the payout,
+    /// which goes back to the payer.
+    ReleaseWithPenalty {
+        bps: u16,
+    },
+    Refund,
+    Pending,
+}
+
+impl SettlementOutcome {
+    /// Maps a share of the payout to the matching release or refund.
+    pub fn from_payout_bps(payout_bps: u16) -> Self {
+        match payout_bps as u64 {
+            0 => SettlementOutcome::Refund,
+            BPS_DENOMINATOR.. => SettlementOutcome::Release,
+            bps => SettlementOutcome::ReleaseWithPenalty {
+                bps: (BPS_DENOMINATOR - bps) as u16,
+            },
+        }
+    }
+
+    /// Share of the remaining payout the provider receives.
+    pub fn payout_bps(&self) -> u16 {
+        match self {
+            SettlementOutcome::Release => BPS_DENOMINATOR as u16,
+            SettlementOutcome::ReleaseWithPenalty { bps } => BPS_DENOMINATOR as u16 - bps,
+            SettlementOutcome::Refund | SettlementOutcome::Pending => 0,
+        }
+    }
+
+    pub fn penalty_bps(&self) -> u16 {
+        match self {
+            SettlementOutcome::ReleaseWithPenalty { bps } => *bps,
+            _ => 0,
+        }
+    }
+}
+
+fn transfer_into_escrow<'info>(
+    payer: &Signer<'info>,
+    escrow: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let accounts = Transfer {
+        from: payer.to_account_info(),
+        to: escrow.clone(),
+    };
+    system_program::transfer(
+        CpiContext::new(system_program.to_account_info(), accounts),
+        amount,
+    )
+}
+
+/// Payer-chosen floor on both the Wilson-bounded share of the service's
+/// escrows that released, which only escrow's CPIs write, and its
+/// composite score, which also weighs probed uptime. A service with no
+/// settled escrows yet scores 0, so any floor rejects it. The service only
+/// vouches for providers its owner runs or allowlists.
+fn check_min_reputation(
+    service: &reputation::Service,
+    provider: &Pubkey,
+    allowlist: &AccountInfo,
+    min_bps: Option<u16>,
+) -> Result<()> {
+    let Some(min_bps) = min_bps else {
+        return Ok(());
+    };
+    require!(
+        service.owner == *provider || allowlist_names(allowlist, provider)?,
+        AssuredError::ServiceNotBoundToProvider
+    );
+    require!(
+        reputation::escrow_success_bps(service) >= min_bps
+            && reputation::composite_score_bps(service) >= min_bps,
+        AssuredError::ReputationBelowMinimum
+    );
+    Ok(())
+}
+
+/// Fails with `EscrowBalanceLow` unless the payer can move `amount` and
+/// either empty its account or keep the `payer_rent` a system account
+/// needs. Runs after Anchor has charged the escrow's own rent.
+fn check_payer_funds(available: u64, amount: u64, payer_rent: u64) -> Result<()> {
+    let left = available
+        .checked_sub(amount)
+        .ok_or(AssuredError::EscrowBalanceLow)?;
+    require!(
+        left == 0 || left >= payer_rent,
+        AssuredError::EscrowBalanceLow
+    );
+    Ok(())
+}
+
+fn validate_call_params(
+    amount: u64,
+    sla_ms: u64,
+    dispute_window_s: u64,
+    total_units: u64,
+) -> Result<()> {
+    require!(amount > 0, AssuredError::ZeroAmount);
+    require!(sla_ms > 0, AssuredError::ZeroSla);
+    require!(sla_ms <= MAX_SLA_MS, AssuredError::SlaTooLong);
+    require!(
+        dispute_window_s <= MAX_DISPUTE_WINDOW_S,
+        AssuredError::DisputeWindowTooLong
+    );
+    require!(
+        (1..=MAX_TOTAL_UNITS).contains(&total_units),
+        AssuredError::InvalidTotalUnits
+    );
+    Ok(())
+}
+
+/// Rejects escrows naming the zero key or the payer itself as provider, so
+/// payers can't farm outcomes for a service they control.
+fn validate_counterparties(payer: &Pubkey, provider: &Pubkey) -> Result<()> {
+    require!(
+        *provider != Pubkey::default(),
+        AssuredError::InvalidProvider
+    );
+    require!(provider != payer, AssuredError::SelfDealingNotAllowed);
+    Ok(())
+}
+
+/// Checks the whole batch up front so a short payer fails before any
+/// escrow is created. Returns the summed escrow amount.
+fn plan_batch(legs: &[BatchInitLeg], available: u64, rent_per_leg: u64) -> Result<u64> {
+    require!(
+        !legs.is_empty() && legs.len() <= MAX_BATCH_INIT,
+        AssuredError::BatchTooLarge
+    );
+    let mut total_amount = 0u64;
+    let mut required = 0u64;
+    for leg in legs {
+        validate_call_params(
+            leg.amount,
+            leg.sla_ms,
+            leg.dispute_window_s,
+            leg.total_units,
+        )?;
+        total_amount = total_amount
+            .checked_add(leg.amount)
+            .ok_or(AssuredError::InsufficientFunds)?;
+        required = required
+            .checked_add(leg.amount)
+            .and_then(|r| r.checked_add(rent_per_leg))
+            .ok_or(AssuredError::InsufficientFunds)?;
+    }
+    require!(required <= available, AssuredError::InsufficientFunds);
+    Ok(total_amount)
+}
+
+fn batch_call(leg: &BatchInitLeg, payer: Pubkey, provider: Pubkey, now: u64) -> EscrowCall {
+    EscrowCall {
+        version: ESCROW_CALL_VERSION,
+        call_id: leg.call_id.clone(),
+        payer,
+        provider,
+        state: CallState {
+            service_id: leg.service_id.clone(),
+            amount: leg.amount,
+            start_ts: now,
+            sla_ms: leg.sla_ms,
+            dispute_window_s: leg.dispute_window_s,
+            status: Status::Init as u8,
+            delivered_ts: None,
+            response_hash: [0u8; 32],
+            disputed: false,
+            total_units: leg.total_units,
+            units_released: 0,
+            provider_sig: Vec::new(),
+            last_heartbeat_ts: 0,
+            refund_after_ts: default_refund_after_ts(now, leg.sla_ms, leg.dispute_window_s),
+            heartbeat_interval_s: 0,
+            heartbeat_required: false,
+            appeal_deadline_ts: 0,
+            appeal_filed_by: None,
+            ruling: None,
+            appeal_resolved: false,
+            provider_attest_key: [0; 32],
+            requires_proofs: false,
+            remainder_policy: RemainderPolicy::FirstUnits as u8,
+            rent_recipient: payer,
+            disputed_units: 0,
+            max_partials: DEFAULT_MAX_PARTIALS,
+            partial_count: 0,
+            disputed_at_ts: 0,
+            provider_proof_hash: [0; 32],
+            hashlock: [0; 32],
+            payout_splits: Vec::new(),
+            referrer: None,
+            referral_fee_bps: 0,
+            provider_eth_address: None,
+            dispute_bond: 0,
+            finalized_at: 0,
+            outstanding_tracked: false,
+            billing_mode: 0,
+            response_size_bytes: 0,
+            sig_mode: 0,
+            expiry_ts: default_expiry_ts(now, leg.sla_ms),
+            content_cid: Vec::new(),
+            late_penalty_bps: 0,
+            sla_tiers: Vec::new(),
+            last_chunk_nonce: [0; 8],
+            redemption_code: None,
+            arbiters: Vec::new(),
+            arbiter_threshold: 0,
+            dispute_kind: 0,
+            dispute_bond_state: 0,
+            fulfilled_at: 0,
+            settled_at: 0,
+            last_chunk_ts: 0,
+        },
+    }
+}
+
+fn check_multisig_cancel(ec: &EscrowCall, now: u64) -> Result<()> {
+    require!(
+        ec.status == Status::AwaitingApproval as u8,
+        AssuredError::InvalidStatus
+    );
+    require!(
+        now >= ec.start_ts.saturating_add(MULTISIG_APPROVAL_WINDOW_S),
+        AssuredError::NotExpired
+    );
+    Ok(())
+}
+
+/// Makes an approved multisig call live. Its deadlines are recomputed so
+/// the time spent collecting approvals does not eat into the SLA.
+fn unlock_multisig_call(ec: &mut EscrowCall, now: u64) {
+    ec.status = Status::Init as u8;
+    ec.start_ts = now;
+    ec.refund_after_ts = default_refund_after_ts(now, ec.sla_ms, ec.dispute_window_s);
+    ec.expiry_ts = default_expiry_ts(now, ec.sla_ms);
+}
+
+/// The call and accounts settling it touches, borrowed from `Settle` or
+/// `SettleV2` so both layouts pay out and report through the same helpers.
+/// For a v2 call `escrow_call` is the `to_v1` copy its handler writes back.
+struct SettleAccounts<'a, 'info> {
+    escrow_call: &'a mut EscrowCall,
+    escrow: AccountInfo<'info>,
+    payer: AccountInfo<'info>,
+    provider: AccountInfo<'info>,
+    global_stats: &'a mut EscrowStats,
+    service_stats: Option<&'a mut EscrowStats>,
+    service: Option<&'a Account<'info, reputation::Service>>,
+    reputation_config: Option<AccountInfo<'info>>,
+    reputation_program: Option<AccountInfo<'info>>,
+    reputation_authority: Option<AccountInfo<'info>>,
+    referrer: Option<AccountInfo<'info>>,
+    insurance_pool: Option<&'a mut Account<'info, InsurancePool>>,
+    treasury: Option<AccountInfo<'info>>,
+    audit_log: Option<AccountInfo<'info>>,
+    token_bond: Option<TokenBondCpi<'info>>,
+}
+
+/// The accounts reputation's `bond_slash_spl` takes past those of
+/// `bond_slash`, present only when an instruction was given all of them.
+struct TokenBondCpi<'info> {
+    mint: AccountInfo<'info>,
+    vault: AccountInfo<'info>,
+    recipient_token: AccountInfo<'info>,
+    treasury_token: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+}
+
+fn token_bond_cpi<'info>(
+    mint: &Option<UncheckedAccount<'info>>,
+    vault: &Option<UncheckedAccount<'info>>,
+    recipient_token: &Option<UncheckedAccount<'info>>,
+    treasury_token: &Option<UncheckedAccount<'info>>,
+    token_program: &Option<UncheckedAccount<'info>>,
+) -> Option<TokenBondCpi<'info>> {
+    Some(TokenBondCpi {
+        mint: mint.as_ref()?.to_account_info(),
+        vault: vault.as_ref()?.to_account_info(),
+        recipient_token: recipient_token.as_ref()?.to_account_info(),
+        treasury_token: treasury_token.as_ref()?.to_account_info(),
+        token_program: token_program.as_ref()?.to_account_info(),
+    })
+}
+
+impl<'info> SettleAccounts<'_, 'info> {
+    fn reputation_cpi(&self) -> ReputationCpi<'info> {
+        ReputationCpi {
+            config: self.reputation_config.clone(),
+            program: self.reputation_program.clone(),
+            caller: self.reputation_authority.clone(),
+        }
+    }
+}
+
+impl<'info> Settle<'info> {
+    fn settle_accounts(&mut self) -> SettleAccounts<'_, 'info> {
+        SettleAccounts {
+            escrow: self.escrow_call.to_account_info(),
+            escrow_call: &mut self.escrow_call,
+            payer: self.payer.to_account_info(),
+            provider: self.provider.to_account_info(),
+            global_stats: &mut self.global_stats,
+            service_stats: self.service_stats.as_deref_mut(),
+            service: self.service.as_deref(),
+            reputation_config: self.reputation_config.as_ref().map(|a| a.to_account_info()),
+            reputation_program: self
+                .reputation_program
+                .as_ref()
+                .map(|a| a.to_account_info()),
+            reputation_authority: self
+                .reputation_authority
+                .as_ref()
+                .map(|a| a.to_account_info()),
+            referrer: self.referrer.as_ref().map(|a| a.to_account_info()),
+            insurance_pool: self.insurance_pool.as_deref_mut(),
+            treasury: self.treasury.as_ref().map(|a| a.to_account_info()),
+            audit_log: self.audit_log.as_ref().map(|a| a.to_account_info()),
+            token_bond: token_bond_cpi(
+                &self.bond_mint,
+                &self.bond_vault,
+                &self.payer_bond_token,
+                &self.treasury_bond_token,
+                &self.token_program,
+            ),
+        }
+    }
+}
+
+impl<'info> SettleV2<'info> {
+    /// `ec` is the escrow's `to_v1` copy; v2 calls have no referrer.
+    fn settle_accounts<'a>(&'a mut self, ec: &'a mut EscrowCall) -> SettleAccounts<'a, 'info> {
+        SettleAccounts {
+            escrow: self.escrow_call.to_account_info(),
+            escrow_call: ec,
+            payer: self.payer.to_account_info(),
+            provider: self.provider.to_account_info(),
+            global_stats: &mut self.global_stats,
+            service_stats: self.service_stats.as_deref_mut(),
+            service: self.service.as_deref(),
+            reputation_config: self.reputation_config.as_ref().map(|a| a.to_account_info()),
+            reputation_program: self
+                .reputation_program
+                .as_ref()
+                .map(|a| a.to_account_info()),
+            reputation_authority: self
+                .reputation_authority
+                .as_ref()
+                .map(|a| a.to_account_info()),
+            referrer: None,
+            insurance_pool: self.insurance_pool.as_deref_mut(),
+            treasury: self.treasury.as_ref().map(|a| a.to_account_info()),
+            audit_log: self.audit_log.as_ref().map(|a| a.to_account_info()),
+            token_bond: token_bond_cpi(
+                &self.bond_mint,
+                &self.bond_vault,
+                &self.payer_bond_token,
+                &self.treasury_bond_token,
+                &self.token_program,
+            ),
+        }
+    }
+}
+
+/// Pays out a call per `evaluate_settlement`, reports the outcome to
+/// reputation and the stats, settles a held dispute bond and frees the
+/// call's slot on its service's cap. The account stays open in its
+/// Released or Refunded state until `finalize`; the caller updates its
+/// own indexes.
+fn settle_call<'info>(
+    accounts: &mut SettleAccounts<'_, 'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    require!(
+        accounts.escrow_call.status == Status::Fulfilled as u8
+            || accounts.escrow_call.status == Status::Init as u8,
+        AssuredError::InvalidStatus
+    );
+    require_keys_eq!(
+        accounts.payer.key(),
+        accounts.escrow_call.payer,
+        AssuredError::InvalidPayer
+    );
+    require_keys_eq!(
+        accounts.provider.key(),
+        accounts.escrow_call.provider,
+        AssuredError::InvalidProvider
+    );
+    let now = Clock::get()?.unix_timestamp as u64;
+    let outcome = evaluate_settlement(accounts.escrow_call, now);
+    check_settle_authority(&outcome, accounts.payer.is_signer)?;
+    let amount = accounts.escrow_call.amount;
+    let released_so_far =
+        amount_for_units(accounts.escrow_call, 0, accounts.escrow_call.units_released);
+    let remaining_units = accounts
+        .escrow_call
+        .total_units
+        .saturating_sub(accounts.escrow_call.units_released);
+    let remaining_amount = amount.saturating_sub(released_so_far);
+    record_delivery_latency(accounts)?;
+    match outcome {
+        SettlementOutcome::Release | SettlementOutcome::ReleaseWithPenalty { .. } => {
+            let mut payout = release_payout(accounts.escrow_call, released_so_far, remaining_units);
+            // Data-volume calls under budget hand the unbilled bytes back.
+            if accounts.escrow_call.billing_mode == BillingMode::DataVolume as u8 {
+                let unbilled = remaining_amount.saturating_sub(payout);
+                pay_out(unbilled, &accounts.escrow, &accounts.payer)?;
+            }
+            let penalty = late_penalty(payout, outcome.penalty_bps());
+            if penalty > 0 {
+                payout = payout
+                    .checked_sub(penalty)
+                    .ok_or(AssuredError::MathOverflow)?;
+                pay_out(penalty, &accounts.escrow, &accounts.payer)?;
+                emit!(late_penalty_event(
+                    accounts.escrow_call,
+                    outcome.penalty_bps(),
+                    penalty
+                ));
+            }
+            let mut provider_payout = pay_referral(
+                accounts.escrow_call,
+                payout,
+                &accounts.escrow,
+                accounts.referrer.as_ref(),
+            )?;
+            let stake_reward = pay_stake_reward(accounts, provider_payout)?;
+            provider_payout = provider_payout
+                .checked_sub(stake_reward)
+                .ok_or(AssuredError::MathOverflow)?;
+            record_settle_volume(accounts, released_so_far.saturating_add(payout), true)?;
+            if provider_payout > 0 {
+                pay_out_split(
+                    provider_payout,
+                    &accounts.escrow,
+                    &accounts.provider,
+                    &accounts.escrow_call.payout_splits,
+                    remaining_accounts,
+                )?;
+            }
+            let paid = released_so_far.saturating_add(payout);
+            accounts.global_stats.record_release(paid);
+            if let Some(stats) = &mut accounts.service_stats {
+                stats.record_release(paid);
+            }
+            let ec = &mut *accounts.escrow_call;
+            let event = released_event(ec, provider_payout);
+            ec.units_released = ec.total_units;
+            ec.status = Status::Released as u8;
+            ec.settled_at = Clock::get()?.unix_timestamp;
+            emit!(event);
+        }
+        SettlementOutcome::Refund => {
+            if remaining_amount > 0 {
+                pay_out(remaining_amount, &accounts.escrow, &accounts.payer)?;
+            }
+            record_dispute_loss(accounts)?;
+            let before_clawback = accounts.payer.lamports();
+            claw_back_released(accounts)?;
+            let recovered = accounts.payer.lamports().saturating_sub(before_clawback);
+            claim_insurance(accounts, released_so_far.saturating_sub(recovered))?;
+            record_settle_volume(accounts, released_so_far.saturating_sub(recovered), false)?;
+            accounts
+                .global_stats
+                .record_refund(remaining_amount, released_so_far);
+            if let Some(stats) = &mut accounts.service_stats {
+                stats.record_refund(remaining_amount, released_so_far);
+            }
+            let ec = &mut *accounts.escrow_call;
+            let event = refunded_event(ec, remaining_amount, released_so_far);
+            ec.status = Status::Refunded as u8;
+            ec.settled_at = Clock::get()?.unix_timestamp;
+            emit!(event);
+        }
+        SettlementOutcome::Pending => return err!(AssuredError::SettlementPending),
+    }
+    settle_held_dispute_bond(accounts)?;
+    release_outstanding_slot(accounts)
+}
+
+/// Tops up a refunded payer from the insurance pool for whatever the
+/// provider kept of a large escrow and the bond clawback did not recover.
+/// Only an explicit Refund ruling on a service the provider owns pays out,
+/// so an expiry or a payer-made service cannot draw on the pool.
+fn claim_insurance(accounts: &mut SettleAccounts, shortfall: u64) -> Result<()> {
+    let ec = &accounts.escrow_call;
+    if ec.ruling != Some(Ruling::Refund as u8) {
+        return Ok(());
+    }
+    let (Some(pool), Some(service)) = (&mut accounts.insurance_pool, &accounts.service) else {
+        return Ok(());
+    };
+    if service.owner != ec.provider {
+        return Ok(());
+    }
+    let amount = pool.claim(ec.amount, shortfall, Clock::get()?.epoch);
+    if amount == 0 {
+        return Ok(());
+    }
+    pay_out(
+        amount,
+        &pool.to_account_info(),
+        &accounts.payer.to_account_info(),
+    )?;
+    emit!(InsuranceClaimed {
+        call_id: ec.call_id.clone(),
+        amount_supplemented: amount,
+    });
+    Ok(())
+}
+
+/// Share of a release the service's stakers earn: its `stake_reward_bps`,
+/// or nothing while nobody is staked.
+fn stake_reward_cut(service: &reputation::Service, payout: u64) -> u64 {
+    if service.total_stake_shares == 0 {
+        return 0;
+    }
+    (payout as u128 * service.stake_reward_bps as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+/// Moves the stakers' cut onto the service PDA and has reputation add it
+/// to the stake pool. Skipped unless all reputation accounts are passed.
+fn pay_stake_reward(accounts: &SettleAccounts, payout: u64) -> Result<u64> {
+    let (Some(service), Some(config), Some(program), Some(caller)) = (
+        &accounts.service,
+        &accounts.reputation_config,
+        &accounts.reputation_program,
+        &accounts.reputation_authority,
+    ) else {
+        return Ok(0);
+    };
+    let reward = stake_reward_cut(service, payout);
+    if reward == 0 {
+        return Ok(0);
+    }
+    pay_out(reward, &accounts.escrow, &service.to_account_info())?;
+    let cpi_accounts = reputation::cpi::accounts::RecordVolume {
+        service: service.to_account_info(),
+        authority: caller.to_account_info(),
+        config: config.to_account_info(),
+    };
+    as_reputation_authority(|signer| {
+        reputation::cpi::distribute_stake_reward(
+            CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer),
+            accounts.escrow_call.service_id.clone(),
+            reward,
+        )
+    })?;
+    Ok(reward)
+}
+
+fn record_dispute_loss(accounts: &SettleAccounts) -> Result<()> {
+    let (Some(service), Some(config), Some(program), Some(caller), Some(audit_log)) = (
+        &accounts.service,
+        &accounts.reputation_config,
+        &accounts.reputation_program,
+        &accounts.reputation_authority,
+        &accounts.audit_log,
+    ) else {
+        return Ok(());
+    };
+    let cpi_accounts = reputation::cpi::accounts::RecordDisputeLoss {
+        service: service.to_account_info(),
+        authority: caller.to_account_info(),
+        config: config.to_account_info(),
+        audit_log: audit_log.to_account_info(),
+    };
+    as_reputation_authority(|signer| {
+        reputation::cpi::record_dispute_loss(
+            CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer),
+            accounts.escrow_call.service_id.clone(),
+        )
+    })
+}
+
+/// Milliseconds between the call's start and its Clock-stamped fulfill;
+/// `None` for a call that was never fulfilled.
+fn delivery_latency_ms(ec: &EscrowCall) -> Option<u64> {
+    if ec.fulfilled_at == 0 {
+        return None;
+    }
+    Some(
+        ec.fulfilled_at
+            .saturating_sub(ec.start_ts)
+            .saturating_mul(1_000),
+    )
+}
+
+/// Feeds the clocked delivery latency into the service's escrow p95.
+/// Skipped for undelivered calls and unless all reputation accounts are
+/// passed.
+fn record_delivery_latency(accounts: &SettleAccounts) -> Result<()> {
+    let Some(latency_ms) = delivery_latency_ms(accounts.escrow_call) else {
+        return Ok(());
+    };
+    let (Some(service), Some(config), Some(program), Some(caller), Some(audit_log)) = (
+        &accounts.service,
+        &accounts.reputation_config,
+        &accounts.reputation_program,
+        &accounts.reputation_authority,
+        &accounts.audit_log,
+    ) else {
+        return Ok(());
+    };
+    let cpi_accounts = reputation::cpi::accounts::RecordDeliveryLatency {
+        service: service.to_account_info(),
+        authority: caller.to_account_info(),
+        config: config.to_account_info(),
+        audit_log: audit_log.to_account_info(),
+    };
+    as_reputation_authority(|signer| {
+        reputation::cpi::record_delivery_latency(
+            CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer),
+            accounts.escrow_call.service_id.clone(),
+            latency_ms,
+        )
+    })
+}
+
+/// The reputation config, program and authority PDA an instruction was
+/// given, as the `AccountInfo`s the reputation CPIs take.
+#[derive(Default)]
+struct ReputationCpi<'info> {
+    config: Option<AccountInfo<'info>>,
+    program: Option<AccountInfo<'info>>,
+    caller: Option<AccountInfo<'info>>,
+}
+
+fn reputation_cpi<'info>(
+    config: &Option<UncheckedAccount<'info>>,
+    program: &Option<Program<'info, reputation::program::Reputation>>,
+    caller: &Option<UncheckedAccount<'info>>,
+) -> ReputationCpi<'info> {
+    ReputationCpi {
+        config: config.as_ref().map(|a| a.to_account_info()),
+        program: program.as_ref().map(|a| a.to_account_info()),
+        caller: caller.as_ref().map(|a| a.to_account_info()),
+    }
+}
+
+/// Adds what the provider earned on a call to the service's lifetime
+/// volume; `released` also counts the call as a release. Skipped when a
+/// refunded call kept nothing, and unless all reputation accounts are
+/// passed.
+fn record_volume<'info>(
+    service: Option<AccountInfo<'info>>,
+    rep: ReputationCpi<'info>,
+    service_id: String,
+    amount: u64,
+    released: bool,
+) -> Result<()> {
+    if amount == 0 && !released {
+        return Ok(());
+    }
+    let (Some(service), Some(config), Some(program), Some(caller)) =
+        (service, rep.config, rep.program, rep.caller)
+    else {
+        return Ok(());
+    };
+    let cpi_accounts = reputation::cpi::accounts::RecordVolume {
+        service,
+        authority: caller,
+        config,
+    };
+    as_reputation_authority(|signer| {
+        reputation::cpi::record_volume(
+            CpiContext::new_with_signer(program, cpi_accounts, signer),
+            service_id,
+            amount,
+            released,
+        )
+    })
+}
+
+fn record_settle_volume(accounts: &SettleAccounts, amount: u64, released: bool) -> Result<()> {
+    record_volume(
+        accounts.service.map(|a| a.to_account_info()),
+        accounts.reputation_cpi(),
+        accounts.escrow_call.service_id.clone(),
+        amount,
+        released,
+    )
+}
+
+/// Under strict pricing, `amount` must cover `price_per_unit * total_units`.
+fn check_price(
+    strict: bool,
+    service: &reputation::Service,
+    amount: u64,
+    total_units: u64,
+) -> Result<()> {
+    if !strict || service.pricing_opt_out {
+        return Ok(());
+    }
+    let price = service
+        .price_per_unit
+        .checked_mul(total_units)
+        .ok_or(AssuredError::UnderpricedEscrow)?;
+    require!(amount >= price, AssuredError::UnderpricedEscrow);
+    Ok(())
+}
+
+fn check_accepting_calls(service: &reputation::Service) -> Result<()> {
+    require!(!service.paused, AssuredError::ServicePaused);
+    Ok(())
+}
+
+/// Whether a new call must be counted against the service's cap; fails
+/// once the cap is reached.
+fn check_call_capacity(service: &reputation::Service) -> Result<bool> {
+    if service.max_outstanding_calls == 0 {
+        return Ok(false);
+    }
+    require!(
+        service.has_call_capacity(),
+        AssuredError::ProviderOverloaded
+    );
+    Ok(true)
+}
+
+/// Pushes a batch leg onto its provider's call index, creating the index
+/// on first use as `init_payment` does.
+fn push_batch_provider_index<'info>(
+    index_info: &AccountInfo<'info>,
+    provider: &Pubkey,
+    call_id: &str,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let (expected, bump) =
+        Pubkey::find_program_address(&[b"provider_index", provider.as_ref()], &crate::ID);
+    require_keys_eq!(
+        index_info.key(),
+        expected,
+        AssuredError::BatchAccountsMismatch
+    );
+    let mut index = if index_info.data_is_empty() {
+        let space = 8 + ProviderCallIndex::INIT_SPACE;
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                CreateAccount {
+                    from: payer.to_account_info(),
+                    to: index_info.clone(),
+                },
+                &[&[b"provider_index", provider.as_ref(), &[bump]]],
+            ),
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            &crate::ID,
+        )?;
+        ProviderCallIndex {
+            provider: *provider,
+            call_ids: Vec::new(),
+            head: 0,
+        }
+    } else {
+        ProviderCallIndex::try_deserialize(&mut &index_info.try_borrow_data()?[..])?
+    };
+    index.push(call_id);
+    index.try_serialize(&mut &mut index_info.try_borrow_mut_data()?[..])
+}
+
+/// Counts a batch leg in its service's stats, creating them on first use
+/// as `init_payment` does. Passing the escrow program id in the slot
+/// skips them, as Anchor does for an omitted optional account.
+fn record_batch_service_open<'info>(
+    stats_info: &AccountInfo<'info>,
+    service_id: &str,
+    amount: u64,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    if stats_info.key() == crate::ID {
+        return Ok(());
+    }
+    let (expected, bump) =
+        Pubkey::find_program_address(&[b"svc_stats", service_id.as_bytes()], &crate::ID);
+    require_keys_eq!(
+        stats_info.key(),
+        expected,
+        AssuredError::BatchAccountsMismatch
+    );
+    let mut stats = if stats_info.data_is_empty() {
+        let space = 8 + EscrowStats::INIT_SPACE;
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                CreateAccount {
+                    from: payer.to_account_info(),
+                    to: stats_info.clone(),
+                },
+                &[&[b"svc_stats", service_id.as_bytes(), &[bump]]],
+            ),
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            &crate::ID,
+        )?;
+        EscrowStats::default()
+    } else {
+        EscrowStats::try_deserialize(&mut &stats_info.try_borrow_data()?[..])?
+    };
+    stats.record_open(amount);
+    stats.try_serialize(&mut &mut stats_info.try_borrow_mut_data()?[..])
+}
+
+/// Has reputation count a call in or out of the service's outstanding
+/// total. Every account is required, and the service must be writable: a
+/// tracked call must not close without releasing its slot.
+fn adjust_outstanding_calls<'info>(
+    open: bool,
+    service: &AccountInfo<'info>,
+    rep: ReputationCpi<'info>,
+    service_id: String,
+) -> Result<()> {
+    require!(service.is_writable, AssuredError::ServiceNotWritable);
+    let (Some(config), Some(program), Some(caller)) = (rep.config, rep.program, rep.caller) else {
+        return err!(AssuredError::ReputationAccountsRequired);
+    };
+    let cpi_accounts = reputation::cpi::accounts::RecordVolume {
+        service: service.clone(),
+        authority: caller,
+        config,
+    };
+    as_reputation_authority(|signer| {
+        let cpi_ctx = CpiContext::new_with_signer(program, cpi_accounts, signer);
+        if open {
+            reputation::cpi::open_outstanding_call(cpi_ctx, service_id)
+        } else {
+            reputation::cpi::close_outstanding_call(cpi_ctx, service_id)
+        }
+    })
+}
+
+/// Counts a new call against its service's cap, if it has one: fails once
+/// the cap is reached, else has reputation open the slot. Every init path
+/// runs it after `check_init_gates`; the result is the call's
+/// `outstanding_tracked`.
+fn claim_outstanding_slot<'info>(
+    service: &reputation::Service,
+    service_info: &AccountInfo<'info>,
+    rep: ReputationCpi<'info>,
+    service_id: &str,
+) -> Result<bool> {
+    let tracked = check_call_capacity(service)?;
+    if tracked {
+        adjust_outstanding_calls(true, service_info, rep, service_id.to_string())?;
+    }
+    Ok(tracked)
+}
+
+/// Rejects a batch leg when it and the later legs on the same service would
+/// take that service past its cap. Earlier legs have already been counted
+/// in `outstanding_calls`, so this runs once per leg with `legs` starting
+/// at the current one.
+fn check_batch_capacity(legs: &[BatchInitLeg], service: &reputation::Service) -> Result<()> {
+    let Some(leg) = legs.first() else {
+        return Ok(());
+    };
+    if service.max_outstanding_calls == 0 {
+        return Ok(());
+    }
+    let opening = legs
+        .iter()
+        .filter(|other| other.service_id == leg.service_id)
+        .count() as u64;
+    require!(
+        service.outstanding_calls as u64 + opening <= service.max_outstanding_calls as u64,
+        AssuredError::ProviderOverloaded
+    );
+    Ok(())
+}
+
+/// Gives back the slot a tracked call holds on its service's cap; a no-op
+/// for untracked calls.
+fn release_call_slot<'info>(
+    ec: &EscrowCall,
+    service: Option<AccountInfo<'info>>,
+    rep: ReputationCpi<'info>,
+) -> Result<()> {
+    if !ec.outstanding_tracked {
+        return Ok(());
+    }
+    let service = service.ok_or(AssuredError::ReputationAccountsRequired)?;
+    adjust_outstanding_calls(false, &service, rep, ec.service_id.clone())
+}
+
+fn release_outstanding_slot(accounts: &SettleAccounts) -> Result<()> {
+    release_call_slot(
+        accounts.escrow_call,
+        accounts.service.map(|a| a.to_account_info()),
+        accounts.reputation_cpi(),
+    )
+}
+
+/// Returns or forfeits a held dispute bond per the ruling. Kept out of
+/// `settle` so its temporaries don't share that handler's stack frame.
+fn settle_held_dispute_bond(accounts: &mut SettleAccounts) -> Result<()> {
+    if accounts.escrow_call.dispute_bond_state != BondCustody::Held as u8 {
+        return Ok(());
+    }
+    let forfeited = settle_dispute_bond(
+        accounts.escrow_call,
+        &accounts.escrow,
+        &accounts.provider,
+        &accounts.payer,
+    )?;
+    // An upheld dispute also pays the reporter out of the provider's
+    // reputation bond.
+    let before_slash = accounts.payer.lamports();
+    if accounts.escrow_call.ruling == Some(Ruling::Refund as u8) {
+        slash_for_reporter(accounts)?;
+    }
+    let reporter_reward = accounts.payer.lamports().saturating_sub(before_slash);
+    let ec = &accounts.escrow_call;
+    emit!(DisputeBondSettled {
+        call_id: ec.call_id.clone(),
+        amount: ec.dispute_bond,
+        forfeited,
+        reporter_reward,
+    });
+    Ok(())
+}
+
+/// Slashes the provider's reputation bond for the dispute's kind to the
+/// payer who raised it, and its token bond too when the service has one
+/// and the token accounts are passed; each is capped on its own. Skipped
+/// unless the reputation accounts and its treasury are passed.
+fn slash_for_reporter(accounts: &SettleAccounts) -> Result<()> {
+    let (Some(service), Some(config), Some(program), Some(caller), Some(treasury), Some(audit_log)) = (
+        &accounts.service,
+        &accounts.reputation_config,
+        &accounts.reputation_program,
+        &accounts.reputation_authority,
+        &accounts.treasury,
+        &accounts.audit_log,
+    ) else {
+        return Ok(());
+    };
+    let cpi_accounts = reputation::cpi::accounts::BondSlashSplit {
+        service: service.to_account_info(),
+        authority: caller.to_account_info(),
+        recipient: accounts.payer.to_account_info(),
+        treasury: treasury.to_account_info(),
+        config: config.to_account_info(),
+        audit_log: audit_log.to_account_info(),
+    };
+    as_reputation_authority(|signer| {
+        reputation::cpi::bond_slash(
+            CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer),
+            accounts.escrow_call.service_id.clone(),
+            accounts.escrow_call.dispute_kind,
+            accounts.escrow_call.amount,
+        )
+    })?;
+    let Some(token) = &accounts.token_bond else {
+        return Ok(());
+    };
+    if service.bond_mint == Pubkey::default() {
+        return Ok(());
+    }
+    let cpi_accounts = reputation::cpi::accounts::BondSlashSpl {
+        service: service.to_account_info(),
+        authority: caller.to_account_info(),
+        mint: token.mint.clone(),
+        vault: token.vault.clone(),
+        recipient: accounts.payer.clone(),
+        recipient_token: token.recipient_token.clone(),
+        treasury_token: token.treasury_token.clone(),
+        config: config.to_account_info(),
+        audit_log: audit_log.to_account_info(),
+        token_program: token.token_program.clone(),
+    };
+    as_reputation_authority(|signer| {
+        reputation::cpi::bond_slash_spl(
+            CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer),
+            accounts.escrow_call.service_id.clone(),
+            accounts.escrow_call.dispute_kind,
+            accounts.escrow_call.amount,
+        )
+    })
+}
+
+/// Recovers the disputed share of already-released units from the
+/// provider's performance bond; reputation caps it at that balance.
+fn claw_back_released(accounts: &SettleAccounts) -> Result<()> {
+    let amount = clawback_request(accounts.escrow_call);
+    if amount == 0 {
+        return Ok(());
+    }
+    let (Some(service), Some(config), Some(program), Some(caller), Some(audit_log)) = (
+        &accounts.service,
+        &accounts.reputation_config,
+        &accounts.reputation_program,
+        &accounts.reputation_authority,
+        &accounts.audit_log,
+    ) else {
+        return Ok(());
+    };
+    let cpi_accounts = reputation::cpi::accounts::BondSlash {
+        service: service.to_account_info(),
+        authority: caller.to_account_info(),
+        recipient: accounts.payer.to_account_info(),
+        config: config.to_account_info(),
+        audit_log: audit_log.to_account_info(),
+    };
+    as_reputation_authority(|signer| {
+        reputation::cpi::bond_clawback(
+            CpiContext::new_with_signer(program.to_account_info(), cpi_accounts, signer),
+            accounts.escrow_call.service_id.clone(),
+            amount,
+        )
+    })
+}
+
+/// Runs a reputation CPI signed by escrow's `reputation_authority` PDA.
+fn as_reputation_authority<R>(cpi: impl FnOnce(&[&[&[u8]]]) -> Result<R>) -> Result<R> {
+    let (_, bump) = Pubkey::find_program_address(&[reputation::ESCROW_AUTHORITY_SEED], &crate::ID);
+    cpi(&[&[reputation::ESCROW_AUTHORITY_SEED, &[bump]]])
+}
+
+fn resize_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    new_len: usize,
+) -> Result<()> {
+    let required = Rent::get()?.minimum_balance(new_len);
+    let shortfall = required.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        let accounts = Transfer {
+            from: payer.to_account_info(),
+            to: account.clone(),
+        };
+        system_program::transfer(
+            CpiContext::new(system_program.to_account_info(), accounts),
+            shortfall,
+        )?;
+    }
+    account.resize(new_len)?;
+    Ok(())
+}
+
+fn pay_out<'info>(
+    amount: u64,
+    escrow: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    require!(escrow.lamports() >= amount, AssuredError::EscrowBalanceLow);
+    let credited = destination
+        .lamports()
+        .checked_add(amount)
+        .ok_or(AssuredError::MathOverflow)?;
+    **escrow.try_borrow_mut_lamports()? -= amount;
+    **destination.try_borrow_mut_lamports()? = credited;
+    Ok(())
+}
+
+pub fn init_memo(call_id: &str, service_id: &str) -> String {
+    format!("assured:init:{call_id}:{service_id}")
+}
+
+pub fn settle_memo(call_id: &str, status: u8) -> String {
+    let outcome = if status == Status::Released as u8 {
+        "release"
+    } else {
+        "refund"
+    };
+    format!("assured:settle:{call_id}:{outcome}")
+}
+
+fn memo_instruction(memo: &str) -> Instruction {
+    Instruction {
+        program_id: MEMO_PROGRAM_ID,
+        accounts: vec![],
+        data: memo.as_bytes().to_vec(),
+    }
+}
+
+fn write_memo(memo_program: &UncheckedAccount, memo: &str) -> Result<()> {
+    anchor_lang::solana_program::program::invoke(
+        &memo_instruction(memo),
+        &[memo_program.to_account_info()],
+    )
+    .map_err(Into::into)
+}
+
+fn validate_referral_fee(referral_fee_bps: u16) -> Result<()> {
+    require!(
+        referral_fee_bps <= MAX_REFERRAL_FEE_BPS,
+        AssuredError::ReferralFeeTooHigh
+    );
+    Ok(())
+}
+
+/// A paid referral must name a referrer the provider approved, so the
+/// payer can't route part of the provider's payout to itself.
+fn check_referrer(
+    referrer: Option<Pubkey>,
+    referral_fee_bps: u16,
+    approved: Option<&ProviderReferrers>,
+) -> Result<()> {
+    let Some(referrer) = referrer.filter(|_| referral_fee_bps > 0) else {
+        return Ok(());
+    };
+    require!(
+        approved.is_some_and(|list| list.is_approved(&referrer)),
+        AssuredError::ReferrerNotApproved
+    );
+    Ok(())
+}
+
+/// Pays the referrer its cut of a release `payout` and returns what is
+/// left for the provider. Partial releases and `settle` both go through
+/// it, so the referrer gets the same share however the call streamed.
+fn pay_referral<'info>(
+    ec: &EscrowCall,
+    payout: u64,
+    escrow: &AccountInfo<'info>,
+    referrer: Option<&AccountInfo<'info>>,
+) -> Result<u64> {
+    let Some((referrer_key, amount)) = referral_cut(ec, payout) else {
+        return Ok(payout);
+    };
+    let referrer_info = referrer
+        .filter(|info| info.key() == referrer_key)
+        .ok_or(AssuredError::InvalidReferrer)?;
+    pay_out(amount, escrow, referrer_info)?;
+    emit!(ReferralPaid {
+        call_id: ec.call_id.clone(),
+        referrer: referrer_key,
+        amount,
+    });
+    Ok(payout
+        .checked_sub(amount)
+        .ok_or(AssuredError::MathOverflow)?)
+}
+
+/// The referrer's share of a release `payout`, or `None` when the call has
+/// no referrer or a zero fee.
+pub fn referral_cut(ec: &EscrowCall, payout: u64) -> Option<(Pubkey, u64)> {
+    let referrer = ec.referrer.filter(|_| ec.referral_fee_bps > 0)?;
+    let amount = (payout as u128 * ec.referral_fee_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+    Some((referrer, amount))
+}
+
+/// Rejects a call `migrate_v1_to_v2` could move but the v2 instructions
+/// could not finish: `settle_v2` pays the provider directly, so no splits
+/// or referral; there is no v2 `redeem`, so no hashlock; `fulfill_v2`
+/// cannot check a proof set; and only `arbitrate_v2` rules on v2 calls,
+/// so no committee. Bonded and non-per-unit calls stay on v1 as well.
+fn check_v2_supported(ec: &EscrowCall) -> Result<()> {
+    require!(
+        ec.payout_splits.is_empty()
+            && referral_cut(ec, 1).is_none()
+            && ec.dispute_bond == 0
+            && ec.billing_mode == BillingMode::PerUnit as u8
+            && ec.hashlock == [0u8; 32]
+            && !ec.requires_proofs
+            && ec.arbiters.is_empty(),
+        AssuredError::NotSupportedByV2
+    );
+    Ok(())
+}
+
+/// Empty splits are valid and leave the whole payout to the provider.
+fn validate_payout_splits(splits: &[PayoutSplit]) -> Result<()> {
+    if splits.is_empty() {
+        return Ok(());
+    }
+    require!(
+        splits.len() <= MAX_PAYOUT_SPLITS && splits.iter().all(|s| s.bps > 0),
+        AssuredError::InvalidPayoutSplits
+    );
+    let total: u64 = splits.iter().map(|s| s.bps as u64).sum();
+    require!(total == BPS_DENOMINATOR, AssuredError::InvalidPayoutSplits);
+    for (i, split) in splits.iter().enumerate() {
+        require!(
+            splits[..i].iter().all(|s| s.recipient != split.recipient),
+            AssuredError::InvalidPayoutSplits
+        );
+    }
+    Ok(())
+}
+
+/// Splits redirect the provider's money, so only the provider sets them,
+/// and only before the call settles.
+fn apply_payout_splits(
+    ec: &mut EscrowCall,
+    provider: &Pubkey,
+    payout_splits: Vec<PayoutSplit>,
+) -> Result<()> {
+    require_keys_eq!(*provider, ec.provider, AssuredError::InvalidProvider);
+    require!(
+        ec.status == Status::Init as u8 || ec.status == Status::Fulfilled as u8,
+        AssuredError::InvalidStatus
+    );
+    validate_payout_splits(&payout_splits)?;
+    ec.payout_splits = payout_splits;
+    Ok(())
+}
+
+/// Tiers must widen the deadline and never raise the payout as lateness
+/// grows. Deliveries within the SLA always pay in full, so every tier
+/// starts past 1x.
+fn validate_sla_tiers(tiers: &[SlaTier]) -> Result<()> {
+    require!(tiers.len() <= MAX_SLA_TIERS, AssuredError::InvalidSlaTiers);
+    let mut prev = SlaTier {
+        deadline_factor_bps: BPS_DENOMINATOR as u32,
+        multiplier_bps: BPS_DENOMINATOR as u16,
+    };
+    for tier in tiers {
+        require!(
+            tier.deadline_factor_bps > prev.deadline_factor_bps
+                && tier.multiplier_bps <= prev.multiplier_bps,
+            AssuredError::InvalidSlaTiers
+        );
+        prev = *tier;
+    }
+    Ok(())
+}
+
+/// Share of the payout, in bps, a delivery earns under the call's tiers:
+/// full within the SLA, the first tier whose deadline it met, else none.
+/// Lateness is the escrow-clocked `delivery_latency_ms`.
+fn tier_payout_bps(ec: &EscrowCall) -> u16 {
+    if delivered_within_sla(ec) {
+        return BPS_DENOMINATOR as u16;
+    }
+    let Some(latency_ms) = delivery_latency_ms(ec) else {
+        return 0;
+    };
+    let latency = latency_ms as u128;
+    ec.sla_tiers
+        .iter()
+        .find(|tier| {
+            latency * BPS_DENOMINATOR as u128
+                <= ec.sla_ms as u128 * tier.deadline_factor_bps as u128
+        })
+        .map_or(0, |tier| tier.multiplier_bps)
+}
+
+/// Each recipient's floor share of `amount`; rounding dust goes to the first.
+pub fn split_payout(amount: u64, splits: &[PayoutSplit]) -> Vec<u64> {
+    let mut shares: Vec<u64> = splits
+        .iter()
+        .map(|s| (amount as u128 * s.bps as u128 / BPS_DENOMINATOR as u128) as u64)
+        .collect();
+    let dust = amount - shares.iter().sum::<u64>();
+    if let Some(first) = shares.first_mut() {
+        *first += dust;
+    }
+    shares
+}
+
+fn split_recipient<'a, 'info>(
+    recipient: &Pubkey,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<&'a AccountInfo<'info>> {
+    remaining_accounts
+        .iter()
+        .find(|info| info.key == recipient && info.is_writable)
+        .ok_or_else(|| error!(AssuredError::MissingSplitRecipient))
+}
+
+/// Pays `amount` to the provider, or across the call's payout splits when
+/// it has any.
+fn pay_out_split<'info>(
+    amount: u64,
+    escrow: &AccountInfo<'info>,
+    provider: &AccountInfo<'info>,
+    splits: &[PayoutSplit],
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    if splits.is_empty() {
+        return pay_out(amount, escrow, provider);
+    }
+    let recipients = splits
+        .iter()
+        .map(|s| split_recipient(&s.recipient, remaining_accounts))
+        .collect::<Result<Vec<_>>>()?;
+    for (recipient, share) in recipients.into_iter().zip(split_payout(amount, splits)) {
+        pay_out(share, escrow, recipient)?;
+    }
+    Ok(())
+}
+
+/// Moves whatever the escrow still holds (its rent, once payouts are done)
+/// to the recorded rent recipient ahead of closing it.
+fn sweep_rent<'info>(escrow: &AccountInfo<'info>, recipient: &AccountInfo<'info>) -> Result<()> {
+    pay_out(escrow.lamports(), escrow, recipient)
+}
+
+struct PartialReleaseState {
+    payout: u64,
+    units: u64,
+    emit_trace: bool,
+}
+
+/// First 8 bytes of the SHA-256 of what a chunk delivers: its hash, units
+/// and content CID. The index and `ts` are left out, so the same chunk
+/// re-signed under a later timestamp still has the same nonce.
+fn chunk_nonce(chunk_hash: &[u8; 32], units: u64, content_cid: &[u8]) -> [u8; 8] {
+    let mut nonce = [0u8; 8];
+    nonce.copy_from_slice(&hashv(&[chunk_hash, &units.to_le_bytes(), content_cid]).to_bytes()[..8]);
+    nonce
+}
+
+fn apply_partial_release(
+    ec: &mut EscrowCall,
+    chunk_hash: [u8; 32],
+    units: u64,
+    ts: u64,
+    provider_sig: &[u8],
+    content_cid: &[u8],
+    now: u64,
+) -> Result<PartialReleaseState> {
+    require!(ec.status == Status::Init as u8, AssuredError::InvalidStatus);
+    require!(units > 0, AssuredError::InvalidUnits);
+    require!(ts > ec.start_ts, AssuredError::InvalidChunkTimestamp);
+    // Chunks must move forward in time, which rules out replaying any
+    // earlier one; the nonce also catches the last chunk delivered again
+    // under a later timestamp.
+    let nonce = chunk_nonce(&chunk_hash, units, content_cid);
+    require!(
+        ec.partial_count == 0 || (ts > ec.last_chunk_ts && nonce != ec.last_chunk_nonce),
+        AssuredError::DuplicateChunk
+    );
+    let start_units = ec.units_released;
+    let new_total = start_units
+        .checked_add(units)
+        .ok_or(AssuredError::InvalidUnits)?;
+    require!(new_total <= ec.total_units, AssuredError::InvalidUnits);
+    // Once the cap is hit the provider may still deliver everything left
+    // in one final partial.
+    require!(
+        ec.partial_count < ec.max_partials || new_total == ec.total_units,
+        AssuredError::PartialLimitReached
+    );
+    ec.partial_count = ec.partial_count.saturating_add(1);
+
+    let payout = amount_for_units(ec, start_units, units);
+    check_cumulative_payout(ec, start_units, payout)?;
+    ec.units_released = new_total;
+    ec.response_hash = chunk_hash;
+    ec.provider_sig = provider_sig.to_vec();
+    ec.last_chunk_nonce = nonce;
+    ec.last_chunk_ts = ts;
+
+    let mut emit_trace = false;
+    if ec.units_released == ec.total_units {
+        ec.delivered_ts = Some(ts);
+        ec.fulfilled_at = now;
+        ec.status = Status::Fulfilled as u8;
+        emit_trace = true;
+    }
+
+    Ok(PartialReleaseState {
+        payout,
+        units,
+        emit_trace,
+    })
+}
+
+/// Escrowed funds share the account with its rent, so a rounding slip
+/// that paid out more than `amount` would eat into the rent.
+fn check_cumulative_payout(ec: &EscrowCall, start_units: u64, payout: u64) -> Result<()> {
+    let paid_so_far = amount_for_units(ec, 0, start_units);
+    require!(
+        paid_so_far
+            .checked_add(payout)
+            .is_some_and(|total| total <= ec.amount),
+        AssuredError::PayoutExceedsAmount
+    );
+    Ok(())
+}
+
+/// Latest time `fulfill` is accepted; past it the call can only refund.
+fn fulfill_deadline(ec: &EscrowCall, grace_s: u64) -> u64 {
+    ec.start_ts
+        .saturating_add(ec.sla_ms.div_ceil(1_000))
+        .saturating_add(grace_s)
+}
+
+/// Checks the preimage and marks the call delivered with every unit
+/// released. Returns the lamports still owed to the provider. A disputed
+/// call waits for its ruling instead.
+fn redeem_hashlock(ec: &mut EscrowCall, preimage: &[u8], now: u64) -> Result<u64> {
+    require!(ec.hashlock != [0u8; 32], AssuredError::NotHashlocked);
+    require!(ec.status == Status::Init as u8, AssuredError::InvalidStatus);
+    require!(!ec.disputed, AssuredError::AlreadyDisputed);
+    require!(now < ec.refund_after_ts, AssuredError::RedeemExpired);
+    require!(
+        preimage.len() <= MAX_PREIMAGE_LEN,
+        AssuredError::PreimageTooLong
+    );
+    require!(
+        hash(preimage).to_bytes() == ec.hashlock,
+        AssuredError::HashlockMismatch
+    );
+    let remaining_units = ec.total_units.saturating_sub(ec.units_released);
+    let payout = amount_for_units(ec, ec.units_released, remaining_units);
+    record_fulfillment(ec, ec.hashlock, now, preimage, now);
+    ec.units_released = ec.total_units;
+    Ok(payout)
+}
+
+/// A call id is free when its PDA was just created (all zero past the
+/// discriminator). Settled escrows are closed, which zeroes the data and
+/// hands the account back to the system program, so a reused id always
+/// starts from a fresh account rather than a revived one.
+fn ensure_call_id_free(ec: &EscrowCall) -> Result<()> {
+    require!(
+        ec.version == 0 && ec.payer == Pubkey::default(),
+        AssuredError::CallIdTaken
+    );
+    Ok(())
+}
+
+fn check_fulfill(
+    ec: &EscrowCall,
+    provider: &Pubkey,
+    provider_sig: &[u8],
+    now: u64,
+    grace_s: u64,
+) -> Result<()> {
+    require!(ec.status == Status::Init as u8, AssuredError::InvalidStatus);
+    require!(
+        ec.hashlock == [0u8; 32],
+        AssuredError::HashlockRequiresRedeem
+    );
+    require_keys_eq!(*provider, ec.provider, AssuredError::InvalidProvider);
+    require!(
+        provider_sig.len() <= MAX_PROVIDER_SIG_LEN,
+        AssuredError::SignatureTooLong
+    );
+    check_fulfill_deadline(ec, now, grace_s)
+}
+
+/// Rejects delivery past `fulfill_deadline`, whether the whole response
+/// through `fulfill` or a chunk through `fulfill_partial`.
+fn check_fulfill_deadline(ec: &EscrowCall, now: u64, grace_s: u64) -> Result<()> {
+    require!(
+        now <= fulfill_deadline(ec, grace_s),
+        AssuredError::SlaDeadlineExpired
+    );
+    Ok(())
+}
+
+/// Validates the requested mode and, for `Secp256k1`, returns the
+/// Ethereum address the provider registered; the payer never picks it. An
+/// attestation key always meant Ed25519 verification, so `None` with a key
+/// set is stored as `Ed25519`.
+fn resolve_sig_mode(sig_mode: u8, keys: Option<&ProviderKeys>) -> Result<(u8, Option<[u8; 20]>)> {
+    match sig_mode {
+        m if m == SigMode::None as u8 && registered_attest_key(keys) != [0u8; 32] => {
+            Ok((SigMode::Ed25519 as u8, None))
+        }
+        m if m == SigMode::None as u8 || m == SigMode::Ed25519 as u8 => Ok((m, None)),
+        m if m == SigMode::Secp256k1 as u8 => {
+            let keys = keys.ok_or(AssuredError::MissingEthSigner)?;
+            require!(
+                keys.eth_address != [0u8; 20],
+                AssuredError::UnregisteredEthAddress
+            );
+            Ok((m, Some(keys.eth_address)))
+        }
+        _ => err!(AssuredError::InvalidSigMode),
+    }
+}
+
+/// The precompile check `fulfill` must find before it, per `SigMode`.
+#[derive(Debug, PartialEq)]
+enum AttestationCheck {
+    None,
+    Ed25519([u8; 32]),
+    Secp256k1([u8; 20]),
+}
+
+fn attestation_check(ec: &EscrowCall) -> Result<AttestationCheck> {
+    match ec.sig_mode {
+        m if m == SigMode::None as u8 => Ok(AttestationCheck::None),
+        m if m == SigMode::Ed25519 as u8 => Ok(AttestationCheck::Ed25519(proof_signing_key(ec))),
+        m if m == SigMode::Secp256k1 as u8 => ec
+            .provider_eth_address
+            .map(AttestationCheck::Secp256k1)
+            .ok_or(error!(AssuredError::MissingEthSigner)),
+        _ => err!(AssuredError::InvalidSigMode),
+    }
+}
+
+/// Branches on the call's `SigMode`; the preceding instruction must be the
+/// matching Ed25519 or secp256k1 precompile check of `provider_sig` over
+/// `message`.
+fn verify_attestation(
+    ec: &EscrowCall,
+    instructions: &AccountInfo,
+    message: &[u8],
+    provider_sig: &[u8],
+) -> Result<()> {
+    let check = attestation_check(ec)?;
+    if check == AttestationCheck::None {
+        return Ok(());
+    }
+    let current = load_current_index_checked(instructions)
+        .map_err(|_| error!(AssuredError::InvalidAttestation))?;
+    let ix = get_instruction_relative(-1, instructions)
+        .map_err(|_| error!(AssuredError::InvalidAttestation))?;
+    check_attestation_ix(
+        &check,
+        &ix,
+        current.saturating_sub(1),
+        message,
+        provider_sig,
+    )
+}
+
+/// `verify_attestation` passing if `provider_sig` covers any of `messages`.
+fn verify_any_attestation(
+    ec: &EscrowCall,
+    instructions: &AccountInfo,
+    messages: &[Vec<u8>],
+    provider_sig: &[u8],
+) -> Result<()> {
+    let mut result = err!(AssuredError::InvalidAttestation);
+    for message in messages {
+        result = verify_attestation(ec, instructions, message, provider_sig);
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+fn check_attestation_ix(
+    check: &AttestationCheck,
+    ix: &Instruction,
+    ix_index: u16,
+    message: &[u8],
+    provider_sig: &[u8],
+) -> Result<()> {
+    match check {
+        AttestationCheck::None => Ok(()),
+        AttestationCheck::Ed25519(key) => verify_ed25519_ix(ix, key, message, provider_sig),
+        AttestationCheck::Secp256k1(eth_address) => {
+            let sig: &[u8; 65] = provider_sig
+                .try_into()
+                .map_err(|_| error!(AssuredError::InvalidAttestation))?;
+            verify_secp256k1_ix(ix, ix_index, eth_address, message, sig)
+        }
+    }
+}
+
+/// What the provider signs for a streamed chunk.
+fn chunk_message(
+    ec: &EscrowCall,
+    chunk_hash: [u8; 32],
+    units: u64,
+    ts: u64,
+    content_cid: &[u8],
+) -> Vec<u8> {
+    use payload::SigningPayload;
+    payload::ChunkPayload {
+        call_id: &ec.call_id,
+        chunk_index: ec.partial_count as u32,
+        chunk_hash,
+        units,
+        ts,
+        content_cid,
+    }
+    .encode()
+}
+
+fn open_dispute(ec: &mut EscrowCall, reporter: &Pubkey, kind: u8, now: u64) -> Result<()> {
+    require_keys_eq!(*reporter, ec.payer, AssuredError::InvalidReporter);
+    require!(
+        ec.status == Status::Init as u8 || ec.status == Status::Fulfilled as u8,
+        AssuredError::InvalidStatus
+    );
+    require!(
+        within_dispute_window(ec, now),
+        AssuredError::DisputeWindowClosed
+    );
+    if kind == DISPUTE_NO_RESPONSE {
+        require!(
+            no_response_dispute_allowed(ec, now),
+            AssuredError::DisputeTooEarly
+        );
+    }
+    ec.disputed = true;
+    ec.disputed_at_ts = now as i64;
+    ec.dispute_kind = kind;
+    Ok(())
+}
+
+fn hold_dispute_bond(ec: &mut EscrowCall, bond: u64) {
+    ec.dispute_bond = bond;
+    ec.dispute_bond_state = if bond > 0 {
+        BondCustody::Held as u8
+    } else {
+        BondCustody::None as u8
+    };
+}
+
+/// Marks the call delivered. `units_released` keeps counting only units
+/// actually paid, so settle releases whatever partials haven't covered.
+fn record_fulfillment(
+    ec: &mut EscrowCall,
+    response_hash: [u8; 32],
+    ts: u64,
+    provider_sig: &[u8],
+    now: u64,
+) {
+    ec.response_hash = response_hash;
+    ec.delivered_ts = Some(ts);
+    ec.fulfilled_at = now;
+    ec.status = Status::Fulfilled as u8;
+    ec.provider_sig = provider_sig.to_vec();
+}
+
+fn validate_content_cid(content_cid: &[u8]) -> Result<()> {
+    require!(
+        content_cid.len() <= MAX_CONTENT_CID_LEN,
+        AssuredError::ContentCidTooLong
+    );
+    Ok(())
+}
+
+fn record_heartbeat(ec: &mut EscrowCall, ts: u64) -> Result<()> {
+    require!(ec.status == Status::Init as u8, AssuredError::InvalidStatus);
+    ec.last_heartbeat_ts = ts;
+    Ok(())
+}
+
+fn extend_call_sla(ec: &mut EscrowCall, new_sla_ms: u64) -> Result<u64> {
+    require!(ec.status == Status::Init as u8, AssuredError::InvalidStatus);
+    require!(new_sla_ms > ec.sla_ms, AssuredError::SlaNotExtended);
+    let old_sla_ms = ec.sla_ms;
+    ec.sla_ms = new_sla_ms;
+    Ok(old_sla_ms)
+}
+
+fn heartbeat_lapsed(ec: &EscrowCall, now: u64) -> bool {
+    if !ec.heartbeat_required {
+        return false;
+    }
+    let last_seen = ec.last_heartbeat_ts.max(ec.start_ts);
+    now.saturating_sub(last_seen) > ec.heartbeat_interval_s.saturating_mul(2)
+}
+
+/// NO_RESPONSE needs the SLA to have run out, unless a required heartbeat
+/// has already gone quiet for two intervals.
+fn no_response_dispute_allowed(ec: &EscrowCall, now: u64) -> bool {
+    let sla_elapsed = now.saturating_sub(ec.start_ts).saturating_mul(1_000) > ec.sla_ms;
+    sla_elapsed || heartbeat_lapsed(ec, now)
+}
+
+/// Delivered calls can only be disputed until `fulfilled_at +
+/// dispute_window_s`; undelivered calls stay disputable.
+fn within_dispute_window(ec: &EscrowCall, now: u64) -> bool {
+    if ec.status != Status::Fulfilled as u8 {
+        return true;
+    }
+    if ec.hashlock != [0u8; 32] {
+        return false;
+    }
+    now < ec.fulfilled_at.saturating_add(ec.dispute_window_s)
+}
+
+fn quote_amount(ec: &EscrowCall, start: u64, units: u64) -> Result<u64> {
+    let end = start.checked_add(units).ok_or(AssuredError::InvalidUnits)?;
+    require!(end <= ec.total_units, AssuredError::InvalidUnits);
+    Ok(amount_for_units(ec, start, units))
+}
+
+/// `amount * bytes / total_units`, rounded down; the rest is refunded.
+pub fn amount_for_bytes(ec: &EscrowCall, bytes: u64) -> u64 {
+    if ec.total_units == 0 {
+        return 0;
+    }
+    (ec.amount as u128 * bytes as u128 / ec.total_units as u128) as u64
+}
+
+/// Records a data-volume delivery and returns what it earns.
+fn record_data_delivery(ec: &mut EscrowCall, response_size_bytes: u64) -> Result<u64> {
+    require!(
+        response_size_bytes <= ec.total_units,
+        AssuredError::DataOverBudget
+    );
+    ec.response_size_bytes = response_size_bytes;
+    Ok(amount_for_bytes(ec, response_size_bytes))
+}
+
+/// What a release pays on top of `released_so_far`: the remaining units,
+/// or for data-volume calls the reported bytes less what partials paid.
+fn release_payout(ec: &EscrowCall, released_so_far: u64, remaining_units: u64) -> u64 {
+    if ec.billing_mode == BillingMode::DataVolume as u8 {
+        return amount_for_bytes(ec, ec.response_size_bytes).saturating_sub(released_so_far);
+    }
+    amount_for_units(ec, ec.units_released, remaining_units)
+}
+
+fn amount_for_units(ec: &EscrowCall, start: u64, units: u64) -> u64 {
+    if units == 0 || ec.total_units == 0 {
+        return 0;
+    }
+    let base = ec.amount / ec.total_units;
+    let remainder = ec.amount % ec.total_units;
+    let mut total = base.saturating_mul(units);
+    if ec.remainder_policy == RemainderPolicy::LastUnit as u8 {
+        if start.saturating_add(units) >= ec.total_units {
+            total = total.saturating_add(remainder);
+        }
+        return total;
+    }
+    if ec.remainder_policy == RemainderPolicy::LastUnits as u8 {
+        return amount_for_units_v2(ec, start, units);
+    }
+    let remainder_units = remainder;
+    if remainder_units > start {
+        let overlap_start = start;
+        let overlap_end = remainder_units.min(start.saturating_add(units));
+        if overlap_end > overlap_start {
+            total = total.saturating_add(overlap_end - overlap_start);
+        }
+    }
+    total
+}
+
+/// `LastUnits` distribution: units from `total_units - remainder` onwards
+/// carry the extra lamport.
+fn amount_for_units_v2(ec: &EscrowCall, start: u64, units: u64) -> u64 {
+    if units == 0 || ec.total_units == 0 {
+        return 0;
+    }
+    let base = ec.amount / ec.total_units;
+    let first_extra = ec.total_units - ec.amount % ec.total_units;
+    let end = start.saturating_add(units);
+    let extra = end.saturating_sub(first_extra.max(start));
+    base.saturating_mul(units).saturating_add(extra)
+}
+
+/// Rounds the SLA up like `fulfill_deadline`, so with the grace bound the
+/// expiry always falls after the last moment `fulfill` is accepted.
+fn default_expiry_ts(start_ts: u64, sla_ms: u64) -> u64 {
+    start_ts
+        .saturating_add(sla_ms.div_ceil(1_000))
+        .saturating_add(EXPIRY_GRACE_S)
+}
+
+fn default_refund_after_ts(start_ts: u64, sla_ms: u64, dispute_window_s: u64) -> u64 {
+    start_ts
+        .saturating_add(sla_ms.div_ceil(1_000))
+        .saturating_add(dispute_window_s)
+}
+
+/// Legacy bytes a provider signs when fulfilling a call. `fulfill` and
+/// `fulfill_v2` still accept it next to `payload::FulfillPayload` for
+/// providers that have not moved to the canonical encoding.
+pub fn attestation_message(call_id: &str, response_hash: &[u8; 32], ts: u64) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(call_id.len() + 40);
+    msg.extend_from_slice(call_id.as_bytes());
+    msg.extend_from_slice(response_hash);
+    msg.extend_from_slice(&ts.to_le_bytes());
+    msg
+}
+
+/// What `fulfill` accepts a signature over: the canonical payload first,
+/// then the legacy message.
+fn fulfill_messages(call_id: &str, response_hash: &[u8; 32], ts: u64) -> [Vec<u8>; 2] {
+    use payload::SigningPayload;
+    let canonical = payload::FulfillPayload {
+        call_id,
+        response_hash: *response_hash,
+        ts,
+    }
+    .encode();
+    [canonical, attestation_message(call_id, response_hash, ts)]
+}
+
+/// Shared body of `fulfill` and `fulfill_with_data`; `provider_sig` must
+/// cover one of `messages`.
+fn fulfill_call(
+    accounts: &mut Fulfill,
+    response_hash: [u8; 32],
+    ts: u64,
+    provider_sig: Vec<u8>,
+    response_size_bytes: u64,
+    content_cid: Vec<u8>,
+    messages: &[Vec<u8>],
+) -> Result<()> {
+    validate_content_cid(&content_cid)?;
+    let now = Clock::get()?.unix_timestamp as u64;
+    let grace_s = accounts.config.fulfill_grace_s;
+    let ec = &mut accounts.escrow_call;
+    check_fulfill(ec, &accounts.provider.key(), &provider_sig, now, grace_s)?;
+    check_proof_set(ec, accounts.proof_set.as_deref())?;
+    verify_any_attestation(ec, &accounts.instructions, messages, &provider_sig)?;
+    if ec.billing_mode == BillingMode::DataVolume as u8 {
+        let amount_paid = record_data_delivery(ec, response_size_bytes)?;
+        emit!(DataDelivered {
+            call_id: ec.call_id.clone(),
+            response_size_bytes,
+            amount_paid,
+        });
+    }
+    record_fulfillment(ec, response_hash, ts, &provider_sig, now);
+    ec.content_cid = content_cid.clone();
+    emit!(Fulfilled {
+        call_id: ec.call_id.clone(),
+        ts
+    });
+    emit!(TraceSaved {
+        call_id: ec.call_id.clone(),
+        response_hash,
+        provider_sig,
+        content_cid,
+    });
+    Ok(())
+}
+
+/// A call opted into chunked delivery can only be fulfilled once its proof
+/// set is verified, whether by `fulfill` or by the partial that releases
+/// its last unit.
+fn check_proof_set(ec: &EscrowCall, proof_set: Option<&FulfillmentProofSet>) -> Result<()> {
+    if !ec.requires_proofs {
+        return Ok(());
+    }
+    let proof_set = proof_set.ok_or(AssuredError::ProofSetIncomplete)?;
+    require!(proof_set.verified, AssuredError::ProofSetIncomplete);
+    Ok(())
+}
+
+/// Data-volume calls bill the signed byte count, so they cannot take the
+/// legacy `fulfill`.
+fn check_size_unsigned(ec: &EscrowCall) -> Result<()> {
+    require!(
+        ec.billing_mode != BillingMode::DataVolume as u8,
+        AssuredError::ResponseSizeUnsigned
+    );
+    Ok(())
+}
+
+/// Bytes a provider signs for `fulfill_with_data`, binding the byte count.
+pub fn data_attestation_message(
+    call_id: &str,
+    response_hash: &[u8; 32],
+    ts: u64,
+    response_size_bytes: u64,
+    content_cid: &[u8],
+) -> Vec<u8> {
+    use payload::SigningPayload;
+    payload::DataFulfillPayload {
+        call_id,
+        response_hash: *response_hash,
+        ts,
+        response_size_bytes,
+        content_cid,
+    }
+    .encode()
+}
+
+/// Bytes a provider signs when rebutting a dispute.
+pub fn proof_message(call_id: &str, proof_hash: &[u8; 32]) -> Vec<u8> {
+    use payload::SigningPayload;
+    payload::ProofPayload {
+        call_id,
+        proof_hash: *proof_hash,
+    }
+    .encode()
+}
+
+/// Bytes a provider signs to abandon the rest of a call.
+pub fn abort_message(call_id: &str, start_ts: u64) -> Vec<u8> {
+    use payload::SigningPayload;
+    payload::AbortPayload { call_id, start_ts }.encode()
+}
+
+/// Bytes the payer signs (`reporter_sig`) when raising a dispute.
+pub fn dispute_message(call_id: &str, kind: u8, reason_hash: &[u8; 32]) -> Vec<u8> {
+    use payload::SigningPayload;
+    payload::DisputePayload {
+        call_id,
+        kind,
+        reason_hash: *reason_hash,
+    }
+    .encode()
+}
+
+/// Returns `(refund_to_payer, already_released)`. Released units were paid
+/// as they streamed, so nothing more goes to the provider.
+fn abort_split(ec: &EscrowCall) -> Result<(u64, u64)> {
+    require!(ec.status == Status::Init as u8, AssuredError::InvalidStatus);
+    let released_so_far = amount_for_units(ec, 0, ec.units_released);
+    Ok((ec.amount.saturating_sub(released_so_far), released_so_far))
+}
+
+/// The attestation key a new escrow or channel records: the provider's
+/// registered one, or zero to fall back to the provider's own key.
+fn registered_attest_key(keys: Option<&ProviderKeys>) -> [u8; 32] {
+    keys.map_or([0u8; 32], |k| k.attest_key)
+}
+
+fn proof_signing_key(ec: &EscrowCall) -> [u8; 32] {
+    if ec.provider_attest_key != [0u8; 32] {
+        ec.provider_attest_key
+    } else {
+        ec.provider.to_bytes()
+    }
+}
+
+/// The seed prefix, call id and rent payer recorded by an orphan of kind
+/// `pda_type`.
+fn orphan_origin(pda_type: u8, orphan: &AccountInfo) -> Result<(&'static [u8], String, Pubkey)> {
+    let data = orphan.try_borrow_data()?;
+    match pda_type {
+        ORPHAN_PROOF_SET => {
+            let proof_set = FulfillmentProofSet::try_deserialize(&mut &data[..])?;
+            Ok((b"proofs", proof_set.call_id, proof_set.rent_payer))
+        }
+        ORPHAN_EVIDENCE => {
+            let evidence = Evidence::try_deserialize(&mut &data[..])?;
+            Ok((b"evidence", evidence.call_id, evidence.rent_payer))
+        }
+        _ => err!(AssuredError::InvalidOrphan),
+    }
+}
+
+/// Drains an orphan into its rent payer once `parent`, the call it was
+/// derived from, has been closed. Returns the lamports recovered.
+fn reclaim_orphan<'info>(
+    pda_type: u8,
+    orphan: &AccountInfo<'info>,
+    parent: &AccountInfo<'info>,
+    rent_payer: &AccountInfo<'info>,
+) -> Result<u64> {
+    require_keys_eq!(*orphan.owner, crate::ID, AssuredError::InvalidOrphan);
+    let (prefix, call_id, funder) = orphan_origin(pda_type, orphan)?;
+    let (expected, _) = Pubkey::find_program_address(&[prefix, call_id.as_bytes()], &crate::ID);
+    require_keys_eq!(orphan.key(), expected, AssuredError::InvalidOrphan);
+    let (call, _) = Pubkey::find_program_address(&[b"call", call_id.as_bytes()], &crate::ID);
+    require_keys_eq!(parent.key(), call, AssuredError::InvalidOrphan);
+    require!(parent.lamports() == 0, AssuredError::ParentCallOpen);
+    require_keys_eq!(rent_payer.key(), funder, AssuredError::InvalidRentRecipient);
+    let amount = orphan.lamports();
+    pay_out(amount, orphan, rent_payer)?;
+    orphan.try_borrow_mut_data()?.fill(0);
+    Ok(amount)
+}
+
+/// Terms of a new call that `check_init_gates` reads.
+pub struct InitTerms {
+    pub amount: u64,
+    pub sla_ms: u64,
+    pub dispute_window_s: u64,
+    pub total_units: u64,
+    pub billing_mode: u8,
+    pub min_reputation_bps: Option<u16>,
+    pub now: i64,
+}
+
+/// The gates every instruction that opens an escrow runs before taking
+/// the payer's funds: the config's limits, the billing mode, the service's
+/// standing (compliance, suspension, pause, strict pricing, minimum
+/// reputation and performance bond), and the payer's approvals, the
+/// blacklist and the service owner's allowlist for `provider`.
+fn check_init_gates(
+    config: &Config,
+    service: &reputation::Service,
+    provider: &Pubkey,
+    approval: Option<&PayerProviderApproval>,
+    blacklist: &AccountInfo,
+    allowlist: &AccountInfo,
+    terms: &InitTerms,
+) -> Result<()> {
+    config.validate_init(terms.sla_ms, terms.dispute_window_s)?;
+    require!(
+        terms.billing_mode == BillingMode::PerUnit as u8
+            || terms.billing_mode == BillingMode::DataVolume as u8,
+        AssuredError::InvalidBillingMode
+    );
+    require!(
+        service.meets_compliance(MIN_COMPLIANCE_BPS),
+        AssuredError::ProviderNotCompliant
+    );
+    require!(
+        !reputation::is_suspended(service, terms.now),
+        AssuredError::ServiceSuspended
+    );
+    check_accepting_calls(service)?;
+    check_price(
+        config.strict_pricing,
+        service,
+        terms.amount,
+        terms.total_units,
+    )?;
+    check_min_reputation(service, provider, allowlist, terms.min_reputation_bps)?;
+    config.check_bond(service.performance_bond_balance)?;
+    if let Some(approval) = approval {
+        require!(
+            approval.is_approved(provider),
+            AssuredError::ProviderNotApproved
+        );
+    }
+    check_blacklist(blacklist, provider)?;
+    check_allowlist(allowlist, provider)
+}
+
+/// Rejects blacklisted providers. Every init path must pass the PDA, so a
+/// payer cannot skip the check by leaving it out; it only passes unread
+/// before the first `add_to_blacklist` creates it.
+fn check_blacklist(blacklist: &AccountInfo, provider: &Pubkey) -> Result<()> {
+    if blacklist.owner != &crate::ID || blacklist.data_is_empty() {
+        return Ok(());
+    }
+    let blacklist = ProviderBlacklist::try_deserialize(&mut &blacklist.try_borrow_data()?[..])?;
+    require!(
+        !blacklist.contains(provider),
+        AssuredError::ProviderBlacklisted
+    );
+    Ok(())
+}
+
+/// Reads a batch leg's service account after checking it is the
+/// reputation PDA for `service_id`.
+fn load_leg_service(info: &AccountInfo, service_id: &str) -> Result<Box<reputation::Service>> {
+    let (expected, _) =
+        Pubkey::find_program_address(&[b"svc", service_id.as_bytes()], &reputation::ID);
+    require_keys_eq!(info.key(), expected, AssuredError::BatchAccountsMismatch);
+    require_keys_eq!(
+        *info.owner,
+        reputation::ID,
+        AssuredError::BatchAccountsMismatch
+    );
+    let service = reputation::Service::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    Ok(Box::new(service))
+}
+
+/// Rejects providers an enabled allowlist leaves out. A service owner who
+/// never created one runs public services.
+fn check_allowlist(allowlist: &AccountInfo, provider: &Pubkey) -> Result<()> {
+    if allowlist.owner != &crate::ID || allowlist.data_is_empty() {
+        return Ok(());
+    }
+    let allowlist = ProviderAllowlist::try_deserialize(&mut &allowlist.try_borrow_data()?[..])?;
+    require!(
+        allowlist.admits(provider),
+        AssuredError::ProviderNotAllowlisted
+    );
+    Ok(())
+}
+
+/// Whether the service owner's allowlist exists, is enabled and lists
+/// `provider`.
+fn allowlist_names(allowlist: &AccountInfo, provider: &Pubkey) -> Result<bool> {
+    if allowlist.owner != &crate::ID || allowlist.data_is_empty() {
+        return Ok(false);
+    }
+    let allowlist = ProviderAllowlist::try_deserialize(&mut &allowlist.try_borrow_data()?[..])?;
+    Ok(allowlist.enabled && allowlist.providers.contains(provider))
+}
+
+/// Evidence is accepted from the party named by `role`, while a dispute
+/// is open and before any ruling.
+fn check_evidence_submitter(ec: &EscrowCall, role: u8, submitter: &Pubkey) -> Result<()> {
+    let party = match role {
+        r if r == EvidenceRole::Payer as u8 => ec.payer,
+        r if r == EvidenceRole::Provider as u8 => ec.provider,
+        _ => return err!(AssuredError::InvalidEvidenceRole),
+    };
+    require_keys_eq!(*submitter, party, AssuredError::Unauthorized);
+    require!(
+        ec.status == Status::Init as u8 || ec.status == Status::Fulfilled as u8,
+        AssuredError::InvalidStatus
+    );
+    require!(ec.disputed, AssuredError::NotDisputed);
+    require!(ec.ruling.is_none(), AssuredError::AlreadyRuled);
+    Ok(())
+}
+
+/// Rebuttals are accepted while a dispute is open and before any ruling;
+/// a later submission replaces the earlier one.
+fn record_provider_proof(ec: &mut EscrowCall, caller: Pubkey, proof_hash: [u8; 32]) -> Result<()> {
+    require_keys_eq!(caller, ec.provider, AssuredError::InvalidProvider);
+    require!(
+        ec.status == Status::Init as u8 || ec.status == Status::Fulfilled as u8,
+        AssuredError::InvalidStatus
+    );
+    require!(ec.disputed, AssuredError::NotDisputed);
+    require!(ec.ruling.is_none(), AssuredError::AlreadyRuled);
+    ec.provider_proof_hash = proof_hash;
+    Ok(())
+}
+
+/// Checks that `ix` is an Ed25519 precompile instruction verifying exactly
+/// one signature over `message` by `attest_key`. The precompile itself has
+/// already rejected the transaction if the signature is invalid.
+fn verify_ed25519_ix(
+    ix: &Instruction,
+    attest_key: &[u8; 32],
+    message: &[u8],
+    sig: &[u8],
+) -> Result<()> {
+    require_keys_eq!(
+        ix.program_id,
+        solana_sdk_ids::ed25519_program::ID,
+        AssuredError::InvalidAttestation
+    );
+    let data = &ix.data;
+    require!(
+        data.len() >= 16 && data[0] == 1,
+        AssuredError::InvalidAttestation
+    );
+    let field = |i: usize| u16::from_le_bytes([data[2 + 2 * i], data[3 + 2 * i]]);
+    let slice = |offset: u16, len: usize| {
+        data.get(offset as usize..offset as usize + len)
+            .ok_or(AssuredError::InvalidAttestation)
+    };
+    // Every offset must point into this instruction's own data.
+    require!(
+        field(1) == u16::MAX && field(3) == u16::MAX && field(6) == u16::MAX,
+        AssuredError::InvalidAttestation
+    );
+    require!(
+        slice(field(2), 32)? == attest_key,
+        AssuredError::InvalidAttestation
+    );
+    require!(
+        field(5) as usize == message.len() && slice(field(4), message.len())? == message,
+        AssuredError::InvalidAttestation
+    );
+    require!(
+        slice(field(0), 64)? == sig,
+        AssuredError::InvalidAttestation
+    );
+    Ok(())
+}
+
+/// Checks a secp256k1 precompile instruction at transaction index
+/// `ix_index` verifies exactly `sig` (r || s || recovery id) over `message`
+/// by `eth_address`. The runtime has already checked the signature itself.
+fn verify_secp256k1_ix(
+    ix: &Instruction,
+    ix_index: u16,
+    eth_address: &[u8; 20],
+    message: &[u8],
+    sig: &[u8; 65],
+) -> Result<()> {
+    require_keys_eq!(
+        ix.program_id,
+        solana_sdk_ids::secp256k1_program::ID,
+        AssuredError::InvalidAttestation
+    );
+    let data = &ix.data;
+    require!(
+        data.len() >= 12 && data[0] == 1,
+        AssuredError::InvalidAttestation
+    );
+    let offset = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let slice = |offset: u16, len: usize| {
+        data.get(offset as usize..offset as usize + len)
+            .ok_or(AssuredError::InvalidAttestation)
+    };
+    // Every offset must point into the precompile instruction's own data.
+    require!(
+        [data[3], data[6], data[11]]
+            .iter()
+            .all(|index| *index as u16 == ix_index),
+        AssuredError::InvalidAttestation
+    );
+    require!(
+        slice(offset(4), 20)? == eth_address,
+        AssuredError::InvalidAttestation
+    );
+    require!(
+        offset(9) as usize == message.len() && slice(offset(7), message.len())? == message,
+        AssuredError::InvalidAttestation
+    );
+    require!(
+        slice(offset(1), 65)? == sig,
+        AssuredError::InvalidAttestation
+    );
+    Ok(())
+}
+
+/// Pays out the payer's held dispute bond: forfeited to the provider when
+/// an arbitrator ruled the dispute invalid, returned otherwise. Returns
+/// whether it was forfeited.
+fn settle_dispute_bond<'info>(
+    ec: &mut EscrowCall,
+    escrow: &AccountInfo<'info>,
+    provider: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+) -> Result<bool> {
+    require!(
+        ec.dispute_bond_state == BondCustody::Held as u8,
+        AssuredError::DisputeBondNotHeld
+    );
+    let ruling = ec.ruling.ok_or(AssuredError::SettlementPending)?;
+    let forfeited = ruling == Ruling::Release as u8;
+    let destination = if forfeited { provider } else { payer };
+    pay_out(ec.dispute_bond, escrow, destination)?;
+    ec.dispute_bond_state = if forfeited {
+        BondCustody::Forfeited as u8
+    } else {
+        BondCustody::Returned as u8
+    };
+    Ok(forfeited)
+}
+
+fn set_disputed_units(ec: &mut EscrowCall, disputed_units: u64) -> Result<()> {
+    if disputed_units == 0 {
+        return Ok(());
+    }
+    require!(
+        ec.ruling == Some(Ruling::Refund as u8),
+        AssuredError::InvalidRuling
+    );
+    require!(
+        disputed_units <= ec.units_released,
+        AssuredError::InvalidUnits
+    );
+    ec.disputed_units = disputed_units;
+    Ok(())
+}
+
+/// Value of the disputed released units, priced like the original releases.
+fn clawback_request(ec: &EscrowCall) -> u64 {
+    let units = ec.disputed_units.min(ec.units_released);
+    amount_for_units(ec, 0, units)
+}
+
+fn record_ruling(ec: &mut EscrowCall, ruling: u8, now: i64) -> Result<()> {
+    require!(
+        ec.status == Status::Init as u8 || ec.status == Status::Fulfilled as u8,
+        AssuredError::InvalidStatus
+    );
+    require!(ec.disputed, AssuredError::NotDisputed);
+    require!(ec.ruling.is_none(), AssuredError::AlreadyRuled);
+    require!(ruling <= Ruling::Refund as u8, AssuredError::InvalidRuling);
+    ec.ruling = Some(ruling);
+    ec.appeal_deadline_ts = now.saturating_add(APPEAL_WINDOW_S);
+    Ok(())
+}
+
+/// A committee needs a strict majority threshold so only one verdict can
+/// reach it. No arbiters means the config admin arbitrates.
+fn validate_arbiters(arbiters: &[Pubkey], threshold: u8) -> Result<()> {
+    if arbiters.is_empty() {
+        require!(threshold == 0, AssuredError::InvalidArbiterCommittee);
+        return Ok(());
+    }
+    require!(
+        arbiters.len() <= MAX_ARBITERS,
+        AssuredError::InvalidArbiterCommittee
+    );
+    for (i, arbiter) in arbiters.iter().enumerate() {
+        require!(
+            *arbiter != Pubkey::default() && !arbiters[..i].contains(arbiter),
+            AssuredError::InvalidArbiterCommittee
+        );
+    }
+    let threshold = threshold as usize;
+    require!(
+        threshold * 2 > arbiters.len() && threshold <= arbiters.len(),
+        AssuredError::InvalidArbiterCommittee
+    );
+    Ok(())
+}
+
+fn committee_deadline(ec: &EscrowCall) -> i64 {
+    ec.disputed_at_ts.saturating_add(COMMITTEE_VOTE_WINDOW_S)
+}
+
+fn check_committee_dispute(ec: &EscrowCall) -> Result<()> {
+    require!(!ec.arbiters.is_empty(), AssuredError::NoArbiterCommittee);
+    require!(
+        ec.status == Status::Init as u8 || ec.status == Status::Fulfilled as u8,
+        AssuredError::InvalidStatus
+    );
+    require!(ec.disputed, AssuredError::NotDisputed);
+    require!(ec.ruling.is_none(), AssuredError::AlreadyRuled);
+    Ok(())
+}
+
+/// Returns the arbiter's vote slot.
+fn check_arbiter_vote(ec: &EscrowCall, arbiter: &Pubkey, now: i64) -> Result<usize> {
+    check_committee_dispute(ec)?;
+    let slot = ec
+        .arbiters
+        .iter()
+        .position(|a| a == arbiter)
+        .ok_or(AssuredError::NotArbiter)?;
+    require!(now < committee_deadline(ec), AssuredError::VotingClosed);
+    Ok(slot)
+}
+
+/// The committee's ruling and whether it came from a quorum rather than
+/// the timeout refund.
+fn committee_verdict(ec: &EscrowCall, votes: Option<&Votes>, now: i64) -> Result<(u8, bool)> {
+    check_committee_dispute(ec)?;
+    if let Some(verdict) = votes.and_then(|v| v.quorum(ec)) {
+        return Ok((verdict, true));
+    }
+    require!(
+        now >= committee_deadline(ec),
+        AssuredError::QuorumNotReached
+    );
+    Ok((Ruling::Refund as u8, false))
+}
+
+/// The new evidence hash overwrites `response_hash`.
+fn reopen_call_dispute(
+    ec: &mut EscrowCall,
+    caller: Pubkey,
+    new_reason_hash: [u8; 32],
+    now: i64,
+) -> Result<()> {
+    require_keys_eq!(caller, ec.payer, AssuredError::InvalidReporter);
+    require!(
+        ec.status == Status::Fulfilled as u8,
+        AssuredError::InvalidStatus
+    );
+    require!(!ec.disputed, AssuredError::AlreadyDisputed);
+    require!(
+        within_dispute_window(ec, now.max(0) as u64),
+        AssuredError::DisputeWindowClosed
+    );
+    ec.disputed = true;
+    ec.response_hash = new_reason_hash;
+    ec.disputed_at_ts = now;
+    Ok(())
+}
+
+fn file_appeal(ec: &mut EscrowCall, appellant: Pubkey, now: i64) -> Result<()> {
+    let ruling = ec.ruling.ok_or(AssuredError::NoAppealPending)?;
+    require!(
+        ec.appeal_filed_by.is_none(),
+        AssuredError::AppealAlreadyFiled
+    );
+    require!(
+        now < ec.appeal_deadline_ts,
+        AssuredError::AppealWindowClosed
+    );
+    let losing_party = if ruling == Ruling::Release as u8 {
+        ec.payer
+    } else {
+        ec.provider
+    };
+    require_keys_eq!(appellant, losing_party, AssuredError::InvalidAppellant);
+    ec.appeal_filed_by = Some(appellant);
+    Ok(())
+}
+
+fn resolve_call_appeal(ec: &mut EscrowCall, ruling: u8) -> Result<()> {
+    require!(
+        ec.appeal_filed_by.is_some() && !ec.appeal_resolved,
+        AssuredError::NoAppealPending
+    );
+    require!(ruling <= Ruling::Refund as u8, AssuredError::InvalidRuling);
+    ec.ruling = Some(ruling);
+    ec.appeal_resolved = true;
+    Ok(())
+}
+
+/// A ruling becomes final once its appeal is resolved, or once the appeal
+/// window closes with no appeal filed.
+fn ruling_outcome(ec: &EscrowCall, ruling: u8, now: u64) -> SettlementOutcome {
+    let is_final = if ec.appeal_filed_by.is_some() {
+        ec.appeal_resolved
+    } else {
+        now as i64 >= ec.appeal_deadline_ts
+    };
+    if !is_final {
+        SettlementOutcome::Pending
+    } else if ruling == Ruling::Release as u8 {
+        SettlementOutcome::Release
+    } else {
+        SettlementOutcome::Refund
+    }
+}
+
+/// Judged on the escrow's own clock, in milliseconds like `sla_ms`; the
+/// provider-supplied `delivered_ts` plays no part.
+fn delivered_within_sla(ec: &EscrowCall) -> bool {
+    delivery_latency_ms(ec).is_some_and(|latency_ms| latency_ms <= ec.sla_ms)
+}
+
+fn released_event(ec: &EscrowCall, amount_to_provider: u64) -> Released {
+    Released {
+        call_id: ec.call_id.clone(),
+        delivered_latency_ms: delivery_latency_ms(ec).unwrap_or(0),
+        amount_to_provider,
+        sla_met: delivered_within_sla(ec),
+    }
+}
+
+fn late_penalty_event(ec: &EscrowCall, penalty_bps: u16, penalty: u64) -> LatePenaltyApplied {
+    LatePenaltyApplied {
+        call_id: ec.call_id.clone(),
+        penalty_bps,
+        penalty,
+    }
+}
+
+fn partial_released_event(ec: &EscrowCall, units: u64) -> PartialReleased {
+    PartialReleased {
+        call_id: ec.call_id.clone(),
+        units,
+        total_units: ec.total_units,
+        units_released: ec.units_released,
+    }
+}
+
+fn refunded_event(ec: &EscrowCall, refund_amount: u64, amount_already_released: u64) -> Refunded {
+    let reason = if ec.disputed {
+        RefundReason::Dispute
+    } else if ec.delivered_ts.is_none() {
+        RefundReason::NeverDelivered
+    } else {
+        RefundReason::SlaMiss
+    };
+    Refunded {
+        call_id: ec.call_id.clone(),
+        refund_amount,
+        amount_already_released,
+        disputed: ec.disputed,
+        reason: reason as u8,
+    }
+}
+
+fn check_expiry(ec: &EscrowCall, now: u64) -> Result<()> {
+    require!(ec.status == Status::Init as u8, AssuredError::InvalidStatus);
+    // A pending dispute is settled by its ruling, bond and all.
+    require!(!ec.disputed, AssuredError::AlreadyDisputed);
+    require!(now >= ec.expiry_ts, AssuredError::NotExpired);
+    Ok(())
+}
+
+fn check_settle_timeout(ec: &EscrowCall, now: u64) -> Result<()> {
+    require!(ec.status == Status::Init as u8, AssuredError::InvalidStatus);
+    require!(ec.delivered_ts.is_none(), AssuredError::InvalidStatus);
+    require!(!ec.disputed, AssuredError::AlreadyDisputed);
+    let deadline = ec.refund_after_ts.saturating_add(SETTLE_GRACE_PERIOD_S);
+    require!(now > deadline, AssuredError::SettleGraceNotElapsed);
+    Ok(())
+}
+
+/// Marks a settled call finalized and returns its outcome status, once
+/// `FINALIZE_DELAY_S` has passed since it settled.
+fn finalize_call(ec: &mut EscrowCall, now: i64) -> Result<u8> {
+    let outcome = ec.status;
+    require!(
+        outcome == Status::Released as u8 || outcome == Status::Refunded as u8,
+        AssuredError::InvalidStatus
+    );
+    require!(
+        now >= ec.settled_at.saturating_add(FINALIZE_DELAY_S),
+        AssuredError::FinalizeTooEarly
+    );
+    ec.status = Status::Finalized as u8;
+    ec.finalized_at = now;
+    Ok(outcome)
+}
+
+/// Releases are permissionless: `evaluate_settlement` only returns one once
+/// the dispute window has passed undisputed, or a hashlock or ruling
+/// decided it, so anyone may crank the provider's payout. Refunds,
+/// including the payer's early opt-out before the window ends, need the
+/// payer's signature.
+fn check_settle_authority(outcome: &SettlementOutcome, payer_signed: bool) -> Result<()> {
+    require!(
+        *outcome != SettlementOutcome::Refund || payer_signed,
+        AssuredError::PayerSignatureRequired
+    );
+    Ok(())
+}
+
+fn evaluate_settlement(ec: &EscrowCall, now: u64) -> SettlementOutcome {
+    if let Some(ruling) = ec.ruling {
+        return ruling_outcome(ec, ruling, now);
+    }
+    // A bonded dispute waits for a ruling to decide who keeps the bond.
+    if ec.dispute_bond_state == BondCustody::Held as u8 {
+        return SettlementOutcome::Pending;
+    }
+    unruled_outcome(ec, now)
+}
+
+fn unruled_outcome(ec: &EscrowCall, now: u64) -> SettlementOutcome {
+    if ec.disputed && !ec.arbiters.is_empty() {
+        // Committee disputes wait for `finalize_resolution`.
+        return SettlementOutcome::Pending;
+    }
+    if ec.hashlock != [0u8; 32] && ec.delivered_ts.is_some() {
+        return SettlementOutcome::Release;
+    }
+    if ec.delivered_ts.is_none() && !ec.disputed && now < ec.refund_after_ts {
+        return SettlementOutcome::Pending;
+    }
+    let dispute_window_elapsed =
+        ec.delivered_ts.is_none() || now.saturating_sub(ec.fulfilled_at) >= ec.dispute_window_s;
+    if !ec.disputed && delivered_within_sla(ec) && dispute_window_elapsed {
+        SettlementOutcome::Release
+    } else if !ec.disputed && !ec.sla_tiers.is_empty() && dispute_window_elapsed {
+        SettlementOutcome::from_payout_bps(tier_payout_bps(ec))
+    } else if !ec.disputed
+        && ec.delivered_ts.is_some()
+        && dispute_window_elapsed
+        && ec.late_penalty_bps > 0
+    {
+        SettlementOutcome::ReleaseWithPenalty {
+            bps: ec.late_penalty_bps,
+        }
+    } else {
+        SettlementOutcome::Refund
+    }
+}
+
+/// Voucher reduction off `amount`, rounded down so the payer never
+/// escrows less than the discounted price.
+fn voucher_discount(amount: u64, bps: u16) -> u64 {
+    (amount as u128 * bps as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+/// Part of a late payout withheld from the provider, rounded down and
+/// never more than the payout itself.
+fn late_penalty(payout: u64, bps: u16) -> u64 {
+    let bps = (bps as u64).min(BPS_DENOMINATOR);
+    (payout as u128 * bps as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_call() -> EscrowCall {
+        EscrowCall {
+            version: ESCROW_CALL_VERSION,
+            call_id: "call-1".to_string(),
+            payer: Pubkey::default(),
+            provider: Pubkey::new_unique(),
+            state: CallState {
+                service_id: "svc".to_string(),
+                amount: 1_000_000,
+                start_ts: 0,
+                sla_ms: 2_000_000,
+                dispute_window_s: 10,
+                status: Status::Fulfilled as u8,
+                delivered_ts: Some(1_000),
+                response_hash: [0u8; 32],
+                disputed: false,
+                total_units: 1,
+                units_released: 1,
+                provider_sig: vec![],
+                last_heartbeat_ts: 0,
+                refund_after_ts: default_refund_after_ts(0, 2_000_000, 10),
+                heartbeat_interval_s: 0,
+                heartbeat_required: false,
+                appeal_deadline_ts: 0,
+                appeal_filed_by: None,
+                ruling: None,
+                appeal_resolved: false,
+                provider_attest_key: [0; 32],
+                requires_proofs: false,
+                remainder_policy: RemainderPolicy::FirstUnits as u8,
+                rent_recipient: Pubkey::default(),
+                disputed_units: 0,
+                max_partials: DEFAULT_MAX_PARTIALS,
+                partial_count: 0,
+                disputed_at_ts: 0,
+                provider_proof_hash: [0; 32],
+                hashlock: [0; 32],
+                payout_splits: Vec::new(),
+                referrer: None,
+                referral_fee_bps: 0,
+                provider_eth_address: None,
+                dispute_bond: 0,
+                finalized_at: 0,
+                outstanding_tracked: false,
+                billing_mode: 0,
+                response_size_bytes: 0,
+                sig_mode: 0,
+                expiry_ts: 0,
+                content_cid: Vec::new(),
+                late_penalty_bps: 0,
+                sla_tiers: Vec::new(),
+                last_chunk_nonce: [0; 8],
+                redemption_code: None,
+                arbiters: Vec::new(),
+                arbiter_threshold: 0,
+                dispute_kind: 0,
+                dispute_bond_state: 0,
+                fulfilled_at: 1_000,
+                settled_at: 0,
+                last_chunk_ts: 0,
+            },
+        }
+    }
+
+    fn streaming_call(total_units: u64, amount: u64) -> EscrowCall {
+        EscrowCall {
+            version: ESCROW_CALL_VERSION,
+            call_id: "stream-call".to_string(),
+            payer: Pubkey::default(),
+            provider: Pubkey::new_unique(),
+            state: CallState {
+                service_id: "svc".to_string(),
+                amount,
+                start_ts: 0,
+                sla_ms: 2_000,
+                dispute_window_s: 10,
+                status: Status::Init as u8,
+                delivered_ts: None,
+                response_hash: [0u8; 32],
+                disputed: false,
+                total_units,
+                units_released: 0,
+                provider_sig: vec![],
+                last_heartbeat_ts: 0,
+                refund_after_ts: default_refund_after_ts(0, 2_000, 10),
+                heartbeat_interval_s: 0,
+                heartbeat_required: false,
+                appeal_deadline_ts: 0,
+                appeal_filed_by: None,
+                ruling: None,
+                appeal_resolved: false,
+                provider_attest_key: [0; 32],
+                requires_proofs: false,
+                remainder_policy: RemainderPolicy::FirstUnits as u8,
+                rent_recipient: Pubkey::default(),
+                disputed_units: 0,
+                max_partials: DEFAULT_MAX_PARTIALS,
+                partial_count: 0,
+                disputed_at_ts: 0,
+                provider_proof_hash: [0; 32],
+                hashlock: [0; 32],
+                payout_splits: Vec::new(),
+                referrer: None,
+                referral_fee_bps: 0,
+                provider_eth_address: None,
+                dispute_bond: 0,
+                finalized_at: 0,
+                outstanding_tracked: false,
+                billing_mode: 0,
+                response_size_bytes: 0,
+                sig_mode: 0,
+                expiry_ts: 0,
+                content_cid: Vec::new(),
+                late_penalty_bps: 0,
+                sla_tiers: Vec::new(),
+                last_chunk_nonce: [0; 8],
+                redemption_code: None,
+                arbiters: Vec::new(),
+                arbiter_threshold: 0,
+                dispute_kind: 0,
+                dispute_bond_state: 0,
+                fulfilled_at: 0,
+                settled_at: 0,
+                last_chunk_ts: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn amount_for_units_distributes_evenly() {
+        let mut ec = base_call();
+        ec.amount = 100;
+        ec.total_units = 3;
+        ec.units_released = 0;
+        assert_eq!(amount_for_units(&ec, 0, 1), 34);
+        assert_eq!(amount_for_units(&ec, 1, 1), 33);
+        assert_eq!(amount_for_units(&ec, 2, 1), 33);
+        assert_eq!(amount_for_units(&ec, 0, 3), 100);
+    }
+
+    #[test]
+    fn last_unit_policy_concentrates_remainder() {
+        let mut ec = base_call();
+        ec.amount = 100;
+        ec.total_units = 3;
+        ec.remainder_policy = RemainderPolicy::LastUnit as u8;
+        assert_eq!(amount_for_units(&ec, 0, 1), 33);
+        assert_eq!(amount_for_units(&ec, 1, 1), 33);
+        assert_eq!(amount_for_units(&ec, 2, 1), 34);
+        assert_eq!(amount_for_units(&ec, 1, 2), 67);
+    }
+
+    #[test]
+    fn last_units_policy_mirrors_first_units() {
+        let mut ec = base_call();
+        ec.amount = 100;
+        ec.total_units = 3;
+        let first: Vec<u64> = (0..3).map(|i| amount_for_units(&ec, i, 1)).collect();
+        ec.remainder_policy = RemainderPolicy::LastUnits as u8;
+        let last: Vec<u64> = (0..3).map(|i| amount_for_units(&ec, i, 1)).collect();
+        assert_eq!(first, vec![34, 33, 33]);
+        assert_eq!(last, vec![33, 33, 34]);
+
+        let mut ec = streaming_call(7, 1_003);
+        ec.remainder_policy = RemainderPolicy::LastUnits as u8;
+        // 1_003 = 7 * 143 + 2: units 5 and 6 carry the extra lamport.
+        assert_eq!(amount_for_units_v2(&ec, 0, 5), 715);
+        assert_eq!(amount_for_units_v2(&ec, 4, 2), 287);
+        assert_eq!(amount_for_units_v2(&ec, 5, 2), 288);
+        assert_eq!(amount_for_units_v2(&ec, 3, 0), 0);
+        ec.remainder_policy = RemainderPolicy::FirstUnits as u8;
+        assert_eq!(amount_for_units(&ec, 0, 5), 717);
+        assert_eq!(amount_for_units(&ec, 5, 2), 286);
+    }
+
+    #[test]
+    fn every_remainder_policy_sums_to_amount() {
+        for policy in [
+            RemainderPolicy::FirstUnits,
+            RemainderPolicy::LastUnit,
+            RemainderPolicy::LastUnits,
+        ] {
+            let mut ec = streaming_call(7, 1_003);
+            ec.remainder_policy = policy as u8;
+            let per_unit: u64 = (0..7).map(|i| amount_for_units(&ec, i, 1)).sum();
+            assert_eq!(per_unit, 1_003);
+            assert_eq!(
+                amount_for_units(&ec, 0, 4) + amount_for_units(&ec, 4, 3),
+                1_003
+            );
+        }
+    }
+
+    #[test]
+    fn partial_releases_capped_but_final_delivery_allowed() {
+        let mut ec = streaming_call(10, 1_000);
+        ec.max_partials = 3;
+        for i in 1..=3 {
+            apply_partial_release(&mut ec, [i as u8; 32], 1, i, &[], &[], i).unwrap();
+        }
+        let err = apply_partial_release(&mut ec, [4u8; 32], 1, 4, &[], &[], 4)
+            .err()
+            .unwrap();
+        assert_eq!(err, AssuredError::PartialLimitReached.into());
+        assert_eq!(ec.units_released, 3);
+
+        let result = apply_partial_release(&mut ec, [5u8; 32], 7, 5, &[], &[], 5).unwrap();
+        assert_eq!(result.payout, amount_for_units(&ec, 3, 7));
+        assert_eq!(ec.status, Status::Fulfilled as u8);
+    }
+
+    #[test]
+    fn quote_units_matches_internal_split() {
+        let ec = streaming_call(7, 1_000);
+        for (start, units) in [(0, 1), (0, 7), (2, 3), (5, 2), (6, 1), (3, 0)] {
+            assert_eq!(
+                quote_amount(&ec, start, units).unwrap(),
+                amount_for_units(&ec, start, units)
+            );
+        }
+        assert!(quote_amount(&ec, 6, 2).is_err());
+        assert!(quote_amount(&ec, u64::MAX, 1).is_err());
+    }
+
+    #[test]
+    fn partial_release_updates_units_and_flags_trace() {
+        let mut ec = streaming_call(3, 90);
+        let first =
+            apply_partial_release(&mut ec, [1u8; 32], 1, 1_000, b"sig1", &[], 1_000).unwrap();
+        assert_eq!(ec.units_released, 1);
+        assert_eq!(ec.status, Status::Init as u8);
+        assert_eq!(first.payout, 30);
+        assert!(!first.emit_trace);
+        assert_eq!(ec.provider_sig, b"sig1".to_vec());
+
+        let second =
+            apply_partial_release(&mut ec, [2u8; 32], 2, 2_000, b"sig2", &[], 2_000).unwrap();
+        assert_eq!(ec.units_released, 3);
+        assert_eq!(ec.status, Status::Fulfilled as u8);
+        assert_eq!(ec.delivered_ts, Some(2_000));
+        assert_eq!(second.payout, 60);
+        assert!(second.emit_trace);
+        assert_eq!(ec.provider_sig, b"sig2".to_vec());
+    }
+
+    #[test]
+    fn partial_release_rejects_invalid_units() {
+        let mut ec = streaming_call(2, 50);
+        assert!(apply_partial_release(&mut ec, [1u8; 32], 0, 1_000, b"sig", &[], 1_000).is_err());
+        assert!(apply_partial_release(&mut ec, [1u8; 32], 3, 1_000, b"sig", &[], 1_000).is_err());
+    }
+
+    #[test]
+    fn replayed_chunk_nonce_is_rejected() {
+        let mut ec = streaming_call(4, 1_000);
+        apply_partial_release(&mut ec, [1u8; 32], 1, 10, b"sig-1", &[], 10).unwrap();
+        assert_eq!(ec.last_chunk_nonce, chunk_nonce(&[1u8; 32], 1, &[]));
+        assert_eq!(ec.last_chunk_ts, 10);
+
+        // The very same submission again, and the same chunk re-signed.
+        for sig in [&b"sig-1"[..], b"sig-1:other"] {
+            let err = apply_partial_release(&mut ec, [1u8; 32], 1, 10, sig, &[], 10)
+                .err()
+                .unwrap();
+            assert_eq!(err, AssuredError::DuplicateChunk.into());
+        }
+        apply_partial_release(&mut ec, [2u8; 32], 1, 11, b"sig-2", &[], 11).unwrap();
+        // Replaying the first chunk after the second is refused as well.
+        let err = apply_partial_release(&mut ec, [1u8; 32], 1, 10, b"sig-1", &[], 12)
+            .err()
+            .unwrap();
+        assert_eq!(err, AssuredError::DuplicateChunk.into());
+        assert_eq!(ec.units_released, 2);
+
+        apply_partial_release(&mut ec, [3u8; 32], 2, 12, b"sig-3", &[], 12).unwrap();
+        assert_eq!(ec.units_released, 4);
+        assert_eq!(ec.last_chunk_ts, 12);
+    }
+
+    #[test]
+    fn unsigned_chunks_are_deduplicated_too() {
+        let mut ec = streaming_call(3, 900);
+        assert_eq!(ec.sig_mode, SigMode::None as u8);
+        apply_partial_release(&mut ec, [1u8; 32], 1, 10, &[], &[], 10).unwrap();
+        let err = apply_partial_release(&mut ec, [1u8; 32], 1, 10, &[], &[], 10)
+            .err()
+            .unwrap();
+        assert_eq!(err, AssuredError::DuplicateChunk.into());
+
+        apply_partial_release(&mut ec, [2u8; 32], 1, 11, &[], &[], 11).unwrap();
+        let err = apply_partial_release(&mut ec, [1u8; 32], 1, 10, &[], &[], 11)
+            .err()
+            .unwrap();
+        assert_eq!(err, AssuredError::DuplicateChunk.into());
+        assert_eq!(ec.units_released, 2);
+        apply_partial_release(&mut ec, [3u8; 32], 1, 12, &[], &[], 12).unwrap();
+        assert_eq!(ec.status, Status::Fulfilled as u8);
+    }
+
+    #[test]
+    fn last_chunk_resent_later_is_rejected() {
+        let mut ec = streaming_call(3, 900);
+        apply_partial_release(&mut ec, [1u8; 32], 1, 10, b"sig-1", b"cid", 10).unwrap();
+        // A later ts clears the timestamp check; only the nonce stops it.
+        let err = apply_partial_release(&mut ec, [1u8; 32], 1, 11, b"sig-1b", b"cid", 11)
+            .err()
+            .unwrap();
+        assert_eq!(err, AssuredError::DuplicateChunk.into());
+        assert_eq!(ec.units_released, 1);
+
+        // Any change to what is delivered makes it a new chunk.
+        apply_partial_release(&mut ec, [1u8; 32], 1, 11, b"sig-2", b"cid2", 11).unwrap();
+        apply_partial_release(&mut ec, [2u8; 32], 1, 12, b"sig-3", b"cid2", 12).unwrap();
+        assert_eq!(ec.status, Status::Fulfilled as u8);
+    }
+
+    #[test]
+    fn chunk_must_postdate_the_escrow() {
+        let mut ec = streaming_call(2, 100);
+        ec.start_ts = 50;
+        let err = apply_partial_release(&mut ec, [1u8; 32], 1, 50, b"a", &[], 50)
+            .err()
+            .unwrap();
+        assert_eq!(err, AssuredError::InvalidChunkTimestamp.into());
+    }
+
+    #[test]
+    fn partial_release_rejected_after_fulfillment() {
+        let mut ec = streaming_call(2, 50);
+        apply_partial_release(&mut ec, [1u8; 32], 2, 1_000, b"sig", &[], 1_000).unwrap();
+        assert_eq!(ec.status, Status::Fulfilled as u8);
+
+        for units in [0, 1] {
+            let err = apply_partial_release(&mut ec, [2u8; 32], units, 1_100, b"sig", &[], 1_100)
+                .err()
+                .unwrap();
+            assert_eq!(err, AssuredError::InvalidStatus.into());
+        }
+        assert_eq!(ec.units_released, 2);
+        assert_eq!(ec.response_hash, [1u8; 32]);
+    }
+
+    #[test]
+    fn extend_sla_only_increases_before_fulfillment() {
+        let mut ec = streaming_call(1, 100);
+        assert_eq!(extend_call_sla(&mut ec, 5_000).unwrap(), 2_000);
+        assert_eq!(ec.sla_ms, 5_000);
+
+        let err = extend_call_sla(&mut ec, 4_000).err().unwrap();
+        assert_eq!(err, AssuredError::SlaNotExtended.into());
+        assert!(extend_call_sla(&mut ec, 5_000).is_err());
+        assert_eq!(ec.sla_ms, 5_000);
+    }
+
+    #[test]
+    fn extend_sla_rejected_after_fulfillment() {
+        let mut ec = base_call();
+        let err = extend_call_sla(&mut ec, 10_000_000).err().unwrap();
+        assert_eq!(err, AssuredError::InvalidStatus.into());
+        assert_eq!(ec.sla_ms, 2_000_000);
+    }
+
+    #[test]
+    fn heartbeat_leaves_payout_state_untouched() {
+        let mut ec = streaming_call(4, 100);
+        apply_partial_release(&mut ec, [1u8; 32], 1, 500, b"sig", &[], 500).unwrap();
+        let next_payout = amount_for_units(&ec, ec.units_released, 1);
+
+        record_heartbeat(&mut ec, 900).unwrap();
+        record_heartbeat(&mut ec, 1_400).unwrap();
+        assert_eq!(ec.last_heartbeat_ts, 1_400);
+        assert_eq!(ec.units_released, 1);
+        assert_eq!(ec.status, Status::Init as u8);
+        assert_eq!(ec.delivered_ts, None);
+        assert_eq!(amount_for_units(&ec, ec.units_released, 1), next_payout);
+
+        ec.status = Status::Fulfilled as u8;
+        assert!(record_heartbeat(&mut ec, 2_000).is_err());
+        assert_eq!(ec.last_heartbeat_ts, 1_400);
+    }
+
+    fn empty_approval() -> PayerProviderApproval {
+        PayerProviderApproval {
+            payer: Pubkey::new_unique(),
+            approved_providers: [Pubkey::default(); MAX_APPROVED_PROVIDERS],
+            count: 0,
+        }
+    }
+
+    #[test]
+    fn approval_list_caps_at_eight_providers() {
+        let mut approval = empty_approval();
+        let providers: Vec<Pubkey> = (0..MAX_APPROVED_PROVIDERS)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        for provider in &providers {
+            approval.approve(*provider).unwrap();
+        }
+        assert_eq!(approval.count as usize, MAX_APPROVED_PROVIDERS);
+        assert!(providers.iter().all(|p| approval.is_approved(p)));
+
+        let ninth = Pubkey::new_unique();
+        assert!(approval.approve(ninth).is_err());
+        assert!(!approval.is_approved(&ninth));
+        approval.approve(providers[0]).unwrap();
+        assert_eq!(approval.count as usize, MAX_APPROVED_PROVIDERS);
+    }
+
+    #[test]
+    fn approval_revoke_then_readd() {
+        let mut approval = empty_approval();
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+        approval.approve(first).unwrap();
+        approval.approve(second).unwrap();
+
+        approval.revoke(&first).unwrap();
+        assert_eq!(approval.count, 1);
+        assert!(!approval.is_approved(&first));
+        assert!(approval.is_approved(&second));
+        assert!(approval.revoke(&first).is_err());
+
+        approval.approve(first).unwrap();
+        assert_eq!(approval.count, 2);
+        assert!(approval.is_approved(&first));
+    }
+
+    #[test]
+    fn blacklist_blocks_until_removed() {
+        let mut blacklist = ProviderBlacklist {
+            authority: Pubkey::new_unique(),
+            entries: Vec::new(),
+            count: 0,
+        };
+        let bad = Pubkey::new_unique();
+        assert!(blacklist.check_authority(&Pubkey::new_unique()).is_err());
+
+        blacklist.add(bad).unwrap();
+        blacklist.add(bad).unwrap();
+        assert_eq!(blacklist.count, 1);
+        assert!(blacklist.contains(&bad));
+        assert!(!blacklist.contains(&Pubkey::new_unique()));
+
+        blacklist.remove(&bad).unwrap();
+        assert!(!blacklist.contains(&bad));
+        let err = blacklist.remove(&bad).err().unwrap();
+        assert_eq!(err, AssuredError::ProviderNotBlacklisted.into());
+    }
+
+    #[test]
+    fn init_gates_read_the_blacklist_pda() {
+        let bad = Pubkey::new_unique();
+        let mut blacklist = ProviderBlacklist {
+            authority: Pubkey::new_unique(),
+            entries: Vec::new(),
+            count: 0,
+        };
+        blacklist.add(bad).unwrap();
+        let (key, owner, mut lamports) = (Pubkey::new_unique(), crate::ID, 1u64);
+        let mut data = Vec::new();
+        blacklist.try_serialize(&mut data).unwrap();
+        let info = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        assert_eq!(
+            check_blacklist(&info, &bad).unwrap_err(),
+            error!(AssuredError::ProviderBlacklisted)
+        );
+        check_blacklist(&info, &Pubkey::new_unique()).unwrap();
+
+        // Before the first `add_to_blacklist` the PDA is still empty.
+        let (system_owner, mut empty_lamports) = (anchor_lang::system_program::ID, 0u64);
+        let mut empty = Vec::new();
+        let missing = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut empty_lamports,
+            &mut empty,
+            &system_owner,
+            false,
+            0,
+        );
+        check_blacklist(&missing, &bad).unwrap();
+    }
+
+    #[test]
+    fn allowlist_admits_only_listed_providers_once_enabled() {
+        let (vetted, stranger) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut allowlist = ProviderAllowlist {
+            owner: Pubkey::new_unique(),
+            enabled: false,
+            providers: Vec::new(),
+        };
+        allowlist.add(vetted).unwrap();
+        allowlist.add(vetted).unwrap();
+        assert_eq!(allowlist.providers.len(), 1);
+        // Not opted in yet: the service stays public.
+        assert!(allowlist.admits(&stranger));
+
+        allowlist.enabled = true;
+        assert!(allowlist.admits(&vetted));
+        assert!(!allowlist.admits(&stranger));
+
+        let key = Pubkey::new_unique();
+        let (owner, mut lamports) = (crate::ID, 1u64);
+        let mut data = Vec::new();
+        allowlist.try_serialize(&mut data).unwrap();
+        let info = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        check_allowlist(&info, &vetted).unwrap();
+        assert_eq!(
+            check_allowlist(&info, &stranger).unwrap_err(),
+            error!(AssuredError::ProviderNotAllowlisted)
+        );
+
+        // An owner who never created an allowlist accepts anyone.
+        let (system, mut empty_lamports, mut empty) = (system_program::ID, 0u64, vec![]);
+        let missing = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut empty_lamports,
+            &mut empty,
+            &system,
+            false,
+            0,
+        );
+        check_allowlist(&missing, &stranger).unwrap();
+
+        allowlist.remove(&vetted).unwrap();
+        assert_eq!(
+            allowlist.remove(&vetted).unwrap_err(),
+            error!(AssuredError::ProviderNotOnAllowlist)
+        );
+        for _ in 0..MAX_ALLOWLIST_SIZE {
+            allowlist.add(Pubkey::new_unique()).unwrap();
+        }
+        assert_eq!(
+            allowlist.add(stranger).unwrap_err(),
+            error!(AssuredError::AllowlistFull)
+        );
+        assert!(allowlist.try_to_vec().unwrap().len() <= ProviderAllowlist::INIT_SPACE);
+    }
+
+    #[test]
+    fn blacklist_caps_entries() {
+        let mut blacklist = ProviderBlacklist {
+            authority: Pubkey::new_unique(),
+            entries: Vec::new(),
+            count: 0,
+        };
+        for _ in 0..MAX_BLACKLIST_SIZE {
+            blacklist.add(Pubkey::new_unique()).unwrap();
+        }
+        let err = blacklist.add(Pubkey::new_unique()).err().unwrap();
+        assert_eq!(err, AssuredError::BlacklistFull.into());
+        assert_eq!(
+            blacklist.try_to_vec().unwrap().len(),
+            ProviderBlacklist::INIT_SPACE
+        );
+    }
+
+    #[test]
+    fn stats_count_three_created_two_settled_one_disputed() {
+        let mut stats = EscrowStats::default();
+        for amount in [1_000, 2_000, 3_000] {
+            stats.record_open(amount);
+        }
+        stats.record_dispute();
+        stats.record_release(1_000);
+        stats.record_refund(2_000, 0);
+        let snapshot = stats.snapshot(15);
+        assert_eq!(snapshot.escrow_count, 3);
+        assert_eq!(snapshot.released_count, 1);
+        assert_eq!(snapshot.refunded_count, 1);
+        assert_eq!(snapshot.volume_lamports, 6_000);
+        assert_eq!(snapshot.disputed_count, 1);
+        assert_eq!(snapshot.slot, 15);
+    }
+
+    #[test]
+    fn stats_written_before_disputes_migrate_by_zero_extension() {
+        let mut old = EscrowStats::DISCRIMINATOR.to_vec();
+        for field in [3u64, 6_000, 1, 1, 1_000, 2_000] {
+            old.extend_from_slice(&field.to_le_bytes());
+        }
+        assert!(EscrowStats::try_deserialize(&mut &old[..]).is_err());
+        old.resize(8 + EscrowStats::INIT_SPACE, 0);
+        let stats = EscrowStats::try_deserialize(&mut &old[..]).unwrap();
+        assert_eq!(stats.escrow_count, 3);
+        assert_eq!(stats.refunded_lamports, 2_000);
+        assert_eq!(stats.disputed_count, 0);
+    }
+
+    #[test]
+    fn stats_tally_scripted_settlements() {
+        let mut stats = EscrowStats::default();
+        for amount in [1_000, 2_000, 3_000, 4_000] {
+            stats.record_open(amount);
+        }
+        // Full release, refund of an undelivered call, a stream that paid
+        // 600 in partials before releasing the rest, and a stream refunded
+        // after 500 was already released.
+        stats.record_release(1_000);
+        stats.record_refund(2_000, 0);
+        stats.record_release(600 + 2_400);
+        stats.record_refund(3_500, 500);
+
+        assert_eq!(stats.escrow_count, 4);
+        assert_eq!(stats.volume_lamports, 10_000);
+        assert_eq!(stats.released_count, 2);
+        assert_eq!(stats.refunded_count, 2);
+        assert_eq!(stats.released_lamports, 4_500);
+        assert_eq!(stats.refunded_lamports, 5_500);
+        assert_eq!(stats.refund_rate_bps(), 5_000);
+
+        let mut saturated = EscrowStats {
+            volume_lamports: u64::MAX,
+            ..Default::default()
+        };
+        saturated.record_open(1);
+        assert_eq!(saturated.volume_lamports, u64::MAX);
+        assert_eq!(EscrowStats::default().refund_rate_bps(), 0);
+    }
+
+    fn channel(budget: u64, price_cap: u64) -> Channel {
+        Channel {
+            channel_id: [1; 32],
+            payer: Pubkey::new_unique(),
+            provider: Pubkey::new_unique(),
+            budget,
+            spent: 0,
+            price_cap,
+            dispute_window_s: 10,
+            provider_attest_key: [0; 32],
+            trace_commitment: [0; 32],
+            debit_count: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn channel_takes_multiple_debits() {
+        let mut ch = channel(1_000, 100);
+        ch.debit([1; 32], 100, &[9; 32], 0).unwrap();
+        let after_one = ch.trace_commitment;
+        ch.debit([2; 32], 50, &[9; 32], 5).unwrap();
+        assert_ne!(ch.trace_commitment, after_one);
+        assert_eq!(ch.spent, 150);
+        assert_eq!(ch.debit_count, 2);
+        assert!(ch.debit([2; 32], 50, &[9; 32], 6).is_err());
+
+        // Only the first debit has matured by t=10.
+        assert_eq!(ch.take_matured(10), 100);
+        assert_eq!(ch.pending.len(), 1);
+        assert_eq!(ch.take_matured(15), 50);
+        assert!(ch.pending.is_empty());
+    }
+
+    #[test]
+    fn channel_rejects_debits_over_cap_or_budget() {
+        let mut ch = channel(150, 100);
+        assert_eq!(
+            ch.debit([1; 32], 101, &[0; 32], 0).err().unwrap(),
+            AssuredError::ChannelPriceCapExceeded.into()
+        );
+        assert!(ch.debit([1; 32], 0, &[0; 32], 0).is_err());
+        ch.debit([1; 32], 100, &[0; 32], 0).unwrap();
+        assert_eq!(
+            ch.debit([2; 32], 100, &[0; 32], 0).err().unwrap(),
+            AssuredError::ChannelBudgetExhausted.into()
+        );
+        assert_eq!(ch.spent, 100);
+    }
+
+    #[test]
+    fn channel_close_waits_for_rulings_on_disputed_debits() {
+        let mut ch = channel(1_000, 100);
+        ch.debit([1; 32], 100, &[0; 32], 0).unwrap();
+        ch.debit([2; 32], 60, &[0; 32], 0).unwrap();
+        ch.debit([3; 32], 40, &[0; 32], 0).unwrap();
+        assert_eq!(ch.dispute(&[2; 32], 9).unwrap(), 60);
+        assert!(ch.dispute(&[2; 32], 9).is_err());
+        assert!(ch.dispute(&[1; 32], 10).is_err());
+        ch.dispute(&[3; 32], 9).unwrap();
+
+        // Debit 1 is still disputable at t=5, so the channel can't close.
+        assert!(ch.close_split(5).is_err());
+        assert_eq!(ch.take_matured(20), 100);
+        assert_eq!(ch.pending.len(), 2);
+        assert_eq!(
+            ch.close_split(20).err().unwrap(),
+            AssuredError::ChannelDisputesUnresolved.into()
+        );
+
+        assert_eq!(
+            ch.rule(&[1; 32], Ruling::Refund as u8).err().unwrap(),
+            AssuredError::UnknownChannelDebit.into()
+        );
+        assert!(ch.rule(&[2; 32], 2).is_err());
+        assert_eq!(ch.rule(&[2; 32], Ruling::Refund as u8).unwrap(), (60, 0));
+        assert_eq!(ch.rule(&[3; 32], Ruling::Release as u8).unwrap(), (40, 40));
+        assert!(ch.pending.is_empty());
+        assert_eq!(ch.spent, 140);
+        assert_eq!(ch.close_split(20).unwrap(), (0, 860));
+    }
+
+    #[test]
+    fn channel_disputes_free_their_slots_once_ruled() {
+        let mut ch = channel(1_000, 10);
+        for i in 0..MAX_CHANNEL_PENDING as u8 {
+            ch.debit([i; 32], 1, &[0; 32], 0).unwrap();
+            ch.dispute(&[i; 32], 1).unwrap();
+        }
+        assert_eq!(ch.take_matured(100), 0);
+        assert_eq!(
+            ch.debit([99; 32], 1, &[0; 32], 100).err().unwrap(),
+            AssuredError::ChannelBacklogFull.into()
+        );
+        ch.rule(&[0; 32], Ruling::Release as u8).unwrap();
+        ch.debit([99; 32], 1, &[0; 32], 100).unwrap();
+    }
+
+    #[test]
+    fn channel_debit_message_is_bound_to_the_channel() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let msg = channel_debit_message(&a, &[1; 32], &[2; 32], 5);
+        assert_eq!(msg.len(), 104);
+        assert_eq!(&msg[..32], a.as_ref());
+        assert_ne!(msg, channel_debit_message(&b, &[1; 32], &[2; 32], 5));
+    }
+
+    #[test]
+    fn memos_name_call_and_outcome() {
+        assert_eq!(init_memo("call-1", "svc"), "assured:init:call-1:svc");
+        assert_eq!(
+            settle_memo("call-1", Status::Released as u8),
+            "assured:settle:call-1:release"
+        );
+        assert_eq!(
+            settle_memo("call-1", Status::Refunded as u8),
+            "assured:settle:call-1:refund"
+        );
+
+        let ix = memo_instruction(&settle_memo("call-1", Status::Released as u8));
+        assert_eq!(ix.program_id, MEMO_PROGRAM_ID);
+        assert!(ix.accounts.is_empty());
+        assert_eq!(ix.data, b"assured:settle:call-1:release");
+    }
+
+    #[test]
+    fn reusing_open_call_id_is_rejected() {
+        let fresh = EscrowCall::deserialize(&mut &vec![0u8; EscrowCall::INIT_SPACE][..]).unwrap();
+        assert!(ensure_call_id_free(&fresh).is_ok());
+
+        let live = base_call();
+        assert_eq!(
+            ensure_call_id_free(&live).err().unwrap(),
+            AssuredError::CallIdTaken.into()
+        );
+        let mut unversioned = fresh.clone();
+        unversioned.payer = Pubkey::new_unique();
+        assert!(ensure_call_id_free(&unversioned).is_err());
+    }
+
+    #[test]
+    fn v2_pda_derives_from_call_id_hash() {
+        let hash = call_id_to_hash("call-1");
+        assert_eq!(hash, call_id_to_hash("call-1"));
+        assert_ne!(hash, call_id_to_hash("call-2"));
+        let (address, bump) = call_v2_address(&hash);
+        assert_eq!(
+            Pubkey::create_program_address(&[b"callv2", &hash, &[bump]], &crate::ID).unwrap(),
+            address
+        );
+        let (v1_address, _) =
+            Pubkey::find_program_address(&[b"call", "call-1".as_bytes()], &crate::ID);
+        assert_ne!(address, v1_address);
+    }
+
+    #[test]
+    fn migration_to_v2_preserves_state() {
+        let mut ec = base_call();
+        ec.ruling = Some(Ruling::Refund as u8);
+        ec.appeal_filed_by = Some(Pubkey::new_unique());
+        ec.provider_sig = vec![1, 2, 3];
+        let hash = call_id_to_hash(&ec.call_id);
+        let v2 = EscrowCallV2::from_v1(hash, &ec);
+        assert_eq!(v2.call_id, hash);
+
+        let back = v2.to_v1();
+        assert_eq!(back.call_id.len(), 64);
+        let mut expected = ec.clone();
+        expected.call_id = back.call_id.clone();
+        assert_eq!(back.try_to_vec().unwrap(), expected.try_to_vec().unwrap());
+        assert_eq!(evaluate_settlement(&back, 0), evaluate_settlement(&ec, 0));
+    }
+
+    #[test]
+    fn both_layouts_end_in_the_same_call_state() {
+        let ec = maximal_call();
+        let state = ec.state.try_to_vec().unwrap();
+        let v2 = EscrowCallV2::from_v1(call_id_to_hash(&ec.call_id), &ec);
+        assert!(ec.try_to_vec().unwrap().ends_with(&state));
+        assert!(v2.try_to_vec().unwrap().ends_with(&state));
+        assert_eq!(
+            EscrowCallV2::MAX_LEN_V2,
+            8 + 1 + 32 * 3 + CallState::INIT_SPACE
+        );
+    }
+
+    #[test]
+    fn plain_calls_can_move_to_v2() {
+        check_v2_supported(&base_call()).unwrap();
+        check_v2_supported(&streaming_call(4, 1_000)).unwrap();
+    }
+
+    #[test]
+    fn hashlocked_calls_stay_on_v1() {
+        assert_eq!(
+            check_v2_supported(&hashlocked_call(b"key")).unwrap_err(),
+            AssuredError::NotSupportedByV2.into()
+        );
+    }
+
+    #[test]
+    fn calls_needing_proofs_stay_on_v1() {
+        let mut ec = base_call();
+        ec.requires_proofs = true;
+        assert_eq!(
+            check_v2_supported(&ec).unwrap_err(),
+            AssuredError::NotSupportedByV2.into()
+        );
+    }
+
+    #[test]
+    fn committee_calls_stay_on_v1() {
+        let arbiters = [Pubkey::new_unique(), Pubkey::new_unique()];
+        assert_eq!(
+            check_v2_supported(&committee_call(&arbiters, 2)).unwrap_err(),
+            AssuredError::NotSupportedByV2.into()
+        );
+    }
+
+    #[test]
+    fn v2_account_is_smaller() {
+        const { assert!(EscrowCallV2::MAX_LEN_V2 < EscrowCall::MAX_LEN) };
+        assert_eq!(
+            EscrowCall::MAX_LEN - EscrowCallV2::MAX_LEN_V2,
+            4 + MAX_CALL_ID_LEN - 32
+        );
+    }
+
+    fn empty_index() -> PayerCallIndex {
+        PayerCallIndex {
+            payer: Pubkey::new_unique(),
+            count: 0,
+            entries: [IndexedCall::default(); PAYER_INDEX_LEN],
+        }
+    }
+
+    #[test]
+    fn payer_index_tracks_creations_and_settlements() {
+        let mut index = empty_index();
+        index.record("a");
+        index.record("b");
+        index.record("c");
+        index.mark_settled("b");
+        index.mark_settled("unknown");
+        assert_eq!(index.count, 3);
+        assert_eq!(
+            index.open_calls(),
+            vec![call_id_to_hash("a"), call_id_to_hash("c")]
+        );
+        assert!(index.recent()[1].settled);
+    }
+
+    fn empty_provider_index() -> ProviderCallIndex {
+        ProviderCallIndex {
+            provider: Pubkey::new_unique(),
+            call_ids: Vec::new(),
+            head: 0,
+        }
+    }
+
+    #[test]
+    fn provider_index_evicts_oldest_after_cap() {
+        let mut index = empty_provider_index();
+        for i in 0..=MAX_INDEX_ENTRIES {
+            index.push(&format!("call-{i}"));
+        }
+        let active = index.active();
+        assert_eq!(active.len(), MAX_INDEX_ENTRIES);
+        assert_eq!(active[0], "call-1");
+        assert_eq!(
+            active[MAX_INDEX_ENTRIES - 1],
+            format!("call-{MAX_INDEX_ENTRIES}")
+        );
+        assert!(!active.contains(&"call-0".to_string()));
+        assert_eq!(index.head, 1);
+
+        let mut full = empty_provider_index();
+        for i in 0..MAX_INDEX_ENTRIES {
+            full.push(&format!("{}{i:02}", "c".repeat(MAX_CALL_ID_LEN - 2)));
+        }
+        assert_eq!(
+            full.try_to_vec().unwrap().len(),
+            ProviderCallIndex::INIT_SPACE
+        );
+    }
+
+    #[test]
+    fn settle_clears_provider_index_entry() {
+        let mut index = empty_provider_index();
+        for id in ["a", "b", "c"] {
+            index.push(id);
+        }
+        index.remove("b");
+        index.remove("unknown");
+        assert_eq!(index.active(), vec!["a".to_string(), "c".to_string()]);
+        assert_eq!(index.call_ids.len(), 3);
+
+        // A call migrated to v2 is removed by its hash on settle_v2.
+        index.remove_hashed(&call_id_to_hash("c"));
+        index.remove_hashed(&call_id_to_hash("unknown"));
+        assert_eq!(index.active(), vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn v2_calls_are_listed_by_hash_until_closed() {
+        let mut index = empty_provider_index();
+        let hash = call_id_to_hash("v2-call");
+        index.push_hashed(&hash);
+        index.push("v1-call");
+        let hex = call_id_hash_hex(&hash);
+        assert_eq!(hex.len(), MAX_CALL_ID_LEN);
+        assert_eq!(index.active(), vec![hex, "v1-call".to_string()]);
+
+        index.remove_hashed(&hash);
+        assert_eq!(index.active(), vec!["v1-call".to_string()]);
+    }
+
+    #[test]
+    fn payer_index_wraps_around() {
+        let mut index = empty_index();
+        for i in 0..PAYER_INDEX_LEN + 3 {
+            index.record(&format!("call-{i}"));
+        }
+        let recent = index.recent();
+        assert_eq!(recent.len(), PAYER_INDEX_LEN);
+        assert_eq!(recent[0].call_id_hash, call_id_to_hash("call-3"));
+        assert_eq!(
+            recent[PAYER_INDEX_LEN - 1].call_id_hash,
+            call_id_to_hash(&format!("call-{}", PAYER_INDEX_LEN + 2))
+        );
+
+        // call-0 rotated out, so settling it touches nothing.
+        index.mark_settled("call-0");
+        assert_eq!(index.open_calls().len(), PAYER_INDEX_LEN);
+        index.mark_settled("call-3");
+        assert_eq!(index.open_calls().len(), PAYER_INDEX_LEN - 1);
+        assert_eq!(
+            index.try_to_vec().unwrap().len(),
+            PayerCallIndex::INIT_SPACE
+        );
+    }
+
+    fn base_config() -> Config {
+        Config {
+            admin: Pubkey::new_unique(),
+            max_sla_ms: 10_000,
+            max_dispute_window_s: 60,
+            fee_bps: 0,
+            fulfill_grace_s: 30,
+            paused: false,
+            min_bond_required: 0,
+            dispute_bond_lamports: 0,
+            strict_pricing: false,
+            late_penalty_bps: 0,
+        }
+    }
+
+    #[test]
+    fn fulfill_deadline_covers_sla_plus_grace() {
+        let ec = streaming_call(1, 100);
+        let config = base_config();
+        // sla 2_000ms from start 0 rounds to 2s, plus 30s grace.
+        assert_eq!(fulfill_deadline(&ec, config.fulfill_grace_s), 32);
+        assert_eq!(fulfill_deadline(&ec, 0), 2);
+    }
+
+    #[test]
+    fn partial_after_the_deadline_is_rejected() {
+        let mut ec = streaming_call(2, 100);
+        let grace_s = base_config().fulfill_grace_s;
+        check_fulfill_deadline(&ec, 32, grace_s).unwrap();
+        apply_partial_release(&mut ec, [1u8; 32], 1, 1, &[], &[], 32).unwrap();
+        assert_eq!(
+            check_fulfill_deadline(&ec, 33, grace_s).unwrap_err(),
+            error!(AssuredError::SlaDeadlineExpired)
+        );
+        assert_eq!(ec.units_released, 1);
+        assert_eq!(ec.status, Status::Init as u8);
+    }
+
+    #[test]
+    fn fulfill_after_partials_leaves_remaining_units_for_settle() {
+        let mut ec = streaming_call(4, 1_003);
+        let first = apply_partial_release(&mut ec, [1u8; 32], 2, 1, &[], &[], 1).unwrap();
+        record_fulfillment(&mut ec, [2u8; 32], 1, &[], 1);
+        assert_eq!(ec.status, Status::Fulfilled as u8);
+        assert_eq!(ec.units_released, 2);
+
+        let remaining = amount_for_units(&ec, ec.units_released, 2);
+        assert_eq!(first.payout + remaining, ec.amount);
+        let event = released_event(&ec, remaining);
+        assert_eq!(event.amount_to_provider, remaining);
+    }
+
+    #[test]
+    fn config_rejects_non_admin_updates() {
+        let config = base_config();
+        assert!(config.check_admin(&config.admin).is_ok());
+        assert!(config.check_admin(&Pubkey::new_unique()).is_err());
+
+        let params = ConfigParams {
+            max_sla_ms: 1,
+            max_dispute_window_s: 1,
+            fee_bps: MAX_FEE_BPS + 1,
+            fulfill_grace_s: 0,
+            paused: false,
+            min_bond_required: 0,
+            dispute_bond_lamports: 0,
+            strict_pricing: false,
+            late_penalty_bps: 0,
+        };
+        assert!(params.validate().is_err());
+
+        let params = ConfigParams {
+            fee_bps: 0,
+            fulfill_grace_s: EXPIRY_GRACE_S,
+            ..params
+        };
+        assert_eq!(
+            params.validate().unwrap_err(),
+            error!(AssuredError::FulfillGraceTooLong)
+        );
+    }
+
+    #[test]
+    fn stake_reward_needs_stakers() {
+        let mut svc = reputation::Service {
+            stake_reward_bps: 500,
+            ..Default::default()
+        };
+        assert_eq!(stake_reward_cut(&svc, 1_000_000), 0);
+        svc.total_stake_shares = 1;
+        assert_eq!(stake_reward_cut(&svc, 1_000_000), 50_000);
+        assert_eq!(stake_reward_cut(&svc, 19), 0);
+    }
+
+    #[test]
+    fn trigger_expiry_waits_for_expiry_ts() {
+        let mut ec = streaming_call(1, 100);
+        ec.start_ts = 1_000;
+        ec.expiry_ts = default_expiry_ts(ec.start_ts, ec.sla_ms);
+        assert_eq!(ec.expiry_ts, 1_000 + 2 + EXPIRY_GRACE_S);
+        assert_eq!(
+            check_expiry(&ec, ec.expiry_ts - 1).unwrap_err(),
+            error!(AssuredError::NotExpired)
+        );
+        assert!(check_expiry(&ec, ec.expiry_ts + 1).is_ok());
+
+        // Even the longest grace leaves fulfill a window before expiry.
+        ec.sla_ms = 1_500;
+        ec.expiry_ts = default_expiry_ts(ec.start_ts, ec.sla_ms);
+        assert!(fulfill_deadline(&ec, EXPIRY_GRACE_S - 1) < ec.expiry_ts);
+
+        ec.disputed = true;
+        assert!(check_expiry(&ec, ec.expiry_ts + 1).is_err());
+        ec.disputed = false;
+        record_fulfillment(&mut ec, [2u8; 32], 1, &[], 1);
+        assert_eq!(
+            check_expiry(&ec, ec.expiry_ts + 1).unwrap_err(),
+            error!(AssuredError::InvalidStatus)
+        );
+    }
+
+    #[test]
+    fn sig_mode_selects_the_fulfill_check() {
+        let key = [7u8; 32];
+        let eth = KEY_ONE_ETH_ADDRESS;
+        let mut keys = ProviderKeys {
+            provider: Pubkey::new_unique(),
+            attest_key: [0; 32],
+            eth_address: [0; 20],
+        };
+        assert_eq!(
+            resolve_sig_mode(0, None).unwrap(),
+            (SigMode::None as u8, None)
+        );
+        assert_eq!(
+            resolve_sig_mode(1, None).unwrap(),
+            (SigMode::Ed25519 as u8, None)
+        );
+        // Secp256k1 only verifies against the address the provider
+        // registered, never one the payer names.
+        assert_eq!(
+            resolve_sig_mode(2, None).unwrap_err(),
+            error!(AssuredError::MissingEthSigner)
+        );
+        assert_eq!(
+            resolve_sig_mode(2, Some(&keys)).unwrap_err(),
+            error!(AssuredError::UnregisteredEthAddress)
+        );
+        keys.eth_address = eth;
+        assert_eq!(
+            resolve_sig_mode(2, Some(&keys)).unwrap(),
+            (SigMode::Secp256k1 as u8, Some(eth))
+        );
+        // An attestation key keeps its old meaning: verify.
+        keys.attest_key = key;
+        assert_eq!(
+            resolve_sig_mode(0, Some(&keys)).unwrap(),
+            (SigMode::Ed25519 as u8, None)
+        );
+        assert_eq!(
+            resolve_sig_mode(3, Some(&keys)).unwrap_err(),
+            error!(AssuredError::InvalidSigMode)
+        );
+
+        let mut ec = streaming_call(1, 100);
+        ec.sig_mode = SigMode::None as u8;
+        assert_eq!(attestation_check(&ec).unwrap(), AttestationCheck::None);
+        ec.sig_mode = SigMode::Ed25519 as u8;
+        assert_eq!(
+            attestation_check(&ec).unwrap(),
+            AttestationCheck::Ed25519(ec.provider.to_bytes())
+        );
+        ec.provider_attest_key = key;
+        assert_eq!(
+            attestation_check(&ec).unwrap(),
+            AttestationCheck::Ed25519(key)
+        );
+        ec.sig_mode = SigMode::Secp256k1 as u8;
+        assert_eq!(
+            attestation_check(&ec).unwrap_err(),
+            error!(AssuredError::MissingEthSigner)
+        );
+        ec.provider_eth_address = Some(eth);
+        assert_eq!(
+            attestation_check(&ec).unwrap(),
+            AttestationCheck::Secp256k1(eth)
+        );
+    }
+
+    /// Signs `message` the way an EVM wallet's raw secp256k1 signer would:
+    /// over its keccak-256 digest, as `r || s || recovery_id`.
+    fn eth_sign(secret: u8, message: &[u8]) -> [u8; 65] {
+        use libsecp256k1::curve::Scalar;
+        let digest = solana_keccak_hasher::hash(message).to_bytes();
+        let scalar = |bytes: [u8; 32]| {
+            let mut s = Scalar::default();
+            let _ = s.set_b32(&bytes);
+            s
+        };
+        let mut key = [0u8; 32];
+        key[31] = secret;
+        let mut nonce = [0u8; 32];
+        nonce[31] = 42;
+        let (r, s, recid) = libsecp256k1::ECMULT_GEN_CONTEXT
+            .sign_raw(&scalar(key), &scalar(digest), &scalar(nonce))
+            .unwrap();
+        let mut sig = [0u8; 65];
+        sig[..32].copy_from_slice(&r.b32());
+        sig[32..64].copy_from_slice(&s.b32());
+        sig[64] = recid;
+        sig
+    }
+
+    fn recovered_eth_address(message: &[u8], sig: &[u8; 65]) -> [u8; 20] {
+        let digest = solana_keccak_hasher::hash(message).to_bytes();
+        let pubkey = libsecp256k1::recover(
+            &libsecp256k1::Message::parse(&digest),
+            &libsecp256k1::Signature::parse_standard_slice(&sig[..64]).unwrap(),
+            &libsecp256k1::RecoveryId::parse(sig[64]).unwrap(),
+        )
+        .unwrap();
+        let hashed = solana_keccak_hasher::hash(&pubkey.serialize()[1..]).to_bytes();
+        hashed[12..].try_into().unwrap()
+    }
+
+    #[test]
+    fn fulfill_and_chunks_checked_against_the_registered_signer() {
+        let mut ec = streaming_call(4, 1_000);
+        ec.sig_mode = SigMode::Secp256k1 as u8;
+        ec.provider_eth_address = Some(KEY_ONE_ETH_ADDRESS);
+        let check = attestation_check(&ec).unwrap();
+
+        let message = attestation_message(&ec.call_id, &[2u8; 32], 1_000);
+        let sig = eth_sign(1, &message);
+        assert_eq!(recovered_eth_address(&message, &sig), KEY_ONE_ETH_ADDRESS);
+        let ix = secp256k1_ix(0, &KEY_ONE_ETH_ADDRESS, &sig, &message);
+        check_attestation_ix(&check, &ix, 0, &message, &sig).unwrap();
+        // A 64-byte Ed25519-shaped signature is not accepted.
+        assert!(check_attestation_ix(&check, &ix, 0, &message, &sig[..64]).is_err());
+
+        // A different EVM key recovers to another address and is refused.
+        let other_sig = eth_sign(2, &message);
+        let other = recovered_eth_address(&message, &other_sig);
+        assert_ne!(other, KEY_ONE_ETH_ADDRESS);
+        let other_ix = secp256k1_ix(0, &other, &other_sig, &message);
+        assert!(check_attestation_ix(&check, &other_ix, 0, &message, &other_sig).is_err());
+
+        // Chunks sign the canonical chunk payload, indexed by partial count.
+        let chunk = chunk_message(&ec, [5u8; 32], 2, 1_001, &[]);
+        let chunk_sig = eth_sign(1, &chunk);
+        let chunk_ix = secp256k1_ix(0, &KEY_ONE_ETH_ADDRESS, &chunk_sig, &chunk);
+        check_attestation_ix(&check, &chunk_ix, 0, &chunk, &chunk_sig).unwrap();
+        apply_partial_release(&mut ec, [5u8; 32], 2, 1_001, &chunk_sig, &[], 1_001).unwrap();
+        let replay = chunk_message(&ec, [5u8; 32], 2, 1_001, &[]);
+        assert_ne!(replay, chunk);
+
+        // Ed25519 calls check chunks the same way.
+        ec.sig_mode = SigMode::Ed25519 as u8;
+        let check = attestation_check(&ec).unwrap();
+        let ed_sig = [4u8; 64];
+        let ed_ix = ed25519_ix(&proof_signing_key(&ec), &ed_sig, &replay);
+        check_attestation_ix(&check, &ed_ix, 0, &replay, &ed_sig).unwrap();
+        assert!(check_attestation_ix(&check, &ed_ix, 0, &chunk, &ed_sig).is_err());
+    }
+
+    #[test]
+    fn full_stream_leaves_exactly_rent_behind() {
+        const RENT: u64 = 2_039_280;
+        for policy in [
+            RemainderPolicy::FirstUnits,
+            RemainderPolicy::LastUnit,
+            RemainderPolicy::LastUnits,
+        ] {
+            let mut ec = streaming_call(7, 1_003);
+            ec.remainder_policy = policy as u8;
+            let mut lamports = RENT + ec.amount;
+            for units in [1, 2, 4] {
+                let state =
+                    apply_partial_release(&mut ec, [1u8; 32], units, units, &[], &[], units)
+                        .unwrap();
+                lamports -= state.payout;
+                assert!(lamports >= RENT);
+            }
+            assert_eq!(ec.status, Status::Fulfilled as u8);
+            assert_eq!(lamports, RENT);
+        }
+    }
+
+    #[test]
+    fn partial_payouts_never_exceed_amount() {
+        let mut ec = streaming_call(4, 1_000);
+        apply_partial_release(&mut ec, [1u8; 32], 2, 1, &[], &[], 1).unwrap();
+        assert!(check_cumulative_payout(&ec, 2, 500).is_ok());
+        assert_eq!(
+            check_cumulative_payout(&ec, 2, 501).unwrap_err(),
+            error!(AssuredError::PayoutExceedsAmount)
+        );
+        assert!(check_cumulative_payout(&ec, 2, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn strict_pricing_rejects_underpriced_escrows() {
+        let mut svc = reputation::Service {
+            price_per_unit: 1_000,
+            ..reputation::Service::default()
+        };
+        assert!(check_price(true, &svc, 10_000, 10).is_ok());
+        assert!(check_price(true, &svc, 10_001, 10).is_ok());
+        assert_eq!(
+            check_price(true, &svc, 9_999, 10).unwrap_err(),
+            error!(AssuredError::UnderpricedEscrow)
+        );
+        // A price that overflows u64 can never be covered.
+        assert_eq!(
+            check_price(true, &svc, u64::MAX, u64::MAX).unwrap_err(),
+            error!(AssuredError::UnderpricedEscrow)
+        );
+        assert!(check_price(false, &svc, 9_999, 10).is_ok());
+        svc.pricing_opt_out = true;
+        assert!(check_price(true, &svc, 9_999, 10).is_ok());
+    }
+
+    #[test]
+    fn init_gates_cover_pricing_billing_and_allowlist() {
+        let svc = reputation::Service {
+            price_per_unit: 1_000,
+            ..reputation::Service::default()
+        };
+        let config = Config {
+            strict_pricing: true,
+            ..base_config()
+        };
+        let (provider, stranger) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut allowlist = ProviderAllowlist {
+            owner: svc.owner,
+            enabled: true,
+            providers: Vec::new(),
+        };
+        allowlist.add(provider).unwrap();
+        let (key, owner, mut lamports) = (Pubkey::new_unique(), crate::ID, 1u64);
+        let mut data = Vec::new();
+        allowlist.try_serialize(&mut data).unwrap();
+        let info = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        let terms = InitTerms {
+            amount: 10_000,
+            sla_ms: 1_000,
+            dispute_window_s: 30,
+            total_units: 10,
+            billing_mode: BillingMode::PerUnit as u8,
+            min_reputation_bps: None,
+            now: 0,
+        };
+        let (blacklist_key, mut blacklist_lamports) = (Pubkey::new_unique(), 0u64);
+        let mut blacklist_data = Vec::new();
+        let system_owner = anchor_lang::system_program::ID;
+        let missing_blacklist = AccountInfo::new(
+            &blacklist_key,
+            false,
+            false,
+            &mut blacklist_lamports,
+            &mut blacklist_data,
+            &system_owner,
+            false,
+            0,
+        );
+        let gates = |provider: &Pubkey, terms: &InitTerms| {
+            check_init_gates(
+                &config,
+                &svc,
+                provider,
+                None,
+                &missing_blacklist,
+                &info,
+                terms,
+            )
+        };
+        gates(&provider, &terms).unwrap();
+        assert_eq!(
+            gates(
+                &provider,
+                &InitTerms {
+                    amount: 9_999,
+                    ..terms
+                }
+            )
+            .unwrap_err(),
+            error!(AssuredError::UnderpricedEscrow)
+        );
+        assert_eq!(
+            gates(
+                &provider,
+                &InitTerms {
+                    billing_mode: 7,
+                    ..terms
+                }
+            )
+            .unwrap_err(),
+            error!(AssuredError::InvalidBillingMode)
+        );
+        assert_eq!(
+            gates(&stranger, &terms).unwrap_err(),
+            error!(AssuredError::ProviderNotAllowlisted)
+        );
+        assert_eq!(
+            gates(
+                &provider,
+                &InitTerms {
+                    min_reputation_bps: Some(1),
+                    ..terms
+                }
+            )
+            .unwrap_err(),
+            error!(AssuredError::ReputationBelowMinimum)
+        );
+    }
+
+    #[test]
+    fn data_volume_pays_pro_rata_up_to_the_byte_budget() {
+        let mut ec = streaming_call(1_000, 50_000);
+        ec.billing_mode = BillingMode::DataVolume as u8;
+
+        // Exact budget earns the whole amount.
+        assert_eq!(record_data_delivery(&mut ec, 1_000).unwrap(), 50_000);
+        assert_eq!(release_payout(&ec, 0, ec.total_units), 50_000);
+
+        // Over budget is rejected and leaves the recorded size alone.
+        assert_eq!(
+            record_data_delivery(&mut ec, 1_001).unwrap_err(),
+            error!(AssuredError::DataOverBudget)
+        );
+        assert_eq!(ec.response_size_bytes, 1_000);
+
+        // Under budget pays for what was sent; settle refunds the rest.
+        assert_eq!(record_data_delivery(&mut ec, 333).unwrap(), 16_650);
+        assert_eq!(release_payout(&ec, 0, ec.total_units), 16_650);
+        assert_eq!(amount_for_bytes(&ec, 1), 50);
+        ec.amount = 100;
+        assert_eq!(amount_for_bytes(&ec, 333), 33);
+    }
+
+    #[test]
+    fn paused_service_refuses_new_calls_but_open_ones_still_fulfill() {
+        let mut svc = reputation::Service::default();
+        let mut ec = streaming_call(1, 100);
+        svc.paused = true;
+        assert_eq!(
+            check_accepting_calls(&svc).unwrap_err(),
+            error!(AssuredError::ServicePaused)
+        );
+        let provider = ec.provider;
+        assert!(check_fulfill(&ec, &provider, &[], 1, 0).is_ok());
+        record_fulfillment(&mut ec, [2u8; 32], 1, &[], 1);
+        assert_eq!(ec.status, Status::Fulfilled as u8);
+        svc.paused = false;
+        assert!(check_accepting_calls(&svc).is_ok());
+    }
+
+    #[test]
+    fn outstanding_cap_rejects_the_call_past_max_until_one_settles() {
+        let mut svc = reputation::Service::default();
+        assert!(!check_call_capacity(&svc).unwrap());
+        svc.max_outstanding_calls = 3;
+        for _ in 0..3 {
+            assert!(check_call_capacity(&svc).unwrap());
+            svc.outstanding_calls += 1;
+        }
+        assert_eq!(
+            check_call_capacity(&svc).unwrap_err(),
+            error!(AssuredError::ProviderOverloaded)
+        );
+        svc.outstanding_calls -= 1;
+        assert!(check_call_capacity(&svc).unwrap());
+    }
+
+    #[test]
+    fn batch_over_the_outstanding_cap_is_rejected() {
+        let leg = |service_id: &str| BatchInitLeg {
+            call_id: "c".into(),
+            service_id: service_id.into(),
+            amount: 1,
+            sla_ms: 1_000,
+            dispute_window_s: 0,
+            total_units: 1,
+        };
+        let mut svc = reputation::Service {
+            max_outstanding_calls: 2,
+            outstanding_calls: 1,
+            ..Default::default()
+        };
+        let over = [leg("svc"), leg("other"), leg("svc")];
+        assert_eq!(
+            check_batch_capacity(&over, &svc).unwrap_err(),
+            error!(AssuredError::ProviderOverloaded)
+        );
+        // Legs on other services do not count against this one.
+        check_batch_capacity(&over[..2], &svc).unwrap();
+        // Later legs see the earlier ones in `outstanding_calls`.
+        svc.outstanding_calls = 2;
+        assert_eq!(
+            check_batch_capacity(&over[2..], &svc).unwrap_err(),
+            error!(AssuredError::ProviderOverloaded)
+        );
+        svc.max_outstanding_calls = 0;
+        check_batch_capacity(&over, &svc).unwrap();
+    }
+
+    #[test]
+    fn capped_calls_need_a_writable_service_from_init_to_settle() {
+        let (key, owner) = (Pubkey::new_unique(), reputation::ID);
+        let (mut lamports, mut data) = (0u64, Vec::new());
+        let count = |open: bool, service: &AccountInfo| {
+            adjust_outstanding_calls(open, service, ReputationCpi::default(), "svc".into())
+        };
+
+        // Uncapped services are never counted, so a read-only account will do.
+        let mut svc = reputation::Service::default();
+        let mut ec = base_call();
+        ec.outstanding_tracked = check_call_capacity(&svc).unwrap();
+        assert!(!ec.outstanding_tracked);
+
+        // init_payment on a capped service tracks the call and must be
+        // able to write the count.
+        svc.max_outstanding_calls = 1;
+        ec.outstanding_tracked = check_call_capacity(&svc).unwrap();
+        assert!(ec.outstanding_tracked);
+        let read_only = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        assert_eq!(
+            count(true, &read_only).unwrap_err(),
+            error!(AssuredError::ServiceNotWritable)
+        );
+        drop(read_only);
+        let writable = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        assert_eq!(
+            count(true, &writable).unwrap_err(),
+            error!(AssuredError::ReputationAccountsRequired)
+        );
+        svc.outstanding_calls += 1;
+        assert_eq!(
+            check_call_capacity(&svc).unwrap_err(),
+            error!(AssuredError::ProviderOverloaded)
+        );
+
+        // settle on the tracked call releases the slot the same way.
+        assert_eq!(
+            count(false, &writable).unwrap_err(),
+            error!(AssuredError::ReputationAccountsRequired)
+        );
+        svc.outstanding_calls -= 1;
+        assert!(check_call_capacity(&svc).unwrap());
+    }
+
+    #[test]
+    fn min_reputation_gates_on_escrow_outcomes() {
+        let provider = Pubkey::new_unique();
+        let (key, mut lamports, mut data) = (Pubkey::new_unique(), 0u64, Vec::new());
+        let system = system_program::ID;
+        let no_allowlist = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &system,
+            false,
+            0,
+        );
+        let gate = |svc: &reputation::Service, min| {
+            check_min_reputation(svc, &provider, &no_allowlist, min)
+        };
+        let mut svc = reputation::Service {
+            owner: provider,
+            ..Default::default()
+        };
+        assert!(gate(&svc, None).is_ok());
+        assert!(gate(&svc, Some(0)).is_ok());
+        assert_eq!(
+            gate(&svc, Some(1)).unwrap_err(),
+            AssuredError::ReputationBelowMinimum.into()
+        );
+
+        // Owner-reported outcomes alone do not lift the escrow-fed rate.
+        svc.success_rate_bp = 9_770;
+        svc.compliance_rate_bps = 9_800;
+        assert!(gate(&svc, Some(9_500)).is_err());
+        svc.escrow_releases = 1_000;
+        assert!(gate(&svc, Some(9_500)).is_ok());
+        assert!(gate(&svc, Some(9_801)).is_err());
+
+        // Failed uptime probes drag the composite score under the floor.
+        svc.uptime_checks_total = 10;
+        svc.uptime_checks_ok = 5;
+        assert!(gate(&svc, Some(9_500)).is_err());
+        assert!(gate(&svc, Some(8_000)).is_ok());
+
+        // Someone else's service cannot vouch for the provider.
+        svc.owner = Pubkey::new_unique();
+        assert_eq!(
+            gate(&svc, Some(8_000)).unwrap_err(),
+            AssuredError::ServiceNotBoundToProvider.into()
+        );
+        assert!(gate(&svc, None).is_ok());
+
+        let mut allowlist = ProviderAllowlist {
+            owner: svc.owner,
+            enabled: false,
+            providers: vec![provider],
+        };
+        let mut lamports = 0u64;
+        let mut data = vec![0u8; 8 + ProviderAllowlist::INIT_SPACE];
+        allowlist.try_serialize(&mut &mut data[..]).unwrap();
+        let owner = crate::ID;
+        let listed = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        assert!(check_min_reputation(&svc, &provider, &listed, Some(8_000)).is_err());
+        drop(listed);
+        allowlist.enabled = true;
+        allowlist.try_serialize(&mut &mut data[..]).unwrap();
+        let listed = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        assert!(check_min_reputation(&svc, &provider, &listed, Some(8_000)).is_ok());
+    }
+
+    #[test]
+    fn init_requires_minimum_performance_bond() {
+        let mut config = base_config();
+        assert!(config.check_bond(0).is_ok());
+
+        config.min_bond_required = 1_000;
+        let mut svc = reputation::Service {
+            bond_balance: 5_000,
+            ..Default::default()
+        };
+        assert_eq!(
+            config
+                .check_bond(svc.performance_bond_balance)
+                .err()
+                .unwrap(),
+            AssuredError::InsufficientPerformanceBond.into()
+        );
+        svc.deposit_performance_bond(1_000);
+        assert!(config.check_bond(svc.performance_bond_balance).is_ok());
+    }
+
+    #[test]
+    fn pause_blocks_init_but_not_settle() {
+        let mut config = base_config();
+        assert!(config.validate_init(2_000, 10).is_ok());
+        assert!(config.validate_init(20_000, 10).is_err());
+        assert!(config.validate_init(2_000, 120).is_err());
+
+        config.paused = true;
+        assert!(config.validate_init(2_000, 10).is_err());
+        assert_eq!(
+            evaluate_settlement(&base_call(), 12_000),
+            SettlementOutcome::Release
+        );
+    }
+
+    #[test]
+    fn heartbeat_within_window_blocks_early_no_response() {
+        let mut ec = streaming_call(1, 100);
+        ec.sla_ms = 60_000;
+        ec.heartbeat_required = true;
+        ec.heartbeat_interval_s = 5;
+
+        assert!(!no_response_dispute_allowed(&ec, 10));
+        record_heartbeat(&mut ec, 9).unwrap();
+        assert!(!heartbeat_lapsed(&ec, 19));
+        assert!(!no_response_dispute_allowed(&ec, 19));
+    }
+
+    #[test]
+    fn disputes_only_within_window_after_delivery() {
+        // Delivered at 1_000 with a 10s window.
+        let ec = base_call();
+        assert!(within_dispute_window(&ec, 1_000));
+        assert!(within_dispute_window(&ec, 1_009));
+        assert!(!within_dispute_window(&ec, 1_010));
+        assert!(!within_dispute_window(&ec, 50_000));
+
+        let pending = streaming_call(1, 100);
+        assert!(within_dispute_window(&pending, u64::MAX));
+    }
+
+    #[test]
+    fn reopens_dispute_only_within_window() {
+        let mut ec = base_call();
+        let payer = ec.payer;
+        assert!(reopen_call_dispute(&mut ec, Pubkey::new_unique(), [7; 32], 1_005).is_err());
+        reopen_call_dispute(&mut ec, payer, [7; 32], 1_005).unwrap();
+        assert!(ec.disputed);
+        assert_eq!(ec.response_hash, [7; 32]);
+        assert_eq!(ec.disputed_at_ts, 1_005);
+        assert!(reopen_call_dispute(&mut ec, payer, [8; 32], 1_006).is_err());
+
+        let mut late = base_call();
+        assert!(reopen_call_dispute(&mut late, payer, [7; 32], 1_010).is_err());
+        assert!(!late.disputed);
+    }
+
+    #[test]
+    fn missing_heartbeat_makes_no_response_eligible() {
+        let mut ec = streaming_call(1, 100);
+        ec.sla_ms = 60_000;
+        ec.heartbeat_required = true;
+        ec.heartbeat_interval_s = 5;
+
+        assert!(no_response_dispute_allowed(&ec, 11));
+        record_heartbeat(&mut ec, 20).unwrap();
+        assert!(!no_response_dispute_allowed(&ec, 30));
+        assert!(no_response_dispute_allowed(&ec, 31));
+
+        ec.heartbeat_required = false;
+        assert!(!no_response_dispute_allowed(&ec, 31));
+        assert!(no_response_dispute_allowed(&ec, 61));
+    }
+
+    #[test]
+    fn escrow_call_keys_sit_at_stable_offsets() {
+        for call_id_len in [1, MAX_CALL_ID_LEN] {
+            let mut ec = base_call();
+            ec.call_id = "c".repeat(call_id_len);
+            ec.service_id = "s".repeat(call_id_len.min(MAX_SERVICE_ID_LEN));
+            let mut data = vec![0u8; 8 + EscrowCall::INIT_SPACE];
+            ec.try_serialize(&mut &mut data[..]).unwrap();
+            assert_eq!(
+                &data[ESCROW_CALL_PAYER_OFFSET..ESCROW_CALL_PAYER_OFFSET + 32],
+                ec.payer.as_ref()
+            );
+            assert_eq!(
+                &data[ESCROW_CALL_PROVIDER_OFFSET..ESCROW_CALL_PROVIDER_OFFSET + 32],
+                ec.provider.as_ref()
+            );
+        }
+        assert_eq!(ESCROW_CALL_PAYER_OFFSET, 9);
+        assert_eq!(ESCROW_CALL_PROVIDER_OFFSET, 41);
+    }
+
+    #[test]
+    fn content_cid_is_only_length_checked() {
+        validate_content_cid(&[]).unwrap();
+        validate_content_cid(&[0xff; MAX_CONTENT_CID_LEN]).unwrap();
+        assert_eq!(
+            validate_content_cid(&[b'b'; MAX_CONTENT_CID_LEN + 1]).unwrap_err(),
+            error!(AssuredError::ContentCidTooLong)
+        );
+    }
+
+    #[test]
+    fn final_partial_keeps_the_content_cid_through_v2() {
+        let mut ec = streaming_call(2, 1_000);
+        ec.content_cid = b"bafkreigh2akiscaildcqabsyg3dfr6chu3fgpregiymsck7e7aqa4s52zy".to_vec();
+        let call_id_hash = call_id_to_hash(&ec.call_id);
+        let v2 = EscrowCallV2::from_v1(call_id_hash, &ec);
+        assert_eq!(v2.to_v1().content_cid, ec.content_cid);
+        let bytes = v2.try_to_vec().unwrap();
+        assert!(bytes.len() <= EscrowCallV2::INIT_SPACE);
+    }
+
+    /// A call with every variable-size field at its maximum.
+    fn maximal_call() -> EscrowCall {
+        let mut ec = base_call();
+        ec.call_id = "c".repeat(MAX_CALL_ID_LEN);
+        ec.service_id = "s".repeat(MAX_SERVICE_ID_LEN);
+        ec.provider_sig = vec![0xff; MAX_PROVIDER_SIG_LEN];
+        ec.delivered_ts = Some(u64::MAX);
+        ec.appeal_filed_by = Some(Pubkey::new_unique());
+        ec.ruling = Some(Ruling::Refund as u8);
+        ec.payout_splits = splits(&[2_500; MAX_PAYOUT_SPLITS]);
+        ec.referrer = Some(Pubkey::new_unique());
+        ec.provider_eth_address = Some(KEY_ONE_ETH_ADDRESS);
+        ec.content_cid = vec![b'b'; MAX_CONTENT_CID_LEN];
+        ec.sla_tiers = tiered_call().state.sla_tiers;
+        ec.sla_tiers.resize(
+            MAX_SLA_TIERS,
+            SlaTier {
+                deadline_factor_bps: u32::MAX,
+                multiplier_bps: 0,
+            },
+        );
+        ec.redemption_code = Some([0xff; 16]);
+        ec.arbiters = vec![Pubkey::new_unique(); MAX_ARBITERS];
+        ec
+    }
+
+    #[test]
+    fn maximal_escrow_call_fits_declared_space() {
+        let ec = maximal_call();
+        let mut data = vec![0u8; 8 + EscrowCall::INIT_SPACE];
+        ec.try_serialize(&mut &mut data[..]).unwrap();
+        assert_eq!(ec.try_to_vec().unwrap().len(), EscrowCall::INIT_SPACE);
+
+        let approval = empty_approval();
+        assert!(approval.try_to_vec().unwrap().len() <= PayerProviderApproval::INIT_SPACE);
+        assert!(base_config().try_to_vec().unwrap().len() <= Config::INIT_SPACE);
+        let mut ps = proof_set(MAX_PROOF_CHUNKS as u32);
+        ps.call_id = "c".repeat(MAX_CALL_ID_LEN);
+        assert_eq!(
+            ps.try_to_vec().unwrap().len(),
+            FulfillmentProofSet::INIT_SPACE
+        );
+    }
+
+    #[test]
+    fn settle_frames_stay_small_with_maximal_calls() {
+        // Anchor builds the accounts struct on the handler's stack; boxed
+        // accounts keep it to a pointer per field.
+        for (name, size) in [
+            ("Settle", std::mem::size_of::<Settle>()),
+            ("SettleV2", std::mem::size_of::<SettleV2>()),
+            ("InitPayment", std::mem::size_of::<InitPayment>()),
+        ] {
+            assert!(size <= 1_024, "{name} accounts take {size} bytes");
+        }
+
+        let mut ec = maximal_call();
+        ec.ruling = None;
+        ec.disputed = false;
+        ec.delivered_ts = Some(ec.start_ts + 1);
+        let outcome = evaluate_settlement(&ec, ec.start_ts + 1);
+        let payout = release_payout(&ec, 0, ec.total_units);
+        let released = released_event(&ec, payout);
+        assert_eq!(released.call_id.len(), MAX_CALL_ID_LEN);
+        let refunded = refunded_event(&ec, ec.amount, 0);
+        assert_eq!(refunded.call_id, released.call_id);
+        assert!(settle_memo(&ec.call_id, ec.status).contains(&ec.call_id));
+        assert_ne!(outcome, SettlementOutcome::Pending);
+    }
+
+    #[test]
+    fn legacy_escrow_layout_migrates_into_current() {
+        let legacy = EscrowCallV0 {
+            call_id: "legacy-call".to_string(),
+            payer: Pubkey::new_unique(),
+            service_id: "svc".to_string(),
+            provider: Pubkey::new_unique(),
+            amount: 500,
+            start_ts: 100,
+            sla_ms: 2_500,
+            dispute_window_s: 30,
+            status: Status::Fulfilled as u8,
+            delivered_ts: Some(102),
+            response_hash: [9u8; 32],
+            disputed: false,
+            total_units: 5,
+            units_released: 5,
+            provider_sig: b"sig".to_vec(),
+        };
+        let mut data = EscrowCall::DISCRIMINATOR.to_vec();
+        data.extend(legacy.try_to_vec().unwrap());
+        data.resize(8 + EscrowCallV0::LEN, 0);
+
+        let ec = EscrowCall::load_versioned(&data).unwrap();
+        assert_eq!(ec.version, ESCROW_CALL_VERSION);
+        assert_eq!(ec.call_id, "legacy-call");
+        assert_eq!(ec.payer, legacy.payer);
+        assert_eq!(ec.rent_recipient, legacy.payer);
+        assert_eq!(ec.provider, legacy.provider);
+        assert_eq!(ec.amount, 500);
+        assert_eq!(ec.delivered_ts, Some(102));
+        assert_eq!(ec.response_hash, [9u8; 32]);
+        assert_eq!(ec.units_released, 5);
+        assert_eq!(ec.provider_sig, b"sig".to_vec());
+        assert_eq!(ec.refund_after_ts, 100 + 3 + 30);
+
+        let mut migrated = vec![0u8; 8 + EscrowCall::INIT_SPACE];
+        ec.try_serialize(&mut &mut migrated[..]).unwrap();
+        let reloaded = EscrowCall::load_versioned(&migrated).unwrap();
+        assert_eq!(reloaded.try_to_vec().unwrap(), ec.try_to_vec().unwrap());
+    }
+
+    /// A version-2 account, written before the fields from `finalized_at`
+    /// on existed, ends where those fields begin.
+    fn short_v2_account(ec: &EscrowCall) -> Vec<u8> {
+        let mut data = Vec::new();
+        ec.try_serialize(&mut data).unwrap();
+        data[8] = 2;
+        while data.last() == Some(&0) {
+            data.pop();
+        }
+        data
+    }
+
+    #[test]
+    fn short_v2_escrow_reads_with_appended_defaults() {
+        let mut ec = base_call();
+        ec.payer = Pubkey::new_unique();
+        ec.rent_recipient = ec.payer;
+        ec.disputed = true;
+        ec.dispute_bond = 5_000;
+        ec.provider_attest_key = [7u8; 32];
+        let data = short_v2_account(&ec);
+        assert!(data.len() < 8 + EscrowCall::INIT_SPACE);
+
+        let migrated = EscrowCall::load_versioned(&data).unwrap();
+        assert_eq!(migrated.version, ESCROW_CALL_VERSION);
+        assert_eq!(migrated.call_id, ec.call_id);
+        assert_eq!(migrated.dispute_bond, 5_000);
+        assert_eq!(migrated.dispute_bond_state, BondCustody::Held as u8);
+        assert_eq!(migrated.sig_mode, SigMode::Ed25519 as u8);
+        assert_eq!(
+            migrated.expiry_ts,
+            default_expiry_ts(ec.start_ts, ec.sla_ms)
+        );
+    }
+
+    #[test]
+    fn v1_escrow_moves_keys_ahead_of_strings() {
+        let mut ec = base_call();
+        ec.call_id = "v1-call".to_string();
+        ec.service_id = "v1-service".to_string();
+        ec.payer = Pubkey::new_unique();
+        ec.provider = Pubkey::new_unique();
+        ec.rent_recipient = ec.payer;
+        let v2 = short_v2_account(&ec);
+        // Version 1 had the keys after their strings.
+        let strings_at = 9 + 64;
+        let call_id_len = 4 + ec.call_id.len();
+        let service_id_at = strings_at + call_id_len;
+        let rest_at = service_id_at + 4 + ec.service_id.len();
+        let mut v1 = v2[..8].to_vec();
+        v1.push(1);
+        v1.extend_from_slice(&v2[strings_at..service_id_at]);
+        v1.extend_from_slice(ec.payer.as_ref());
+        v1.extend_from_slice(&v2[service_id_at..rest_at]);
+        v1.extend_from_slice(ec.provider.as_ref());
+        v1.extend_from_slice(&v2[rest_at..]);
+        assert_eq!(v1.len(), v2.len());
+
+        let migrated = EscrowCall::load_versioned(&v1).unwrap();
+        assert_eq!(migrated.version, ESCROW_CALL_VERSION);
+        assert_eq!(migrated.payer, ec.payer);
+        assert_eq!(migrated.provider, ec.provider);
+        assert_eq!(migrated.call_id, "v1-call");
+        assert_eq!(migrated.service_id, "v1-service");
+        assert_eq!(migrated.amount, ec.amount);
+
+        let mut unknown = v2.clone();
+        unknown[8] = ESCROW_CALL_VERSION + 1;
+        assert!(EscrowCall::load_versioned(&unknown).is_err());
+    }
+
+    #[test]
+    fn v1_escrow_v2_moves_provider_ahead_of_service_id() {
+        let mut ec = base_call();
+        ec.payer = Pubkey::new_unique();
+        ec.rent_recipient = ec.payer;
+        ec.dispute_bond = 5_000;
+        ec.disputed = true;
+        let current = EscrowCallV2::from_v1([9; 32], &ec);
+        let mut data = Vec::new();
+        current.try_serialize(&mut data).unwrap();
+        // Version 1 had `provider` after `service_id`.
+        let service_id_at = 8 + 1 + 32 + 32;
+        let rest_at = service_id_at + 32 + 4 + ec.service_id.len();
+        let mut v1 = data[..service_id_at].to_vec();
+        v1[8] = 1;
+        v1.extend_from_slice(&data[service_id_at + 32..rest_at]);
+        v1.extend_from_slice(ec.provider.as_ref());
+        v1.extend_from_slice(&data[rest_at..]);
+        assert_eq!(v1.len(), data.len());
+
+        let migrated = EscrowCallV2::load_versioned(&v1).unwrap();
+        assert_eq!(migrated.version, ESCROW_CALL_VERSION);
+        assert_eq!(migrated.call_id, [9; 32]);
+        assert_eq!(migrated.provider, ec.provider);
+        assert_eq!(migrated.service_id, ec.service_id);
+        assert_eq!(migrated.dispute_bond_state, BondCustody::Held as u8);
+        assert_eq!(
+            EscrowCallV2::load_versioned(&data)
+                .unwrap()
+                .try_to_vec()
+                .unwrap(),
+            current.try_to_vec().unwrap()
+        );
+
+        let mut unknown = data;
+        unknown[8] = ESCROW_CALL_VERSION + 1;
+        assert!(EscrowCallV2::load_versioned(&unknown).is_err());
+    }
+
+    fn leg(call_id: &str, amount: u64) -> BatchInitLeg {
+        BatchInitLeg {
+            call_id: call_id.to_string(),
+            service_id: "svc".to_string(),
+            amount,
+            sla_ms: 2_000,
+            dispute_window_s: 10,
+            total_units: 4,
+        }
+    }
+
+    #[test]
+    fn batch_of_three_sums_amounts_and_builds_calls() {
+        let legs = vec![leg("a", 100), leg("b", 250), leg("c", 1)];
+        let total = plan_batch(&legs, 10_000, 1_000).unwrap();
+        assert_eq!(total, 351);
+
+        let payer = Pubkey::new_unique();
+        let provider = Pubkey::new_unique();
+        let ec = batch_call(&legs[1], payer, provider, 50);
+        assert_eq!(ec.call_id, "b");
+        assert_eq!(ec.amount, 250);
+        assert_eq!(ec.payer, payer);
+        assert_eq!(ec.provider, provider);
+        assert_eq!(ec.total_units, 4);
+        assert_eq!(ec.status, Status::Init as u8);
+        assert_eq!(ec.refund_after_ts, 50 + 2 + 10);
+    }
+
+    #[test]
+    fn underfunded_payer_rejected_before_transfer() {
+        let rent = 890_880;
+        assert_eq!(
+            check_payer_funds(999_999, 1_000_000, rent).unwrap_err(),
+            AssuredError::EscrowBalanceLow.into()
+        );
+        assert_eq!(
+            check_payer_funds(1_000_001, 1_000_000, rent).unwrap_err(),
+            AssuredError::EscrowBalanceLow.into()
+        );
+        check_payer_funds(1_000_000, 1_000_000, rent).unwrap();
+        check_payer_funds(1_000_000 + rent, 1_000_000, rent).unwrap();
+    }
+
+    #[test]
+    fn batch_rejects_short_payer_and_oversized_batches() {
+        let legs = vec![leg("a", 100), leg("b", 250), leg("c", 1)];
+        let err = plan_batch(&legs, 3_350, 1_000).err().unwrap();
+        assert_eq!(err, AssuredError::InsufficientFunds.into());
+        assert!(plan_batch(&legs, 3_351, 1_000).is_ok());
+
+        let too_many: Vec<_> = (0..=MAX_BATCH_INIT).map(|_| leg("x", 1)).collect();
+        let err = plan_batch(&too_many, u64::MAX, 0).err().unwrap();
+        assert_eq!(err, AssuredError::BatchTooLarge.into());
+        assert!(plan_batch(&[], u64::MAX, 0).is_err());
+    }
+
+    #[test]
+    fn call_params_reject_degenerate_inputs() {
+        let cases = [
+            ((0, 2_000, 10, 1), AssuredError::ZeroAmount),
+            ((1, 0, 10, 1), AssuredError::ZeroSla),
+            ((1, MAX_SLA_MS + 1, 10, 1), AssuredError::SlaTooLong),
+            (
+                (1, 2_000, MAX_DISPUTE_WINDOW_S + 1, 1),
+                AssuredError::DisputeWindowTooLong,
+            ),
+            ((1, 2_000, 10, 0), AssuredError::InvalidTotalUnits),
+            (
+                (1, 2_000, 10, MAX_TOTAL_UNITS + 1),
+                AssuredError::InvalidTotalUnits,
+            ),
+        ];
+        for ((amount, sla_ms, window, units), expected) in cases {
+            let err = validate_call_params(amount, sla_ms, window, units)
+                .err()
+                .unwrap();
+            assert_eq!(err, expected.into());
+        }
+    }
+
+    #[test]
+    fn batch_legs_count_in_their_service_stats() {
+        let (payer_key, mut payer_lamports, mut payer_data) = (Pubkey::new_unique(), 0u64, vec![]);
+        let system_owner = system_program::ID;
+        let payer_info = AccountInfo::new(
+            &payer_key,
+            true,
+            true,
+            &mut payer_lamports,
+            &mut payer_data,
+            &system_owner,
+            false,
+            0,
+        );
+        let payer = Signer::try_from(&payer_info).unwrap();
+        let (loader, mut sys_lamports, mut sys_data) =
+            (solana_sdk_ids::native_loader::ID, 0u64, vec![]);
+        let sys_info = AccountInfo::new(
+            &system_owner,
+            false,
+            false,
+            &mut sys_lamports,
+            &mut sys_data,
+            &loader,
+            true,
+            0,
+        );
+        let system = Program::<System>::try_from(&sys_info).unwrap();
+
+        let (key, _) = Pubkey::find_program_address(&[b"svc_stats", b"svc"], &crate::ID);
+        let (owner, mut lamports) = (crate::ID, 1u64);
+        let mut data = Vec::new();
+        EscrowStats::default().try_serialize(&mut data).unwrap();
+        let stats_info = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        record_batch_service_open(&stats_info, "svc", 700, &payer, &system).unwrap();
+        record_batch_service_open(&stats_info, "svc", 300, &payer, &system).unwrap();
+        let stats =
+            EscrowStats::try_deserialize(&mut &stats_info.try_borrow_data().unwrap()[..]).unwrap();
+        assert_eq!((stats.escrow_count, stats.volume_lamports), (2, 1_000));
+
+        // Another service's stats account is refused.
+        assert_eq!(
+            record_batch_service_open(&stats_info, "other", 1, &payer, &system).unwrap_err(),
+            error!(AssuredError::BatchAccountsMismatch)
+        );
+
+        // The program id in the slot skips the leg's stats.
+        let (program_id, mut skip_lamports, mut skip_data) = (crate::ID, 0u64, vec![]);
+        let skip = AccountInfo::new(
+            &program_id,
+            false,
+            false,
+            &mut skip_lamports,
+            &mut skip_data,
+            &system_owner,
+            false,
+            0,
+        );
+        record_batch_service_open(&skip, "svc", 1, &payer, &system).unwrap();
+    }
+
+    #[test]
+    fn call_params_accept_boundaries() {
+        assert!(validate_call_params(1, 1, 0, 1).is_ok());
+        assert!(
+            validate_call_params(u64::MAX, MAX_SLA_MS, MAX_DISPUTE_WINDOW_S, MAX_TOTAL_UNITS)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn counterparties_reject_self_dealing_and_default_provider() {
+        let payer = Pubkey::new_unique();
+        let err = validate_counterparties(&payer, &payer).err().unwrap();
+        assert_eq!(err, AssuredError::SelfDealingNotAllowed.into());
+        let err = validate_counterparties(&payer, &Pubkey::default())
+            .err()
+            .unwrap();
+        assert_eq!(err, AssuredError::InvalidProvider.into());
+        assert!(validate_counterparties(&payer, &Pubkey::new_unique()).is_ok());
+    }
+
+    /// Lays out an Ed25519 precompile instruction the way the client SDKs do:
+    /// header, then pubkey, signature and message in the same data buffer.
+    fn ed25519_ix(key: &[u8; 32], sig: &[u8; 64], message: &[u8]) -> Instruction {
+        let (key_off, sig_off, msg_off) = (16u16, 48u16, 112u16);
+        let mut data = vec![1u8, 0];
+        for v in [
+            sig_off,
+            u16::MAX,
+            key_off,
+            u16::MAX,
+            msg_off,
+            message.len() as u16,
+            u16::MAX,
+        ] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.extend_from_slice(key);
+        data.extend_from_slice(sig);
+        data.extend_from_slice(message);
+        Instruction {
+            program_id: solana_sdk_ids::ed25519_program::ID,
+            accounts: vec![],
+            data,
+        }
+    }
+
+    /// Ethereum address of the secp256k1 key with secret scalar 1.
+    const KEY_ONE_ETH_ADDRESS: [u8; 20] = [
+        0x7e, 0x5f, 0x45, 0x52, 0x09, 0x1a, 0x69, 0x12, 0x5d, 0x5d, 0xfc, 0xb7, 0xb8, 0xc2, 0x65,
+        0x90, 0x29, 0x39, 0x5b, 0xdf,
+    ];
+
+    fn secp256k1_ix(
+        index: u8,
+        eth_address: &[u8; 20],
+        sig: &[u8; 65],
+        message: &[u8],
+    ) -> Instruction {
+        let (addr_off, sig_off, msg_off) = (12u16, 32u16, 97u16);
+        let mut data = vec![1u8];
+        data.extend_from_slice(&sig_off.to_le_bytes());
+        data.push(index);
+        data.extend_from_slice(&addr_off.to_le_bytes());
+        data.push(index);
+        data.extend_from_slice(&msg_off.to_le_bytes());
+        data.extend_from_slice(&(message.len() as u16).to_le_bytes());
+        data.push(index);
+        data.extend_from_slice(eth_address);
+        data.extend_from_slice(sig);
+        data.extend_from_slice(message);
+        Instruction {
+            program_id: solana_sdk_ids::secp256k1_program::ID,
+            accounts: vec![],
+            data,
+        }
+    }
+
+    #[test]
+    fn secp256k1_check_binds_address_message_and_signature() {
+        let ec = base_call();
+        let sig = eth_sign(1, &ec.response_hash);
+        assert_eq!(
+            recovered_eth_address(&ec.response_hash, &sig),
+            KEY_ONE_ETH_ADDRESS
+        );
+        let ix = secp256k1_ix(2, &KEY_ONE_ETH_ADDRESS, &sig, &ec.response_hash);
+        verify_secp256k1_ix(&ix, 2, &KEY_ONE_ETH_ADDRESS, &ec.response_hash, &sig).unwrap();
+
+        let mut other = KEY_ONE_ETH_ADDRESS;
+        other[0] ^= 1;
+        assert!(verify_secp256k1_ix(&ix, 2, &other, &ec.response_hash, &sig).is_err());
+        assert!(verify_secp256k1_ix(&ix, 2, &KEY_ONE_ETH_ADDRESS, &[9u8; 32], &sig).is_err());
+        let mut flipped = sig;
+        flipped[64] ^= 1;
+        assert!(
+            verify_secp256k1_ix(&ix, 2, &KEY_ONE_ETH_ADDRESS, &ec.response_hash, &flipped).is_err()
+        );
+        // Offsets pointing at another instruction's data are rejected.
+        assert!(
+            verify_secp256k1_ix(&ix, 1, &KEY_ONE_ETH_ADDRESS, &ec.response_hash, &sig).is_err()
+        );
+        let ed = ed25519_ix(&[1u8; 32], &[3u8; 64], &ec.response_hash);
+        assert!(
+            verify_secp256k1_ix(&ed, 2, &KEY_ONE_ETH_ADDRESS, &ec.response_hash, &sig).is_err()
+        );
+    }
+
+    #[test]
+    fn attestation_verified_against_attest_key_not_provider() {
+        let ec = base_call();
+        let attest_key = [7u8; 32];
+        assert_ne!(attest_key, ec.provider.to_bytes());
+        let sig = [9u8; 64];
+        let message = attestation_message(&ec.call_id, &ec.response_hash, 1_000);
+        let ix = ed25519_ix(&attest_key, &sig, &message);
+
+        verify_ed25519_ix(&ix, &attest_key, &message, &sig).unwrap();
+
+        let err = verify_ed25519_ix(&ix, &ec.provider.to_bytes(), &message, &sig)
+            .err()
+            .unwrap();
+        assert_eq!(err, AssuredError::InvalidAttestation.into());
+
+        let other_message = attestation_message(&ec.call_id, &ec.response_hash, 1_001);
+        assert!(verify_ed25519_ix(&ix, &attest_key, &other_message, &sig).is_err());
+        assert!(verify_ed25519_ix(&ix, &attest_key, &message, &[0u8; 64]).is_err());
+
+        let mut wrong_program = ix.clone();
+        wrong_program.program_id = Pubkey::new_unique();
+        assert!(verify_ed25519_ix(&wrong_program, &attest_key, &message, &sig).is_err());
+    }
+
+    #[test]
+    fn signatures_cover_the_canonical_payloads() {
+        use payload::SigningPayload;
+        let ec = base_call();
+        let sig = [9u8; 64];
+        let [canonical, legacy] = fulfill_messages(&ec.call_id, &[2; 32], 1_000);
+        let expected = payload::FulfillPayload {
+            call_id: &ec.call_id,
+            response_hash: [2; 32],
+            ts: 1_000,
+        };
+        assert_eq!(canonical, expected.encode());
+        assert_eq!(legacy, attestation_message(&ec.call_id, &[2; 32], 1_000));
+
+        let message = dispute_message(&ec.call_id, 1, &[4; 32]);
+        let ix = ed25519_ix(&ec.payer.to_bytes(), &sig, &message);
+        verify_ed25519_ix(&ix, &ec.payer.to_bytes(), &message, &sig).unwrap();
+        assert!(verify_ed25519_ix(&ix, &ec.provider.to_bytes(), &message, &sig).is_err());
+        let other_kind = dispute_message(&ec.call_id, 2, &[4; 32]);
+        assert!(verify_ed25519_ix(&ix, &ec.payer.to_bytes(), &other_kind, &sig).is_err());
+    }
+
+    fn hashlocked_call(preimage: &[u8]) -> EscrowCall {
+        let mut ec = streaming_call(4, 1_000);
+        ec.hashlock = hash(preimage).to_bytes();
+        ec.refund_after_ts = 100;
+        ec
+    }
+
+    #[test]
+    fn redeem_rejects_wrong_preimage() {
+        let mut ec = hashlocked_call(b"key");
+        assert_eq!(
+            redeem_hashlock(&mut ec, b"nope", 10).err().unwrap(),
+            AssuredError::HashlockMismatch.into()
+        );
+        assert_eq!(ec.status, Status::Init as u8);
+        assert!(redeem_hashlock(&mut ec, &[0u8; MAX_PREIMAGE_LEN + 1], 10).is_err());
+
+        let mut plain = streaming_call(4, 1_000);
+        assert_eq!(
+            redeem_hashlock(&mut plain, b"key", 10).err().unwrap(),
+            AssuredError::NotHashlocked.into()
+        );
+    }
+
+    #[test]
+    fn redeem_rejected_while_disputed() {
+        let mut ec = hashlocked_call(b"key");
+        ec.disputed = true;
+        assert_eq!(
+            redeem_hashlock(&mut ec, b"key", 10).err().unwrap(),
+            AssuredError::AlreadyDisputed.into()
+        );
+        assert_eq!(ec.status, Status::Init as u8);
+        assert_eq!(ec.units_released, 0);
+    }
+
+    #[test]
+    fn redeem_rejected_after_timeout() {
+        let mut ec = hashlocked_call(b"key");
+        assert_eq!(
+            redeem_hashlock(&mut ec, b"key", 100).err().unwrap(),
+            AssuredError::RedeemExpired.into()
+        );
+        assert_eq!(evaluate_settlement(&ec, 100), SettlementOutcome::Refund);
+        assert_eq!(evaluate_settlement(&ec, 99), SettlementOutcome::Pending);
+    }
+
+    #[test]
+    fn redeem_pays_out_everything_and_skips_dispute_window() {
+        let mut ec = hashlocked_call(b"key");
+        let payout = redeem_hashlock(&mut ec, b"key", 10).unwrap();
+        assert_eq!(payout, 1_000);
+        assert_eq!(ec.status, Status::Fulfilled as u8);
+        assert_eq!(ec.units_released, ec.total_units);
+        assert_eq!(ec.provider_sig, b"key".to_vec());
+        assert!(redeem_hashlock(&mut ec, b"key", 11).is_err());
+
+        // Settle only closes the account: nothing is left to pay or dispute.
+        assert!(!within_dispute_window(&ec, 10));
+        assert_eq!(evaluate_settlement(&ec, 10), SettlementOutcome::Release);
+        assert_eq!(amount_for_units(&ec, ec.units_released, 0), 0);
+    }
+
+    #[test]
+    fn abort_keeps_streamed_units_paid_and_refunds_the_rest() {
+        let mut ec = streaming_call(4, 1_000);
+        let paid = apply_partial_release(&mut ec, [1u8; 32], 1, 1, &[], &[], 1).unwrap();
+        assert_eq!(paid.payout, 250);
+
+        let (refund, released) = abort_split(&ec).unwrap();
+        assert_eq!(released, 250);
+        assert_eq!(refund, 750);
+        assert_eq!(refund + released, ec.amount);
+
+        record_fulfillment(&mut ec, [2u8; 32], 2, &[], 2);
+        assert!(abort_split(&ec).is_err());
+    }
+
+    #[test]
+    fn abort_consent_signed_by_provider() {
+        let ec = streaming_call(4, 1_000);
+        let sig = [6u8; 64];
+        let message = abort_message(&ec.call_id, ec.start_ts);
+        let ix = ed25519_ix(&ec.provider.to_bytes(), &sig, &message);
+        verify_ed25519_ix(&ix, &proof_signing_key(&ec), &message, &sig).unwrap();
+        assert!(verify_ed25519_ix(&ix, &ec.payer.to_bytes(), &message, &sig).is_err());
+        let replay = abort_message(&ec.call_id, ec.start_ts + 1);
+        assert!(verify_ed25519_ix(&ix, &proof_signing_key(&ec), &replay, &sig).is_err());
+    }
+
+    #[test]
+    fn attest_key_only_comes_from_provider_registration() {
+        let mut ec = streaming_call(4, 1_000);
+        ec.provider_attest_key = registered_attest_key(None);
+        assert_eq!(proof_signing_key(&ec), ec.provider.to_bytes());
+
+        let keys = ProviderKeys {
+            provider: ec.provider,
+            attest_key: [7u8; 32],
+            eth_address: [0; 20],
+        };
+        ec.provider_attest_key = registered_attest_key(Some(&keys));
+        assert_eq!(proof_signing_key(&ec), [7u8; 32]);
+    }
+
+    #[test]
+    fn only_recorded_provider_submits_proof() {
+        let mut ec = base_call();
+        let provider = ec.provider;
+        let payer = ec.payer;
+        assert_eq!(
+            record_provider_proof(&mut ec, provider, [3; 32])
+                .err()
+                .unwrap(),
+            AssuredError::NotDisputed.into()
+        );
+
+        ec.disputed = true;
+        assert_eq!(
+            record_provider_proof(&mut ec, payer, [3; 32])
+                .err()
+                .unwrap(),
+            AssuredError::InvalidProvider.into()
+        );
+        assert!(record_provider_proof(&mut ec, Pubkey::new_unique(), [3; 32]).is_err());
+        record_provider_proof(&mut ec, provider, [3; 32]).unwrap();
+        assert_eq!(ec.provider_proof_hash, [3; 32]);
+
+        ec.ruling = Some(Ruling::Refund as u8);
+        assert!(record_provider_proof(&mut ec, provider, [4; 32]).is_err());
+        assert_eq!(ec.provider_proof_hash, [3; 32]);
+    }
+
+    #[test]
+    fn proof_signature_checked_against_provider_without_attest_key() {
+        let mut ec = base_call();
+        let sig = [5u8; 64];
+        let message = proof_message(&ec.call_id, &[3; 32]);
+        let ix = ed25519_ix(&ec.provider.to_bytes(), &sig, &message);
+        verify_ed25519_ix(&ix, &proof_signing_key(&ec), &message, &sig).unwrap();
+
+        ec.provider_attest_key = [7u8; 32];
+        assert!(verify_ed25519_ix(&ix, &proof_signing_key(&ec), &message, &sig).is_err());
+        assert_ne!(message, attestation_message(&ec.call_id, &[3; 32], 0));
+    }
+
+    #[test]
+    fn delivery_latency_is_clocked_from_start_to_fulfill() {
+        let mut ec = base_call();
+        ec.start_ts = 400;
+        assert_eq!(delivery_latency_ms(&ec), Some(600_000));
+        // The provider-supplied delivered_ts plays no part.
+        ec.delivered_ts = Some(401);
+        assert_eq!(delivery_latency_ms(&ec), Some(600_000));
+        ec.fulfilled_at = 0;
+        assert_eq!(delivery_latency_ms(&ec), None);
+    }
+
+    #[test]
+    fn data_volume_size_is_signed() {
+        let mut ec = base_call();
+        let message = data_attestation_message(&ec.call_id, &[2; 32], 1_000, 500, b"bafy");
+        let sig = [5u8; 64];
+        let ix = ed25519_ix(&ec.provider.to_bytes(), &sig, &message);
+        verify_ed25519_ix(&ix, &ec.provider.to_bytes(), &message, &sig).unwrap();
+        // A different size is a different message, so the signature fails.
+        let inflated = data_attestation_message(&ec.call_id, &[2; 32], 1_000, 501, b"bafy");
+        assert!(verify_ed25519_ix(&ix, &ec.provider.to_bytes(), &inflated, &sig).is_err());
+        // So does pointing the trace at different content.
+        let moved = data_attestation_message(&ec.call_id, &[2; 32], 1_000, 500, b"bafz");
+        assert!(verify_ed25519_ix(&ix, &ec.provider.to_bytes(), &moved, &sig).is_err());
+        assert!(!fulfill_messages(&ec.call_id, &[2; 32], 1_000).contains(&message));
+
+        // The legacy fulfill still serves per-unit calls only.
+        assert!(check_size_unsigned(&ec).is_ok());
+        ec.billing_mode = BillingMode::DataVolume as u8;
+        assert_eq!(
+            check_size_unsigned(&ec).unwrap_err(),
+            error!(AssuredError::ResponseSizeUnsigned)
+        );
+    }
+
+    fn proof_set(chunks: u32) -> FulfillmentProofSet {
+        FulfillmentProofSet {
+            call_id: "call-1".to_string(),
+            chunk_count: chunks,
+            submitted_count: 0,
+            proof_hashes: vec![[0u8; 32]; chunks as usize],
+            verified: false,
+            rent_payer: Pubkey::default(),
+        }
+    }
+
+    #[test]
+    fn chunk_proofs_accept_out_of_order_submission() {
+        let mut ps = proof_set(3);
+        ps.record_chunk(2, [3u8; 32]).unwrap();
+        ps.record_chunk(0, [1u8; 32]).unwrap();
+        let err = ps.mark_verified().err().unwrap();
+        assert_eq!(err, AssuredError::ProofSetIncomplete.into());
+        assert!(!ps.verified);
+
+        ps.record_chunk(1, [2u8; 32]).unwrap();
+        ps.mark_verified().unwrap();
+        assert!(ps.verified);
+        assert_eq!(ps.proof_hashes, vec![[1u8; 32], [2u8; 32], [3u8; 32]]);
+    }
+
+    #[test]
+    fn completing_a_chunked_call_needs_a_verified_proof_set() {
+        let mut ec = streaming_call(2, 100);
+        check_proof_set(&ec, None).unwrap();
+        ec.requires_proofs = true;
+        assert_eq!(
+            check_proof_set(&ec, None).unwrap_err(),
+            error!(AssuredError::ProofSetIncomplete)
+        );
+        let mut ps = proof_set(1);
+        assert_eq!(
+            check_proof_set(&ec, Some(&ps)).unwrap_err(),
+            error!(AssuredError::ProofSetIncomplete)
+        );
+        ps.record_chunk(0, [1u8; 32]).unwrap();
+        ps.mark_verified().unwrap();
+        check_proof_set(&ec, Some(&ps)).unwrap();
+    }
+
+    #[test]
+    fn chunk_proofs_reject_duplicates_and_bad_indices() {
+        let mut ps = proof_set(2);
+        ps.record_chunk(1, [5u8; 32]).unwrap();
+        let err = ps.record_chunk(1, [6u8; 32]).err().unwrap();
+        assert_eq!(err, AssuredError::ChunkAlreadySubmitted.into());
+        let err = ps.record_chunk(2, [6u8; 32]).err().unwrap();
+        assert_eq!(err, AssuredError::InvalidChunkProof.into());
+        let err = ps.record_chunk(0, [0u8; 32]).err().unwrap();
+        assert_eq!(err, AssuredError::InvalidChunkProof.into());
+        assert_eq!(ps.submitted_count, 1);
+    }
+
+    #[test]
+    fn clawback_prices_disputed_units_like_releases() {
+        let mut ec = streaming_call(3, 100);
+        ec.disputed = true;
+        apply_partial_release(&mut ec, [1u8; 32], 2, 1, &[], &[], 1).unwrap();
+        record_ruling(&mut ec, Ruling::Refund as u8, 0).unwrap();
+
+        let err = set_disputed_units(&mut ec, 3).err().unwrap();
+        assert_eq!(err, AssuredError::InvalidUnits.into());
+        set_disputed_units(&mut ec, 1).unwrap();
+        assert_eq!(clawback_request(&ec), amount_for_units(&ec, 0, 1));
+        assert_eq!(clawback_request(&ec), 34);
+
+        // The bond covers the full clawback, or only what it holds.
+        assert_eq!(reputation::clawback_amount(34, 1_000), 34);
+        assert_eq!(reputation::clawback_amount(34, 20), 20);
+    }
+
+    #[test]
+    fn clawback_requires_refund_ruling() {
+        let mut ec = streaming_call(3, 100);
+        ec.disputed = true;
+        apply_partial_release(&mut ec, [1u8; 32], 2, 1, &[], &[], 1).unwrap();
+        record_ruling(&mut ec, Ruling::Release as u8, 0).unwrap();
+        let err = set_disputed_units(&mut ec, 1).err().unwrap();
+        assert_eq!(err, AssuredError::InvalidRuling.into());
+        set_disputed_units(&mut ec, 0).unwrap();
+        assert_eq!(clawback_request(&ec), 0);
+    }
+
+    fn ruled_call(ruling: Ruling) -> EscrowCall {
+        let mut ec = base_call();
+        ec.disputed = true;
+        record_ruling(&mut ec, ruling as u8, 1_000).unwrap();
+        ec
+    }
+
+    #[test]
+    fn appeal_window_timing() {
+        let mut ec = ruled_call(Ruling::Release);
+        let (payer, provider) = (ec.payer, ec.provider);
+        let deadline = 1_000 + APPEAL_WINDOW_S;
+        assert_eq!(ec.appeal_deadline_ts, deadline);
+        assert_eq!(
+            evaluate_settlement(&ec, deadline as u64 - 1),
+            SettlementOutcome::Pending
+        );
+        assert_eq!(
+            evaluate_settlement(&ec, deadline as u64),
+            SettlementOutcome::Release
+        );
+
+        let err = file_appeal(&mut ec, payer, deadline).err().unwrap();
+        assert_eq!(err, AssuredError::AppealWindowClosed.into());
+        let err = file_appeal(&mut ec, provider, deadline - 1).err().unwrap();
+        assert_eq!(err, AssuredError::InvalidAppellant.into());
+
+        file_appeal(&mut ec, payer, deadline - 1).unwrap();
+        assert_eq!(
+            evaluate_settlement(&ec, deadline as u64 + 1),
+            SettlementOutcome::Pending
+        );
+        resolve_call_appeal(&mut ec, Ruling::Refund as u8).unwrap();
+        assert_eq!(
+            evaluate_settlement(&ec, deadline as u64 + 1),
+            SettlementOutcome::Refund
+        );
+    }
+
+    #[test]
+    fn double_appeal_and_double_ruling_rejected() {
+        let mut ec = ruled_call(Ruling::Refund);
+        let provider = ec.provider;
+        let err = record_ruling(&mut ec, Ruling::Release as u8, 1_500)
+            .err()
+            .unwrap();
+        assert_eq!(err, AssuredError::AlreadyRuled.into());
+
+        file_appeal(&mut ec, provider, 1_500).unwrap();
+        let err = file_appeal(&mut ec, provider, 1_600).err().unwrap();
+        assert_eq!(err, AssuredError::AppealAlreadyFiled.into());
+
+        resolve_call_appeal(&mut ec, Ruling::Release as u8).unwrap();
+        let err = resolve_call_appeal(&mut ec, Ruling::Refund as u8)
+            .err()
+            .unwrap();
+        assert_eq!(err, AssuredError::NoAppealPending.into());
+        assert_eq!(ec.ruling, Some(Ruling::Release as u8));
+
+        let mut undisputed = base_call();
+        assert!(record_ruling(&mut undisputed, Ruling::Refund as u8, 0).is_err());
+    }
+
+    #[test]
+    fn settles_release_when_sla_met_and_no_dispute() {
+        let ec = base_call();
+        let outcome = evaluate_settlement(&ec, 12_000);
+        assert_eq!(outcome, SettlementOutcome::Release);
+    }
+
+    #[test]
+    fn rent_sweeps_to_recorded_recipient_not_payer() {
+        let mut ec = base_call();
+        ec.rent_recipient = Pubkey::new_unique();
+        assert_ne!(ec.rent_recipient, ec.payer);
+        let (escrow_key, owner) = (Pubkey::new_unique(), crate::ID);
+        let system = system_program::ID;
+        let (mut escrow_lamports, mut payer_lamports, mut relayer_lamports) =
+            (2_000_000u64, 0u64, 0u64);
+        let (mut d1, mut d2, mut d3) = (vec![], vec![], vec![]);
+        let escrow = AccountInfo::new(
+            &escrow_key,
+            false,
+            true,
+            &mut escrow_lamports,
+            &mut d1,
+            &owner,
+            false,
+            0,
+        );
+        let payer = AccountInfo::new(
+            &ec.payer,
+            false,
+            true,
+            &mut payer_lamports,
+            &mut d2,
+            &system,
+            false,
+            0,
+        );
+        let relayer = AccountInfo::new(
+            &ec.rent_recipient,
+            false,
+            true,
+            &mut relayer_lamports,
+            &mut d3,
+            &system,
+            false,
+            0,
+        );
+        sweep_rent(&escrow, &relayer).unwrap();
+        assert_eq!(escrow.lamports(), 0);
+        assert_eq!(relayer.lamports(), 2_000_000);
+        assert_eq!(payer.lamports(), 0);
+    }
+
+    #[test]
+    fn rent_returns_to_relayer_on_release_and_refund() {
+        const RENT: u64 = 1_500_000;
+        let relayer_key = Pubkey::new_unique();
+        for outcome in [SettlementOutcome::Release, SettlementOutcome::Refund] {
+            let mut ec = base_call();
+            ec.rent_recipient = relayer_key;
+            ec.payer = Pubkey::new_unique();
+            let (escrow_key, owner, system) = (Pubkey::new_unique(), crate::ID, system_program::ID);
+            let mut lamports = [ec.amount + RENT, 0u64, 0u64, 0u64];
+            let [escrow_l, payer_l, provider_l, relayer_l] = &mut lamports;
+            let (mut d1, mut d2, mut d3, mut d4) = (vec![], vec![], vec![], vec![]);
+            let escrow = AccountInfo::new(
+                &escrow_key,
+                false,
+                true,
+                escrow_l,
+                &mut d1,
+                &owner,
+                false,
+                0,
+            );
+            let payer =
+                AccountInfo::new(&ec.payer, false, true, payer_l, &mut d2, &system, false, 0);
+            let provider = AccountInfo::new(
+                &ec.provider,
+                false,
+                true,
+                provider_l,
+                &mut d3,
+                &system,
+                false,
+                0,
+            );
+            let relayer = AccountInfo::new(
+                &relayer_key,
+                false,
+                true,
+                relayer_l,
+                &mut d4,
+                &system,
+                false,
+                0,
+            );
+
+            let to = if outcome == SettlementOutcome::Release {
+                &provider
+            } else {
+                &payer
+            };
+            pay_out(ec.amount, &escrow, to).unwrap();
+            sweep_rent(&escrow, &relayer).unwrap();
+
+            assert_eq!(to.lamports(), ec.amount);
+            assert_eq!(relayer.lamports(), RENT);
+            assert_eq!(escrow.lamports(), 0);
+        }
+    }
+
+    fn splits(bps: &[u16]) -> Vec<PayoutSplit> {
+        bps.iter()
+            .map(|bps| PayoutSplit {
+                recipient: Pubkey::new_unique(),
+                bps: *bps,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_payout_sends_dust_to_first_recipient() {
+        let table = splits(&[3_333, 3_333, 3_334]);
+        validate_payout_splits(&table).unwrap();
+        let shares = split_payout(1_001, &table);
+        assert_eq!(shares, vec![335, 333, 333]);
+        assert_eq!(shares.iter().sum::<u64>(), 1_001);
+        assert_eq!(
+            split_payout(10_000, &splits(&[7_000, 3_000])),
+            vec![7_000, 3_000]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_payout_splits() {
+        validate_payout_splits(&[]).unwrap();
+        for bad in [
+            splits(&[5_000, 4_999]),
+            splits(&[10_000, 0]),
+            splits(&[2_000, 2_000, 2_000, 2_000, 2_000]),
+        ] {
+            assert_eq!(
+                validate_payout_splits(&bad).unwrap_err(),
+                AssuredError::InvalidPayoutSplits.into()
+            );
+        }
+        let mut dup = splits(&[5_000, 5_000]);
+        dup[1].recipient = dup[0].recipient;
+        assert!(validate_payout_splits(&dup).is_err());
+    }
+
+    #[test]
+    fn only_the_provider_sets_payout_splits_before_settle() {
+        let mut ec = base_call();
+        let (provider, payer) = (ec.provider, ec.payer);
+        let table = splits(&[5_000, 5_000]);
+        assert_eq!(
+            apply_payout_splits(&mut ec, &payer, table.clone()).unwrap_err(),
+            AssuredError::InvalidProvider.into()
+        );
+        assert!(apply_payout_splits(&mut ec, &provider, splits(&[5_000])).is_err());
+        apply_payout_splits(&mut ec, &provider, table.clone()).unwrap();
+        assert_eq!(ec.payout_splits, table);
+        apply_payout_splits(&mut ec, &provider, Vec::new()).unwrap();
+        assert!(ec.payout_splits.is_empty());
+
+        ec.status = Status::Released as u8;
+        assert_eq!(
+            apply_payout_splits(&mut ec, &provider, table).unwrap_err(),
+            AssuredError::InvalidStatus.into()
+        );
+    }
+
+    fn pool(balance: u64) -> InsurancePool {
+        InsurancePool {
+            balance,
+            max_claim_bps: DEFAULT_INSURANCE_MAX_CLAIM_BPS,
+            claims_this_epoch: 0,
+            epoch: 0,
+        }
+    }
+
+    #[test]
+    fn insurance_covers_shortfall_up_to_claim_cap() {
+        let large = INSURANCE_THRESHOLD_LAMPORTS + 1;
+        let mut full = pool(10_000);
+        assert_eq!(full.claim(large, 3_000, 0), 3_000);
+        assert_eq!((full.balance, full.claims_this_epoch), (7_000, 1));
+
+        let mut low = pool(1_000);
+        assert_eq!(low.claim(large, 3_000, 0), 500);
+        assert_eq!(low.balance, 500);
+
+        assert_eq!(
+            pool(10_000).claim(INSURANCE_THRESHOLD_LAMPORTS, 3_000, 0),
+            0
+        );
+        let mut untouched = pool(10_000);
+        assert_eq!(untouched.claim(large, 0, 0), 0);
+        assert_eq!(untouched.claims_this_epoch, 0);
+    }
+
+    #[test]
+    fn insurance_claims_stop_at_epoch_cap() {
+        let large = INSURANCE_THRESHOLD_LAMPORTS + 1;
+        let mut pool = pool(u64::MAX / 2);
+        for _ in 0..MAX_CLAIMS_PER_EPOCH {
+            assert_eq!(pool.claim(large, 100, 7), 100);
+        }
+        assert_eq!(pool.claim(large, 100, 7), 0);
+        assert_eq!(pool.claim(large, 100, 8), 100);
+        assert_eq!((pool.epoch, pool.claims_this_epoch), (8, 1));
+        pool.claims_this_epoch = 0;
+        assert_eq!(pool.claim(large, 100, 8), 100);
+    }
+
+    #[test]
+    fn dispute_bond_forfeited_only_on_invalid_ruling() {
+        let (escrow_key, owner, system) = (Pubkey::new_unique(), crate::ID, system_program::ID);
+        let mut ec = base_call();
+        ec.disputed = true;
+        ec.dispute_bond = 400;
+        let (provider_key, payer_key) = (ec.provider, ec.payer);
+        let (mut escrow_lamports, mut provider_lamports, mut payer_lamports) =
+            (1_200u64, 0u64, 0u64);
+        let (mut d1, mut d2, mut d3) = (vec![], vec![], vec![]);
+        let escrow = AccountInfo::new(
+            &escrow_key,
+            false,
+            true,
+            &mut escrow_lamports,
+            &mut d1,
+            &owner,
+            false,
+            0,
+        );
+        let provider = AccountInfo::new(
+            &provider_key,
+            false,
+            true,
+            &mut provider_lamports,
+            &mut d2,
+            &system,
+            false,
+            0,
+        );
+        let payer = AccountInfo::new(
+            &payer_key,
+            false,
+            true,
+            &mut payer_lamports,
+            &mut d3,
+            &system,
+            false,
+            0,
+        );
+
+        ec.ruling = Some(Ruling::Release as u8);
+        hold_dispute_bond(&mut ec, 400);
+        assert!(settle_dispute_bond(&mut ec, &escrow, &provider, &payer).unwrap());
+        assert_eq!(provider.lamports(), 400);
+        assert_eq!(payer.lamports(), 0);
+        assert_eq!(ec.dispute_bond_state, BondCustody::Forfeited as u8);
+        assert_eq!(
+            settle_dispute_bond(&mut ec, &escrow, &provider, &payer).unwrap_err(),
+            error!(AssuredError::DisputeBondNotHeld)
+        );
 
-#[derive(Accounts)]
-pub struct RaiseDispute<'info> {
-    #[account(mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump)]
-    pub escrow_call: Account<'info, EscrowCall>,
-    pub reporter: Signer<'info>,
-}
+        ec.ruling = Some(Ruling::Refund as u8);
+        hold_dispute_bond(&mut ec, 400);
+        assert!(!settle_dispute_bond(&mut ec, &escrow, &provider, &payer).unwrap());
+        assert_eq!(payer.lamports(), 400);
+        assert_eq!(ec.dispute_bond_state, BondCustody::Returned as u8);
 
-#[derive(Accounts)]
-pub struct Settle<'info> {
-    #[account(mut, seeds=[b"call", escrow_call.call_id.as_bytes()], bump, close = payer)]
-    pub escrow_call: Account<'info, EscrowCall>,
-    #[account(mut)]
-    pub payer: SystemAccount<'info>,
-    #[account(mut)]
-    pub provider: SystemAccount<'info>,
-}
+        // Without a ruling the bond stays on the escrow.
+        ec.ruling = None;
+        hold_dispute_bond(&mut ec, 400);
+        assert_eq!(
+            settle_dispute_bond(&mut ec, &escrow, &provider, &payer).unwrap_err(),
+            error!(AssuredError::SettlementPending)
+        );
+        assert_eq!(ec.dispute_bond_state, BondCustody::Held as u8);
+        assert_eq!(escrow.lamports(), 400);
+        assert_eq!((payer.lamports(), provider.lamports()), (400, 400));
+    }
 
-#[account]
-pub struct EscrowCall {
-    pub call_id: String,
-    pub payer: Pubkey,
-    pub service_id: String,
-    pub provider: Pubkey,
-    pub amount: u64,
-    pub start_ts: u64,
-    pub sla_ms: u64,
-    pub dispute_window_s: u64,
-    pub status: u8, // 0 init, 1 fulfilled, 2 released, 3 refunded
-    pub delivered_ts: Option<u64>,
-    pub response_hash: [u8; 32],
-    pub disputed: bool,
-    pub total_units: u64,
-    pub units_released: u64,
-    pub provider_sig: Vec<u8>,
-}
+    #[test]
+    fn bonded_disputes_settle_only_after_a_ruling() {
+        let mut ec = base_call();
+        let payer = ec.payer;
+        open_dispute(&mut ec, &payer, 0, 1_005).unwrap();
+        hold_dispute_bond(&mut ec, 400);
+        // The payer can no longer dispute and then refund itself for free.
+        assert_eq!(evaluate_settlement(&ec, 12_000), SettlementOutcome::Pending);
 
-impl EscrowCall {
-    pub const MAX_LEN: usize = 4 + 64 // call_id (Anchor stores string as length prefix + data)
-        + 32 // payer
-        + 4 + 64 // service_id
-        + 32 // provider
-        + 8 // amount
-        + 8 // start_ts
-        + 8 // sla_ms
-        + 8 // dispute_window_s
-        + 1 // status
-        + 9 // delivered_ts (Option<u64>)
-        + 32 // response_hash
-        + 1 // disputed
-        + 8 // total_units
-        + 8 // units_released
-        + 4 + MAX_PROVIDER_SIG_LEN; // provider_sig vec
-}
+        // v2 escrows carry the bond through the same checks.
+        let v2 = EscrowCallV2::from_v1([7; 32], &ec).to_v1();
+        assert_eq!(v2.dispute_bond_state, BondCustody::Held as u8);
+        assert_eq!(evaluate_settlement(&v2, 12_000), SettlementOutcome::Pending);
+
+        record_ruling(&mut ec, Ruling::Refund as u8, 2_000).unwrap();
+        let after_appeals = (2_000 + APPEAL_WINDOW_S) as u64;
+        assert_eq!(
+            evaluate_settlement(&ec, after_appeals),
+            SettlementOutcome::Refund
+        );
+    }
+
+    #[test]
+    fn dispute_bond_custody_follows_the_ruling() {
+        let mut ec = base_call();
+        hold_dispute_bond(&mut ec, 0);
+        assert_eq!(ec.dispute_bond_state, BondCustody::None as u8);
+
+        ec.dispute_kind = 2;
+        hold_dispute_bond(&mut ec, 400);
+        assert_eq!(ec.dispute_bond_state, BondCustody::Held as u8);
+
+        // Upheld: the payer gets its bond back plus the kind's slash of
+        // the provider bond (50% of the escrow for a bad proof).
+        let reward =
+            reputation::slash_amount(ec.dispute_kind, ec.amount, u64::MAX, 20_000).unwrap();
+        assert_eq!(reward, ec.amount / 2);
+        // Capped by whatever the provider actually has bonded.
+        let reward = reputation::slash_amount(ec.dispute_kind, ec.amount, 7, 20_000).unwrap();
+        assert_eq!(reward, 7);
+    }
+
+    #[test]
+    fn split_release_needs_every_recipient_account() {
+        let table = splits(&[6_000, 4_000]);
+        let (escrow_key, owner, system) = (Pubkey::new_unique(), crate::ID, system_program::ID);
+        let provider_key = Pubkey::new_unique();
+        let (mut escrow_lamports, mut provider_lamports, mut a_lamports, mut b_lamports) =
+            (1_000u64, 0u64, 0u64, 0u64);
+        let (mut d1, mut d2, mut d3, mut d4) = (vec![], vec![], vec![], vec![]);
+        let escrow = AccountInfo::new(
+            &escrow_key,
+            false,
+            true,
+            &mut escrow_lamports,
+            &mut d1,
+            &owner,
+            false,
+            0,
+        );
+        let provider = AccountInfo::new(
+            &provider_key,
+            false,
+            true,
+            &mut provider_lamports,
+            &mut d2,
+            &system,
+            false,
+            0,
+        );
+        let first = AccountInfo::new(
+            &table[0].recipient,
+            false,
+            true,
+            &mut a_lamports,
+            &mut d3,
+            &system,
+            false,
+            0,
+        );
+        let mut second = AccountInfo::new(
+            &table[1].recipient,
+            false,
+            false,
+            &mut b_lamports,
+            &mut d4,
+            &system,
+            false,
+            0,
+        );
+        let partial = [first.clone()];
+        assert_eq!(
+            pay_out_split(500, &escrow, &provider, &table, &partial).unwrap_err(),
+            AssuredError::MissingSplitRecipient.into()
+        );
+        let read_only = [first.clone(), second.clone()];
+        assert!(pay_out_split(500, &escrow, &provider, &table, &read_only).is_err());
+        assert_eq!(escrow.lamports(), 1_000);
+
+        second.is_writable = true;
+        let all = [second.clone(), first.clone()];
+        pay_out_split(500, &escrow, &provider, &table, &all).unwrap();
+        assert_eq!(first.lamports(), 300);
+        assert_eq!(second.lamports(), 200);
+        assert_eq!(provider.lamports(), 0);
+
+        pay_out_split(100, &escrow, &provider, &[], &[]).unwrap();
+        assert_eq!(provider.lamports(), 100);
+    }
+
+    #[test]
+    fn payout_splits_leave_refunds_to_the_payer() {
+        let mut ec = base_call();
+        ec.payout_splits = splits(&[5_000, 5_000]);
+        ec.disputed = true;
+        assert_eq!(evaluate_settlement(&ec, 12_000), SettlementOutcome::Refund);
+        let event = refunded_event(&ec, ec.amount, 0);
+        assert_eq!(event.refund_amount, ec.amount);
+    }
+
+    #[test]
+    fn referral_cut_comes_out_of_release_payout() {
+        let mut ec = base_call();
+        assert_eq!(referral_cut(&ec, 1_000_000), None);
+        let referrer = Pubkey::new_unique();
+        ec.referrer = Some(referrer);
+        assert_eq!(referral_cut(&ec, 1_000_000), None);
+        ec.referral_fee_bps = 250;
+        assert_eq!(evaluate_settlement(&ec, 1_010), SettlementOutcome::Release);
+        assert_eq!(referral_cut(&ec, 1_000_000), Some((referrer, 25_000)));
+        assert_eq!(referral_cut(&ec, 3), Some((referrer, 0)));
+    }
+
+    #[test]
+    fn referral_skipped_when_refunded() {
+        let mut ec = base_call();
+        ec.referrer = Some(Pubkey::new_unique());
+        ec.referral_fee_bps = MAX_REFERRAL_FEE_BPS;
+        ec.disputed = true;
+        assert_eq!(evaluate_settlement(&ec, 12_000), SettlementOutcome::Refund);
+        assert_eq!(refunded_event(&ec, ec.amount, 0).refund_amount, ec.amount);
+    }
+
+    #[test]
+    fn referral_fee_capped_at_ten_percent() {
+        validate_referral_fee(MAX_REFERRAL_FEE_BPS).unwrap();
+        assert_eq!(
+            validate_referral_fee(MAX_REFERRAL_FEE_BPS + 1).unwrap_err(),
+            AssuredError::ReferralFeeTooHigh.into()
+        );
+        let mut ec = base_call();
+        ec.referrer = Some(Pubkey::new_unique());
+        ec.referral_fee_bps = MAX_REFERRAL_FEE_BPS;
+        assert_eq!(referral_cut(&ec, 1_000).unwrap().1, 100);
+    }
+
+    #[test]
+    fn paid_referrals_need_the_providers_approval() {
+        let referrer = Pubkey::new_unique();
+        let mut list = ProviderReferrers {
+            provider: Pubkey::new_unique(),
+            referrers: [Pubkey::default(); MAX_APPROVED_REFERRERS],
+            count: 0,
+        };
+        check_referrer(None, 500, None).unwrap();
+        check_referrer(Some(referrer), 0, None).unwrap();
+        assert_eq!(
+            check_referrer(Some(referrer), 500, None).unwrap_err(),
+            AssuredError::ReferrerNotApproved.into()
+        );
+        assert!(check_referrer(Some(referrer), 500, Some(&list)).is_err());
+
+        list.approve(referrer).unwrap();
+        check_referrer(Some(referrer), 500, Some(&list)).unwrap();
+        list.revoke(&referrer).unwrap();
+        assert!(check_referrer(Some(referrer), 500, Some(&list)).is_err());
+        assert!(list.revoke(&referrer).is_err());
+    }
+
+    #[test]
+    fn referral_cut_is_taken_from_partials_and_settle_alike() {
+        let mut ec = streaming_call(4, 1_000);
+        let referrer_key = Pubkey::new_unique();
+        ec.referrer = Some(referrer_key);
+        ec.referral_fee_bps = MAX_REFERRAL_FEE_BPS;
+        let (escrow_key, owner, system) = (Pubkey::new_unique(), crate::ID, system_program::ID);
+        let (mut escrow_l, mut referrer_l) = (ec.amount, 0u64);
+        let (mut d1, mut d2) = (vec![], vec![]);
+        let escrow = AccountInfo::new(
+            &escrow_key,
+            false,
+            true,
+            &mut escrow_l,
+            &mut d1,
+            &owner,
+            false,
+            0,
+        );
+        let referrer = AccountInfo::new(
+            &referrer_key,
+            false,
+            true,
+            &mut referrer_l,
+            &mut d2,
+            &system,
+            false,
+            0,
+        );
+
+        let chunk = apply_partial_release(&mut ec, [1u8; 32], 1, 1, &[], &[], 1).unwrap();
+        assert_eq!(
+            pay_referral(&ec, chunk.payout, &escrow, None).unwrap_err(),
+            AssuredError::InvalidReferrer.into()
+        );
+        let to_provider = pay_referral(&ec, chunk.payout, &escrow, Some(&referrer)).unwrap();
+        assert_eq!(to_provider, 225);
+        let rest = release_payout(&ec, chunk.payout, ec.total_units - ec.units_released);
+        assert_eq!(
+            pay_referral(&ec, rest, &escrow, Some(&referrer)).unwrap(),
+            675
+        );
+        assert_eq!(referrer.lamports(), 100);
+    }
+
+    #[test]
+    fn partial_released_event_reports_progress() {
+        let mut ec = streaming_call(4, 1_000);
+        let first = apply_partial_release(&mut ec, [1u8; 32], 1, 1, &[], &[], 1).unwrap();
+        let second = apply_partial_release(&mut ec, [2u8; 32], 2, 2, &[], &[], 2).unwrap();
+        assert_eq!(first.units, 1);
+        let event = partial_released_event(&ec, second.units);
+        assert_eq!(event.units, 2);
+        assert_eq!(event.units_released, 3);
+        assert_eq!(event.total_units, 4);
+        assert_eq!(event.total_units - event.units_released, 1);
+    }
+
+    #[test]
+    fn released_event_reports_latency_and_payout() {
+        let mut ec = streaming_call(4, 1_000);
+        ec.delivered_ts = Some(3);
+        ec.fulfilled_at = 3;
+        ec.sla_ms = 5_000;
+        apply_partial_release(&mut ec, [1u8; 32], 1, 1, &[], &[], 1).unwrap();
+        let remaining = amount_for_units(&ec, ec.units_released, 3);
+        let event = released_event(&ec, remaining);
+        assert_eq!(event.delivered_latency_ms, 3_000);
+        assert!(event.sla_met);
+        assert_eq!(
+            amount_for_units(&ec, 0, ec.units_released) + event.amount_to_provider,
+            ec.amount
+        );
+    }
+
+    #[test]
+    fn refunded_event_classifies_reason() {
+        let mut ec = streaming_call(4, 1_000);
+        apply_partial_release(&mut ec, [1u8; 32], 1, 1, &[], &[], 1).unwrap();
+        let event = refunded_event(&ec, 750, 250);
+        assert_eq!(event.reason, RefundReason::NeverDelivered as u8);
+        assert_eq!(
+            event.refund_amount + event.amount_already_released,
+            ec.amount
+        );
+
+        ec.delivered_ts = Some(10_000);
+        assert_eq!(
+            refunded_event(&ec, 0, 0).reason,
+            RefundReason::SlaMiss as u8
+        );
+
+        ec.disputed = true;
+        let event = refunded_event(&ec, 0, 0);
+        assert_eq!(event.reason, RefundReason::Dispute as u8);
+        assert!(event.disputed);
+    }
+
+    #[test]
+    fn multisig_two_of_three_unlocks_at_threshold() {
+        let signers = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        let mut ms = MultiSigConfig::new(&signers, 2).unwrap();
+        assert!(!ms.approve(&signers[0]).unwrap());
+        assert_eq!(
+            ms.approve(&signers[0]).unwrap_err(),
+            error!(AssuredError::AlreadyApproved)
+        );
+        assert_eq!(
+            ms.approve(&Pubkey::new_unique()).unwrap_err(),
+            error!(AssuredError::NotMultisigSigner)
+        );
+        assert_eq!(
+            ms.approve(&Pubkey::default()).unwrap_err(),
+            error!(AssuredError::NotMultisigSigner)
+        );
+        assert!(!ms.is_approved());
+        assert!(ms.approve(&signers[2]).unwrap());
+        assert_eq!(ms.approved_count, 2);
+
+        let mut ec = base_call();
+        ec.status = Status::AwaitingApproval as u8;
+        assert!(check_expiry(&ec, u64::MAX).is_err());
+        unlock_multisig_call(&mut ec, 5_000);
+        assert_eq!(ec.status, Status::Init as u8);
+        assert_eq!(ec.start_ts, 5_000);
+        assert_eq!(ec.expiry_ts, default_expiry_ts(5_000, ec.sla_ms));
+    }
+
+    #[test]
+    fn multisig_revoke_before_threshold_resets_the_approval() {
+        let signers = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+        let mut ms = MultiSigConfig::new(&signers, 2).unwrap();
+        ms.approve(&signers[1]).unwrap();
+        ms.revoke(&signers[1]).unwrap();
+        assert_eq!(ms.approved_count, 0);
+        assert!(!ms.approvals[1]);
+        assert_eq!(
+            ms.revoke(&signers[1]).unwrap_err(),
+            error!(AssuredError::NotApproved)
+        );
+        // The revoked signer's approval no longer counts toward the threshold.
+        assert!(!ms.approve(&signers[0]).unwrap());
+        assert!(ms.approve(&signers[1]).unwrap());
+    }
+
+    #[test]
+    fn multisig_payer_cancels_only_after_the_approval_window() {
+        let mut ec = base_call();
+        ec.status = Status::AwaitingApproval as u8;
+        ec.start_ts = 1_000;
+        let deadline = 1_000 + MULTISIG_APPROVAL_WINDOW_S;
+        assert_eq!(
+            check_multisig_cancel(&ec, deadline - 1).unwrap_err(),
+            error!(AssuredError::NotExpired)
+        );
+        check_multisig_cancel(&ec, deadline).unwrap();
+        // Once unlocked the call follows the normal refund paths instead.
+        unlock_multisig_call(&mut ec, deadline);
+        assert_eq!(
+            check_multisig_cancel(&ec, u64::MAX).unwrap_err(),
+            error!(AssuredError::InvalidStatus)
+        );
+    }
+
+    #[test]
+    fn multisig_rejects_bad_signer_sets() {
+        let a = Pubkey::new_unique();
+        let b = Pubkey::new_unique();
+        assert!(MultiSigConfig::new(&[], 1).is_err());
+        assert!(MultiSigConfig::new(&[a, a], 1).is_err());
+        assert!(MultiSigConfig::new(&[a, Pubkey::default()], 1).is_err());
+        assert!(MultiSigConfig::new(&[Pubkey::new_unique(); 6], 1).is_err());
+        assert_eq!(
+            MultiSigConfig::new(&[a, b], 3).err(),
+            Some(error!(AssuredError::InvalidMultisigThreshold))
+        );
+        assert!(MultiSigConfig::new(&[a, b], 0).is_err());
+        assert!(MultiSigConfig::new(&[a, b], 2).is_ok());
+    }
+
+    #[test]
+    fn timeout_refund_waits_out_the_grace_period() {
+        let mut ec = streaming_call(4, 1_000);
+        ec.refund_after_ts = 100;
+        let deadline = 100 + SETTLE_GRACE_PERIOD_S;
+        assert_eq!(
+            check_settle_timeout(&ec, deadline).unwrap_err(),
+            AssuredError::SettleGraceNotElapsed.into()
+        );
+        check_settle_timeout(&ec, deadline + 1).unwrap();
+
+        let mut disputed = ec.clone();
+        disputed.disputed = true;
+        assert!(check_settle_timeout(&disputed, deadline + 1).is_err());
+
+        let mut settled = ec.clone();
+        settled.status = Status::Released as u8;
+        assert!(check_settle_timeout(&settled, deadline + 1).is_err());
+    }
+
+    #[test]
+    fn timeout_refund_never_refunds_a_delivered_call() {
+        let mut ec = streaming_call(4, 1_000);
+        ec.refund_after_ts = 100;
+        record_fulfillment(&mut ec, [2u8; 32], 1, &[], 1);
+        assert_eq!(
+            check_settle_timeout(&ec, u64::MAX).unwrap_err(),
+            AssuredError::InvalidStatus.into()
+        );
+
+        let mut stale = streaming_call(4, 1_000);
+        stale.delivered_ts = Some(1);
+        assert_eq!(
+            check_settle_timeout(&stale, u64::MAX).unwrap_err(),
+            AssuredError::InvalidStatus.into()
+        );
+    }
+
+    #[test]
+    fn settles_refund_when_disputed_or_sla_missed() {
+        let mut disputed = base_call();
+        disputed.disputed = true;
+        let outcome = evaluate_settlement(&disputed, 12_000);
+        assert_eq!(outcome, SettlementOutcome::Refund);
+
+        let mut late = base_call();
+        late.delivered_ts = Some(10_000);
+        late.fulfilled_at = 10_000;
+        let outcome_late = evaluate_settlement(&late, 12_000);
+        assert_eq!(outcome_late, SettlementOutcome::Refund);
+    }
+
+    #[test]
+    fn late_undisputed_delivery_releases_less_the_penalty() {
+        let mut on_time = base_call();
+        on_time.late_penalty_bps = 2_500;
+        assert_eq!(
+            evaluate_settlement(&on_time, 12_000),
+            SettlementOutcome::Release
+        );
+
+        let mut late = base_call();
+        late.late_penalty_bps = 2_500;
+        late.delivered_ts = Some(10_000);
+        late.fulfilled_at = 10_000;
+        let outcome = evaluate_settlement(&late, 12_000);
+        assert_eq!(
+            outcome,
+            SettlementOutcome::ReleaseWithPenalty { bps: 2_500 }
+        );
+        check_settle_authority(&outcome, false).unwrap();
+        let payout = release_payout(&late, 0, late.total_units);
+        let penalty = late_penalty(payout, outcome.penalty_bps());
+        assert_eq!(penalty, 250_000);
+        assert_eq!(payout - penalty, 750_000);
+
+        let mut disputed = late.clone();
+        disputed.disputed = true;
+        assert_eq!(
+            evaluate_settlement(&disputed, 12_000),
+            SettlementOutcome::Refund
+        );
+    }
+
+    #[test]
+    fn late_penalty_rounds_down_and_is_capped_by_config() {
+        assert_eq!(late_penalty(999, 1), 0);
+        assert_eq!(late_penalty(u64::MAX, 10_000), u64::MAX);
+        let mut params = ConfigParams {
+            max_sla_ms: 1,
+            max_dispute_window_s: 1,
+            fee_bps: 0,
+            fulfill_grace_s: 0,
+            paused: false,
+            min_bond_required: 0,
+            dispute_bond_lamports: 0,
+            strict_pricing: false,
+            late_penalty_bps: 10_000,
+        };
+        params.validate().unwrap();
+        params.late_penalty_bps = 10_001;
+        assert_eq!(
+            params.validate().unwrap_err(),
+            error!(AssuredError::InvalidLatePenalty)
+        );
+    }
+
+    fn tiered_call() -> EscrowCall {
+        let mut ec = base_call();
+        ec.sla_tiers = vec![
+            SlaTier {
+                deadline_factor_bps: 15_000,
+                multiplier_bps: 7_500,
+            },
+            SlaTier {
+                deadline_factor_bps: 20_000,
+                multiplier_bps: 5_000,
+            },
+        ];
+        ec
+    }
+
+    #[test]
+    fn sla_tiers_pay_by_lateness_at_every_edge() {
+        // sla_ms = 2_000_000: full to 2_000s, 75% to 3_000s, 50% to 4_000s.
+        for (latency, payout_bps) in [
+            (0, 10_000),
+            (2_000, 10_000),
+            (2_001, 7_500),
+            (3_000, 7_500),
+            (3_001, 5_000),
+            (4_000, 5_000),
+            (4_001, 0),
+            (u64::MAX, 0),
+        ] {
+            let mut ec = tiered_call();
+            ec.start_ts = 1;
+            ec.delivered_ts = Some(latency);
+            ec.fulfilled_at = latency.saturating_add(1);
+            assert_eq!(tier_payout_bps(&ec), payout_bps, "latency {latency}");
+            let now = ec.fulfilled_at.saturating_add(ec.dispute_window_s);
+            let outcome = evaluate_settlement(&ec, now);
+            assert_eq!(outcome.payout_bps(), payout_bps, "latency {latency}");
+            assert_eq!(outcome, SettlementOutcome::from_payout_bps(payout_bps));
+        }
+
+        let mut ec = tiered_call();
+        ec.delivered_ts = Some(2_500);
+        ec.fulfilled_at = 2_500;
+        assert_eq!(
+            evaluate_settlement(&ec, 2_509),
+            SettlementOutcome::Refund,
+            "window still open"
+        );
+        ec.disputed = true;
+        assert_eq!(evaluate_settlement(&ec, 2_510), SettlementOutcome::Refund);
+        ec.disputed = false;
+        ec.delivered_ts = None;
+        ec.fulfilled_at = 0;
+        assert_eq!(
+            evaluate_settlement(&ec, ec.refund_after_ts),
+            SettlementOutcome::Refund
+        );
+    }
+
+    #[test]
+    fn tiered_split_rounds_toward_the_provider_and_loses_nothing() {
+        for payout in [0, 1, 3, 999, 1_000_001, u64::MAX] {
+            for payout_bps in [1, 2_500, 5_000, 7_500, 9_999] {
+                let outcome = SettlementOutcome::from_payout_bps(payout_bps);
+                let penalty = late_penalty(payout, outcome.penalty_bps());
+                let to_provider = payout - penalty;
+                let exact = payout as u128 * payout_bps as u128;
+                assert!(to_provider as u128 * BPS_DENOMINATOR as u128 >= exact);
+                assert!((to_provider as u128) * (BPS_DENOMINATOR as u128) < exact + 10_000);
+            }
+        }
+        assert_eq!(late_penalty(999, 2_500), 249);
+        assert_eq!(
+            SettlementOutcome::from_payout_bps(10_000),
+            SettlementOutcome::Release
+        );
+        assert_eq!(
+            SettlementOutcome::from_payout_bps(0),
+            SettlementOutcome::Refund
+        );
+    }
+
+    #[test]
+    fn empty_tiers_keep_all_or_nothing() {
+        let mut ec = base_call();
+        ec.fulfilled_at = 2_001;
+        assert_eq!(evaluate_settlement(&ec, 12_000), SettlementOutcome::Refund);
+        ec.fulfilled_at = 2_000;
+        assert_eq!(evaluate_settlement(&ec, 12_000), SettlementOutcome::Release);
+    }
+
+    #[test]
+    fn sla_tiers_must_widen_and_never_pay_more() {
+        let tier = |deadline_factor_bps, multiplier_bps| SlaTier {
+            deadline_factor_bps,
+            multiplier_bps,
+        };
+        validate_sla_tiers(&[]).unwrap();
+        validate_sla_tiers(&[tier(10_001, 10_000)]).unwrap();
+        validate_sla_tiers(&[
+            tier(12_500, 9_000),
+            tier(15_000, 7_500),
+            tier(20_000, 5_000),
+            tier(30_000, 0),
+        ])
+        .unwrap();
+        for bad in [
+            vec![tier(10_000, 5_000)],
+            vec![tier(15_000, 10_001)],
+            vec![tier(20_000, 5_000), tier(15_000, 2_500)],
+            vec![tier(15_000, 5_000), tier(15_000, 2_500)],
+            vec![tier(15_000, 5_000), tier(20_000, 7_500)],
+            vec![tier(11_000, 10); 5],
+        ] {
+            assert_eq!(
+                validate_sla_tiers(&bad).unwrap_err(),
+                error!(AssuredError::InvalidSlaTiers)
+            );
+        }
+    }
+
+    fn evidence_entry(tag: u8) -> EvidenceEntry {
+        EvidenceEntry {
+            evidence_hash: [tag; 32],
+            uri_hash: [tag.wrapping_add(1); 32],
+            submitted_at: tag as i64,
+        }
+    }
+
+    #[test]
+    fn evidence_slots_are_bounded_per_party() {
+        let mut evidence = Evidence {
+            call_id: "c".repeat(MAX_CALL_ID_LEN),
+            rent_payer: Pubkey::new_unique(),
+            payer_entries: Vec::new(),
+            provider_entries: Vec::new(),
+        };
+        for i in 0..MAX_EVIDENCE_PER_PARTY as u8 {
+            assert_eq!(
+                evidence
+                    .submit(EvidenceRole::Payer as u8, evidence_entry(i))
+                    .unwrap(),
+                i
+            );
+        }
+        assert_eq!(
+            evidence
+                .submit(EvidenceRole::Payer as u8, evidence_entry(9))
+                .unwrap_err(),
+            error!(AssuredError::EvidenceSlotsFull)
+        );
+        // The payer filling up does not eat into the provider's slots.
+        assert_eq!(
+            evidence
+                .submit(EvidenceRole::Provider as u8, evidence_entry(7))
+                .unwrap(),
+            0
+        );
+        assert_eq!(evidence.counts(), (MAX_EVIDENCE_PER_PARTY as u8, 1));
+        assert_eq!(evidence.provider_entries[0], evidence_entry(7));
+        assert_eq!(
+            evidence.submit(2, evidence_entry(1)).unwrap_err(),
+            error!(AssuredError::InvalidEvidenceRole)
+        );
+        assert!(
+            evidence.try_to_vec().unwrap().len() <= Evidence::INIT_SPACE,
+            "full evidence fits its account"
+        );
+    }
+
+    #[test]
+    fn orphan_rent_returns_to_the_funder_once_the_call_is_gone() {
+        let mut ps = proof_set(2);
+        ps.rent_payer = Pubkey::new_unique();
+        let mut data = Vec::new();
+        ps.try_serialize(&mut data).unwrap();
+        let (orphan_key, _) =
+            Pubkey::find_program_address(&[b"proofs", ps.call_id.as_bytes()], &crate::ID);
+        let (call_key, _) =
+            Pubkey::find_program_address(&[b"call", ps.call_id.as_bytes()], &crate::ID);
+        let (owner, system) = (crate::ID, system_program::ID);
+        let (mut orphan_lamports, mut call_lamports, mut payer_lamports) = (900u64, 5u64, 0u64);
+        let (mut d2, mut d3) = (vec![], vec![]);
+        let orphan = AccountInfo::new(
+            &orphan_key,
+            false,
+            true,
+            &mut orphan_lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        let parent = AccountInfo::new(
+            &call_key,
+            false,
+            false,
+            &mut call_lamports,
+            &mut d2,
+            &owner,
+            false,
+            0,
+        );
+        let payer = AccountInfo::new(
+            &ps.rent_payer,
+            false,
+            true,
+            &mut payer_lamports,
+            &mut d3,
+            &system,
+            false,
+            0,
+        );
+
+        // The call still exists, so nothing is orphaned yet.
+        assert_eq!(
+            reclaim_orphan(ORPHAN_PROOF_SET, &orphan, &parent, &payer).unwrap_err(),
+            error!(AssuredError::ParentCallOpen)
+        );
+        assert_eq!(
+            reclaim_orphan(ORPHAN_EVIDENCE, &orphan, &parent, &payer).unwrap_err(),
+            error!(ErrorCode::AccountDiscriminatorMismatch)
+        );
+        assert_eq!(
+            reclaim_orphan(ORPHAN_REVIEW, &orphan, &parent, &payer).unwrap_err(),
+            error!(AssuredError::InvalidOrphan)
+        );
+
+        **parent.try_borrow_mut_lamports().unwrap() = 0;
+        assert_eq!(
+            reclaim_orphan(ORPHAN_PROOF_SET, &orphan, &parent, &orphan).unwrap_err(),
+            error!(AssuredError::InvalidRentRecipient)
+        );
+        assert_eq!(
+            reclaim_orphan(ORPHAN_PROOF_SET, &orphan, &parent, &payer).unwrap(),
+            900
+        );
+        assert_eq!(orphan.lamports(), 0);
+        assert_eq!(payer.lamports(), 900);
+        assert!(orphan.try_borrow_data().unwrap().iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn evidence_only_from_the_parties_during_a_dispute() {
+        let mut ec = base_call();
+        ec.payer = Pubkey::new_unique();
+        let (payer, provider) = (ec.payer, ec.provider);
+        let payer_role = EvidenceRole::Payer as u8;
+        let provider_role = EvidenceRole::Provider as u8;
+        assert_eq!(
+            check_evidence_submitter(&ec, payer_role, &payer).unwrap_err(),
+            error!(AssuredError::NotDisputed)
+        );
+
+        ec.disputed = true;
+        check_evidence_submitter(&ec, payer_role, &payer).unwrap();
+        check_evidence_submitter(&ec, provider_role, &provider).unwrap();
+        for (role, who) in [
+            (payer_role, provider),
+            (provider_role, payer),
+            (payer_role, Pubkey::new_unique()),
+        ] {
+            assert_eq!(
+                check_evidence_submitter(&ec, role, &who).unwrap_err(),
+                error!(AssuredError::Unauthorized)
+            );
+        }
+
+        ec.ruling = Some(Ruling::Refund as u8);
+        assert_eq!(
+            check_evidence_submitter(&ec, payer_role, &payer).unwrap_err(),
+            error!(AssuredError::AlreadyRuled)
+        );
+    }
+
+    #[test]
+    fn settled_call_stays_readable_until_finalized() {
+        let mut ec = base_call();
+        ec.status = Status::Init as u8;
+        assert!(finalize_call(&mut ec, FINALIZE_DELAY_S).is_err());
+
+        ec.status = Status::Released as u8;
+        let mut data = vec![0u8; 8 + EscrowCall::INIT_SPACE];
+        ec.try_serialize(&mut &mut data[..]).unwrap();
+        let read = EscrowCall::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(read.status, Status::Released as u8);
+        assert_eq!(read.finalized_at, 0);
+
+        ec.settled_at = 50;
+        let ready = 50 + FINALIZE_DELAY_S;
+        assert_eq!(
+            finalize_call(&mut ec, ready - 1).unwrap_err(),
+            error!(AssuredError::FinalizeTooEarly)
+        );
+        assert_eq!(ec.status, Status::Released as u8);
+        assert_eq!(
+            finalize_call(&mut ec, ready).unwrap(),
+            Status::Released as u8
+        );
+        assert_eq!(ec.status, Status::Finalized as u8);
+        assert_eq!(ec.finalized_at, ready);
+        assert!(finalize_call(&mut ec, ready + 1).is_err());
+    }
 
-#[event]
-pub struct Fulfilled {
-    pub call_id: String,
-    pub ts: u64,
-}
-#[event]
-pub struct Released {
-    pub call_id: String,
-}
-#[event]
-pub struct Refunded {
-    pub call_id: String,
-}
-#[event]
-pub struct Disputed {
-    pub call_id: String,
-    pub kind: u8,
-    pub reason_hash: [u8; 32],
-}
-#[event]
-pub struct PartialReleased {
-    pub call_id: String,
-    pub units: u64,
-    pub total_units: u64,
-}
-#[event]
-pub struct TraceSaved {
-    pub call_id: String,
-    pub response_hash: [u8; 32],
-    pub provider_sig: Vec<u8>,
-}
+    #[test]
+    fn third_party_can_crank_release_after_window() {
+        let ec = base_call();
+        let early = evaluate_settlement(&ec, 1_005);
+        assert_eq!(early, SettlementOutcome::Refund);
+        assert_eq!(
+            check_settle_authority(&early, false).unwrap_err(),
+            AssuredError::PayerSignatureRequired.into()
+        );
+        assert!(check_settle_authority(&early, true).is_ok());
 
-#[error_code]
-pub enum AssuredError {
-    #[msg("Invalid status")]
-    InvalidStatus,
-    #[msg("Invalid provider")]
-    InvalidProvider,
-    #[msg("Invalid payer")]
-    InvalidPayer,
-    #[msg("Invalid reporter")]
-    InvalidReporter,
-    #[msg("Escrow account underfunded")]
-    EscrowBalanceLow,
-    #[msg("Provider signature too long")]
-    SignatureTooLong,
-    #[msg("Invalid units for partial release")]
-    InvalidUnits,
-}
+        let expired = evaluate_settlement(&ec, 1_010);
+        assert_eq!(expired, SettlementOutcome::Release);
+        assert!(check_settle_authority(&expired, false).is_ok());
 
-#[repr(u8)]
-pub enum Status {
-    Init = 0,
-    Fulfilled = 1,
-    Released = 2,
-    Refunded = 3,
-}
+        let mut disputed = base_call();
+        disputed.disputed = true;
+        let outcome = evaluate_settlement(&disputed, 1_010);
+        assert!(check_settle_authority(&outcome, false).is_err());
+    }
 
-#[derive(PartialEq, Eq, Debug)]
-pub enum SettlementOutcome {
-    Release,
-    Refund,
-}
+    #[test]
+    fn undelivered_call_refunds_from_deadline_second() {
+        let ec = streaming_call(2, 100);
+        assert_eq!(ec.refund_after_ts, 12);
+        assert_eq!(evaluate_settlement(&ec, 11), SettlementOutcome::Pending);
+        assert_eq!(evaluate_settlement(&ec, 12), SettlementOutcome::Refund);
 
-fn transfer_into_escrow<'info>(
-    payer: &Signer<'info>,
-    escrow: &Account<'info, EscrowCall>,
-    system_program: &Program<'info, System>,
-    amount: u64,
-) -> Result<()> {
-    if amount == 0 {
-        return Ok(());
+        let mut disputed = streaming_call(2, 100);
+        disputed.disputed = true;
+        assert_eq!(evaluate_settlement(&disputed, 0), SettlementOutcome::Refund);
     }
-    let accounts = Transfer {
-        from: payer.to_account_info(),
-        to: escrow.to_account_info(),
-    };
-    system_program::transfer(
-        CpiContext::new(system_program.to_account_info(), accounts),
-        amount,
-    )
-}
 
-fn pay_out<'info>(
-    amount: u64,
-    escrow: &AccountInfo<'info>,
-    destination: &AccountInfo<'info>,
-) -> Result<()> {
-    if amount == 0 {
-        return Ok(());
+    #[test]
+    fn partial_releases_do_not_move_refund_deadline() {
+        let mut ec = streaming_call(3, 90);
+        apply_partial_release(&mut ec, [1u8; 32], 1, 1, b"sig1", &[], 1).unwrap();
+        assert_eq!(ec.refund_after_ts, 12);
+        assert_eq!(evaluate_settlement(&ec, 11), SettlementOutcome::Pending);
+        assert_eq!(evaluate_settlement(&ec, 12), SettlementOutcome::Refund);
+
+        apply_partial_release(&mut ec, [2u8; 32], 2, 2, b"sig2", &[], 2).unwrap();
+        assert_eq!(evaluate_settlement(&ec, 11), SettlementOutcome::Refund);
+        assert_eq!(evaluate_settlement(&ec, 12), SettlementOutcome::Release);
     }
-    require!(escrow.lamports() >= amount, AssuredError::EscrowBalanceLow);
-    **escrow.try_borrow_mut_lamports()? -= amount;
-    **destination.try_borrow_mut_lamports()? += amount;
-    Ok(())
-}
 
-struct PartialReleaseState {
-    payout: u64,
-    units: u64,
-    total_units: u64,
-    emit_trace: bool,
-}
+    #[test]
+    fn backdated_delivery_does_not_shorten_dispute_window() {
+        let mut ec = base_call();
+        ec.status = Status::Init as u8;
+        ec.delivered_ts = None;
+        record_fulfillment(&mut ec, [2u8; 32], 1, &[], 1_000);
 
-fn apply_partial_release(
-    ec: &mut EscrowCall,
-    chunk_hash: [u8; 32],
-    units: u64,
-    ts: u64,
-    provider_sig: &[u8],
-) -> Result<PartialReleaseState> {
-    require!(units > 0, AssuredError::InvalidUnits);
-    let start_units = ec.units_released;
-    let new_total = start_units
-        .checked_add(units)
-        .ok_or(AssuredError::InvalidUnits)?;
-    require!(new_total <= ec.total_units, AssuredError::InvalidUnits);
+        assert!(within_dispute_window(&ec, 1_009));
+        assert!(!within_dispute_window(&ec, 1_010));
+        assert_eq!(evaluate_settlement(&ec, 1_009), SettlementOutcome::Refund);
+        assert_eq!(evaluate_settlement(&ec, 1_010), SettlementOutcome::Release);
+    }
 
-    let payout = amount_for_units(ec, start_units, units);
-    ec.units_released = new_total;
-    ec.response_hash = chunk_hash;
-    ec.provider_sig = provider_sig.to_vec();
+    #[test]
+    fn zero_dispute_window_settles_immediately_after_delivery() {
+        let mut ec = base_call();
+        ec.dispute_window_s = 0;
+        ec.sla_ms = 2_000;
+        ec.refund_after_ts = default_refund_after_ts(0, ec.sla_ms, 0);
+        ec.delivered_ts = Some(2);
+        ec.fulfilled_at = 2;
 
-    let mut emit_trace = false;
-    if ec.units_released == ec.total_units {
-        ec.delivered_ts = Some(ts);
-        ec.status = Status::Fulfilled as u8;
-        emit_trace = true;
+        assert_eq!(evaluate_settlement(&ec, 2), SettlementOutcome::Release);
+        assert!(!within_dispute_window(&ec, 2));
+
+        // Undelivered calls still wait out the SLA before refunding.
+        ec.status = Status::Init as u8;
+        ec.delivered_ts = None;
+        ec.fulfilled_at = 0;
+        assert!(within_dispute_window(&ec, 2));
+        assert_eq!(evaluate_settlement(&ec, 1), SettlementOutcome::Pending);
+        assert_eq!(evaluate_settlement(&ec, 2), SettlementOutcome::Refund);
     }
 
-    Ok(PartialReleaseState {
-        payout,
-        units,
-        total_units: ec.total_units,
-        emit_trace,
-    })
-}
+    #[test]
+    fn dispute_window_is_bounded() {
+        assert!(validate_call_params(1, 2_000, 0, 1).is_ok());
+        assert!(validate_call_params(1, 2_000, MAX_DISPUTE_WINDOW_S + 1, 1).is_err());
 
-fn amount_for_units(ec: &EscrowCall, start: u64, units: u64) -> u64 {
-    if units == 0 || ec.total_units == 0 {
-        return 0;
+        let mut params = ConfigParams {
+            max_sla_ms: 1,
+            max_dispute_window_s: MAX_DISPUTE_WINDOW_S,
+            fee_bps: 0,
+            fulfill_grace_s: 0,
+            paused: false,
+            min_bond_required: 0,
+            dispute_bond_lamports: 0,
+            strict_pricing: false,
+            late_penalty_bps: 0,
+        };
+        assert!(params.validate().is_ok());
+        params.max_dispute_window_s += 1;
+        assert!(params.validate().is_err());
     }
-    let base = ec.amount / ec.total_units;
-    let remainder = ec.amount % ec.total_units;
-    let mut total = base * units;
-    let remainder_units = remainder as u64;
-    if remainder_units > start {
-        let overlap_start = start;
-        let overlap_end = remainder_units.min(start.saturating_add(units));
-        if overlap_end > overlap_start {
-            total = total.saturating_add(overlap_end - overlap_start);
+
+    fn voucher_registry() -> VoucherRegistry {
+        VoucherRegistry {
+            authority: Pubkey::new_unique(),
+            codes: Vec::new(),
+            redeemed: Vec::new(),
+            discount_bps: Vec::new(),
         }
     }
-    total
-}
 
-fn evaluate_settlement(ec: &EscrowCall, now: u64) -> SettlementOutcome {
-    let delivered_within_sla = ec
-        .delivered_ts
-        .map(|ts| ts.saturating_sub(ec.start_ts) <= ec.sla_ms)
-        .unwrap_or(false);
-    let dispute_window_elapsed = ec
-        .delivered_ts
-        .map(|ts| now.saturating_sub(ts) >= ec.dispute_window_s)
-        .unwrap_or(true);
-    if !ec.disputed && delivered_within_sla && dispute_window_elapsed {
-        SettlementOutcome::Release
-    } else {
-        SettlementOutcome::Refund
+    #[test]
+    fn voucher_redeems_once_for_its_discount() {
+        let mut registry = voucher_registry();
+        registry.issue([7; 16], 2_500).unwrap();
+
+        let bps = registry.redeem(&[7; 16]).unwrap();
+        assert_eq!(bps, 2_500);
+        assert_eq!(voucher_discount(1_000_003, bps), 250_000);
+        assert!(registry.redeemed[0]);
+        assert_eq!(
+            registry.redeem(&[7; 16]).err(),
+            Some(error!(AssuredError::VoucherAlreadyRedeemed))
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn voucher_rejects_unknown_codes_and_bad_issues() {
+        let mut registry = voucher_registry();
+        assert_eq!(
+            registry.redeem(&[1; 16]).err(),
+            Some(error!(AssuredError::InvalidVoucher))
+        );
 
-    fn base_call() -> EscrowCall {
-        EscrowCall {
-            call_id: "call-1".to_string(),
-            payer: Pubkey::default(),
-            service_id: "svc".to_string(),
-            provider: Pubkey::new_unique(),
-            amount: 1_000_000,
-            start_ts: 0,
-            sla_ms: 2_000,
-            dispute_window_s: 10,
-            status: Status::Fulfilled as u8,
-            delivered_ts: Some(1_000),
-            response_hash: [0u8; 32],
-            disputed: false,
-            total_units: 1,
-            units_released: 1,
-            provider_sig: vec![],
+        assert!(registry.issue([1; 16], 0).is_err());
+        assert!(registry.issue([1; 16], BPS_DENOMINATOR as u16).is_err());
+        registry.issue([1; 16], 100).unwrap();
+        assert_eq!(
+            registry.issue([1; 16], 100).err(),
+            Some(error!(AssuredError::VoucherExists))
+        );
+        for i in 1..MAX_VOUCHERS {
+            registry.issue([i as u8 + 1; 16], 100).unwrap();
         }
+        assert_eq!(
+            registry.issue([0; 16], 100).err(),
+            Some(error!(AssuredError::VoucherRegistryFull))
+        );
+        // The largest discount still leaves a lamport to escrow.
+        assert_eq!(1 - voucher_discount(1, 9_999), 1);
     }
 
-    fn streaming_call(total_units: u64, amount: u64) -> EscrowCall {
-        EscrowCall {
-            call_id: "stream-call".to_string(),
-            payer: Pubkey::default(),
-            service_id: "svc".to_string(),
-            provider: Pubkey::new_unique(),
-            amount,
-            start_ts: 0,
-            sla_ms: 2_000,
-            dispute_window_s: 10,
-            status: Status::Init as u8,
-            delivered_ts: None,
-            response_hash: [0u8; 32],
-            disputed: false,
-            total_units,
-            units_released: 0,
-            provider_sig: vec![],
+    #[test]
+    fn extreme_amounts_and_units_do_not_panic() {
+        for policy in [
+            RemainderPolicy::FirstUnits,
+            RemainderPolicy::LastUnit,
+            RemainderPolicy::LastUnits,
+        ] {
+            let mut ec = streaming_call(MAX_TOTAL_UNITS, u64::MAX);
+            ec.remainder_policy = policy as u8;
+            assert_eq!(amount_for_units(&ec, 0, MAX_TOTAL_UNITS), u64::MAX);
+            assert_eq!(amount_for_units(&ec, u64::MAX, u64::MAX), u64::MAX);
+            assert_eq!(release_payout(&ec, 0, MAX_TOTAL_UNITS), u64::MAX);
         }
+
+        assert_eq!(late_penalty(u64::MAX, u16::MAX), u64::MAX);
+        assert_eq!(late_penalty(u64::MAX, 5_000), u64::MAX / 2);
+        assert_eq!(
+            voucher_discount(u64::MAX, 9_999),
+            18_444_899_399_302_180_659
+        );
+
+        let mut pool = InsurancePool {
+            balance: u64::MAX,
+            max_claim_bps: BPS_DENOMINATOR as u16,
+            claims_this_epoch: 0,
+            epoch: 0,
+        };
+        assert_eq!(pool.claim(u64::MAX, u64::MAX, 0), u64::MAX);
+        assert_eq!(pool.balance, 0);
     }
 
-    #[test]
-    fn amount_for_units_distributes_evenly() {
+    fn committee_call(arbiters: &[Pubkey], threshold: u8) -> EscrowCall {
         let mut ec = base_call();
-        ec.amount = 100;
-        ec.total_units = 3;
-        ec.units_released = 0;
-        assert_eq!(amount_for_units(&ec, 0, 1), 34);
-        assert_eq!(amount_for_units(&ec, 1, 1), 33);
-        assert_eq!(amount_for_units(&ec, 2, 1), 33);
-        assert_eq!(amount_for_units(&ec, 0, 3), 100);
+        ec.arbiters = arbiters.to_vec();
+        ec.arbiter_threshold = threshold;
+        ec.disputed = true;
+        ec.disputed_at_ts = 1_005;
+        ec
     }
 
-    #[test]
-    fn partial_release_updates_units_and_flags_trace() {
-        let mut ec = streaming_call(3, 90);
-        let first = apply_partial_release(&mut ec, [1u8; 32], 1, 1_000, b"sig1").unwrap();
-        assert_eq!(ec.units_released, 1);
-        assert_eq!(ec.status, Status::Init as u8);
-        assert_eq!(first.payout, 30);
-        assert!(!first.emit_trace);
-        assert_eq!(ec.provider_sig, b"sig1".to_vec());
+    fn empty_votes() -> Votes {
+        Votes {
+            call_start_ts: 0,
+            disputed_at_ts: 0,
+            verdicts: [None; MAX_ARBITERS],
+        }
+    }
 
-        let second = apply_partial_release(&mut ec, [2u8; 32], 2, 2_000, b"sig2").unwrap();
-        assert_eq!(ec.units_released, 3);
-        assert_eq!(ec.status, Status::Fulfilled as u8);
-        assert_eq!(ec.delivered_ts, Some(2_000));
-        assert_eq!(second.payout, 60);
-        assert!(second.emit_trace);
-        assert_eq!(ec.provider_sig, b"sig2".to_vec());
+    #[test]
+    fn arbiter_committee_needs_a_strict_majority() {
+        let keys: Vec<Pubkey> = (0..6).map(|_| Pubkey::new_unique()).collect();
+        validate_arbiters(&[], 0).unwrap();
+        validate_arbiters(&keys[..3], 2).unwrap();
+        validate_arbiters(&keys[..5], 5).unwrap();
+        for (arbiters, threshold) in [
+            (&keys[..0], 1),
+            (&keys[..3], 1),
+            (&keys[..4], 2),
+            (&keys[..3], 4),
+            (&keys[..6], 4),
+        ] {
+            assert_eq!(
+                validate_arbiters(arbiters, threshold).unwrap_err(),
+                error!(AssuredError::InvalidArbiterCommittee)
+            );
+        }
+        assert!(validate_arbiters(&[keys[0], keys[0], keys[1]], 2).is_err());
+        assert!(validate_arbiters(&[keys[0], Pubkey::default(), keys[1]], 2).is_err());
     }
 
     #[test]
-    fn partial_release_rejects_invalid_units() {
-        let mut ec = streaming_call(2, 50);
-        assert!(apply_partial_release(&mut ec, [1u8; 32], 0, 1_000, b"sig").is_err());
-        assert!(apply_partial_release(&mut ec, [1u8; 32], 3, 1_000, b"sig").is_err());
+    fn split_committee_votes_below_quorum_do_not_settle() {
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let ec = committee_call(&keys, 2);
+        let mut votes = empty_votes();
+        let now = 1_010;
+
+        let slot = check_arbiter_vote(&ec, &keys[0], now).unwrap();
+        votes.cast(&ec, slot, Ruling::Release as u8).unwrap();
+        let slot = check_arbiter_vote(&ec, &keys[2], now).unwrap();
+        votes.cast(&ec, slot, Ruling::Refund as u8).unwrap();
+
+        assert_eq!(votes.quorum(&ec), None);
+        assert_eq!(
+            committee_verdict(&ec, Some(&votes), now).unwrap_err(),
+            error!(AssuredError::QuorumNotReached)
+        );
+        assert_eq!(
+            evaluate_settlement(&ec, now as u64),
+            SettlementOutcome::Pending
+        );
+        // Votes are final, and only arbiters vote.
+        assert_eq!(
+            votes.cast(&ec, 0, Ruling::Refund as u8).unwrap_err(),
+            error!(AssuredError::AlreadyVoted)
+        );
+        assert_eq!(
+            check_arbiter_vote(&ec, &Pubkey::new_unique(), now).unwrap_err(),
+            error!(AssuredError::NotArbiter)
+        );
     }
 
     #[test]
-    fn settles_release_when_sla_met_and_no_dispute() {
-        let ec = base_call();
-        let outcome = evaluate_settlement(&ec, 12_000);
-        assert_eq!(outcome, SettlementOutcome::Release);
+    fn committee_quorum_rules_and_pays_out() {
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let mut ec = committee_call(&keys, 2);
+        let mut votes = empty_votes();
+        let now = 1_010;
+        votes.cast(&ec, 1, Ruling::Release as u8).unwrap();
+        votes.cast(&ec, 2, Ruling::Release as u8).unwrap();
+
+        let (ruling, quorum_reached) = committee_verdict(&ec, Some(&votes), now).unwrap();
+        assert_eq!((ruling, quorum_reached), (Ruling::Release as u8, true));
+        record_ruling(&mut ec, ruling, now).unwrap();
+        assert_eq!(
+            evaluate_settlement(&ec, (now + APPEAL_WINDOW_S) as u64),
+            SettlementOutcome::Release
+        );
+        assert_eq!(
+            check_arbiter_vote(&ec, &keys[0], now).unwrap_err(),
+            error!(AssuredError::AlreadyRuled)
+        );
+
+        // Votes from a previous dispute on the same call id never count.
+        let mut later = committee_call(&keys, 2);
+        later.disputed_at_ts = 2_000;
+        assert_eq!(votes.quorum(&later), None);
+        votes.cast(&later, 1, Ruling::Refund as u8).unwrap();
+        assert_eq!(votes.verdicts[2], None);
     }
 
     #[test]
-    fn settles_refund_when_disputed_or_sla_missed() {
-        let mut disputed = base_call();
-        disputed.disputed = true;
-        let outcome = evaluate_settlement(&disputed, 12_000);
-        assert_eq!(outcome, SettlementOutcome::Refund);
+    fn committee_without_quorum_refunds_after_the_vote_window() {
+        let keys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let mut ec = committee_call(&keys, 2);
+        let deadline = ec.disputed_at_ts + COMMITTEE_VOTE_WINDOW_S;
 
-        let mut late = base_call();
-        late.delivered_ts = Some(10_000);
-        let outcome_late = evaluate_settlement(&late, 12_000);
-        assert_eq!(outcome_late, SettlementOutcome::Refund);
+        assert!(committee_verdict(&ec, None, deadline - 1).is_err());
+        assert_eq!(
+            check_arbiter_vote(&ec, &keys[0], deadline).unwrap_err(),
+            error!(AssuredError::VotingClosed)
+        );
+        let (ruling, quorum_reached) = committee_verdict(&ec, None, deadline).unwrap();
+        assert_eq!((ruling, quorum_reached), (Ruling::Refund as u8, false));
+        record_ruling(&mut ec, ruling, deadline).unwrap();
+        assert_eq!(
+            evaluate_settlement(&ec, (deadline + APPEAL_WINDOW_S) as u64),
+            SettlementOutcome::Refund
+        );
     }
 }
@@ -1,10 +1,68 @@
 use anchor_lang::prelude::*;
 use anchor_lang::system_program::{self, Transfer};
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, TransferChecked};
 
-const ESCROW_PROGRAM_ID: Pubkey = pubkey!("6zpAcx4Yo9MmDf4w8pBGez8bm47zyKuyjr5Y5QkC3ayL");
+#[cfg(not(feature = "localnet"))]
+const DEFAULT_AUTHORIZED_CALLER: Option<Pubkey> =
+    Some(pubkey!("6zpAcx4Yo9MmDf4w8pBGez8bm47zyKuyjr5Y5QkC3ayL"));
+// Localnet deployments get fresh program ids, so the admin stands in until
+// set_authorized_caller points at the freshly deployed escrow.
+#[cfg(feature = "localnet")]
+const DEFAULT_AUTHORIZED_CALLER: Option<Pubkey> = None;
 const EWMA_ALPHA: f64 = 0.2;
 const QUANTILE_INC: f64 = 0.05;
 const QUANTILE_DEC: f64 = 0.01;
+const COMPLIANCE_ALPHA: f64 = 0.1;
+pub const COMPLIANCE_MAX_BPS: u16 = 10_000;
+pub const LATENCY_HISTORY_LEN: usize = 8;
+/// Bucket i counts samples in `[2^i, 2^(i+1))` ms; the last bucket also
+/// takes everything slower.
+pub const LATENCY_BUCKETS: usize = 16;
+/// Each full period a service sat idle doubles the next p95 step, so a
+/// stale estimate catches up with fresh samples faster.
+pub const P95_DECAY_PERIOD_S: i64 = 24 * 60 * 60;
+/// Caps the idle boost at `2^6`, so a service idle for six or more periods
+/// moves its p95 at most 64x the usual step per sample.
+pub const MAX_P95_DECAY_SHIFT: u32 = 6;
+/// About one sample every ten minutes over a two-day epoch.
+pub const DEFAULT_MAX_SAMPLES_PER_EPOCH: u16 = 288;
+/// Share of `composite_score` taken by uptime; compliance fills the rest.
+pub const UPTIME_WEIGHT: f32 = 0.3;
+pub const DEFAULT_OUTCOME_WINDOW_S: i64 = 7 * 24 * 60 * 60;
+/// How much the previous window's outcomes count next to the current one's.
+pub const PREV_WINDOW_WEIGHT: f32 = 0.5;
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+pub const DEFAULT_SLASH_CAP_MULTIPLIER_BPS: u32 = 20_000;
+/// Bond a suspended service must add to be reinstated.
+pub const REINSTATE_MIN_TOP_UP_LAMPORTS: u64 = 100_000_000;
+/// How long a reinstatement top-up stays locked in the bond.
+pub const REINSTATE_LOCK_S: i64 = 30 * 24 * 60 * 60;
+/// Wait between `stake_request_withdraw` and `stake_withdraw`; the stake
+/// still absorbs slashes meanwhile.
+pub const STAKE_COOLDOWN_S: i64 = 7 * 24 * 60 * 60;
+/// How long after the cooldown a withdrawal request stays usable; a
+/// staker who misses it must request again.
+pub const STAKE_WITHDRAW_WINDOW_S: i64 = 2 * 24 * 60 * 60;
+pub const MAX_STAKE_REWARD_BPS: u16 = 2_000;
+/// Outcome counts are weighted floats; the Wilson score reads them as
+/// fixed-point integers with this many units per outcome.
+pub const OUTCOME_COUNT_SCALE: f32 = 1_000.0;
+/// z for a 95% confidence interval, in hundredths.
+pub const WILSON_Z_CENTI: u128 = 196;
+/// Bumped to 2 for the fields appended from `is_active` on;
+/// `migrate_service` brings version-1 accounts up to date.
+pub const SERVICE_VERSION: u8 = 2;
+pub const HEALTH_FAILURE_THRESHOLD: u32 = 5;
+pub const BASE_SUSPENSION_S: i64 = 60 * 60;
+pub const MAX_SUSPENSION_S: i64 = 30 * 24 * 60 * 60;
+pub const UPDATE_QUOTA_PER_EPOCH: u16 = 100;
+pub const MAX_AUDIT_ENTRIES: usize = 64;
+const MAX_SERVICE_ID_LEN: usize = 64;
+/// Seed of the PDA, under the authorized escrow program, that signs the
+/// escrow-only instructions.
+pub const ESCROW_AUTHORITY_SEED: &[u8] = b"rep_authority";
+const BPS_DENOMINATOR: u64 = 10_000;
 
 declare_id!("8QFXHzWC1hDC7GQTNqBhsVRLURpYfXFBzT5Vb4NTxDh5");
 
@@ -12,15 +70,110 @@ declare_id!("8QFXHzWC1hDC7GQTNqBhsVRLURpYfXFBzT5Vb4NTxDh5");
 pub mod reputation {
     use super::*;
 
+    pub fn initialize_config(ctx: Context<InitializeConfig>, params: ConfigParams) -> Result<()> {
+        params.validate()?;
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.authorized_caller = DEFAULT_AUTHORIZED_CALLER.unwrap_or(config.admin);
+        config.apply(&params);
+        emit!(config.updated_event());
+        Ok(())
+    }
+
+    /// Reallocs a config written before its later fields existed and
+    /// rewrites it from `params`, keeping the stored admin and authorized
+    /// caller.
+    pub fn migrate_config(ctx: Context<MigrateConfig>, params: ConfigParams) -> Result<()> {
+        params.validate()?;
+        let info = ctx.accounts.config.to_account_info();
+        let (admin, authorized_caller) = {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() >= 8 + 64 && data[..8] == *Config::DISCRIMINATOR,
+                ErrorCode::AccountDiscriminatorMismatch
+            );
+            require!(
+                data.len() < 8 + Config::INIT_SPACE,
+                ReputationError::AlreadyMigrated
+            );
+            let key = |at: usize| Pubkey::new_from_array(data[at..at + 32].try_into().unwrap());
+            (key(8), key(40))
+        };
+        require_keys_eq!(
+            ctx.accounts.admin.key(),
+            admin,
+            ReputationError::InvalidAuthority
+        );
+        let mut config = Config {
+            admin,
+            authorized_caller,
+            ..Config::default()
+        };
+        config.apply(&params);
+        resize_account(
+            &info,
+            &ctx.accounts.admin,
+            &ctx.accounts.system_program,
+            8 + Config::INIT_SPACE,
+        )?;
+        config.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+        emit!(config.updated_event());
+        Ok(())
+    }
+
+    pub fn update_config(ctx: Context<UpdateConfig>, params: ConfigParams) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.check_admin(&ctx.accounts.admin.key())?;
+        params.validate()?;
+        config.apply(&params);
+        emit!(config.updated_event());
+        Ok(())
+    }
+
+    /// Sets the standing a payer needs before its reviews may move a
+    /// service's reputation, creating the account on first use.
+    pub fn set_reviewer_threshold(
+        ctx: Context<SetReviewerThreshold>,
+        min_completed_calls: u32,
+        min_payer_bond: u64,
+    ) -> Result<()> {
+        ctx.accounts.config.check_admin(&ctx.accounts.admin.key())?;
+        let threshold = &mut ctx.accounts.reviewer_threshold;
+        threshold.min_completed_calls = min_completed_calls;
+        threshold.min_payer_bond = min_payer_bond;
+        emit!(ReviewerThresholdUpdated {
+            min_completed_calls,
+            min_payer_bond,
+        });
+        Ok(())
+    }
+
+    pub fn set_authorized_caller(ctx: Context<SetAuthorizedCaller>, caller: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.authorized_caller = caller;
+        emit!(AuthorizedCallerUpdated { caller });
+        Ok(())
+    }
+
+    /// Creates the audit log for a service that predates it. Anyone may
+    /// pay for it; every instruction that changes the service's score or
+    /// bond requires it and appends to it.
+    pub fn init_audit_log(ctx: Context<InitAuditLog>, service_id: String) -> Result<()> {
+        ctx.accounts.audit_log.service_id = service_id;
+        Ok(())
+    }
+
     pub fn update_weighted(
         ctx: Context<Update>,
         service_id: String,
         outcome: u8,
         weight_f32: f32,
     ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ReputationError::ProgramPaused);
+        let epoch = Clock::get()?.epoch;
         let svc = &mut ctx.accounts.service;
         if svc.owner == Pubkey::default() {
-            svc.owner = ctx.accounts.payer.key();
+            svc.claim(ctx.accounts.payer.key(), epoch);
         } else {
             require_keys_eq!(
                 svc.owner,
@@ -28,13 +181,37 @@ pub mod reputation {
                 ReputationError::InvalidOwner
             );
         }
-        let w = weight_f32.clamp(0.0, 1.0);
-        svc.apply_outcome(outcome, w);
-        let _ = service_id; // seeds bind PDA; suppress unused
+        let weight = outcome_weight(weight_f32, ctx.accounts.config.max_outcome_weight)?;
+        svc.consume_update_quota(epoch)?;
+        let now = Clock::get()?.unix_timestamp;
+        let ok_before = svc.ok;
+        svc.apply_outcome(outcome, weight, now, ctx.accounts.config.outcome_window_s)?;
+        ctx.accounts.audit_log.claim(&service_id);
+        record_audit(
+            &mut ctx.accounts.audit_log,
+            AuditAction::Outcome,
+            now,
+            0,
+            svc.ok - ok_before,
+        );
+        if svc.track_failure_streak(outcome == 0, ctx.accounts.config.failure_threshold, now) {
+            emit!(ServiceSuspended {
+                service_id: service_id.clone(),
+                suspension_count: svc.suspension_count,
+                suspension_until_ts: svc.suspension_until_ts,
+            });
+        }
+        emit!(OutcomeRecorded {
+            service_id,
+            outcome,
+            weight,
+        });
         Ok(())
     }
 
-    pub fn bond_deposit(ctx: Context<Bond>, service_id: String, amount: u64) -> Result<()> {
+    /// Creates the service on first use, so a new provider can bond
+    /// before any reputation has been recorded.
+    pub fn bond_deposit(ctx: Context<BondDeposit>, service_id: String, amount: u64) -> Result<()> {
         require!(amount > 0, ReputationError::InvalidAmount);
         let service_info = ctx.accounts.service.to_account_info();
         transfer_into_service(
@@ -43,20 +220,264 @@ pub mod reputation {
             &ctx.accounts.system_program,
             amount,
         )?;
+        let clock = Clock::get()?;
+        ctx.accounts
+            .service
+            .deposit_bond(&ctx.accounts.provider.key(), amount, clock.epoch)?;
+        ctx.accounts.audit_log.claim(&service_id);
+        record_audit(
+            &mut ctx.accounts.audit_log,
+            AuditAction::BondDeposit,
+            clock.unix_timestamp,
+            audit_amount(amount),
+            0.0,
+        );
+        Ok(())
+    }
+
+    /// Stakes lamports behind a service for shares of its stake pool.
+    pub fn stake_deposit(
+        ctx: Context<StakeAccounts>,
+        service_id: String,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, ReputationError::InvalidAmount);
+        let service_info = ctx.accounts.service.to_account_info();
+        transfer_into_service(
+            &ctx.accounts.staker,
+            &service_info,
+            &ctx.accounts.system_program,
+            amount,
+        )?;
+        let shares = ctx.accounts.service.delegate(amount)?;
+        let stake = &mut ctx.accounts.stake;
+        stake.service = ctx.accounts.service.key();
+        stake.staker = ctx.accounts.staker.key();
+        stake.shares = stake.shares.saturating_add(shares);
+        emit!(StakeChanged {
+            service_id,
+            staker: stake.staker,
+            shares: stake.shares,
+            total_delegated: ctx.accounts.service.total_delegated,
+        });
+        Ok(())
+    }
+
+    /// Starts the `STAKE_COOLDOWN_S` countdown for `stake_withdraw`, which
+    /// must then follow within `STAKE_WITHDRAW_WINDOW_S`.
+    pub fn stake_request_withdraw(ctx: Context<StakeAccounts>, service_id: String) -> Result<()> {
+        require!(
+            ctx.accounts.stake.shares > 0,
+            ReputationError::InvalidAmount
+        );
+        ctx.accounts.stake.withdraw_requested_ts = Clock::get()?.unix_timestamp;
+        let _ = service_id;
+        Ok(())
+    }
+
+    pub fn stake_withdraw(
+        ctx: Context<StakeAccounts>,
+        service_id: String,
+        shares: u64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.stake.check_withdraw(shares, now)?;
+        let amount = ctx.accounts.service.undelegate(shares);
+        let service_info = ctx.accounts.service.to_account_info();
+        pay_out(
+            amount,
+            &service_info,
+            &ctx.accounts.staker.to_account_info(),
+        )?;
+        let stake = &mut ctx.accounts.stake;
+        stake.shares = stake.shares.saturating_sub(shares);
+        stake.withdraw_requested_ts = 0;
+        emit!(StakeChanged {
+            service_id,
+            staker: stake.staker,
+            shares: stake.shares,
+            total_delegated: ctx.accounts.service.total_delegated,
+        });
+        Ok(())
+    }
+
+    pub fn set_stake_reward_bps(ctx: Context<Bond>, service_id: String, bps: u16) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.service.owner,
+            ctx.accounts.provider.key(),
+            ReputationError::InvalidOwner
+        );
+        require!(
+            bps <= MAX_STAKE_REWARD_BPS,
+            ReputationError::InvalidStakeReward
+        );
+        ctx.accounts.service.stake_reward_bps = bps;
+        let _ = service_id;
+        Ok(())
+    }
+
+    /// Called by escrow after moving `amount` lamports of a release onto
+    /// the service PDA; adds them to the stake pool once the PDA's balance
+    /// shows they arrived.
+    pub fn distribute_stake_reward(
+        ctx: Context<RecordVolume>,
+        service_id: String,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .config
+            .check_caller(&ctx.accounts.authority.key())?;
+        let service_info = ctx.accounts.service.to_account_info();
+        let rent = Rent::get()?.minimum_balance(service_info.data_len());
+        ctx.accounts
+            .service
+            .check_reward_funded(service_info.lamports(), rent, amount)?;
         let svc = &mut ctx.accounts.service;
-        if svc.owner == Pubkey::default() {
-            svc.owner = ctx.accounts.provider.key();
-        }
+        svc.total_delegated = svc.total_delegated.saturating_add(amount);
+        emit!(StakeRewardDistributed {
+            service_id,
+            amount,
+            total_delegated: svc.total_delegated,
+        });
+        Ok(())
+    }
+
+    pub fn set_max_outstanding_calls(
+        ctx: Context<Bond>,
+        service_id: String,
+        max: u32,
+    ) -> Result<()> {
         require_keys_eq!(
-            svc.owner,
+            ctx.accounts.service.owner,
+            ctx.accounts.provider.key(),
+            ReputationError::InvalidOwner
+        );
+        ctx.accounts.service.max_outstanding_calls = max;
+        let _ = service_id;
+        Ok(())
+    }
+
+    /// Called by escrow when it opens a call against a capped service.
+    pub fn open_outstanding_call(ctx: Context<RecordVolume>, service_id: String) -> Result<()> {
+        ctx.accounts
+            .config
+            .check_caller(&ctx.accounts.authority.key())?;
+        let svc = &mut ctx.accounts.service;
+        svc.outstanding_calls = svc.outstanding_calls.saturating_add(1);
+        let _ = service_id;
+        Ok(())
+    }
+
+    /// Called by escrow when a counted call settles or is aborted.
+    pub fn close_outstanding_call(ctx: Context<RecordVolume>, service_id: String) -> Result<()> {
+        ctx.accounts
+            .config
+            .check_caller(&ctx.accounts.authority.key())?;
+        let svc = &mut ctx.accounts.service;
+        svc.outstanding_calls = svc.outstanding_calls.saturating_sub(1);
+        let _ = service_id;
+        Ok(())
+    }
+
+    pub fn set_latency_threshold(
+        ctx: Context<Bond>,
+        service_id: String,
+        threshold_ms: u64,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.service.owner,
+            ctx.accounts.provider.key(),
+            ReputationError::InvalidOwner
+        );
+        ctx.accounts.service.latency_alert_threshold_ms = threshold_ms;
+        let _ = service_id;
+        Ok(())
+    }
+
+    /// Permissionless: any signer may fire the alert once `alert_p95_ms`
+    /// crosses the owner's threshold. It stays latched until
+    /// `clear_latency_alert`.
+    pub fn emit_latency_alert(ctx: Context<UpdateLatency>, service_id: String) -> Result<()> {
+        let svc = &mut ctx.accounts.service;
+        svc.fire_latency_alert()?;
+        emit!(LatencyAlertFired {
+            service_id,
+            p95_ms: svc.alert_p95_ms(),
+            threshold_ms: svc.latency_alert_threshold_ms,
+        });
+        Ok(())
+    }
+
+    pub fn clear_latency_alert(ctx: Context<UpdateLatency>, service_id: String) -> Result<()> {
+        let provider = ctx.accounts.provider.key();
+        ctx.accounts.service.clear_latency_alert(&provider)?;
+        let _ = service_id;
+        Ok(())
+    }
+
+    pub fn set_price(
+        ctx: Context<Bond>,
+        service_id: String,
+        price_per_unit: u64,
+        pricing_opt_out: bool,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.service.owner,
             ctx.accounts.provider.key(),
             ReputationError::InvalidOwner
         );
-        svc.bond_balance = svc.bond_balance.saturating_add(amount);
+        let svc = &mut ctx.accounts.service;
+        svc.price_per_unit = price_per_unit;
+        svc.pricing_opt_out = pricing_opt_out;
         let _ = service_id;
         Ok(())
     }
 
+    /// Stops escrow from opening new calls against the service; open
+    /// calls still fulfill and settle as usual.
+    pub fn pause_service(ctx: Context<Bond>, service_id: String) -> Result<()> {
+        set_service_paused(ctx, service_id, true)
+    }
+
+    pub fn unpause_service(ctx: Context<Bond>, service_id: String) -> Result<()> {
+        set_service_paused(ctx, service_id, false)
+    }
+
+    /// Lifts a failure-streak suspension once its backoff has run out and
+    /// the owner adds at least `REINSTATE_MIN_TOP_UP_LAMPORTS` to the bond,
+    /// locked for `REINSTATE_LOCK_S`.
+    pub fn reinstate(ctx: Context<Bond>, service_id: String, top_up: u64) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.service.owner,
+            ctx.accounts.provider.key(),
+            ReputationError::InvalidOwner
+        );
+        let now = Clock::get()?.unix_timestamp;
+        check_reinstate(&ctx.accounts.service, top_up, now)?;
+        let service_info = ctx.accounts.service.to_account_info();
+        transfer_into_service(
+            &ctx.accounts.provider,
+            &service_info,
+            &ctx.accounts.system_program,
+            top_up,
+        )?;
+        let svc = &mut ctx.accounts.service;
+        svc.reinstate(top_up, now);
+        record_audit(
+            &mut ctx.accounts.audit_log,
+            AuditAction::BondDeposit,
+            now,
+            audit_amount(top_up),
+            0.0,
+        );
+        emit!(ServiceReinstated {
+            service_id,
+            top_up,
+            bond_locked_until_ts: svc.reinstate_lock_until_ts,
+        });
+        Ok(())
+    }
+
     pub fn bond_withdraw(ctx: Context<Bond>, service_id: String, amount: u64) -> Result<()> {
         require!(amount > 0, ReputationError::InvalidAmount);
         {
@@ -67,7 +488,7 @@ pub mod reputation {
                 ReputationError::InvalidOwner
             );
             require!(
-                svc.bond_balance >= amount,
+                svc.withdrawable_bond(Clock::get()?.unix_timestamp) >= amount,
                 ReputationError::InsufficientBond
             );
         }
@@ -78,26 +499,269 @@ pub mod reputation {
 
         let svc = &mut ctx.accounts.service;
         svc.bond_balance = svc.bond_balance.saturating_sub(amount);
+        record_audit(
+            &mut ctx.accounts.audit_log,
+            AuditAction::BondWithdraw,
+            Clock::get()?.unix_timestamp,
+            -audit_amount(amount),
+            0.0,
+        );
         let _ = service_id;
         Ok(())
     }
 
-    pub fn bond_slash(ctx: Context<BondSlash>, service_id: String, amount: u64) -> Result<()> {
-        require!(
-            ctx.accounts.authority.key() == ESCROW_PROGRAM_ID,
-            ReputationError::InvalidAuthority
+    pub fn bond_slash(
+        ctx: Context<BondSlashSplit>,
+        service_id: String,
+        dispute_kind: u8,
+        escrow_amount: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .config
+            .check_caller(&ctx.accounts.authority.key())?;
+        let actual = slash_amount(
+            dispute_kind,
+            escrow_amount,
+            ctx.accounts.service.slashable_balance(),
+            ctx.accounts.config.slash_cap_multiplier_bps,
+        )?;
+        if actual > 0 {
+            let (to_recipient, to_treasury) = split_slash(actual, ctx.accounts.config.treasury_bps);
+            let service_info = ctx.accounts.service.to_account_info();
+            let recipient_info = ctx.accounts.recipient.to_account_info();
+            let treasury_info = ctx.accounts.treasury.to_account_info();
+            pay_out(to_recipient, &service_info, &recipient_info)?;
+            pay_out(to_treasury, &service_info, &treasury_info)?;
+            ctx.accounts.service.slash_bond_and_stakes(actual);
+            record_audit(
+                &mut ctx.accounts.audit_log,
+                AuditAction::BondSlash,
+                Clock::get()?.unix_timestamp,
+                -audit_amount(actual),
+                0.0,
+            );
+        }
+        let _ = service_id;
+        Ok(())
+    }
+
+    /// Token counterpart of `bond_deposit`, signed by the service owner.
+    /// The first deposit fixes the service's bond mint; the vault is the
+    /// service PDA's ATA for it.
+    pub fn bond_deposit_spl(ctx: Context<BondSpl>, service_id: String, amount: u64) -> Result<()> {
+        require!(amount > 0, ReputationError::InvalidAmount);
+        let svc = &mut ctx.accounts.service;
+        require_keys_eq!(
+            svc.owner,
+            ctx.accounts.provider.key(),
+            ReputationError::InvalidOwner
         );
-        let actual = {
-            let svc = &ctx.accounts.service;
-            amount.min(svc.bond_balance)
+        svc.set_bond_mint(ctx.accounts.mint.key())?;
+        svc.deposit_token_bond(amount);
+        let accounts = TransferChecked {
+            from: ctx.accounts.provider_token.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.vault.to_account_info(),
+            authority: ctx.accounts.provider.to_account_info(),
         };
+        token::transfer_checked(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), accounts),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+        record_audit(
+            &mut ctx.accounts.audit_log,
+            AuditAction::BondDeposit,
+            Clock::get()?.unix_timestamp,
+            audit_amount(amount),
+            0.0,
+        );
+        let _ = service_id;
+        Ok(())
+    }
+
+    pub fn bond_withdraw_spl(ctx: Context<BondSpl>, service_id: String, amount: u64) -> Result<()> {
+        require!(amount > 0, ReputationError::InvalidAmount);
+        let svc = &mut ctx.accounts.service;
+        require_keys_eq!(
+            svc.owner,
+            ctx.accounts.provider.key(),
+            ReputationError::InvalidOwner
+        );
+        require_keys_eq!(
+            svc.bond_mint,
+            ctx.accounts.mint.key(),
+            ReputationError::BondMintMismatch
+        );
+        svc.withdraw_token_bond(amount)?;
+        vault_transfer(
+            &ctx.accounts.vault,
+            &ctx.accounts.provider_token,
+            &ctx.accounts.mint,
+            &ctx.accounts.service,
+            &ctx.accounts.token_program,
+            &service_id,
+            ctx.bumps.service,
+            amount,
+        )?;
+        record_audit(
+            &mut ctx.accounts.audit_log,
+            AuditAction::BondWithdraw,
+            Clock::get()?.unix_timestamp,
+            -audit_amount(amount),
+            0.0,
+        );
+        Ok(())
+    }
+
+    /// Token counterpart of `bond_slash`, callable only by escrow. The slash
+    /// is capped at the token bond and paid to the recipient's ATA, with
+    /// the treasury's share to the treasury's ATA.
+    pub fn bond_slash_spl(
+        ctx: Context<BondSlashSpl>,
+        service_id: String,
+        dispute_kind: u8,
+        escrow_amount: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .config
+            .check_caller(&ctx.accounts.authority.key())?;
+        let slash_cap = ctx.accounts.config.slash_cap_multiplier_bps;
+        let actual =
+            ctx.accounts
+                .service
+                .slash_token_bond(dispute_kind, escrow_amount, slash_cap)?;
+        if actual > 0 {
+            let (to_recipient, to_treasury) = split_slash(actual, ctx.accounts.config.treasury_bps);
+            for (destination, amount) in [
+                (&ctx.accounts.recipient_token, to_recipient),
+                (&ctx.accounts.treasury_token, to_treasury),
+            ] {
+                vault_transfer(
+                    &ctx.accounts.vault,
+                    destination,
+                    &ctx.accounts.mint,
+                    &ctx.accounts.service,
+                    &ctx.accounts.token_program,
+                    &service_id,
+                    ctx.bumps.service,
+                    amount,
+                )?;
+            }
+            record_audit(
+                &mut ctx.accounts.audit_log,
+                AuditAction::BondSlash,
+                Clock::get()?.unix_timestamp,
+                -audit_amount(actual),
+                0.0,
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns up to `amount` of the performance bond to the recipient, for
+    /// releases the provider later lost on dispute. Callable only by escrow.
+    pub fn bond_clawback(ctx: Context<BondSlash>, service_id: String, amount: u64) -> Result<()> {
+        ctx.accounts
+            .config
+            .check_caller(&ctx.accounts.authority.key())?;
+        let actual = ctx.accounts.service.claw_back_performance_bond(amount);
         if actual > 0 {
             let service_info = ctx.accounts.service.to_account_info();
             let recipient_info = ctx.accounts.recipient.to_account_info();
             pay_out(actual, &service_info, &recipient_info)?;
+            record_audit(
+                &mut ctx.accounts.audit_log,
+                AuditAction::BondSlash,
+                Clock::get()?.unix_timestamp,
+                -audit_amount(actual),
+                0.0,
+            );
+        }
+        let _ = service_id;
+        Ok(())
+    }
+
+    pub fn deposit_performance_bond(
+        ctx: Context<Bond>,
+        service_id: String,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, ReputationError::InvalidAmount);
+        let service_info = ctx.accounts.service.to_account_info();
+        transfer_into_service(
+            &ctx.accounts.provider,
+            &service_info,
+            &ctx.accounts.system_program,
+            amount,
+        )?;
+        let svc = &mut ctx.accounts.service;
+        require_keys_eq!(
+            svc.owner,
+            ctx.accounts.provider.key(),
+            ReputationError::InvalidOwner
+        );
+        svc.deposit_performance_bond(amount);
+        record_audit(
+            &mut ctx.accounts.audit_log,
+            AuditAction::BondDeposit,
+            Clock::get()?.unix_timestamp,
+            audit_amount(amount),
+            0.0,
+        );
+        let _ = service_id;
+        Ok(())
+    }
+
+    pub fn withdraw_performance_bond(
+        ctx: Context<Bond>,
+        service_id: String,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, ReputationError::InvalidAmount);
+        {
             let svc = &mut ctx.accounts.service;
-            svc.bond_balance = svc.bond_balance.saturating_sub(actual);
+            require_keys_eq!(
+                svc.owner,
+                ctx.accounts.provider.key(),
+                ReputationError::InvalidOwner
+            );
+            svc.withdraw_performance_bond(amount)?;
         }
+        let service_info = ctx.accounts.service.to_account_info();
+        let provider_info = ctx.accounts.provider.to_account_info();
+        pay_out(amount, &service_info, &provider_info)?;
+        record_audit(
+            &mut ctx.accounts.audit_log,
+            AuditAction::BondWithdraw,
+            Clock::get()?.unix_timestamp,
+            -audit_amount(amount),
+            0.0,
+        );
+        let _ = service_id;
+        Ok(())
+    }
+
+    /// Grows a service written by an older layout to the current size and
+    /// rewrites it at `SERVICE_VERSION`; the payer covers the extra rent.
+    pub fn migrate_service(ctx: Context<MigrateService>, service_id: String) -> Result<()> {
+        let info = ctx.accounts.service.to_account_info();
+        let migrated = {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() < 8 + Service::INIT_SPACE || data.get(8) != Some(&SERVICE_VERSION),
+                ReputationError::AlreadyMigrated
+            );
+            Service::load_versioned(&data)?
+        };
+        resize_account(
+            &info,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            8 + Service::INIT_SPACE,
+        )?;
+        let mut data = info.try_borrow_mut_data()?;
+        migrated.try_serialize(&mut &mut data[..])?;
         let _ = service_id;
         Ok(())
     }
@@ -107,6 +771,8 @@ pub mod reputation {
         service_id: String,
         sample_ms: u64,
     ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ReputationError::ProgramPaused);
+        let gains = ctx.accounts.config.latency_gains();
         let svc = &mut ctx.accounts.service;
         if svc.owner == Pubkey::default() {
             svc.owner = ctx.accounts.provider.key();
@@ -116,25 +782,191 @@ pub mod reputation {
             ctx.accounts.provider.key(),
             ReputationError::InvalidOwner
         );
-        svc.record_latency(sample_ms);
-        let _ = service_id;
+        let clock = Clock::get()?;
+        svc.consume_latency_sample(clock.epoch, ctx.accounts.config.max_samples_per_epoch)?;
+        let now = clock.unix_timestamp;
+        let gains = gains.aged(svc.idle_s(now));
+        svc.record_latency_with(sample_ms, &gains);
+        svc.last_active_ts = now;
+        record_audit(
+            &mut ctx.accounts.audit_log,
+            AuditAction::LatencyUpdate,
+            now,
+            0,
+            0.0,
+        );
+        let bucket_index = latency_bucket(sample_ms);
+        emit!(HistogramUpdated {
+            service_id,
+            bucket_index: bucket_index as u8,
+            bucket_count: svc.latency_histogram[bucket_index],
+        });
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(service_id: String)]
-pub struct Update<'info> {
-    #[account(
-        init_if_needed,
-        payer = payer,
-        space = 8 + Service::MAX_LEN,
-        seeds=[b"svc", service_id.as_bytes()],
-        bump
-    )]
+    /// Records a probe run by the protocol's prober, signing as the config
+    /// admin; owners never report their own uptime.
+    pub fn record_uptime_check(
+        ctx: Context<ServiceGovernance>,
+        service_id: String,
+        is_ok: bool,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, ReputationError::ProgramPaused);
+        ctx.accounts.config.check_admin(&ctx.accounts.admin.key())?;
+        let svc = &mut ctx.accounts.service;
+        svc.record_uptime(is_ok);
+        emit!(UptimeRecorded {
+            service_id,
+            ratio_bps: (uptime_ratio(svc) * 10_000.0) as u16,
+        });
+        Ok(())
+    }
+
+    pub fn submit_health_check(
+        ctx: Context<HealthCheck>,
+        service_id: String,
+        is_healthy: bool,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.service.owner,
+            ctx.accounts.owner.key(),
+            ReputationError::InvalidOwner
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let health = &mut ctx.accounts.health;
+        health.owner = ctx.accounts.owner.key();
+        health.record(is_healthy, now);
+
+        let svc = &mut ctx.accounts.service;
+        if health.is_degraded() && svc.is_active {
+            svc.is_active = false;
+            emit!(ServiceDegraded { service_id });
+        }
+        Ok(())
+    }
+
+    /// Called by escrow when a settlement refunds the payer; each loss
+    /// doubles the suspension the previous one earned.
+    pub fn record_dispute_loss(ctx: Context<RecordDisputeLoss>, service_id: String) -> Result<()> {
+        ctx.accounts
+            .config
+            .check_caller(&ctx.accounts.authority.key())?;
+        let now = Clock::get()?.unix_timestamp;
+        let svc = &mut ctx.accounts.service;
+        svc.escrow_refunds = svc.escrow_refunds.saturating_add(1);
+        svc.record_suspension(now);
+        record_audit(
+            &mut ctx.accounts.audit_log,
+            AuditAction::Outcome,
+            now,
+            0,
+            0.0,
+        );
+        emit!(ServiceSuspended {
+            service_id,
+            suspension_count: svc.suspension_count,
+            suspension_until_ts: svc.suspension_until_ts,
+        });
+        Ok(())
+    }
+
+    /// Called by escrow when `settle` closes out a delivered call, with
+    /// the latency it clocked between the call's start and its fulfill.
+    pub fn record_delivery_latency(
+        ctx: Context<RecordDeliveryLatency>,
+        service_id: String,
+        latency_ms: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .config
+            .check_caller(&ctx.accounts.authority.key())?;
+        let gains = ctx.accounts.config.latency_gains();
+        ctx.accounts
+            .service
+            .record_delivery_latency(latency_ms, &gains);
+        record_audit(
+            &mut ctx.accounts.audit_log,
+            AuditAction::LatencyUpdate,
+            Clock::get()?.unix_timestamp,
+            0,
+            0.0,
+        );
+        let _ = service_id;
+        Ok(())
+    }
+
+    /// Called by escrow with what the provider earned on a call as it
+    /// ends: `released` when `settle` released it, otherwise the partial
+    /// releases a refunded or aborted call keeps.
+    pub fn record_volume(
+        ctx: Context<RecordVolume>,
+        service_id: String,
+        amount: u64,
+        released: bool,
+    ) -> Result<()> {
+        ctx.accounts
+            .config
+            .check_caller(&ctx.accounts.authority.key())?;
+        ctx.accounts.service.record_volume(amount, released);
+        let _ = service_id;
+        Ok(())
+    }
+
+    pub fn reset_quota_by_governance(
+        ctx: Context<ServiceGovernance>,
+        service_id: String,
+    ) -> Result<()> {
+        ctx.accounts.config.check_admin(&ctx.accounts.admin.key())?;
+        let epoch = Clock::get()?.epoch;
+        ctx.accounts.service.reset_update_quota(epoch);
+        let _ = service_id;
+        Ok(())
+    }
+
+    pub fn clear_suspension(ctx: Context<ServiceGovernance>, service_id: String) -> Result<()> {
+        ctx.accounts.config.check_admin(&ctx.accounts.admin.key())?;
+        ctx.accounts.service.clear_suspension();
+        let _ = service_id;
+        Ok(())
+    }
+
+    pub fn restore_service_health(ctx: Context<RestoreHealth>, service_id: String) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.service.owner,
+            ctx.accounts.owner.key(),
+            ReputationError::InvalidOwner
+        );
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.health.restore(now);
+        ctx.accounts.service.is_active = true;
+        let _ = service_id;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct Update<'info> {
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + Service::INIT_SPACE,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump
+    )]
     pub service: Account<'info, Service>,
     #[account(mut)]
     pub payer: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + ServiceAuditLog::INIT_SPACE,
+        seeds=[b"audit", service_id.as_bytes()],
+        bump
+    )]
+    pub audit_log: Account<'info, ServiceAuditLog>,
     pub system_program: Program<'info, System>,
 }
 
@@ -149,185 +981,3106 @@ pub struct Bond<'info> {
     pub service: Account<'info, Service>,
     #[account(mut)]
     pub provider: Signer<'info>,
+    #[account(mut, seeds=[b"audit", service_id.as_bytes()], bump)]
+    pub audit_log: Account<'info, ServiceAuditLog>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 #[instruction(service_id: String)]
-pub struct BondSlash<'info> {
+pub struct BondDeposit<'info> {
     #[account(
-        mut,
+        init_if_needed,
+        payer = provider,
+        space = 8 + Service::INIT_SPACE,
         seeds=[b"svc", service_id.as_bytes()],
         bump
     )]
     pub service: Account<'info, Service>,
-    /// CHECK: validated against known program id
-    pub authority: UncheckedAccount<'info>,
     #[account(mut)]
-    pub recipient: SystemAccount<'info>,
+    pub provider: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = provider,
+        space = 8 + ServiceAuditLog::INIT_SPACE,
+        seeds=[b"audit", service_id.as_bytes()],
+        bump
+    )]
+    pub audit_log: Account<'info, ServiceAuditLog>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 #[instruction(service_id: String)]
-pub struct UpdateLatency<'info> {
+pub struct BondSpl<'info> {
     #[account(
         mut,
         seeds=[b"svc", service_id.as_bytes()],
         bump
     )]
     pub service: Account<'info, Service>,
+    #[account(mut)]
     pub provider: Signer<'info>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = provider
+    )]
+    pub provider_token: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = provider,
+        associated_token::mint = mint,
+        associated_token::authority = service
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(mut, seeds=[b"audit", service_id.as_bytes()], bump)]
+    pub audit_log: Account<'info, ServiceAuditLog>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
 }
 
-#[account]
-pub struct Service {
-    pub owner: Pubkey,
-    pub ok: f32,
-    pub late: f32,
-    pub disputed: f32,
-    pub bond_balance: u64,
-    pub ewma_latency_ms: u64,
-    pub p95_est_ms: u64,
-    pub latency_samples: u64,
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct BondSlashSpl<'info> {
+    #[account(
+        mut,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump
+    )]
+    pub service: Account<'info, Service>,
+    /// Escrow's `ESCROW_AUTHORITY_SEED` PDA, signing through its CPI.
+    pub authority: Signer<'info>,
+    #[account(address = service.bond_mint @ ReputationError::BondMintMismatch)]
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = service
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    /// CHECK: only used to derive the recipient's ATA
+    #[account(constraint = recipient.key() != config.treasury @ ReputationError::InvalidSlashRecipient)]
+    pub recipient: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = recipient
+    )]
+    pub recipient_token: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = config.treasury
+    )]
+    pub treasury_token: Account<'info, TokenAccount>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds=[b"audit", service_id.as_bytes()], bump)]
+    pub audit_log: Account<'info, ServiceAuditLog>,
+    pub token_program: Program<'info, Token>,
 }
 
-impl Service {
-    pub const MAX_LEN: usize = 32 // owner
-        + 4 * 3 // outcome weights
-        + 8 // bond balance
-        + 8 // ewma latency
-        + 8 // p95 estimate
-        + 8; // sample count
-
-    pub fn apply_outcome(&mut self, outcome: u8, weight: f32) {
-        match outcome {
-            0 => self.ok += weight,
-            1 => self.late += weight,
-            2 => self.disputed += weight,
-            _ => {}
-        }
-    }
-
-    pub fn record_latency(&mut self, sample_ms: u64) {
-        let sample = sample_ms as f64;
-        if self.latency_samples == 0 {
-            self.ewma_latency_ms = sample_ms;
-            self.p95_est_ms = sample_ms;
-        } else {
-            let current_ewma = self.ewma_latency_ms as f64;
-            let ewma = EWMA_ALPHA * sample + (1.0 - EWMA_ALPHA) * current_ewma;
-            self.ewma_latency_ms = ewma.round().clamp(0.0, f64::MAX) as u64;
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct BondSlash<'info> {
+    #[account(
+        mut,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump
+    )]
+    pub service: Account<'info, Service>,
+    /// Escrow's `ESCROW_AUTHORITY_SEED` PDA, signing through its CPI.
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds=[b"audit", service_id.as_bytes()], bump)]
+    pub audit_log: Account<'info, ServiceAuditLog>,
+}
 
-            let current_p95 = self.p95_est_ms as f64;
-            let diff = sample - current_p95;
-            let next_p95 = if diff >= 0.0 {
-                current_p95 + diff * QUANTILE_INC
-            } else {
-                current_p95 + diff * QUANTILE_DEC
-            };
-            self.p95_est_ms = next_p95.max(0.0).round() as u64;
-        }
-        self.latency_samples = self.latency_samples.saturating_add(1);
-    }
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct BondSlashSplit<'info> {
+    #[account(
+        mut,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump
+    )]
+    pub service: Account<'info, Service>,
+    /// Escrow's `ESCROW_AUTHORITY_SEED` PDA, signing through its CPI.
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = recipient.key() != treasury.key() @ ReputationError::InvalidSlashRecipient
+    )]
+    pub recipient: SystemAccount<'info>,
+    #[account(mut, address = config.treasury @ ReputationError::InvalidTreasury)]
+    pub treasury: SystemAccount<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds=[b"audit", service_id.as_bytes()], bump)]
+    pub audit_log: Account<'info, ServiceAuditLog>,
 }
 
-impl Default for Service {
-    fn default() -> Self {
-        Self {
-            owner: Pubkey::default(),
-            ok: 0.0,
-            late: 0.0,
-            disputed: 0.0,
-            bond_balance: 0,
-            ewma_latency_ms: 0,
-            p95_est_ms: 0,
-            latency_samples: 0,
-        }
-    }
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct RecordDisputeLoss<'info> {
+    #[account(
+        mut,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump
+    )]
+    pub service: Account<'info, Service>,
+    /// Escrow's `ESCROW_AUTHORITY_SEED` PDA, signing through its CPI.
+    pub authority: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds=[b"audit", service_id.as_bytes()], bump)]
+    pub audit_log: Account<'info, ServiceAuditLog>,
 }
 
-fn transfer_into_service<'info>(
-    provider: &Signer<'info>,
-    service: &AccountInfo<'info>,
-    system_program: &Program<'info, System>,
-    amount: u64,
-) -> Result<()> {
-    if amount == 0 {
-        return Ok(());
-    }
-    let accounts = Transfer {
-        from: provider.to_account_info(),
-        to: service.clone(),
-    };
-    system_program::transfer(
-        CpiContext::new(system_program.to_account_info(), accounts),
-        amount,
-    )
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct RecordDeliveryLatency<'info> {
+    #[account(
+        mut,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump
+    )]
+    pub service: Account<'info, Service>,
+    /// Escrow's `ESCROW_AUTHORITY_SEED` PDA, signing through its CPI.
+    pub authority: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds=[b"audit", service_id.as_bytes()], bump)]
+    pub audit_log: Account<'info, ServiceAuditLog>,
 }
 
-fn pay_out<'info>(
-    amount: u64,
-    source: &AccountInfo<'info>,
-    destination: &AccountInfo<'info>,
-) -> Result<()> {
-    if amount == 0 {
-        return Ok(());
-    }
-    require!(
-        source.lamports() >= amount,
-        ReputationError::InsufficientBond
-    );
-    **source.try_borrow_mut_lamports()? -= amount;
-    **destination.try_borrow_mut_lamports()? += amount;
-    Ok(())
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct RecordVolume<'info> {
+    #[account(
+        mut,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump
+    )]
+    pub service: Account<'info, Service>,
+    /// Escrow's `ESCROW_AUTHORITY_SEED` PDA, signing through its CPI.
+    pub authority: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
 }
 
-#[error_code]
-pub enum ReputationError {
-    #[msg("Invalid owner for operation")]
-    InvalidOwner,
-    #[msg("Amount must be positive")]
-    InvalidAmount,
-    #[msg("Insufficient bonded balance")]
-    InsufficientBond,
-    #[msg("Invalid authority")]
-    InvalidAuthority,
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct ServiceGovernance<'info> {
+    #[account(
+        mut,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump
+    )]
+    pub service: Account<'info, Service>,
+    pub admin: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct UpdateLatency<'info> {
+    #[account(
+        mut,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump
+    )]
+    pub service: Account<'info, Service>,
+    pub provider: Signer<'info>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut, seeds=[b"audit", service_id.as_bytes()], bump)]
+    pub audit_log: Account<'info, ServiceAuditLog>,
+}
 
-    #[test]
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct InitAuditLog<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ServiceAuditLog::INIT_SPACE,
+        seeds=[b"audit", service_id.as_bytes()],
+        bump
+    )]
+    pub audit_log: Account<'info, ServiceAuditLog>,
+    #[account(seeds=[b"svc", service_id.as_bytes()], bump)]
+    pub service: Account<'info, Service>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct HealthCheck<'info> {
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + ServiceHealthStatus::INIT_SPACE,
+        seeds=[b"health", service_id.as_bytes()],
+        bump
+    )]
+    pub health: Account<'info, ServiceHealthStatus>,
+    #[account(
+        mut,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump
+    )]
+    pub service: Account<'info, Service>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct StakeAccounts<'info> {
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = 8 + Stake::INIT_SPACE,
+        seeds=[b"stake", service_id.as_bytes(), staker.key().as_ref()],
+        bump
+    )]
+    pub stake: Account<'info, Stake>,
+    #[account(
+        mut,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump
+    )]
+    pub service: Account<'info, Service>,
+    #[account(mut)]
+    pub staker: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct RestoreHealth<'info> {
+    #[account(
+        mut,
+        seeds=[b"health", service_id.as_bytes()],
+        bump
+    )]
+    pub health: Account<'info, ServiceHealthStatus>,
+    #[account(
+        mut,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump
+    )]
+    pub service: Account<'info, Service>,
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(service_id: String)]
+pub struct MigrateService<'info> {
+    /// CHECK: legacy layouts don't deserialize as Service; the handler
+    /// checks the discriminator before rewriting
+    #[account(
+        mut,
+        seeds=[b"svc", service_id.as_bytes()],
+        bump,
+        owner = crate::ID
+    )]
+    pub service: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(init, payer = admin, space = 8 + Config::INIT_SPACE, seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::Reputation>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ ReputationError::InvalidAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetAuthorizedCaller<'info> {
+    #[account(mut, seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    pub authority: Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::Reputation>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ ReputationError::InvalidAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(mut, seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    /// CHECK: an older layout doesn't deserialize as Config; the handler
+    /// checks the discriminator and the stored admin
+    #[account(mut, seeds=[b"config"], bump, owner = crate::ID)]
+    pub config: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetReviewerThreshold<'info> {
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + ReviewerThreshold::INIT_SPACE,
+        seeds=[b"reviewer-threshold"],
+        bump
+    )]
+    pub reviewer_threshold: Account<'info, ReviewerThreshold>,
+    #[account(seeds=[b"config"], bump)]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace, Default)]
+pub struct Config {
+    pub admin: Pubkey,
+    pub authorized_caller: Pubkey,
+    pub ewma_alpha: f64,
+    pub quantile_inc: f64,
+    pub quantile_dec: f64,
+    pub paused: bool,
+    /// Receives `treasury_bps` of every `bond_slash`; the rest goes to the
+    /// wronged payer.
+    pub treasury: Pubkey,
+    pub treasury_bps: u16,
+    /// Latency samples one service may record per epoch.
+    pub max_samples_per_epoch: u16,
+    /// Cap on a single `update_weighted` weight, so one large escrow cannot
+    /// dominate a score.
+    pub max_outcome_weight: f32,
+    /// Length of the windows `Service` buckets outcomes into.
+    pub outcome_window_s: i64,
+    /// Consecutive non-ok outcomes that suspend a service.
+    pub failure_threshold: u32,
+    /// Most one dispute may slash, as a multiple of the escrow amount in
+    /// basis points (20_000 = 2x).
+    pub slash_cap_multiplier_bps: u32,
+    /// Whether reviews must pass `ReviewerThreshold` before they may
+    /// feed into the reviewed service's reputation.
+    pub enforce_review_threshold: bool,
+}
+
+impl Config {
+    pub fn check_admin(&self, signer: &Pubkey) -> Result<()> {
+        require_keys_eq!(*signer, self.admin, ReputationError::InvalidAuthority);
+        Ok(())
+    }
+
+    /// `caller` has signed, so it must be the authorized escrow's
+    /// authority PDA, which only that program can sign for. The admin
+    /// stand-in used on localnet signs as itself.
+    pub fn check_caller(&self, caller: &Pubkey) -> Result<()> {
+        let expected = if self.authorized_caller == self.admin {
+            self.admin
+        } else {
+            escrow_authority(&self.authorized_caller)
+        };
+        require_keys_eq!(*caller, expected, ReputationError::InvalidAuthority);
+        Ok(())
+    }
+
+    pub fn apply(&mut self, params: &ConfigParams) {
+        self.ewma_alpha = params.ewma_alpha;
+        self.quantile_inc = params.quantile_inc;
+        self.quantile_dec = params.quantile_dec;
+        self.paused = params.paused;
+        self.treasury = params.treasury;
+        self.treasury_bps = params.treasury_bps;
+        self.max_samples_per_epoch = params.max_samples_per_epoch;
+        self.max_outcome_weight = params.max_outcome_weight;
+        self.outcome_window_s = params.outcome_window_s;
+        self.failure_threshold = params.failure_threshold;
+        self.slash_cap_multiplier_bps = params.slash_cap_multiplier_bps;
+        self.enforce_review_threshold = params.enforce_review_threshold;
+    }
+
+    pub fn updated_event(&self) -> ConfigUpdated {
+        ConfigUpdated {
+            admin: self.admin,
+            ewma_alpha: self.ewma_alpha,
+            quantile_inc: self.quantile_inc,
+            quantile_dec: self.quantile_dec,
+            paused: self.paused,
+            treasury: self.treasury,
+            treasury_bps: self.treasury_bps,
+            max_samples_per_epoch: self.max_samples_per_epoch,
+            max_outcome_weight: self.max_outcome_weight,
+            outcome_window_s: self.outcome_window_s,
+            failure_threshold: self.failure_threshold,
+            slash_cap_multiplier_bps: self.slash_cap_multiplier_bps,
+            enforce_review_threshold: self.enforce_review_threshold,
+        }
+    }
+
+    pub fn latency_gains(&self) -> LatencyGains {
+        LatencyGains {
+            ewma_alpha: self.ewma_alpha,
+            quantile_inc: self.quantile_inc,
+            quantile_dec: self.quantile_dec,
+            p95_shift: 0,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ConfigParams {
+    pub ewma_alpha: f64,
+    pub quantile_inc: f64,
+    pub quantile_dec: f64,
+    pub paused: bool,
+    pub treasury: Pubkey,
+    pub treasury_bps: u16,
+    pub max_samples_per_epoch: u16,
+    pub max_outcome_weight: f32,
+    pub outcome_window_s: i64,
+    pub failure_threshold: u32,
+    pub slash_cap_multiplier_bps: u32,
+    pub enforce_review_threshold: bool,
+}
+
+impl ConfigParams {
+    pub fn validate(&self) -> Result<()> {
+        let in_unit = |g: f64| g > 0.0 && g <= 1.0;
+        require!(
+            in_unit(self.ewma_alpha) && in_unit(self.quantile_inc) && in_unit(self.quantile_dec),
+            ReputationError::InvalidGain
+        );
+        require!(
+            self.treasury_bps as u64 <= BPS_DENOMINATOR,
+            ReputationError::InvalidTreasuryBps
+        );
+        require!(
+            self.max_samples_per_epoch > 0,
+            ReputationError::InvalidSampleLimit
+        );
+        require!(
+            self.max_outcome_weight > 0.0 && self.max_outcome_weight <= 1.0,
+            ReputationError::InvalidWeight
+        );
+        require!(
+            self.outcome_window_s > 0,
+            ReputationError::InvalidOutcomeWindow
+        );
+        require!(
+            self.failure_threshold > 0,
+            ReputationError::InvalidFailureThreshold
+        );
+        require!(
+            self.slash_cap_multiplier_bps > 0,
+            ReputationError::InvalidSlashCap
+        );
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AuditAction {
+    Outcome = 0,
+    BondDeposit = 1,
+    BondWithdraw = 2,
+    BondSlash = 3,
+    LatencyUpdate = 4,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, InitSpace)]
+pub struct AuditEntry {
+    pub ts: i64,
+    /// An `AuditAction`.
+    pub action: u8,
+    /// Signed change to the bond the entry touched: the reputation,
+    /// performance or token bond.
+    pub amount_delta: i64,
+    /// Change to the service's `ok` score.
+    pub score_delta_ok: f32,
+}
+
+/// The last `MAX_AUDIT_ENTRIES` changes to a service, at
+/// `["audit", service_id]`. Once full, `head` is the oldest entry and the
+/// next one to be overwritten.
+#[account]
+#[derive(InitSpace)]
+pub struct ServiceAuditLog {
+    #[max_len(MAX_SERVICE_ID_LEN)]
+    pub service_id: String,
+    #[max_len(MAX_AUDIT_ENTRIES)]
+    pub entries: Vec<AuditEntry>,
+    pub head: u32,
+}
+
+impl ServiceAuditLog {
+    /// Names a log `Update` or `BondDeposit` has just created alongside
+    /// its service.
+    pub fn claim(&mut self, service_id: &str) {
+        if self.service_id.is_empty() {
+            self.service_id = service_id.to_string();
+        }
+    }
+
+    pub fn append(&mut self, entry: AuditEntry) {
+        if self.entries.len() < MAX_AUDIT_ENTRIES {
+            self.entries.push(entry);
+        } else {
+            self.entries[self.head as usize] = entry;
+            self.head = (self.head + 1) % MAX_AUDIT_ENTRIES as u32;
+        }
+    }
+}
+
+/// Governance minimums for a reviewer, at `["reviewer-threshold"]`.
+#[account]
+#[derive(InitSpace, Default)]
+pub struct ReviewerThreshold {
+    pub min_completed_calls: u32,
+    pub min_payer_bond: u64,
+}
+
+impl ReviewerThreshold {
+    /// Disputes the payer won count towards its completed calls.
+    pub fn admits(&self, calls_completed: u32, disputes_won: u32, payer_bond: u64) -> bool {
+        calls_completed.saturating_add(disputes_won) >= self.min_completed_calls
+            && payer_bond >= self.min_payer_bond
+    }
+}
+
+/// Whether a review may feed into the service's reputation. Reviews that
+/// fail this are still stored, and `ReviewThrottled` is emitted instead.
+pub fn review_feeds_reputation(
+    config: &Config,
+    threshold: &ReviewerThreshold,
+    calls_completed: u32,
+    disputes_won: u32,
+    payer_bond: u64,
+) -> bool {
+    !config.enforce_review_threshold || threshold.admits(calls_completed, disputes_won, payer_bond)
+}
+
+pub struct LatencyGains {
+    pub ewma_alpha: f64,
+    pub quantile_inc: f64,
+    pub quantile_dec: f64,
+    /// Left shift applied to each whole-ms p95 step; see [`Self::aged`].
+    pub p95_shift: u32,
+}
+
+impl LatencyGains {
+    /// Shifts the p95 step left by `idle_s / P95_DECAY_PERIOD_S`, capped at
+    /// `MAX_P95_DECAY_SHIFT`. The shifted step never overshoots the sample.
+    pub fn aged(&self, idle_s: i64) -> LatencyGains {
+        let periods = idle_s.max(0) / P95_DECAY_PERIOD_S;
+        LatencyGains {
+            ewma_alpha: self.ewma_alpha,
+            quantile_inc: self.quantile_inc,
+            quantile_dec: self.quantile_dec,
+            p95_shift: periods.min(MAX_P95_DECAY_SHIFT as i64) as u32,
+        }
+    }
+
+    /// Whole-ms p95 move towards a sample `diff` ms away at `gain`.
+    fn p95_step(&self, diff: u64, gain: f64) -> u64 {
+        let step = (diff as f64 * gain).round() as u64;
+        step.saturating_mul(1 << self.p95_shift).min(diff)
+    }
+
+    /// Moves a p95 estimate one step towards `sample`.
+    pub fn next_p95(&self, current: u64, sample: u64) -> u64 {
+        if sample >= current {
+            current + self.p95_step(sample - current, self.quantile_inc)
+        } else {
+            current - self.p95_step(current - sample, self.quantile_dec)
+        }
+    }
+}
+
+impl Default for LatencyGains {
+    fn default() -> Self {
+        Self {
+            ewma_alpha: EWMA_ALPHA,
+            quantile_inc: QUANTILE_INC,
+            quantile_dec: QUANTILE_DEC,
+            p95_shift: 0,
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Service {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub ok: f32,
+    pub late: f32,
+    pub disputed: f32,
+    pub bond_balance: u64,
+    pub ewma_latency_ms: u64,
+    pub p95_est_ms: u64,
+    pub latency_samples: u64,
+    pub compliance_rate_bps: u16,
+    pub latency_history: [u64; LATENCY_HISTORY_LEN],
+    pub latency_history_idx: u8,
+    pub is_active: bool,
+    pub suspension_count: u32,
+    pub suspension_until_ts: i64,
+    pub update_quota_remaining: u16,
+    pub quota_reset_epoch: u64,
+    /// Backs per-call guarantees and absorbs dispute clawbacks, separate
+    /// from `bond_balance` which stakes reputation.
+    pub performance_bond_balance: u64,
+    pub latency_histogram: [u32; LATENCY_BUCKETS],
+    /// Delivered only some of the paid units.
+    pub partial: f32,
+    /// Never delivered before the escrow timed out.
+    pub timeout: f32,
+    /// SPL mint of the token bond; the default key until the first deposit.
+    pub bond_mint: Pubkey,
+    /// Token bond held in the service PDA's ATA for `bond_mint`, kept apart
+    /// from the lamport `bond_balance`.
+    pub token_bond_balance: u64,
+    /// When the last latency sample landed; 0 before the first one.
+    pub last_active_ts: i64,
+    pub last_latency_epoch: u64,
+    pub samples_this_epoch: u16,
+    pub uptime_checks_ok: u32,
+    pub uptime_checks_total: u32,
+    /// Wilson lower bound of the ok share in basis points, recomputed on
+    /// every outcome so small samples rank below proven services.
+    pub success_rate_bp: u16,
+    /// Start of the current outcome window; 0 before the first outcome.
+    pub current_window_start: i64,
+    pub window_ok: f32,
+    pub window_late: f32,
+    /// Disputed, partial and timed-out outcomes in the current window.
+    pub window_disputed: f32,
+    pub prev_window_ok: f32,
+    pub prev_window_late: f32,
+    pub prev_window_disputed: f32,
+    /// Non-ok outcomes since the last ok one.
+    pub consecutive_failures: u32,
+    /// Set once `consecutive_failures` reaches `config.failure_threshold`,
+    /// which also adds a backoff to `suspension_until_ts`; only `reinstate`
+    /// or governance's `clear_suspension` clears it.
+    pub suspended: bool,
+    /// Lamports staked behind the service by third parties, held on the
+    /// service PDA alongside the bonds. Slashes and rewards move it, which
+    /// reprices every `Stake` share at once.
+    pub total_delegated: u64,
+    pub total_stake_shares: u64,
+    /// Share of each released escrow escrow routes to stakers.
+    pub stake_reward_bps: u16,
+    /// Open escrows the owner accepts at once; 0 means unlimited.
+    pub max_outstanding_calls: u32,
+    /// Open escrows counted against `max_outstanding_calls`.
+    pub outstanding_calls: u32,
+    /// Owner-set maintenance flag; escrow refuses new calls while set.
+    pub paused: bool,
+    /// Published lamports per unit; 0 means unpriced.
+    pub price_per_unit: u64,
+    /// Exempts the service from escrow's strict pricing check.
+    pub pricing_opt_out: bool,
+    /// p95 above this lets anyone fire `LatencyAlertFired`; 0 disables it.
+    pub latency_alert_threshold_ms: u64,
+    /// Latches after an alert until the owner clears it.
+    pub alert_emitted: bool,
+    /// Escrow amounts of every released call, in lamports.
+    pub lifetime_volume: u64,
+    /// Top-up `reinstate` locked in `bond_balance` until
+    /// `reinstate_lock_until_ts`.
+    pub reinstate_lock_lamports: u64,
+    pub reinstate_lock_until_ts: i64,
+    /// Escrows settled against the service, as reported by escrow's
+    /// `record_volume` and `record_dispute_loss` CPIs; unlike the outcome
+    /// counters, the owner cannot write these.
+    pub escrow_releases: u64,
+    pub escrow_refunds: u64,
+    /// p95 of delivery latency as escrow clocked it (fulfill time minus
+    /// call start), fed by `record_delivery_latency`; the owner cannot
+    /// write it, so it keeps latency alerts honest.
+    pub escrow_p95_ms: u64,
+    pub escrow_latency_samples: u64,
+}
+
+impl Service {
+    /// Size of a version-1 service before any field was appended.
+    pub const V1_BASE_LEN: usize = 1 // version
+        + ServiceV0::LEN
+        + 2 // compliance_rate_bps
+        + 8 * LATENCY_HISTORY_LEN
+        + 1; // latency_history_idx
+
+    /// Reads any layout a service has been written with, so older accounts
+    /// stay readable until migrated. Version 1 only grew by appending, and
+    /// an all-zero field decodes as its default, so a short account is
+    /// zero-extended and read as the current layout.
+    pub fn load_versioned(data: &[u8]) -> Result<Service> {
+        require!(
+            data.len() >= 8 && data[..8] == *Service::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        if data.len() == 8 + ServiceV0::LEN {
+            let legacy = ServiceV0::deserialize(&mut &data[8..])?;
+            return Ok(legacy.into_current());
+        }
+        let mut buf = data.to_vec();
+        buf.resize(buf.len().max(8 + Service::INIT_SPACE), 0);
+        let mut svc = Service::try_deserialize(&mut &buf[..])?;
+        match svc.version {
+            1 => {
+                // `is_active` was the first appended field; zero would
+                // read as deactivated.
+                if data.len() <= 8 + Service::V1_BASE_LEN {
+                    svc.is_active = true;
+                }
+                svc.version = SERVICE_VERSION;
+            }
+            SERVICE_VERSION => {}
+            _ => return err!(ReputationError::UnsupportedVersion),
+        }
+        Ok(svc)
+    }
+
+    /// Outcome codes: 0 ok, 1 late, 2 disputed, 3 partial, 4 timeout. Only
+    /// ok counts towards compliance.
+    pub fn apply_outcome(
+        &mut self,
+        outcome: u8,
+        weight: f32,
+        now: i64,
+        window_s: i64,
+    ) -> Result<()> {
+        require!(
+            weight.is_finite() && weight >= 0.0,
+            ReputationError::InvalidWeight
+        );
+        let total = match outcome {
+            0 => &mut self.ok,
+            1 => &mut self.late,
+            2 => &mut self.disputed,
+            3 => &mut self.partial,
+            4 => &mut self.timeout,
+            _ => return err!(ReputationError::UnknownOutcome),
+        };
+        *total = add_weight(*total, weight);
+        self.roll_window(now, window_s);
+        let window_total = match outcome {
+            0 => &mut self.window_ok,
+            1 => &mut self.window_late,
+            _ => &mut self.window_disputed,
+        };
+        *window_total = add_weight(*window_total, weight);
+        let sample = if outcome == 0 {
+            COMPLIANCE_MAX_BPS as f64
+        } else {
+            0.0
+        };
+        let current_rate = self.compliance_rate_bps as f64;
+        let new_rate =
+            (COMPLIANCE_ALPHA * sample + (1.0 - COMPLIANCE_ALPHA) * current_rate).round();
+        self.compliance_rate_bps = new_rate.clamp(0.0, COMPLIANCE_MAX_BPS as f64) as u16;
+        self.success_rate_bp = wilson_lower_bound_bps(
+            (self.ok * OUTCOME_COUNT_SCALE) as u64,
+            (self.outcome_total() * OUTCOME_COUNT_SCALE) as u64,
+        );
+        Ok(())
+    }
+
+    /// Starts a new window once `now` passes the current one's end. The
+    /// finished window becomes the previous one, unless a whole window
+    /// went by without outcomes, in which case both start empty.
+    pub fn roll_window(&mut self, now: i64, window_s: i64) {
+        if self.current_window_start == 0 {
+            self.current_window_start = now;
+            return;
+        }
+        let elapsed = now.saturating_sub(self.current_window_start);
+        if elapsed < window_s {
+            return;
+        }
+        (
+            self.prev_window_ok,
+            self.prev_window_late,
+            self.prev_window_disputed,
+        ) = if elapsed < window_s.saturating_mul(2) {
+            (self.window_ok, self.window_late, self.window_disputed)
+        } else {
+            (0.0, 0.0, 0.0)
+        };
+        (self.window_ok, self.window_late, self.window_disputed) = (0.0, 0.0, 0.0);
+        self.current_window_start = now.saturating_sub(elapsed % window_s);
+    }
+
+    pub fn outcome_total(&self) -> f32 {
+        (self.ok + self.late + self.disputed + self.partial + self.timeout).min(f32::MAX)
+    }
+
+    /// Services without any recorded outcome have no history to judge, so
+    /// they pass until their first outcome lands. Suspension is checked
+    /// separately by `is_suspended`.
+    pub fn meets_compliance(&self, min_bps: u16) -> bool {
+        let has_history = self.outcome_total() > 0.0;
+        !has_history || self.compliance_rate_bps >= min_bps
+    }
+
+    /// Counts a settlement towards the failure streak, resetting it on
+    /// success. Returns true when this outcome tripped the suspension,
+    /// which is recorded like a dispute loss so its backoff escalates too.
+    pub fn track_failure_streak(&mut self, ok: bool, threshold: u32, now: i64) -> bool {
+        if ok {
+            self.consecutive_failures = 0;
+            return false;
+        }
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if self.suspended || self.consecutive_failures < threshold {
+            return false;
+        }
+        self.suspended = true;
+        self.record_suspension(now);
+        true
+    }
+
+    /// Lamports `shares` of the stake pool are currently worth.
+    pub fn stake_value(&self, shares: u64) -> u64 {
+        if self.total_stake_shares == 0 {
+            return 0;
+        }
+        (shares as u128 * self.total_delegated as u128 / self.total_stake_shares as u128) as u64
+    }
+
+    /// Adds `amount` to the stake pool and returns the shares minted for
+    /// it. Rounding favours the existing stakers.
+    pub fn delegate(&mut self, amount: u64) -> Result<u64> {
+        let shares = if self.total_stake_shares == 0 {
+            amount
+        } else {
+            require!(self.total_delegated > 0, ReputationError::StakePoolDepleted);
+            (amount as u128 * self.total_stake_shares as u128 / self.total_delegated as u128) as u64
+        };
+        require!(shares > 0, ReputationError::InvalidAmount);
+        self.total_delegated = self.total_delegated.saturating_add(amount);
+        self.total_stake_shares = self.total_stake_shares.saturating_add(shares);
+        Ok(shares)
+    }
+
+    /// Burns `shares` and returns their lamports. The last shares out take
+    /// whatever is left, so rounding dust never stays behind.
+    pub fn undelegate(&mut self, shares: u64) -> u64 {
+        let amount = if shares >= self.total_stake_shares {
+            self.total_delegated
+        } else {
+            self.stake_value(shares)
+        };
+        self.total_stake_shares = self.total_stake_shares.saturating_sub(shares);
+        self.total_delegated = self.total_delegated.saturating_sub(amount);
+        amount
+    }
+
+    /// Takes `amount` from the owner's bond first and the stake pool for
+    /// the rest, returning `(from_bond, from_stakes)`.
+    pub fn slash_bond_and_stakes(&mut self, amount: u64) -> (u64, u64) {
+        let from_bond = amount.min(self.bond_balance);
+        let from_stakes = (amount - from_bond).min(self.total_delegated);
+        self.bond_balance -= from_bond;
+        self.total_delegated -= from_stakes;
+        (from_bond, from_stakes)
+    }
+
+    /// The p95 alerts are judged on: the escrow-clocked one, or the
+    /// owner's own estimate if that is worse.
+    pub fn alert_p95_ms(&self) -> u64 {
+        self.escrow_p95_ms.max(self.p95_est_ms)
+    }
+
+    pub fn clear_latency_alert(&mut self, owner: &Pubkey) -> Result<()> {
+        require_keys_eq!(self.owner, *owner, ReputationError::InvalidOwner);
+        self.alert_emitted = false;
+        Ok(())
+    }
+
+    pub fn record_delivery_latency(&mut self, latency_ms: u64, gains: &LatencyGains) {
+        self.escrow_p95_ms = if self.escrow_latency_samples == 0 {
+            latency_ms
+        } else {
+            gains.next_p95(self.escrow_p95_ms, latency_ms)
+        };
+        self.escrow_latency_samples = self.escrow_latency_samples.saturating_add(1);
+    }
+
+    /// Latches the latency alert; fails unless p95 is over a set threshold
+    /// and no alert is outstanding.
+    pub fn fire_latency_alert(&mut self) -> Result<()> {
+        require!(
+            self.latency_alert_threshold_ms > 0
+                && self.alert_p95_ms() > self.latency_alert_threshold_ms,
+            ReputationError::LatencyWithinThreshold
+        );
+        require!(!self.alert_emitted, ReputationError::AlertAlreadyEmitted);
+        self.alert_emitted = true;
+        Ok(())
+    }
+
+    pub fn record_volume(&mut self, amount: u64, released: bool) {
+        self.lifetime_volume = self.lifetime_volume.saturating_add(amount);
+        if released {
+            self.escrow_releases = self.escrow_releases.saturating_add(1);
+        }
+    }
+
+    pub fn has_call_capacity(&self) -> bool {
+        self.max_outstanding_calls == 0 || self.outstanding_calls < self.max_outstanding_calls
+    }
+
+    /// Lamports the service's books say sit on its PDA: both bonds and
+    /// the stake pool.
+    pub fn held_lamports(&self) -> u64 {
+        self.bond_balance
+            .saturating_add(self.performance_bond_balance)
+            .saturating_add(self.total_delegated)
+    }
+
+    /// A stake reward must already be on the PDA, above its rent and
+    /// everything the books already account for.
+    pub fn check_reward_funded(&self, lamports: u64, rent: u64, amount: u64) -> Result<()> {
+        let unaccounted = lamports
+            .saturating_sub(rent)
+            .saturating_sub(self.held_lamports());
+        require!(unaccounted >= amount, ReputationError::RewardNotFunded);
+        Ok(())
+    }
+
+    pub fn slashable_balance(&self) -> u64 {
+        self.bond_balance.saturating_add(self.total_delegated)
+    }
+
+    pub fn reinstate(&mut self, top_up: u64, now: i64) {
+        self.bond_balance = self.bond_balance.saturating_add(top_up);
+        self.consecutive_failures = 0;
+        self.suspended = false;
+        self.reinstate_lock_lamports = top_up;
+        self.reinstate_lock_until_ts = now.saturating_add(REINSTATE_LOCK_S);
+    }
+
+    /// Bond the owner may take out now: all of it, less a reinstatement
+    /// top-up that is still locked.
+    pub fn withdrawable_bond(&self, now: i64) -> u64 {
+        if now >= self.reinstate_lock_until_ts {
+            return self.bond_balance;
+        }
+        self.bond_balance
+            .saturating_sub(self.reinstate_lock_lamports)
+    }
+
+    /// Seconds since the last latency sample, or 0 for a service with none.
+    pub fn idle_s(&self, now: i64) -> i64 {
+        if self.last_active_ts == 0 {
+            0
+        } else {
+            now.saturating_sub(self.last_active_ts).max(0)
+        }
+    }
+
+    pub fn record_latency(&mut self, sample_ms: u64) {
+        self.record_latency_with(sample_ms, &LatencyGains::default());
+    }
+
+    pub fn record_latency_with(&mut self, sample_ms: u64, gains: &LatencyGains) {
+        let sample = sample_ms as f64;
+        if self.latency_samples == 0 {
+            self.ewma_latency_ms = sample_ms;
+            self.p95_est_ms = sample_ms;
+        } else {
+            let current_ewma = self.ewma_latency_ms as f64;
+            let ewma = gains.ewma_alpha * sample + (1.0 - gains.ewma_alpha) * current_ewma;
+            self.ewma_latency_ms = ewma.round().clamp(0.0, f64::MAX) as u64;
+
+            self.p95_est_ms = gains.next_p95(self.p95_est_ms, sample_ms);
+        }
+        self.latency_history[self.latency_history_idx as usize] = sample_ms;
+        self.latency_history_idx =
+            ((self.latency_history_idx as usize + 1) % LATENCY_HISTORY_LEN) as u8;
+        self.latency_samples = self.latency_samples.saturating_add(1);
+        let bucket = &mut self.latency_histogram[latency_bucket(sample_ms)];
+        *bucket = bucket.saturating_add(1);
+    }
+
+    /// Sets up a freshly created (zeroed) service for its first owner.
+    pub fn claim(&mut self, owner: Pubkey, epoch: u64) {
+        self.version = SERVICE_VERSION;
+        self.is_active = true;
+        self.owner = owner;
+        self.reset_update_quota(epoch);
+    }
+
+    /// Adds `amount` lamports to the bond, claiming an unowned service for
+    /// `depositor` first. The lamports themselves are moved by the caller.
+    pub fn deposit_bond(&mut self, depositor: &Pubkey, amount: u64, epoch: u64) -> Result<()> {
+        if self.owner == Pubkey::default() {
+            self.claim(*depositor, epoch);
+        }
+        require_keys_eq!(self.owner, *depositor, ReputationError::InvalidOwner);
+        self.bond_balance = self.bond_balance.saturating_add(amount);
+        Ok(())
+    }
+
+    pub fn reset_update_quota(&mut self, epoch: u64) {
+        self.update_quota_remaining = UPDATE_QUOTA_PER_EPOCH;
+        self.quota_reset_epoch = epoch;
+    }
+
+    /// Spends one `update_weighted` call from this epoch's quota, refilling
+    /// it first when a new epoch has started.
+    pub fn consume_update_quota(&mut self, epoch: u64) -> Result<()> {
+        if epoch > self.quota_reset_epoch {
+            self.reset_update_quota(epoch);
+        }
+        require!(
+            self.update_quota_remaining > 0,
+            ReputationError::UpdateQuotaExceeded
+        );
+        self.update_quota_remaining -= 1;
+        Ok(())
+    }
+
+    /// Counts a latency sample against `epoch`, allowing at most `max` per
+    /// epoch so a provider cannot flood the estimates with fast samples.
+    pub fn consume_latency_sample(&mut self, epoch: u64, max: u16) -> Result<()> {
+        if epoch != self.last_latency_epoch {
+            self.last_latency_epoch = epoch;
+            self.samples_this_epoch = 0;
+        }
+        require!(self.samples_this_epoch < max, ReputationError::RateLimited);
+        self.samples_this_epoch += 1;
+        Ok(())
+    }
+
+    pub fn record_uptime(&mut self, is_ok: bool) {
+        self.uptime_checks_total = self.uptime_checks_total.saturating_add(1);
+        if is_ok {
+            self.uptime_checks_ok = self.uptime_checks_ok.saturating_add(1);
+        }
+    }
+
+    pub fn deposit_performance_bond(&mut self, amount: u64) {
+        self.performance_bond_balance = self.performance_bond_balance.saturating_add(amount);
+    }
+
+    pub fn withdraw_performance_bond(&mut self, amount: u64) -> Result<()> {
+        require!(
+            self.performance_bond_balance >= amount,
+            ReputationError::InsufficientBond
+        );
+        self.performance_bond_balance -= amount;
+        Ok(())
+    }
+
+    /// A service bonds in at most one mint, fixed by its first token deposit.
+    pub fn set_bond_mint(&mut self, mint: Pubkey) -> Result<()> {
+        if self.bond_mint == Pubkey::default() {
+            self.bond_mint = mint;
+        }
+        require_keys_eq!(self.bond_mint, mint, ReputationError::BondMintMismatch);
+        Ok(())
+    }
+
+    pub fn deposit_token_bond(&mut self, amount: u64) {
+        self.token_bond_balance = self.token_bond_balance.saturating_add(amount);
+    }
+
+    pub fn withdraw_token_bond(&mut self, amount: u64) -> Result<()> {
+        require!(
+            self.token_bond_balance >= amount,
+            ReputationError::InsufficientBond
+        );
+        self.token_bond_balance -= amount;
+        Ok(())
+    }
+
+    /// Applies `slash_amount` to the token bond and returns what was taken.
+    pub fn slash_token_bond(
+        &mut self,
+        dispute_kind: u8,
+        escrow_amount: u64,
+        cap_multiplier_bps: u32,
+    ) -> Result<u64> {
+        let actual = slash_amount(
+            dispute_kind,
+            escrow_amount,
+            self.token_bond_balance,
+            cap_multiplier_bps,
+        )?;
+        self.token_bond_balance -= actual;
+        Ok(actual)
+    }
+
+    /// Takes up to `requested` from the performance bond and returns what
+    /// was taken; the reputation bond is never touched.
+    pub fn claw_back_performance_bond(&mut self, requested: u64) -> u64 {
+        let actual = clawback_amount(requested, self.performance_bond_balance);
+        self.performance_bond_balance -= actual;
+        actual
+    }
+
+    pub fn record_suspension(&mut self, now: i64) {
+        self.suspension_count = self.suspension_count.saturating_add(1);
+        self.suspension_until_ts = now.saturating_add(suspension_duration(self.suspension_count));
+    }
+
+    pub fn clear_suspension(&mut self) {
+        self.suspension_count = 0;
+        self.suspension_until_ts = 0;
+        self.consecutive_failures = 0;
+        self.suspended = false;
+    }
+
+    /// Most recent raw latency samples, oldest first.
+    pub fn recent_latencies(&self) -> Vec<u64> {
+        let filled = (self.latency_samples as usize).min(LATENCY_HISTORY_LEN);
+        let start = if filled < LATENCY_HISTORY_LEN {
+            0
+        } else {
+            self.latency_history_idx as usize
+        };
+        (0..filled)
+            .map(|i| self.latency_history[(start + i) % LATENCY_HISTORY_LEN])
+            .collect()
+    }
+}
+
+impl Default for Service {
+    fn default() -> Self {
+        Self {
+            version: SERVICE_VERSION,
+            owner: Pubkey::default(),
+            ok: 0.0,
+            late: 0.0,
+            disputed: 0.0,
+            bond_balance: 0,
+            ewma_latency_ms: 0,
+            p95_est_ms: 0,
+            latency_samples: 0,
+            compliance_rate_bps: 0,
+            latency_history: [0; LATENCY_HISTORY_LEN],
+            latency_history_idx: 0,
+            is_active: true,
+            suspension_count: 0,
+            suspension_until_ts: 0,
+            update_quota_remaining: UPDATE_QUOTA_PER_EPOCH,
+            quota_reset_epoch: 0,
+            performance_bond_balance: 0,
+            latency_histogram: [0; LATENCY_BUCKETS],
+            partial: 0.0,
+            timeout: 0.0,
+            bond_mint: Pubkey::default(),
+            token_bond_balance: 0,
+            last_active_ts: 0,
+            last_latency_epoch: 0,
+            samples_this_epoch: 0,
+            uptime_checks_ok: 0,
+            uptime_checks_total: 0,
+            success_rate_bp: 0,
+            current_window_start: 0,
+            window_ok: 0.0,
+            window_late: 0.0,
+            window_disputed: 0.0,
+            prev_window_ok: 0.0,
+            prev_window_late: 0.0,
+            prev_window_disputed: 0.0,
+            consecutive_failures: 0,
+            suspended: false,
+            total_delegated: 0,
+            total_stake_shares: 0,
+            stake_reward_bps: 0,
+            max_outstanding_calls: 0,
+            outstanding_calls: 0,
+            paused: false,
+            price_per_unit: 0,
+            pricing_opt_out: false,
+            latency_alert_threshold_ms: 0,
+            alert_emitted: false,
+            lifetime_volume: 0,
+            reinstate_lock_lamports: 0,
+            reinstate_lock_until_ts: 0,
+            escrow_releases: 0,
+            escrow_refunds: 0,
+            escrow_p95_ms: 0,
+            escrow_latency_samples: 0,
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct ServiceHealthStatus {
+    pub owner: Pubkey,
+    pub is_healthy: bool,
+    pub last_checked_ts: i64,
+    pub failure_count: u32,
+    pub consecutive_failures: u32,
+}
+
+impl ServiceHealthStatus {
+    pub fn record(&mut self, is_healthy: bool, now: i64) {
+        self.is_healthy = is_healthy;
+        self.last_checked_ts = now;
+        if is_healthy {
+            self.consecutive_failures = 0;
+        } else {
+            self.failure_count = self.failure_count.saturating_add(1);
+            self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        }
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.consecutive_failures >= HEALTH_FAILURE_THRESHOLD
+    }
+
+    pub fn restore(&mut self, now: i64) {
+        self.is_healthy = true;
+        self.last_checked_ts = now;
+        self.consecutive_failures = 0;
+    }
+}
+
+/// One staker's shares of a service's stake pool; worth
+/// `Service::stake_value(shares)` lamports.
+#[account]
+#[derive(InitSpace)]
+pub struct Stake {
+    pub service: Pubkey,
+    pub staker: Pubkey,
+    pub shares: u64,
+    /// When the staker asked to withdraw; 0 when no request is pending.
+    pub withdraw_requested_ts: i64,
+}
+
+impl Stake {
+    pub fn check_withdraw(&self, shares: u64, now: i64) -> Result<()> {
+        require!(
+            shares > 0 && shares <= self.shares,
+            ReputationError::InvalidAmount
+        );
+        let ready = self.withdraw_requested_ts.saturating_add(STAKE_COOLDOWN_S);
+        require!(
+            self.withdraw_requested_ts > 0 && now >= ready,
+            ReputationError::StakeCoolingDown
+        );
+        require!(
+            now < ready.saturating_add(STAKE_WITHDRAW_WINDOW_S),
+            ReputationError::StakeRequestExpired
+        );
+        Ok(())
+    }
+}
+
+/// Service as laid out before accounts carried a version byte.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ServiceV0 {
+    pub owner: Pubkey,
+    pub ok: f32,
+    pub late: f32,
+    pub disputed: f32,
+    pub bond_balance: u64,
+    pub ewma_latency_ms: u64,
+    pub p95_est_ms: u64,
+    pub latency_samples: u64,
+}
+
+impl ServiceV0 {
+    pub const LEN: usize = 32 // owner
+        + 4 * 3 // outcome weights
+        + 8 * 4; // bond balance, ewma, p95, sample count
+
+    /// Legacy services have no compliance history, so seed it from the
+    /// aggregate ok share instead of starting every provider at zero.
+    pub fn into_current(self) -> Service {
+        let total = self.ok + self.late + self.disputed;
+        let compliance_rate_bps = if total > 0.0 {
+            ((self.ok / total) as f64 * COMPLIANCE_MAX_BPS as f64).round() as u16
+        } else {
+            0
+        };
+        Service {
+            owner: self.owner,
+            ok: self.ok,
+            late: self.late,
+            disputed: self.disputed,
+            bond_balance: self.bond_balance,
+            ewma_latency_ms: self.ewma_latency_ms,
+            p95_est_ms: self.p95_est_ms,
+            latency_samples: self.latency_samples,
+            compliance_rate_bps,
+            ..Service::default()
+        }
+    }
+}
+
+/// `floor(log2(sample_ms))`, with 0ms in the first bucket.
+pub fn latency_bucket(sample_ms: u64) -> usize {
+    (sample_ms.checked_ilog2().unwrap_or(0) as usize).min(LATENCY_BUCKETS - 1)
+}
+
+/// Upper bound in ms of the bucket holding the 95th percentile sample, or
+/// 0 for an empty histogram.
+pub fn histogram_p95(histogram: &[u32; LATENCY_BUCKETS]) -> u64 {
+    let total: u64 = histogram.iter().map(|c| *c as u64).sum();
+    if total == 0 {
+        return 0;
+    }
+    let target = total.saturating_mul(95).div_ceil(100);
+    let mut seen = 0u64;
+    for (i, count) in histogram.iter().enumerate() {
+        seen = seen.saturating_add(*count as u64);
+        if seen >= target {
+            return 1u64 << (i + 1);
+        }
+    }
+    1u64 << LATENCY_BUCKETS
+}
+
+/// `BASE_SUSPENSION_S * 2^(count - 1)`, capped at `MAX_SUSPENSION_S`.
+pub fn suspension_duration(count: u32) -> i64 {
+    if count == 0 {
+        return 0;
+    }
+    BASE_SUSPENSION_S
+        .checked_shl(count - 1)
+        .filter(|d| *d > 0 && *d <= MAX_SUSPENSION_S)
+        .unwrap_or(MAX_SUSPENSION_S)
+}
+
+pub fn check_reinstate(svc: &Service, top_up: u64, now: i64) -> Result<()> {
+    require!(svc.suspended, ReputationError::NotSuspended);
+    require!(
+        now >= svc.suspension_until_ts,
+        ReputationError::SuspensionActive
+    );
+    require!(
+        top_up >= REINSTATE_MIN_TOP_UP_LAMPORTS,
+        ReputationError::InvalidAmount
+    );
+    Ok(())
+}
+
+/// A service is suspended while its backoff runs and, after a failure
+/// streak, until the owner reinstates it.
+pub fn is_suspended(svc: &Service, now: i64) -> bool {
+    svc.suspended || now < svc.suspension_until_ts
+}
+
+/// Rejects NaN and negative weights, then caps at `max` (itself at most 1).
+pub fn outcome_weight(weight: f32, max: f32) -> Result<f32> {
+    require!(
+        !weight.is_nan() && weight >= 0.0,
+        ReputationError::InvalidWeight
+    );
+    Ok(weight.min(max).min(1.0))
+}
+
+/// Adds an outcome weight, pinning the running total at `f32::MAX` rather
+/// than letting it become infinite.
+fn add_weight(total: f32, weight: f32) -> f32 {
+    (total + weight).min(f32::MAX)
+}
+
+/// Share of uptime checks that passed; 0 before the first check.
+pub fn uptime_ratio(svc: &Service) -> f32 {
+    svc.uptime_checks_ok as f32 / svc.uptime_checks_total.max(1) as f32
+}
+
+/// Lower bound of the 95% Wilson score interval for `ok / total`, in basis
+/// points, using integer math only. Both counts are fixed-point with
+/// `OUTCOME_COUNT_SCALE` units per outcome; off-chain rankers should call
+/// this to match on-chain values exactly.
+pub fn wilson_lower_bound_bps(ok_scaled: u64, total_scaled: u64) -> u16 {
+    const MICRO: u128 = 1_000_000;
+    if total_scaled == 0 {
+        return 0;
+    }
+    let to_micro = |scaled: u64| scaled as u128 * MICRO / OUTCOME_COUNT_SCALE as u128;
+    let n = to_micro(total_scaled);
+    let s = to_micro(ok_scaled.min(total_scaled));
+    let z2 = WILSON_Z_CENTI * WILSON_Z_CENTI * MICRO / 10_000;
+    // n·p(1-p) + z²/4, in MICRO² so its square root lands back in MICRO.
+    let p = s * MICRO / n;
+    let spread = isqrt(p * (MICRO - p) * n / MICRO + z2 * MICRO / 4);
+    let numerator = (s + z2 / 2).saturating_sub(WILSON_Z_CENTI * spread / 100);
+    (numerator * COMPLIANCE_MAX_BPS as u128 / (n + z2)) as u16
+}
+
+fn isqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    let mut x = 1u128 << (value.ilog2() / 2 + 1);
+    loop {
+        let next = (x + value / x) / 2;
+        if next >= x {
+            return x;
+        }
+        x = next;
+    }
+}
+
+/// Ok share of the current window plus `PREV_WINDOW_WEIGHT` of the
+/// previous one, or `None` when both are empty.
+pub fn windowed_ok_ratio(svc: &Service) -> Option<f32> {
+    let ok = svc.window_ok + PREV_WINDOW_WEIGHT * svc.prev_window_ok;
+    let total = ok
+        + svc.window_late
+        + svc.window_disputed
+        + PREV_WINDOW_WEIGHT * (svc.prev_window_late + svc.prev_window_disputed);
+    (total > 0.0).then(|| ok / total)
+}
+
+/// Blends recent reliability with uptime into a score in `[0, 1]`.
+/// Reliability is the windowed ok ratio, falling back to the compliance
+/// rate for services without windowed outcomes. A service never probed is
+/// scored on reliability alone.
+pub fn composite_score(svc: &Service) -> f32 {
+    let reliability = windowed_ok_ratio(svc)
+        .unwrap_or(svc.compliance_rate_bps as f32 / COMPLIANCE_MAX_BPS as f32);
+    if svc.uptime_checks_total == 0 {
+        return reliability;
+    }
+    (1.0 - UPTIME_WEIGHT) * reliability + UPTIME_WEIGHT * uptime_ratio(svc)
+}
+
+/// Wilson lower bound of the share of escrow-settled calls that released,
+/// in basis points; 0 before escrow has settled any.
+pub fn escrow_success_bps(svc: &Service) -> u16 {
+    let scale = OUTCOME_COUNT_SCALE as u64;
+    let total = svc.escrow_releases.saturating_add(svc.escrow_refunds);
+    wilson_lower_bound_bps(
+        svc.escrow_releases.saturating_mul(scale),
+        total.saturating_mul(scale),
+    )
+}
+
+/// `composite_score` in basis points, as escrow's reputation floor reads it.
+pub fn composite_score_bps(svc: &Service) -> u16 {
+    (composite_score(svc) * COMPLIANCE_MAX_BPS as f32).round() as u16
+}
+
+/// Share of the escrow amount slashed per dispute kind, in basis points.
+/// Kinds follow escrow's dispute enum: 0 LATE, 1 NO_RESPONSE, 2 BAD_PROOF,
+/// 3 MISMATCH_HASH.
+pub fn slash_fraction_bps(dispute_kind: u8) -> Result<u64> {
+    match dispute_kind {
+        0 => Ok(1_000),
+        1 => Ok(10_000),
+        2 | 3 => Ok(5_000),
+        _ => err!(ReputationError::InvalidDisputeKind),
+    }
+}
+
+/// The dispute kind's share of `escrow_amount`, capped at
+/// `min(bond_balance, escrow_amount * cap_multiplier_bps / 10_000)`.
+pub fn slash_amount(
+    dispute_kind: u8,
+    escrow_amount: u64,
+    bond_balance: u64,
+    cap_multiplier_bps: u32,
+) -> Result<u64> {
+    let bps = slash_fraction_bps(dispute_kind)?;
+    let scaled = escrow_amount as u128 * bps as u128 / BPS_DENOMINATOR as u128;
+    let per_call_cap = escrow_amount as u128 * cap_multiplier_bps as u128 / BPS_DENOMINATOR as u128;
+    Ok(scaled.min(per_call_cap).min(bond_balance as u128) as u64)
+}
+
+/// Splits a slash into `(to_recipient, to_treasury)`. The treasury share
+/// rounds down and the recipient gets the rest, so the parts always sum to
+/// `actual`.
+pub fn split_slash(actual: u64, treasury_bps: u16) -> (u64, u64) {
+    let bps = (treasury_bps as u64).min(BPS_DENOMINATOR);
+    let to_treasury = (actual as u128 * bps as u128 / BPS_DENOMINATOR as u128) as u64;
+    (actual - to_treasury, to_treasury)
+}
+
+/// The PDA `escrow_program` signs reputation CPIs with.
+pub fn escrow_authority(escrow_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[ESCROW_AUTHORITY_SEED], escrow_program).0
+}
+
+pub fn clawback_amount(requested: u64, bond_balance: u64) -> u64 {
+    requested.min(bond_balance)
+}
+
+fn set_service_paused(ctx: Context<Bond>, service_id: String, paused: bool) -> Result<()> {
+    require_keys_eq!(
+        ctx.accounts.service.owner,
+        ctx.accounts.provider.key(),
+        ReputationError::InvalidOwner
+    );
+    ctx.accounts.service.paused = paused;
+    if paused {
+        emit!(ServicePaused { service_id });
+    } else {
+        emit!(ServiceUnpaused { service_id });
+    }
+    Ok(())
+}
+
+fn transfer_into_service<'info>(
+    provider: &Signer<'info>,
+    service: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let accounts = Transfer {
+        from: provider.to_account_info(),
+        to: service.clone(),
+    };
+    system_program::transfer(
+        CpiContext::new(system_program.to_account_info(), accounts),
+        amount,
+    )
+}
+
+/// Moves tokens out of a service's bond vault, signed by the service PDA.
+#[allow(clippy::too_many_arguments)]
+fn vault_transfer<'info>(
+    vault: &Account<'info, TokenAccount>,
+    destination: &Account<'info, TokenAccount>,
+    mint: &Account<'info, Mint>,
+    service: &Account<'info, Service>,
+    token_program: &Program<'info, Token>,
+    service_id: &str,
+    bump: u8,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let seeds: &[&[u8]] = &[b"svc", service_id.as_bytes(), &[bump]];
+    let accounts = TransferChecked {
+        from: vault.to_account_info(),
+        mint: mint.to_account_info(),
+        to: destination.to_account_info(),
+        authority: service.to_account_info(),
+    };
+    token::transfer_checked(
+        CpiContext::new_with_signer(token_program.to_account_info(), accounts, &[seeds]),
+        amount,
+        mint.decimals,
+    )
+}
+
+fn resize_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    new_len: usize,
+) -> Result<()> {
+    let required = Rent::get()?.minimum_balance(new_len);
+    let shortfall = required.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        let accounts = Transfer {
+            from: payer.to_account_info(),
+            to: account.clone(),
+        };
+        system_program::transfer(
+            CpiContext::new(system_program.to_account_info(), accounts),
+            shortfall,
+        )?;
+    }
+    account.resize(new_len)?;
+    Ok(())
+}
+
+/// Appends to the service's audit log.
+fn record_audit(
+    log: &mut ServiceAuditLog,
+    action: AuditAction,
+    ts: i64,
+    amount_delta: i64,
+    score_delta_ok: f32,
+) {
+    log.append(AuditEntry {
+        ts,
+        action: action as u8,
+        amount_delta,
+        score_delta_ok,
+    });
+    emit!(AuditLogEntry {
+        service_id: log.service_id.clone(),
+        action: action as u8,
+        ts,
+    });
+}
+
+fn audit_amount(amount: u64) -> i64 {
+    i64::try_from(amount).unwrap_or(i64::MAX)
+}
+
+fn pay_out<'info>(
+    amount: u64,
+    source: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    require!(
+        source.lamports() >= amount,
+        ReputationError::InsufficientBond
+    );
+    let credited = destination
+        .lamports()
+        .checked_add(amount)
+        .ok_or(ReputationError::MathOverflow)?;
+    **source.try_borrow_mut_lamports()? -= amount;
+    **destination.try_borrow_mut_lamports()? = credited;
+    Ok(())
+}
+
+#[event]
+pub struct OutcomeRecorded {
+    pub service_id: String,
+    pub outcome: u8,
+    /// Weight actually applied, after the config cap.
+    pub weight: f32,
+}
+
+#[event]
+pub struct UptimeRecorded {
+    pub service_id: String,
+    pub ratio_bps: u16,
+}
+
+#[event]
+pub struct HistogramUpdated {
+    pub service_id: String,
+    pub bucket_index: u8,
+    pub bucket_count: u32,
+}
+
+#[event]
+pub struct ServiceDegraded {
+    pub service_id: String,
+}
+
+#[event]
+pub struct StakeChanged {
+    pub service_id: String,
+    pub staker: Pubkey,
+    pub shares: u64,
+    pub total_delegated: u64,
+}
+
+#[event]
+pub struct StakeRewardDistributed {
+    pub service_id: String,
+    pub amount: u64,
+    pub total_delegated: u64,
+}
+
+#[event]
+pub struct LatencyAlertFired {
+    pub service_id: String,
+    pub p95_ms: u64,
+    pub threshold_ms: u64,
+}
+
+#[event]
+pub struct ServicePaused {
+    pub service_id: String,
+}
+
+#[event]
+pub struct ServiceUnpaused {
+    pub service_id: String,
+}
+
+#[event]
+pub struct ServiceSuspended {
+    pub service_id: String,
+    pub suspension_count: u32,
+    pub suspension_until_ts: i64,
+}
+
+#[event]
+pub struct ServiceReinstated {
+    pub service_id: String,
+    pub top_up: u64,
+    pub bond_locked_until_ts: i64,
+}
+
+#[event]
+pub struct ConfigUpdated {
+    pub admin: Pubkey,
+    pub ewma_alpha: f64,
+    pub quantile_inc: f64,
+    pub quantile_dec: f64,
+    pub paused: bool,
+    pub treasury: Pubkey,
+    pub treasury_bps: u16,
+    pub max_samples_per_epoch: u16,
+    pub max_outcome_weight: f32,
+    pub outcome_window_s: i64,
+    pub failure_threshold: u32,
+    pub slash_cap_multiplier_bps: u32,
+    pub enforce_review_threshold: bool,
+}
+
+#[event]
+pub struct ReviewerThresholdUpdated {
+    pub min_completed_calls: u32,
+    pub min_payer_bond: u64,
+}
+
+#[event]
+pub struct ReviewThrottled {
+    pub call_id: String,
+    pub reviewer: Pubkey,
+}
+
+#[event]
+pub struct AuditLogEntry {
+    pub service_id: String,
+    pub action: u8,
+    pub ts: i64,
+}
+
+#[event]
+pub struct AuthorizedCallerUpdated {
+    pub caller: Pubkey,
+}
+
+#[error_code]
+pub enum ReputationError {
+    #[msg("Invalid owner for operation")]
+    InvalidOwner,
+    #[msg("Amount must be positive")]
+    InvalidAmount,
+    #[msg("Insufficient bonded balance")]
+    InsufficientBond,
+    #[msg("Invalid authority")]
+    InvalidAuthority,
+    #[msg("Program is paused")]
+    ProgramPaused,
+    #[msg("Gain must be within (0, 1]")]
+    InvalidGain,
+    #[msg("Account is already on the current layout")]
+    AlreadyMigrated,
+    #[msg("Unsupported account version")]
+    UnsupportedVersion,
+    #[msg("Unknown dispute kind")]
+    InvalidDisputeKind,
+    #[msg("Update quota exhausted for this epoch")]
+    UpdateQuotaExceeded,
+    #[msg("Treasury share must be at most 10000 bps")]
+    InvalidTreasuryBps,
+    #[msg("Treasury account does not match config")]
+    InvalidTreasury,
+    #[msg("Slash recipient cannot be the treasury")]
+    InvalidSlashRecipient,
+    #[msg("Unknown outcome code")]
+    UnknownOutcome,
+    #[msg("Mint does not match the service's bond mint")]
+    BondMintMismatch,
+    #[msg("Too many latency samples this epoch")]
+    RateLimited,
+    #[msg("Samples per epoch must be positive")]
+    InvalidSampleLimit,
+    #[msg("Weight must be a non-negative number")]
+    InvalidWeight,
+    #[msg("Outcome window must be positive")]
+    InvalidOutcomeWindow,
+    #[msg("Failure threshold must be positive")]
+    InvalidFailureThreshold,
+    #[msg("Slash cap multiplier must be positive")]
+    InvalidSlashCap,
+    #[msg("p95 latency is within the alert threshold")]
+    LatencyWithinThreshold,
+    #[msg("Latency alert already emitted")]
+    AlertAlreadyEmitted,
+    #[msg("Service is not suspended")]
+    NotSuspended,
+    #[msg("Stake pool was slashed to zero; no new deposits")]
+    StakePoolDepleted,
+    #[msg("Withdrawal not requested or still cooling down")]
+    StakeCoolingDown,
+    #[msg("Stake reward exceeds 2000 bps")]
+    InvalidStakeReward,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Suspension backoff has not run out yet")]
+    SuspensionActive,
+    #[msg("Stake reward lamports have not reached the service")]
+    RewardNotFunded,
+    #[msg("Withdrawal request expired; request again")]
+    StakeRequestExpired,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
     fn applies_ok_outcome() {
         let mut svc = Service::default();
-        svc.apply_outcome(0, 0.5);
-        assert!((svc.ok - 0.5).abs() < f32::EPSILON);
-        assert_eq!(svc.late, 0.0);
-        assert_eq!(svc.disputed, 0.0);
+        svc.apply_outcome(0, 0.5, 0, DEFAULT_OUTCOME_WINDOW_S)
+            .unwrap();
+        assert!((svc.ok - 0.5).abs() < f32::EPSILON);
+        assert_eq!(svc.late, 0.0);
+        assert_eq!(svc.disputed, 0.0);
+    }
+
+    #[test]
+    fn applies_other_outcomes() {
+        let mut svc = Service::default();
+        svc.apply_outcome(1, 1.0, 0, DEFAULT_OUTCOME_WINDOW_S)
+            .unwrap();
+        svc.apply_outcome(2, 0.25, 0, DEFAULT_OUTCOME_WINDOW_S)
+            .unwrap();
+        svc.apply_outcome(3, 0.5, 0, DEFAULT_OUTCOME_WINDOW_S)
+            .unwrap();
+        svc.apply_outcome(4, 0.75, 0, DEFAULT_OUTCOME_WINDOW_S)
+            .unwrap();
+        assert_eq!(svc.ok, 0.0);
+        assert!((svc.late - 1.0).abs() < f32::EPSILON);
+        assert!((svc.disputed - 0.25).abs() < f32::EPSILON);
+        assert!((svc.partial - 0.5).abs() < f32::EPSILON);
+        assert!((svc.timeout - 0.75).abs() < f32::EPSILON);
+        assert!((svc.outcome_total() - 2.5).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn outcome_weight_rejects_nan_and_negative_and_caps() {
+        for bad in [f32::NAN, -0.5, f32::NEG_INFINITY] {
+            assert_eq!(
+                outcome_weight(bad, 1.0).unwrap_err(),
+                ReputationError::InvalidWeight.into()
+            );
+        }
+        assert_eq!(outcome_weight(0.0, 1.0).unwrap(), 0.0);
+        assert_eq!(outcome_weight(0.2, 0.25).unwrap(), 0.2);
+        assert_eq!(outcome_weight(0.9, 0.25).unwrap(), 0.25);
+        assert_eq!(outcome_weight(f32::INFINITY, 1.0).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn rejects_unknown_outcome_codes() {
+        let mut svc = Service::default();
+        assert_eq!(
+            svc.apply_outcome(5, 1.0, 0, DEFAULT_OUTCOME_WINDOW_S)
+                .unwrap_err(),
+            ReputationError::UnknownOutcome.into()
+        );
+        assert_eq!(svc.outcome_total(), 0.0);
+        assert_eq!(svc.compliance_rate_bps, 0);
+    }
+
+    #[test]
+    fn latency_samples_land_in_log2_buckets() {
+        let mut svc = Service::default();
+        for sample in [0, 1, 3, 4, 1_000, 40_000, u64::MAX] {
+            svc.record_latency(sample);
+        }
+        assert_eq!(svc.latency_histogram[0], 2);
+        assert_eq!(svc.latency_histogram[1], 1);
+        assert_eq!(svc.latency_histogram[2], 1);
+        assert_eq!(svc.latency_histogram[9], 1);
+        assert_eq!(svc.latency_histogram[15], 2);
+    }
+
+    #[test]
+    fn histogram_p95_uniform_and_spike() {
+        assert_eq!(histogram_p95(&[0; LATENCY_BUCKETS]), 0);
+
+        // 100 samples per bucket: 95% of 1_600 is only reached in the last.
+        assert_eq!(histogram_p95(&[100; LATENCY_BUCKETS]), 1 << 16);
+
+        let mut spike = [0u32; LATENCY_BUCKETS];
+        spike[7] = 1_000;
+        assert_eq!(histogram_p95(&spike), 256);
+
+        // A 4% tail stays below the p95.
+        spike[12] = 40;
+        assert_eq!(histogram_p95(&spike), 256);
+        spike[12] = 60;
+        assert_eq!(histogram_p95(&spike), 8_192);
+    }
+
+    #[test]
+    fn record_latency_initialises_and_tracks() {
+        let mut svc = Service::default();
+        svc.record_latency(150);
+        assert_eq!(svc.ewma_latency_ms, 150);
+        assert_eq!(svc.p95_est_ms, 150);
+        assert_eq!(svc.latency_samples, 1);
+
+        svc.record_latency(450);
+        assert_eq!(svc.latency_samples, 2);
+        assert!(svc.ewma_latency_ms >= 150);
+        assert!(svc.p95_est_ms >= 150);
+    }
+
+    #[test]
+    fn config_rejects_non_admin_and_bad_gains() {
+        let config = Config {
+            admin: Pubkey::new_unique(),
+            authorized_caller: Pubkey::new_unique(),
+            ewma_alpha: EWMA_ALPHA,
+            quantile_inc: QUANTILE_INC,
+            quantile_dec: QUANTILE_DEC,
+            paused: false,
+            treasury: Pubkey::new_unique(),
+            treasury_bps: 0,
+            max_samples_per_epoch: DEFAULT_MAX_SAMPLES_PER_EPOCH,
+            max_outcome_weight: 1.0,
+            outcome_window_s: DEFAULT_OUTCOME_WINDOW_S,
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            slash_cap_multiplier_bps: DEFAULT_SLASH_CAP_MULTIPLIER_BPS,
+            enforce_review_threshold: false,
+        };
+        assert!(config.check_admin(&config.admin).is_ok());
+        assert!(config.check_admin(&Pubkey::new_unique()).is_err());
+
+        let mut params = ConfigParams {
+            ewma_alpha: 0.0,
+            quantile_inc: QUANTILE_INC,
+            quantile_dec: QUANTILE_DEC,
+            paused: false,
+            treasury: Pubkey::new_unique(),
+            treasury_bps: 3_000,
+            max_samples_per_epoch: DEFAULT_MAX_SAMPLES_PER_EPOCH,
+            max_outcome_weight: 1.0,
+            outcome_window_s: DEFAULT_OUTCOME_WINDOW_S,
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            slash_cap_multiplier_bps: DEFAULT_SLASH_CAP_MULTIPLIER_BPS,
+            enforce_review_threshold: false,
+        };
+        assert!(params.validate().is_err());
+        params.ewma_alpha = EWMA_ALPHA;
+        assert!(params.validate().is_ok());
+        params.treasury_bps = 10_001;
+        assert!(params.validate().is_err());
+        params.treasury_bps = 3_000;
+        params.max_samples_per_epoch = 0;
+        assert!(params.validate().is_err());
+        params.max_samples_per_epoch = DEFAULT_MAX_SAMPLES_PER_EPOCH;
+        params.max_outcome_weight = 0.0;
+        assert!(params.validate().is_err());
+        params.max_outcome_weight = f32::NAN;
+        assert!(params.validate().is_err());
+        params.max_outcome_weight = 0.25;
+        assert!(params.validate().is_ok());
+        params.outcome_window_s = 0;
+        assert!(params.validate().is_err());
+        params.outcome_window_s = DEFAULT_OUTCOME_WINDOW_S;
+        params.failure_threshold = 0;
+        assert!(params.validate().is_err());
+        params.failure_threshold = DEFAULT_FAILURE_THRESHOLD;
+        params.slash_cap_multiplier_bps = 0;
+        assert!(params.validate().is_err());
+    }
+
+    #[test]
+    fn slash_splits_seventy_thirty() {
+        assert_eq!(split_slash(1_000_000, 3_000), (700_000, 300_000));
+        // Odd lamports round in the recipient's favour.
+        assert_eq!(split_slash(999, 3_000), (700, 299));
+        assert_eq!(split_slash(999, 0), (999, 0));
+        assert_eq!(split_slash(999, 10_000), (0, 999));
+        let (payer, treasury) = split_slash(u64::MAX, 3_000);
+        assert_eq!(payer + treasury, u64::MAX);
+    }
+
+    #[test]
+    fn slash_caller_follows_config() {
+        let devnet_escrow = pubkey!("6zpAcx4Yo9MmDf4w8pBGez8bm47zyKuyjr5Y5QkC3ayL");
+        let first_escrow = Pubkey::new_unique();
+        let second_escrow = Pubkey::new_unique();
+        let mut config = Config {
+            admin: Pubkey::new_unique(),
+            authorized_caller: first_escrow,
+            ewma_alpha: EWMA_ALPHA,
+            quantile_inc: QUANTILE_INC,
+            quantile_dec: QUANTILE_DEC,
+            paused: false,
+            treasury: Pubkey::new_unique(),
+            treasury_bps: 0,
+            max_samples_per_epoch: DEFAULT_MAX_SAMPLES_PER_EPOCH,
+            max_outcome_weight: 1.0,
+            outcome_window_s: DEFAULT_OUTCOME_WINDOW_S,
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            slash_cap_multiplier_bps: DEFAULT_SLASH_CAP_MULTIPLIER_BPS,
+            enforce_review_threshold: false,
+        };
+        assert!(config
+            .check_caller(&escrow_authority(&first_escrow))
+            .is_ok());
+        assert!(config
+            .check_caller(&escrow_authority(&second_escrow))
+            .is_err());
+        assert!(config
+            .check_caller(&escrow_authority(&devnet_escrow))
+            .is_err());
+        // The program id itself is not the signer escrow uses.
+        assert!(config.check_caller(&first_escrow).is_err());
+
+        config.authorized_caller = second_escrow;
+        assert!(config
+            .check_caller(&escrow_authority(&second_escrow))
+            .is_ok());
+        assert!(config
+            .check_caller(&escrow_authority(&first_escrow))
+            .is_err());
+
+        // The localnet stand-in signs as the admin.
+        config.authorized_caller = config.admin;
+        assert!(config.check_caller(&config.admin).is_ok());
+        assert!(config
+            .check_caller(&escrow_authority(&config.admin))
+            .is_err());
+    }
+
+    #[test]
+    fn record_latency_uses_configured_gains() {
+        let mut svc = Service::default();
+        let gains = LatencyGains {
+            ewma_alpha: 1.0,
+            ..LatencyGains::default()
+        };
+        svc.record_latency_with(100, &gains);
+        svc.record_latency_with(300, &gains);
+        assert_eq!(svc.ewma_latency_ms, 300);
+    }
+
+    #[test]
+    fn idle_gap_lets_fast_sample_pull_p95_down_faster() {
+        let mut fresh = Service::default();
+        for _ in 0..20 {
+            fresh.record_latency(1_000);
+        }
+        fresh.last_active_ts = 1_000;
+        let mut idle = fresh.clone();
+
+        let gains = LatencyGains::default();
+        let now = 1_000 + 3 * P95_DECAY_PERIOD_S;
+        assert_eq!(idle.idle_s(now), 3 * P95_DECAY_PERIOD_S);
+        fresh.record_latency_with(100, &gains.aged(0));
+        idle.record_latency_with(100, &gains.aged(idle.idle_s(now)));
+        assert_eq!(fresh.p95_est_ms, 991);
+        assert_eq!(idle.p95_est_ms, 928);
+
+        let capped = gains.aged(i64::MAX);
+        assert_eq!(capped.p95_shift, MAX_P95_DECAY_SHIFT);
+        let mut slow = idle.clone();
+        slow.record_latency_with(1_000_000, &capped);
+        assert_eq!(slow.p95_est_ms, 1_000_000);
+        assert_eq!(Service::default().idle_s(now), 0);
+    }
+
+    #[test]
+    fn latency_samples_rate_limited_per_epoch() {
+        let mut svc = Service::default();
+        for _ in 0..DEFAULT_MAX_SAMPLES_PER_EPOCH {
+            svc.consume_latency_sample(7, DEFAULT_MAX_SAMPLES_PER_EPOCH)
+                .unwrap();
+        }
+        // A flood is cut off for the rest of the epoch, not just the slot.
+        for _ in 0..10 {
+            assert_eq!(
+                svc.consume_latency_sample(7, DEFAULT_MAX_SAMPLES_PER_EPOCH)
+                    .unwrap_err(),
+                ReputationError::RateLimited.into()
+            );
+        }
+        for epoch in 8..12 {
+            svc.consume_latency_sample(epoch, DEFAULT_MAX_SAMPLES_PER_EPOCH)
+                .unwrap();
+        }
+        assert_eq!(svc.samples_this_epoch, 1);
+    }
+
+    #[test]
+    fn wilson_lower_bound_matches_reference_values() {
+        // (ok, total, floor of the bound from a 50-digit reference).
+        let cases: [(f32, f32, u16); 13] = [
+            (0.0, 0.0, 0),
+            (0.0, 1.0, 0),
+            (1.0, 1.0, 2065),
+            (3.0, 3.0, 4384),
+            (1.0, 2.0, 945),
+            (5.0, 10.0, 2365),
+            (9.0, 10.0, 5958),
+            (98.0, 100.0, 9299),
+            (9_800.0, 10_000.0, 9770),
+            (10_000.0, 10_000.0, 9996),
+            (999_999.0, 1_000_000.0, 9999),
+            (0.5, 1.0, 546),
+            (2.5, 3.0, 3099),
+        ];
+        for (ok, total, expected) in cases {
+            let got = wilson_lower_bound_bps(
+                (ok * OUTCOME_COUNT_SCALE) as u64,
+                (total * OUTCOME_COUNT_SCALE) as u64,
+            );
+            assert!(
+                got.abs_diff(expected) <= 1,
+                "{ok}/{total}: {got} vs {expected}"
+            );
+        }
+        assert_eq!(wilson_lower_bound_bps(u64::MAX, u64::MAX), 9999);
+    }
+
+    #[test]
+    fn success_rate_ranks_small_perfect_below_large_near_perfect() {
+        let mut small = Service::default();
+        for _ in 0..3 {
+            small
+                .apply_outcome(0, 1.0, 0, DEFAULT_OUTCOME_WINDOW_S)
+                .unwrap();
+        }
+        let mut large = Service {
+            ok: 9_799.0,
+            disputed: 200.0,
+            ..Service::default()
+        };
+        large
+            .apply_outcome(0, 1.0, 0, DEFAULT_OUTCOME_WINDOW_S)
+            .unwrap();
+        assert!(small.success_rate_bp < large.success_rate_bp);
+        assert!(small.success_rate_bp.abs_diff(4384) <= 1);
+    }
+
+    #[test]
+    fn uptime_ratio_counts_passing_checks() {
+        let mut svc = Service::default();
+        assert_eq!(uptime_ratio(&svc), 0.0);
+        for _ in 0..4 {
+            svc.record_uptime(true);
+        }
+        assert_eq!(uptime_ratio(&svc), 1.0);
+        for _ in 0..4 {
+            svc.record_uptime(false);
+        }
+        assert_eq!((svc.uptime_checks_ok, svc.uptime_checks_total), (4, 8));
+        assert_eq!(uptime_ratio(&svc), 0.5);
     }
 
     #[test]
-    fn applies_other_outcomes() {
+    fn windowed_ratio_tracks_recent_windows() {
+        let week = DEFAULT_OUTCOME_WINDOW_S;
+        let start = 1_700_000_000;
         let mut svc = Service::default();
-        svc.apply_outcome(1, 1.0);
-        svc.apply_outcome(2, 0.25);
-        assert_eq!(svc.ok, 0.0);
-        assert!((svc.late - 1.0).abs() < f32::EPSILON);
-        assert!((svc.disputed - 0.25).abs() < f32::EPSILON);
+        let record = |svc: &mut Service, now: i64, ok: usize, bad: usize| {
+            for _ in 0..ok {
+                svc.apply_outcome(0, 1.0, now, week).unwrap();
+            }
+            for _ in 0..bad {
+                svc.apply_outcome(2, 1.0, now, week).unwrap();
+            }
+        };
+        assert_eq!(windowed_ok_ratio(&svc), None);
+
+        // A degraded first window.
+        record(&mut svc, start, 2, 8);
+        assert!((windowed_ok_ratio(&svc).unwrap() - 0.2).abs() < 1e-6);
+
+        // Recovery: the bad window now counts at half weight.
+        record(&mut svc, start + week + 10, 10, 0);
+        assert_eq!(svc.current_window_start, start + week);
+        assert_eq!((svc.prev_window_ok, svc.prev_window_disputed), (2.0, 8.0));
+        assert!((windowed_ok_ratio(&svc).unwrap() - 11.0 / 15.0).abs() < 1e-6);
+
+        // One more window and the bad one is gone entirely.
+        record(&mut svc, start + 2 * week + 10, 10, 0);
+        assert_eq!(windowed_ok_ratio(&svc), Some(1.0));
+        let recovered = composite_score(&svc);
+
+        // A fresh collapse pulls the score back down despite lifetime oks.
+        record(&mut svc, start + 3 * week, 0, 10);
+        assert!((windowed_ok_ratio(&svc).unwrap() - 5.0 / 15.0).abs() < 1e-6);
+        assert!(composite_score(&svc) < recovered);
+        assert!(svc.ok > svc.disputed);
+
+        // After an idle window both buckets start over.
+        record(&mut svc, start + 5 * week + 1, 1, 0);
+        assert_eq!(svc.current_window_start, start + 5 * week);
+        assert_eq!(
+            (
+                svc.prev_window_ok,
+                svc.prev_window_late,
+                svc.prev_window_disputed
+            ),
+            (0.0, 0.0, 0.0)
+        );
+        assert_eq!(windowed_ok_ratio(&svc), Some(1.0));
     }
 
     #[test]
-    fn record_latency_initialises_and_tracks() {
+    fn composite_score_moves_with_uptime() {
+        let mut svc = Service {
+            compliance_rate_bps: COMPLIANCE_MAX_BPS,
+            ..Service::default()
+        };
+        svc.record_uptime(true);
+        assert!((composite_score(&svc) - 1.0).abs() < f32::EPSILON);
+        svc.record_uptime(false);
+        assert!((composite_score(&svc) - 0.85).abs() < 1e-6);
+        svc.compliance_rate_bps = 0;
+        assert!((composite_score(&svc) - 0.15).abs() < 1e-6);
+        assert_eq!(composite_score_bps(&svc), 1_500);
+
+        let unprobed = Service {
+            compliance_rate_bps: 9_000,
+            ..Service::default()
+        };
+        assert_eq!(composite_score_bps(&unprobed), 9_000);
+    }
+
+    #[test]
+    fn latency_history_wraps_after_capacity() {
         let mut svc = Service::default();
-        svc.record_latency(150);
-        assert_eq!(svc.ewma_latency_ms, 150);
-        assert_eq!(svc.p95_est_ms, 150);
-        assert_eq!(svc.latency_samples, 1);
+        for sample in 1..=3 {
+            svc.record_latency(sample * 10);
+        }
+        assert_eq!(svc.recent_latencies(), vec![10, 20, 30]);
 
-        svc.record_latency(450);
-        assert_eq!(svc.latency_samples, 2);
-        assert!(svc.ewma_latency_ms >= 150);
-        assert!(svc.p95_est_ms >= 150);
+        for sample in 4..=11 {
+            svc.record_latency(sample * 10);
+        }
+        assert_eq!(svc.latency_history_idx, 3);
+        assert_eq!(
+            svc.recent_latencies(),
+            vec![40, 50, 60, 70, 80, 90, 100, 110]
+        );
+    }
+
+    #[test]
+    fn legacy_service_layout_migrates_into_current() {
+        let legacy = ServiceV0 {
+            owner: Pubkey::new_unique(),
+            ok: 3.0,
+            late: 1.0,
+            disputed: 0.0,
+            bond_balance: 42,
+            ewma_latency_ms: 120,
+            p95_est_ms: 300,
+            latency_samples: 9,
+        };
+        let mut data = Service::DISCRIMINATOR.to_vec();
+        data.extend(legacy.try_to_vec().unwrap());
+        assert_eq!(data.len(), 8 + ServiceV0::LEN);
+
+        let svc = Service::load_versioned(&data).unwrap();
+        assert_eq!(svc.version, SERVICE_VERSION);
+        assert_eq!(svc.owner, legacy.owner);
+        assert_eq!(svc.ok, 3.0);
+        assert_eq!(svc.late, 1.0);
+        assert_eq!(svc.bond_balance, 42);
+        assert_eq!(svc.ewma_latency_ms, 120);
+        assert_eq!(svc.p95_est_ms, 300);
+        assert_eq!(svc.latency_samples, 9);
+        assert_eq!(svc.compliance_rate_bps, 7_500);
+
+        let mut migrated = vec![0u8; 8 + Service::INIT_SPACE];
+        svc.try_serialize(&mut &mut migrated[..]).unwrap();
+        let reloaded = Service::load_versioned(&migrated).unwrap();
+        assert_eq!(reloaded.try_to_vec().unwrap(), svc.try_to_vec().unwrap());
+    }
+
+    #[test]
+    fn v1_service_reads_with_appended_defaults() {
+        let svc = Service {
+            owner: Pubkey::new_unique(),
+            bond_balance: 42,
+            p95_est_ms: 300,
+            ..Service::default()
+        };
+        let mut full = Vec::new();
+        svc.try_serialize(&mut full).unwrap();
+        full[8] = 1;
+        let base = full[..8 + Service::V1_BASE_LEN].to_vec();
+
+        let migrated = Service::load_versioned(&base).unwrap();
+        assert_eq!(migrated.version, SERVICE_VERSION);
+        assert_eq!(migrated.owner, svc.owner);
+        assert_eq!(migrated.bond_balance, 42);
+        assert_eq!(migrated.p95_est_ms, 300);
+        assert!(migrated.is_active);
+        assert_eq!(migrated.max_outstanding_calls, 0);
+
+        // A later version-1 account keeps the flag it stored.
+        let mut deactivated = full.clone();
+        deactivated[8 + Service::V1_BASE_LEN] = 0;
+        assert!(!Service::load_versioned(&deactivated).unwrap().is_active);
+
+        full[8] = SERVICE_VERSION + 1;
+        assert!(Service::load_versioned(&full).is_err());
+    }
+
+    fn empty_health() -> ServiceHealthStatus {
+        ServiceHealthStatus {
+            owner: Pubkey::new_unique(),
+            is_healthy: true,
+            last_checked_ts: 0,
+            failure_count: 0,
+            consecutive_failures: 0,
+        }
+    }
+
+    #[test]
+    fn slash_takes_bond_then_stakes_for_exact_amount() {
+        let mut svc = Service {
+            bond_balance: 1_000,
+            ..Service::default()
+        };
+        svc.delegate(3_000).unwrap();
+        assert_eq!(svc.slash_bond_and_stakes(400), (400, 0));
+        assert_eq!(svc.slash_bond_and_stakes(1_000), (600, 400));
+        assert_eq!((svc.bond_balance, svc.total_delegated), (0, 2_600));
+        // Capped at what is left.
+        assert_eq!(svc.slash_bond_and_stakes(5_000), (0, 2_600));
+
+        for requested in [1, 999, 1_000, 1_001, 3_999, 4_000] {
+            let mut svc = Service {
+                bond_balance: 1_000,
+                ..Service::default()
+            };
+            svc.delegate(3_000).unwrap();
+            let actual = slash_amount(
+                1,
+                requested,
+                svc.slashable_balance(),
+                DEFAULT_SLASH_CAP_MULTIPLIER_BPS,
+            )
+            .unwrap();
+            let (from_bond, from_stakes) = svc.slash_bond_and_stakes(actual);
+            assert_eq!(from_bond + from_stakes, requested);
+            assert_eq!(svc.bond_balance + svc.total_delegated, 4_000 - requested);
+        }
+    }
+
+    #[test]
+    fn stake_pool_shares_slashes_and_rewards_without_leaks() {
+        let mut svc = Service::default();
+        let deposits = [1_000u64, 333, 2_501, 7];
+        let shares: Vec<u64> = deposits.iter().map(|d| svc.delegate(*d).unwrap()).collect();
+        assert_eq!(svc.total_delegated, 3_841);
+
+        svc.bond_balance = 100;
+        assert_eq!(svc.slash_bond_and_stakes(1_000), (100, 900));
+        svc.total_delegated += 77; // reward
+        let pool = svc.total_delegated;
+        // Each stake lost and gained in proportion to its shares.
+        assert_eq!(svc.stake_value(shares[0]), 1_000 * pool / 3_841);
+
+        let late = svc.delegate(500).unwrap();
+        assert!(svc.stake_value(late) <= 500);
+
+        let mut paid = 0;
+        for s in shares.iter().chain([&late]) {
+            paid += svc.undelegate(*s);
+        }
+        assert_eq!(paid, pool + 500);
+        assert_eq!((svc.total_delegated, svc.total_stake_shares), (0, 0));
+    }
+
+    #[test]
+    fn stake_reward_must_already_sit_on_the_service() {
+        let mut svc = Service {
+            bond_balance: 1_000,
+            performance_bond_balance: 200,
+            ..Default::default()
+        };
+        svc.delegate(300).unwrap();
+        let rent = 50;
+        let books = rent + 1_500;
+        assert!(svc.check_reward_funded(books + 40, rent, 40).is_ok());
+        assert_eq!(
+            svc.check_reward_funded(books + 39, rent, 40).unwrap_err(),
+            ReputationError::RewardNotFunded.into()
+        );
+        assert!(svc.check_reward_funded(0, rent, 1).is_err());
+        assert!(svc.check_reward_funded(books, rent, 0).is_ok());
+    }
+
+    #[test]
+    fn stake_withdraw_waits_for_cooldown() {
+        let mut stake = Stake {
+            service: Pubkey::new_unique(),
+            staker: Pubkey::new_unique(),
+            shares: 100,
+            withdraw_requested_ts: 0,
+        };
+        assert!(stake.check_withdraw(100, i64::MAX).is_err());
+        stake.withdraw_requested_ts = 1_000;
+        assert_eq!(
+            stake
+                .check_withdraw(100, 1_000 + STAKE_COOLDOWN_S - 1)
+                .unwrap_err(),
+            ReputationError::StakeCoolingDown.into()
+        );
+        assert!(stake.check_withdraw(100, 1_000 + STAKE_COOLDOWN_S).is_ok());
+        assert!(stake.check_withdraw(101, 1_000 + STAKE_COOLDOWN_S).is_err());
+        assert!(stake.check_withdraw(0, 1_000 + STAKE_COOLDOWN_S).is_err());
+        let expiry = 1_000 + STAKE_COOLDOWN_S + STAKE_WITHDRAW_WINDOW_S;
+        assert!(stake.check_withdraw(100, expiry - 1).is_ok());
+        assert_eq!(
+            stake.check_withdraw(100, expiry).unwrap_err(),
+            ReputationError::StakeRequestExpired.into()
+        );
+
+        let mut wiped = Service::default();
+        wiped.delegate(10).unwrap();
+        wiped.slash_bond_and_stakes(10);
+        assert_eq!(
+            wiped.delegate(10).unwrap_err(),
+            ReputationError::StakePoolDepleted.into()
+        );
+    }
+
+    #[test]
+    fn failure_streak_trips_and_resets_on_ok() {
+        let mut svc = Service::default();
+        for _ in 1..DEFAULT_FAILURE_THRESHOLD {
+            assert!(!svc.track_failure_streak(false, DEFAULT_FAILURE_THRESHOLD, 0));
+        }
+        assert!(!svc.track_failure_streak(true, DEFAULT_FAILURE_THRESHOLD, 0));
+        assert_eq!(svc.consecutive_failures, 0);
+        assert!(!is_suspended(&svc, 0));
+
+        for _ in 1..DEFAULT_FAILURE_THRESHOLD {
+            svc.track_failure_streak(false, DEFAULT_FAILURE_THRESHOLD, 0);
+        }
+        assert!(svc.track_failure_streak(false, DEFAULT_FAILURE_THRESHOLD, 1_000));
+        assert!(svc.suspended);
+        // The trip shares the dispute-loss backoff, so the event is accurate.
+        assert_eq!(svc.suspension_count, 1);
+        assert_eq!(svc.suspension_until_ts, 1_000 + BASE_SUSPENSION_S);
+        // Still suspended after the backoff, until reinstated.
+        assert!(is_suspended(&svc, 1_000 + BASE_SUSPENSION_S));
+        // Further failures do not trip it again, and an ok does not lift it.
+        assert!(!svc.track_failure_streak(false, DEFAULT_FAILURE_THRESHOLD, 2_000));
+        assert_eq!(svc.suspension_count, 1);
+        svc.track_failure_streak(true, DEFAULT_FAILURE_THRESHOLD, 2_000);
+        assert!(svc.suspended);
+    }
+
+    #[test]
+    fn reinstate_requires_suspension_and_top_up() {
+        let mut svc = Service::default();
+        assert_eq!(
+            check_reinstate(&svc, REINSTATE_MIN_TOP_UP_LAMPORTS, 0).unwrap_err(),
+            ReputationError::NotSuspended.into()
+        );
+        for _ in 0..DEFAULT_FAILURE_THRESHOLD {
+            svc.track_failure_streak(false, DEFAULT_FAILURE_THRESHOLD, 0);
+        }
+        assert_eq!(
+            check_reinstate(&svc, REINSTATE_MIN_TOP_UP_LAMPORTS, BASE_SUSPENSION_S - 1)
+                .unwrap_err(),
+            ReputationError::SuspensionActive.into()
+        );
+        let now = BASE_SUSPENSION_S;
+        assert_eq!(
+            check_reinstate(&svc, REINSTATE_MIN_TOP_UP_LAMPORTS - 1, now).unwrap_err(),
+            ReputationError::InvalidAmount.into()
+        );
+        check_reinstate(&svc, REINSTATE_MIN_TOP_UP_LAMPORTS, now).unwrap();
+        svc.bond_balance = 500;
+        svc.reinstate(REINSTATE_MIN_TOP_UP_LAMPORTS, now);
+        assert!(!svc.suspended);
+        assert!(!is_suspended(&svc, now));
+        assert_eq!(svc.consecutive_failures, 0);
+        assert_eq!(svc.bond_balance, REINSTATE_MIN_TOP_UP_LAMPORTS + 500);
+
+        // The top-up stays locked; only the older bond can leave.
+        assert_eq!(svc.withdrawable_bond(now), 500);
+        let unlock = now + REINSTATE_LOCK_S;
+        assert_eq!(svc.withdrawable_bond(unlock - 1), 500);
+        assert_eq!(
+            svc.withdrawable_bond(unlock),
+            REINSTATE_MIN_TOP_UP_LAMPORTS + 500
+        );
+    }
+
+    #[test]
+    fn health_degrades_after_consecutive_failures() {
+        let mut health = empty_health();
+        for ts in 1..HEALTH_FAILURE_THRESHOLD as i64 {
+            health.record(false, ts);
+            assert!(!health.is_degraded());
+        }
+        health.record(true, 10);
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.failure_count, HEALTH_FAILURE_THRESHOLD - 1);
+
+        for ts in 0..HEALTH_FAILURE_THRESHOLD as i64 {
+            health.record(false, 20 + ts);
+        }
+        assert!(health.is_degraded());
+        assert!(!health.is_healthy);
+        assert_eq!(health.failure_count, 2 * HEALTH_FAILURE_THRESHOLD - 1);
+    }
+
+    #[test]
+    fn health_restore_clears_degradation() {
+        let mut health = empty_health();
+        for ts in 0..HEALTH_FAILURE_THRESHOLD as i64 {
+            health.record(false, ts);
+        }
+        assert!(health.is_degraded());
+        health.restore(99);
+        assert!(!health.is_degraded());
+        assert!(health.is_healthy);
+        assert_eq!(health.last_checked_ts, 99);
+        assert_eq!(health.failure_count, HEALTH_FAILURE_THRESHOLD);
+    }
+
+    #[test]
+    fn maximal_service_fits_declared_space() {
+        let svc = Service {
+            latency_history: [u64::MAX; LATENCY_HISTORY_LEN],
+            ..Service::default()
+        };
+        assert_eq!(svc.try_to_vec().unwrap().len(), Service::INIT_SPACE);
+        assert_eq!(
+            empty_health().try_to_vec().unwrap().len(),
+            ServiceHealthStatus::INIT_SPACE
+        );
+    }
+
+    #[test]
+    fn first_offense_suspends_for_base_period() {
+        let mut svc = Service::default();
+        svc.record_suspension(1_000);
+        assert_eq!(svc.suspension_count, 1);
+        assert_eq!(svc.suspension_until_ts, 1_000 + BASE_SUSPENSION_S);
+        assert!(is_suspended(&svc, 1_000 + BASE_SUSPENSION_S - 1));
+        assert!(!is_suspended(&svc, 1_000 + BASE_SUSPENSION_S));
+    }
+
+    #[test]
+    fn third_offense_backs_off_exponentially() {
+        let mut svc = Service::default();
+        for _ in 0..3 {
+            svc.record_suspension(0);
+        }
+        assert_eq!(svc.suspension_until_ts, 4 * BASE_SUSPENSION_S);
+        assert_eq!(suspension_duration(4), 8 * BASE_SUSPENSION_S);
+        assert_eq!(suspension_duration(40), MAX_SUSPENSION_S);
+        assert_eq!(suspension_duration(u32::MAX), MAX_SUSPENSION_S);
+    }
+
+    #[test]
+    fn governance_clear_lifts_suspension() {
+        let mut svc = Service::default();
+        svc.record_suspension(0);
+        for _ in 0..DEFAULT_FAILURE_THRESHOLD {
+            svc.track_failure_streak(false, DEFAULT_FAILURE_THRESHOLD, 0);
+        }
+        svc.clear_suspension();
+        assert!(!is_suspended(&svc, 0));
+        assert_eq!(svc.consecutive_failures, 0);
+        svc.record_suspension(0);
+        assert_eq!(svc.suspension_until_ts, BASE_SUSPENSION_S);
+    }
+
+    #[test]
+    fn update_quota_exhausts_then_resets_next_epoch() {
+        let mut svc = Service::default();
+        svc.reset_update_quota(5);
+        for _ in 0..UPDATE_QUOTA_PER_EPOCH {
+            svc.consume_update_quota(5).unwrap();
+        }
+        let err = svc.consume_update_quota(5).err().unwrap();
+        assert_eq!(err, ReputationError::UpdateQuotaExceeded.into());
+
+        svc.consume_update_quota(6).unwrap();
+        assert_eq!(svc.quota_reset_epoch, 6);
+        assert_eq!(svc.update_quota_remaining, UPDATE_QUOTA_PER_EPOCH - 1);
+    }
+
+    #[test]
+    fn governance_reset_refills_quota_mid_epoch() {
+        let mut svc = Service::default();
+        svc.reset_update_quota(3);
+        svc.update_quota_remaining = 0;
+        assert!(svc.consume_update_quota(3).is_err());
+        svc.reset_update_quota(3);
+        svc.consume_update_quota(3).unwrap();
+        assert_eq!(svc.update_quota_remaining, UPDATE_QUOTA_PER_EPOCH - 1);
+    }
+
+    #[test]
+    fn slash_fraction_tracks_dispute_severity() {
+        assert_eq!(slash_fraction_bps(0).unwrap(), 1_000);
+        assert_eq!(slash_fraction_bps(1).unwrap(), 10_000);
+        assert_eq!(slash_fraction_bps(2).unwrap(), 5_000);
+        assert_eq!(slash_fraction_bps(3).unwrap(), 5_000);
+        assert!(slash_fraction_bps(4).is_err());
+    }
+
+    #[test]
+    fn lifetime_volume_accumulates_across_settlements() {
+        let mut svc = Service::default();
+        for amount in [1_000, 250_000, 3] {
+            svc.record_volume(amount, true);
+        }
+        assert_eq!(svc.lifetime_volume, 251_003);
+        // Partials kept by a refunded call add volume but no release.
+        svc.record_volume(400, false);
+        assert_eq!(svc.lifetime_volume, 251_403);
+        assert_eq!(svc.escrow_releases, 3);
+        svc.record_volume(u64::MAX, true);
+        assert_eq!(svc.lifetime_volume, u64::MAX);
+        svc.record_volume(1, true);
+        assert_eq!(svc.lifetime_volume, u64::MAX);
+        assert_eq!(svc.escrow_releases, 5);
+    }
+
+    #[test]
+    fn escrow_success_rate_ignores_self_reported_outcomes() {
+        let mut svc = Service::default();
+        svc.apply_outcome(0, 1.0, 0, DEFAULT_OUTCOME_WINDOW_S)
+            .unwrap();
+        assert!(svc.success_rate_bp > 0);
+        assert_eq!(escrow_success_bps(&svc), 0);
+
+        svc.escrow_releases = 1_000;
+        let clean = escrow_success_bps(&svc);
+        assert!(clean > 9_900);
+        svc.escrow_refunds = 100;
+        assert!(escrow_success_bps(&svc) < 9_100);
+    }
+
+    #[test]
+    fn latency_alert_fires_once_until_cleared() {
+        let owner = Pubkey::new_unique();
+        let mut svc = Service {
+            owner,
+            p95_est_ms: 900,
+            ..Service::default()
+        };
+        // No threshold set yet.
+        assert!(svc.fire_latency_alert().is_err());
+        svc.latency_alert_threshold_ms = 900;
+        assert_eq!(
+            svc.fire_latency_alert().unwrap_err(),
+            error!(ReputationError::LatencyWithinThreshold)
+        );
+        svc.p95_est_ms = 901;
+        assert!(svc.fire_latency_alert().is_ok());
+        assert!(svc.alert_emitted);
+        assert_eq!(
+            svc.fire_latency_alert().unwrap_err(),
+            error!(ReputationError::AlertAlreadyEmitted)
+        );
+        assert_eq!(
+            svc.clear_latency_alert(&Pubkey::new_unique()).unwrap_err(),
+            error!(ReputationError::InvalidOwner)
+        );
+        svc.clear_latency_alert(&owner).unwrap();
+        assert!(!svc.alert_emitted);
+        assert!(svc.fire_latency_alert().is_ok());
+    }
+
+    #[test]
+    fn escrow_clocked_latency_fires_alerts_the_owner_underreports() {
+        let owner = Pubkey::new_unique();
+        let gains = LatencyGains::default();
+        let mut svc = Service {
+            owner,
+            latency_alert_threshold_ms: 900,
+            ..Service::default()
+        };
+        // The owner reports fast samples only.
+        for _ in 0..20 {
+            svc.record_latency_with(100, &gains);
+        }
+        assert_eq!(
+            svc.fire_latency_alert().unwrap_err(),
+            error!(ReputationError::LatencyWithinThreshold)
+        );
+
+        // Escrow clocks the real deliveries.
+        svc.record_delivery_latency(2_000, &gains);
+        assert_eq!(svc.escrow_p95_ms, 2_000);
+        for _ in 0..5 {
+            svc.record_delivery_latency(2_000, &gains);
+        }
+        assert_eq!(svc.escrow_latency_samples, 6);
+        assert_eq!(svc.alert_p95_ms(), 2_000);
+        svc.fire_latency_alert().unwrap();
+        svc.clear_latency_alert(&owner).unwrap();
+
+        // Fast deliveries pull the escrow estimate back under the threshold.
+        for _ in 0..200 {
+            svc.record_delivery_latency(100, &gains);
+        }
+        assert!(svc.escrow_p95_ms < 900);
+        assert!(svc.fire_latency_alert().is_err());
+    }
+
+    #[test]
+    fn slash_is_capped_at_a_multiple_of_the_escrow_amount() {
+        // The bond covers 10x the escrow, but a half-amount cap wins.
+        assert_eq!(
+            slash_amount(1, 1_000_000, 10_000_000, 5_000).unwrap(),
+            500_000
+        );
+        // Below the fraction, the cap doesn't bind.
+        assert_eq!(
+            slash_amount(0, 1_000_000, 10_000_000, 5_000).unwrap(),
+            100_000
+        );
+        // The bond still caps when it is the smaller bound.
+        assert_eq!(slash_amount(1, 1_000_000, 200_000, 5_000).unwrap(), 200_000);
+        assert_eq!(
+            slash_amount(1, 1_000_000, 10_000_000, DEFAULT_SLASH_CAP_MULTIPLIER_BPS).unwrap(),
+            1_000_000
+        );
+        let mut svc = Service {
+            token_bond_balance: 10_000_000,
+            ..Service::default()
+        };
+        assert_eq!(svc.slash_token_bond(1, 1_000_000, 2_500).unwrap(), 250_000);
+        assert_eq!(svc.token_bond_balance, 9_750_000);
+    }
+
+    #[test]
+    fn no_response_slashes_more_than_late() {
+        let late = slash_amount(0, 1_000_000, 5_000_000, DEFAULT_SLASH_CAP_MULTIPLIER_BPS).unwrap();
+        let no_response =
+            slash_amount(1, 1_000_000, 5_000_000, DEFAULT_SLASH_CAP_MULTIPLIER_BPS).unwrap();
+        assert_eq!(late, 100_000);
+        assert_eq!(no_response, 1_000_000);
+        assert!(no_response > late);
+
+        assert_eq!(
+            slash_amount(1, 1_000_000, 250_000, DEFAULT_SLASH_CAP_MULTIPLIER_BPS).unwrap(),
+            250_000
+        );
+        assert_eq!(
+            slash_amount(1, u64::MAX, u64::MAX, DEFAULT_SLASH_CAP_MULTIPLIER_BPS).unwrap(),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn performance_bond_is_independent_of_reputation_bond() {
+        let mut svc = Service {
+            bond_balance: 1_000,
+            ..Service::default()
+        };
+        svc.deposit_performance_bond(500);
+        assert_eq!(svc.performance_bond_balance, 500);
+        assert_eq!(svc.bond_balance, 1_000);
+
+        assert!(svc.withdraw_performance_bond(600).is_err());
+        svc.withdraw_performance_bond(200).unwrap();
+        assert_eq!(svc.performance_bond_balance, 300);
+        assert_eq!(svc.bond_balance, 1_000);
+
+        assert_eq!(svc.claw_back_performance_bond(250), 250);
+        assert_eq!(svc.claw_back_performance_bond(250), 50);
+        assert_eq!(svc.performance_bond_balance, 0);
+        assert_eq!(svc.bond_balance, 1_000);
+    }
+
+    #[test]
+    fn token_bond_lifecycle_is_separate_from_lamport_bond() {
+        let mut svc = Service {
+            bond_balance: 5_000,
+            ..Service::default()
+        };
+        let usdc = Pubkey::new_unique();
+        svc.set_bond_mint(usdc).unwrap();
+        svc.deposit_token_bond(1_000);
+        svc.set_bond_mint(usdc).unwrap();
+        svc.deposit_token_bond(500);
+        assert_eq!(
+            svc.set_bond_mint(Pubkey::new_unique()).unwrap_err(),
+            ReputationError::BondMintMismatch.into()
+        );
+        assert_eq!(svc.token_bond_balance, 1_500);
+
+        assert!(svc.withdraw_token_bond(1_501).is_err());
+        svc.withdraw_token_bond(300).unwrap();
+        assert_eq!(svc.token_bond_balance, 1_200);
+
+        // NO_RESPONSE slashes 100% of a 2_000 escrow but only 1_200 is bonded.
+        assert_eq!(
+            svc.slash_token_bond(1, 2_000, DEFAULT_SLASH_CAP_MULTIPLIER_BPS)
+                .unwrap(),
+            1_200
+        );
+        assert_eq!(svc.token_bond_balance, 0);
+        assert_eq!(
+            svc.slash_token_bond(1, 2_000, DEFAULT_SLASH_CAP_MULTIPLIER_BPS)
+                .unwrap(),
+            0
+        );
+        assert!(svc
+            .slash_token_bond(9, 2_000, DEFAULT_SLASH_CAP_MULTIPLIER_BPS)
+            .is_err());
+        assert_eq!(svc.bond_balance, 5_000);
+        assert_eq!(svc.bond_mint, usdc);
+    }
+
+    #[test]
+    fn compliance_rate_converges_towards_ok_share() {
+        let mut svc = Service::default();
+        assert!(svc.meets_compliance(COMPLIANCE_MAX_BPS));
+        for _ in 0..22 {
+            svc.apply_outcome(0, 1.0, 0, DEFAULT_OUTCOME_WINDOW_S)
+                .unwrap();
+        }
+        assert!((8_900..=9_100).contains(&svc.compliance_rate_bps));
+        for _ in 0..100 {
+            svc.apply_outcome(0, 1.0, 0, DEFAULT_OUTCOME_WINDOW_S)
+                .unwrap();
+        }
+        assert!(svc.compliance_rate_bps > 9_900);
+        assert!(svc.meets_compliance(9_900));
+    }
+
+    #[test]
+    fn compliance_rate_decays_on_late_and_disputed() {
+        let mut svc = Service {
+            compliance_rate_bps: COMPLIANCE_MAX_BPS,
+            ..Service::default()
+        };
+        svc.apply_outcome(1, 1.0, 0, DEFAULT_OUTCOME_WINDOW_S)
+            .unwrap();
+        assert_eq!(svc.compliance_rate_bps, 9_000);
+        svc.apply_outcome(2, 1.0, 0, DEFAULT_OUTCOME_WINDOW_S)
+            .unwrap();
+        assert_eq!(svc.compliance_rate_bps, 8_100);
+        svc.apply_outcome(4, 1.0, 0, DEFAULT_OUTCOME_WINDOW_S)
+            .unwrap();
+        assert_eq!(svc.compliance_rate_bps, 7_290);
+        assert!(svc
+            .apply_outcome(7, 1.0, 0, DEFAULT_OUTCOME_WINDOW_S)
+            .is_err());
+        assert_eq!(svc.compliance_rate_bps, 7_290);
+        assert!(!svc.meets_compliance(9_000));
+    }
+
+    #[test]
+    fn fresh_provider_can_bond_as_first_interaction() {
+        // What `init_if_needed` hands the handler: an all-zero account.
+        let zeroed = vec![0u8; Service::INIT_SPACE];
+        let mut svc = Service::deserialize(&mut &zeroed[..]).unwrap();
+        assert_eq!(svc.owner, Pubkey::default());
+
+        let provider = Pubkey::new_unique();
+        svc.deposit_bond(&provider, 5_000, 7).unwrap();
+        assert_eq!(svc.owner, provider);
+        assert_eq!(svc.bond_balance, 5_000);
+        assert_eq!(svc.version, SERVICE_VERSION);
+        assert!(svc.is_active);
+        assert_eq!(svc.update_quota_remaining, UPDATE_QUOTA_PER_EPOCH);
+        assert_eq!(svc.quota_reset_epoch, 7);
+
+        svc.deposit_bond(&provider, 1_000, 8).unwrap();
+        assert_eq!(svc.bond_balance, 6_000);
+        assert_eq!(svc.quota_reset_epoch, 7);
+        assert_eq!(
+            svc.deposit_bond(&Pubkey::new_unique(), 1, 8).unwrap_err(),
+            error!(ReputationError::InvalidOwner)
+        );
+    }
+
+    #[test]
+    fn extreme_inputs_saturate_instead_of_panicking() {
+        let mut svc = Service {
+            ok: f32::MAX,
+            window_ok: f32::MAX,
+            late: f32::MAX,
+            current_window_start: 1,
+            ..Default::default()
+        };
+        svc.apply_outcome(0, 1.0, 2, i64::MAX).unwrap();
+        assert_eq!(svc.ok, f32::MAX);
+        assert_eq!(svc.window_ok, f32::MAX);
+        assert_eq!(svc.outcome_total(), f32::MAX);
+        assert!(svc.success_rate_bp <= COMPLIANCE_MAX_BPS);
+        for weight in [f32::NAN, f32::INFINITY, -1.0] {
+            assert_eq!(
+                svc.apply_outcome(0, weight, 2, i64::MAX).unwrap_err(),
+                error!(ReputationError::InvalidWeight)
+            );
+        }
+
+        svc.roll_window(i64::MAX, i64::MAX / 2 + 1);
+        svc.roll_window(i64::MIN, 1);
+
+        assert_eq!(
+            histogram_p95(&[u32::MAX; LATENCY_BUCKETS]),
+            1u64 << LATENCY_BUCKETS
+        );
+
+        svc.total_stake_shares = u64::MAX;
+        svc.total_delegated = u64::MAX;
+        assert_eq!(svc.undelegate(u64::MAX), u64::MAX);
+        assert_eq!(svc.total_delegated, 0);
+    }
+
+    #[test]
+    fn reviews_from_new_payers_are_throttled() {
+        let mut config = Config {
+            admin: Pubkey::new_unique(),
+            authorized_caller: Pubkey::new_unique(),
+            ewma_alpha: EWMA_ALPHA,
+            quantile_inc: QUANTILE_INC,
+            quantile_dec: QUANTILE_DEC,
+            paused: false,
+            treasury: Pubkey::new_unique(),
+            treasury_bps: 0,
+            max_samples_per_epoch: DEFAULT_MAX_SAMPLES_PER_EPOCH,
+            max_outcome_weight: 1.0,
+            outcome_window_s: DEFAULT_OUTCOME_WINDOW_S,
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            slash_cap_multiplier_bps: DEFAULT_SLASH_CAP_MULTIPLIER_BPS,
+            enforce_review_threshold: true,
+        };
+        let threshold = ReviewerThreshold {
+            min_completed_calls: 5,
+            min_payer_bond: 1_000,
+        };
+
+        assert!(!review_feeds_reputation(&config, &threshold, 0, 0, 1_000));
+        assert!(!review_feeds_reputation(&config, &threshold, 10, 0, 999));
+        assert!(review_feeds_reputation(&config, &threshold, 3, 2, 1_000));
+        assert!(review_feeds_reputation(
+            &config,
+            &threshold,
+            u32::MAX,
+            u32::MAX,
+            u64::MAX
+        ));
+
+        config.enforce_review_threshold = false;
+        assert!(review_feeds_reputation(&config, &threshold, 0, 0, 0));
+    }
+
+    fn audit_log() -> ServiceAuditLog {
+        ServiceAuditLog {
+            service_id: "svc".to_string(),
+            entries: Vec::new(),
+            head: 0,
+        }
+    }
+
+    #[test]
+    fn bond_changes_are_audited_in_order() {
+        let mut log = audit_log();
+        record_audit(
+            &mut log,
+            AuditAction::BondDeposit,
+            10,
+            audit_amount(500),
+            0.0,
+        );
+        record_audit(
+            &mut log,
+            AuditAction::BondWithdraw,
+            11,
+            -audit_amount(200),
+            0.0,
+        );
+        record_audit(
+            &mut log,
+            AuditAction::BondSlash,
+            12,
+            -audit_amount(100),
+            0.0,
+        );
+
+        let actions: Vec<(u8, i64, i64)> = log
+            .entries
+            .iter()
+            .map(|e| (e.action, e.ts, e.amount_delta))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                (AuditAction::BondDeposit as u8, 10, 500),
+                (AuditAction::BondWithdraw as u8, 11, -200),
+                (AuditAction::BondSlash as u8, 12, -100),
+            ]
+        );
+        assert_eq!(log.head, 0);
+        assert_eq!(audit_amount(u64::MAX), i64::MAX);
+    }
+
+    #[test]
+    fn a_new_log_is_named_once() {
+        let mut log = audit_log();
+        log.service_id.clear();
+        log.claim("svc");
+        assert_eq!(log.service_id, "svc");
+        log.claim("other");
+        assert_eq!(log.service_id, "svc");
+    }
+
+    #[test]
+    fn audit_log_wraps_at_capacity() {
+        let mut log = audit_log();
+        log.service_id = "s".repeat(MAX_SERVICE_ID_LEN);
+        for ts in 0..MAX_AUDIT_ENTRIES as i64 {
+            record_audit(&mut log, AuditAction::Outcome, ts, 0, 0.25);
+        }
+        assert_eq!(log.entries.len(), MAX_AUDIT_ENTRIES);
+        assert_eq!(log.head, 0);
+        assert!(log.try_to_vec().unwrap().len() <= ServiceAuditLog::INIT_SPACE);
+
+        // The 65th entry replaces the oldest, and head moves past it.
+        record_audit(&mut log, AuditAction::LatencyUpdate, 64, 0, 0.0);
+        assert_eq!(log.entries.len(), MAX_AUDIT_ENTRIES);
+        assert_eq!(log.entries[0].ts, 64);
+        assert_eq!(log.entries[0].action, AuditAction::LatencyUpdate as u8);
+        assert_eq!(log.head, 1);
+        assert_eq!(log.entries[log.head as usize].ts, 1);
+
+        for ts in 65..65 + MAX_AUDIT_ENTRIES as i64 - 1 {
+            record_audit(&mut log, AuditAction::Outcome, ts, 0, 0.0);
+        }
+        assert_eq!(log.head, 0);
+        assert_eq!(log.entries[0].ts, 64);
     }
 }
@@ -0,0 +1,87 @@
+// sdk/ts/cid.ts
+// Content ids for `fulfill_with_data` / `fulfill_partial`. The escrow only
+// length-checks `content_cid`; these helpers build a CIDv1 (raw codec,
+// sha2-256) whose digest is the call's `response_hash`, so a trace can be
+// checked against where the response is stored.
+import { createHash } from 'crypto';
+
+/** `MAX_CONTENT_CID_LEN` in the escrow program. */
+export const MAX_CONTENT_CID_LEN = 64;
+
+const CID_V1 = 0x01;
+const RAW_CODEC = 0x55;
+const SHA2_256 = 0x12;
+const BASE32_ALPHABET = 'abcdefghijklmnopqrstuvwxyz234567';
+
+/** SHA-256 of the response, as committed in `response_hash`. */
+export function responseHash(bytes: Uint8Array): Uint8Array {
+  return new Uint8Array(createHash('sha256').update(bytes).digest());
+}
+
+/** CIDv1 string (`b`-prefixed base32) for the given response bytes. */
+export function computeCid(bytes: Uint8Array): string {
+  return cidFromResponseHash(responseHash(bytes));
+}
+
+/** CIDv1 string for an already computed `response_hash`. */
+export function cidFromResponseHash(hash: Uint8Array): string {
+  if (hash.length !== 32) throw new Error('expected a 32-byte hash');
+  const raw = Buffer.concat([Buffer.from([CID_V1, RAW_CODEC, SHA2_256, 32]), Buffer.from(hash)]);
+  return 'b' + base32Encode(raw);
+}
+
+/** True if `cid` is a raw sha2-256 CIDv1 whose digest is `hash`. */
+export function cidMatchesResponseHash(cid: string, hash: Uint8Array): boolean {
+  if (!cid.startsWith('b')) return false;
+  const raw = base32Decode(cid.slice(1));
+  if (!raw || raw.length !== 36) return false;
+  if (raw[0] !== CID_V1 || raw[1] !== RAW_CODEC || raw[2] !== SHA2_256 || raw[3] !== 32) return false;
+  return Buffer.from(raw.subarray(4)).equals(Buffer.from(hash));
+}
+
+/** Bytes to pass as `contentCid`; throws past `MAX_CONTENT_CID_LEN`. */
+export function encodeContentCid(cid: string): Uint8Array {
+  const bytes = new Uint8Array(Buffer.from(cid, 'utf8'));
+  if (bytes.length > MAX_CONTENT_CID_LEN) {
+    throw new Error(`content CID is ${bytes.length} bytes, max ${MAX_CONTENT_CID_LEN}`);
+  }
+  return bytes;
+}
+
+/** Reads `content_cid` back from an escrow account or `TraceSaved` event. */
+export function decodeContentCid(bytes: Uint8Array): string | null {
+  return bytes.length === 0 ? null : Buffer.from(bytes).toString('utf8');
+}
+
+function base32Encode(bytes: Uint8Array): string {
+  let out = '';
+  let bits = 0;
+  let value = 0;
+  for (const byte of bytes) {
+    value = (value << 8) | byte;
+    bits += 8;
+    while (bits >= 5) {
+      out += BASE32_ALPHABET[(value >>> (bits - 5)) & 31];
+      bits -= 5;
+    }
+  }
+  if (bits > 0) out += BASE32_ALPHABET[(value << (5 - bits)) & 31];
+  return out;
+}
+
+function base32Decode(text: string): Uint8Array | null {
+  const out: number[] = [];
+  let bits = 0;
+  let value = 0;
+  for (const ch of text) {
+    const idx = BASE32_ALPHABET.indexOf(ch);
+    if (idx < 0) return null;
+    value = ((value << 5) | idx) & 0xffff;
+    bits += 5;
+    if (bits >= 8) {
+      out.push((value >>> (bits - 8)) & 0xff);
+      bits -= 8;
+    }
+  }
+  return new Uint8Array(out);
+}
@@ -12,6 +12,7 @@ import {
 } from '@solana/web3.js';
 
 import escrowIdlJson from '../../idl/escrow.json';
+import reputationIdlJson from '../../idl/reputation.json';
 
 export type PaymentRequirements = {
   price: string;
@@ -85,23 +86,66 @@ export function nativeFacilitator({ connection, wallet, escrowProgramId }: Nativ
         [Buffer.from('call'), Buffer.from(callId, 'utf8')],
         programId
       );
-
-      const tx = await program.methods
+      const reputationProgramId = new PublicKey(assured.reputationProgram ?? REPUTATION_PROGRAM_ID);
+      const [service] = PublicKey.findProgramAddressSync(
+        [Buffer.from('svc'), Buffer.from(assured.serviceId, 'utf8')],
+        reputationProgramId
+      );
+      const reputation = new Program(
+        { ...(reputationIdlJson as any), address: reputationProgramId.toBase58() },
+        provider
+      );
+      const serviceState = await (reputation.account as any).service.fetch(service);
+      const [allowlist] = PublicKey.findProgramAddressSync(
+        [Buffer.from('allowlist'), serviceState.owner.toBuffer()],
+        programId
+      );
+      const providerKeys = await findProviderKeys(connection, programId, providerKey);
+      // Only a service that caps outstanding calls is written, through a
+      // reputation CPI; everyone else leaves it read-only so payments to
+      // the same service do not serialize.
+      const capped = serviceState.maxOutstandingCalls > 0;
+      const capAccounts = capped
+        ? {
+            reputationConfig: PublicKey.findProgramAddressSync(
+              [Buffer.from('config')],
+              reputationProgramId
+            )[0],
+            reputationProgram: reputationProgramId,
+            reputationAuthority: PublicKey.findProgramAddressSync(
+              [Buffer.from('rep_authority')],
+              programId
+            )[0],
+          }
+        : {};
+
+      const initTx = await program.methods
         .initPayment(
           callId,
           assured.serviceId,
           amount,
           slaMs,
           disputeWindow,
-          totalUnits
+          totalUnits,
+          defaultInitPaymentParams()
         )
-        .accounts({
+        .accountsPartial({
           escrowCall,
           payer: wallet.publicKey,
           provider: providerKey,
+          service,
+          allowlist,
+          providerKeys,
+          ...capAccounts,
           systemProgram: SystemProgram.programId,
         })
-        .rpc();
+        .transaction();
+      if (capped) {
+        for (const key of initTx.instructions[0].keys) {
+          if (key.pubkey.equals(service)) key.isWritable = true;
+        }
+      }
+      const tx = await provider.sendAndConfirm(initTx);
 
       const headerValue = encodePaymentHeader({ callId, txSig: tx, facilitator: 'native' });
 
@@ -113,6 +157,44 @@ export function nativeFacilitator({ connection, wallet, escrowProgramId }: Nativ
   };
 }
 
+const REPUTATION_PROGRAM_ID = '8QFXHzWC1hDC7GQTNqBhsVRLURpYfXFBzT5Vb4NTxDh5';
+
+/// Every optional `init_payment` setting left off, matching
+/// `InitPaymentParams::default()` on-chain.
+export function defaultInitPaymentParams() {
+  return {
+    refundAfterTs: null,
+    heartbeatIntervalS: new BN(0),
+    heartbeatRequired: false,
+    remainderPolicy: 0,
+    rentRecipient: null,
+    hashlock: Array(32).fill(0),
+    referrer: null,
+    referralFeeBps: 0,
+    minReputationBps: null,
+    billingMode: 0,
+    sigMode: 0,
+    slaTiers: [],
+    redemptionCode: null,
+    arbiters: [],
+    arbiterThreshold: 0,
+  };
+}
+
+/// The provider's registered attestation key account, or null when it has
+/// not registered one and the escrow should fall back to its own key.
+async function findProviderKeys(
+  connection: Connection,
+  programId: PublicKey,
+  provider: PublicKey
+): Promise<PublicKey | null> {
+  const [providerKeys] = PublicKey.findProgramAddressSync(
+    [Buffer.from('provider_keys'), provider.toBuffer()],
+    programId
+  );
+  return (await connection.getAccountInfo(providerKeys)) ? providerKeys : null;
+}
+
 function generateCallId(serviceId: string): string {
   const serviceSlug = createHash('sha256').update(serviceId).digest('hex').slice(0, 8);
   const tsPart = Date.now().toString(36).slice(-8).padStart(8, '0');
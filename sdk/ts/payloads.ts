@@ -0,0 +1,117 @@
+// sdk/ts/payloads.ts
+// Canonical signing payloads, byte for byte the encodings in
+// contracts/assured-core. Each starts with its u32-length-prefixed domain
+// tag, then the call id (also length-prefixed), then fixed-width fields.
+import { createHash } from 'crypto';
+
+export const FULFILL_DOMAIN = 'x402-assured:fulfill:v1';
+export const DATA_FULFILL_DOMAIN = 'x402-assured:fulfill-data:v1';
+export const CHUNK_DOMAIN = 'x402-assured:chunk:v1';
+export const DISPUTE_DOMAIN = 'x402-assured:dispute:v1';
+export const PROOF_DOMAIN = 'x402-assured:proof:v1';
+export const ABORT_DOMAIN = 'x402-assured:abort:v1';
+
+class PayloadWriter {
+  private parts: Buffer[] = [];
+
+  constructor(domain: string) {
+    this.bytes(Buffer.from(domain, 'utf8'));
+  }
+
+  bytes(value: Uint8Array): this {
+    const len = Buffer.alloc(4);
+    len.writeUInt32LE(value.length);
+    this.parts.push(len, Buffer.from(value));
+    return this;
+  }
+
+  hash32(value: Uint8Array): this {
+    if (value.length !== 32) throw new Error('expected a 32-byte hash');
+    this.parts.push(Buffer.from(value));
+    return this;
+  }
+
+  u8(value: number): this {
+    this.parts.push(Buffer.from([value]));
+    return this;
+  }
+
+  u32(value: number): this {
+    const buf = Buffer.alloc(4);
+    buf.writeUInt32LE(value);
+    this.parts.push(buf);
+    return this;
+  }
+
+  u64(value: number | bigint): this {
+    const buf = Buffer.alloc(8);
+    buf.writeBigUInt64LE(BigInt(value));
+    this.parts.push(buf);
+    return this;
+  }
+
+  finish(): Uint8Array {
+    return new Uint8Array(Buffer.concat(this.parts));
+  }
+}
+
+const callId = (domain: string, id: string) => new PayloadWriter(domain).bytes(Buffer.from(id, 'utf8'));
+
+/** Signed by the provider for `fulfill` / `fulfill_v2`. */
+export function encodeFulfillPayload(id: string, responseHash: Uint8Array, ts: number | bigint): Uint8Array {
+  return callId(FULFILL_DOMAIN, id).hash32(responseHash).u64(ts).finish();
+}
+
+/** Signed by the provider for `fulfill_with_data`; `contentCid` as passed to it. */
+export function encodeDataFulfillPayload(
+  id: string,
+  responseHash: Uint8Array,
+  ts: number | bigint,
+  responseSizeBytes: number | bigint,
+  contentCid: Uint8Array = new Uint8Array()
+): Uint8Array {
+  return callId(DATA_FULFILL_DOMAIN, id)
+    .hash32(responseHash)
+    .u64(ts)
+    .u64(responseSizeBytes)
+    .bytes(contentCid)
+    .finish();
+}
+
+/** Signed by the provider for each streamed chunk (`fulfill_partial`). */
+export function encodeChunkPayload(
+  id: string,
+  chunkIndex: number,
+  chunkHash: Uint8Array,
+  units: number | bigint,
+  ts: number | bigint,
+  contentCid: Uint8Array = new Uint8Array()
+): Uint8Array {
+  return callId(CHUNK_DOMAIN, id)
+    .u32(chunkIndex)
+    .hash32(chunkHash)
+    .u64(units)
+    .u64(ts)
+    .bytes(contentCid)
+    .finish();
+}
+
+/** Signed by the payer as `reporterSig` in `raise_dispute`. */
+export function encodeDisputePayload(id: string, kind: number, reasonHash: Uint8Array): Uint8Array {
+  return callId(DISPUTE_DOMAIN, id).u8(kind).hash32(reasonHash).finish();
+}
+
+/** Signed by the provider for `submit_proof`. */
+export function encodeProofPayload(id: string, proofHash: Uint8Array): Uint8Array {
+  return callId(PROOF_DOMAIN, id).hash32(proofHash).finish();
+}
+
+/** Signed by the provider for `abort`; `startTs` is the escrow's. */
+export function encodeAbortPayload(id: string, startTs: number | bigint): Uint8Array {
+  return callId(ABORT_DOMAIN, id).u64(startTs).finish();
+}
+
+/** SHA-256 of a payload, for signers that take a digest. */
+export function payloadHash(payload: Uint8Array): Uint8Array {
+  return new Uint8Array(createHash('sha256').update(payload).digest());
+}